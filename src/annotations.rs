@@ -0,0 +1,341 @@
+//! Selections as object-detection annotations instead of crops.
+//!
+//! YOLO annotations go into a text file next to each image (`photo.txt`), with
+//! one `class center_x center_y width height` line per selection in fractions of
+//! the image size. COCO annotations of all images in a directory share one
+//! `annotations.json` there, in which the entries of an image are replaced when
+//! it is annotated again.
+//!
+//! Existing annotations, and `photo.crops.json` sidecars listing crop rectangles
+//! as `[x, y, width, height]` in pixels or as `"X,Y,W,H"` crop specs with
+//! percentages, are read back as selections.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{crop::CropSpec, fs_utils::SUPPORTED_EXTENSIONS, selection::Selection};
+
+/// Name of the COCO file inside the image directory
+pub const COCO_FILE_NAME: &str = "annotations.json";
+/// Class names of the YOLO files in a directory, one per line
+pub const YOLO_CLASSES_FILE_NAME: &str = "classes.txt";
+/// Extension of the sidecar listing crop rectangles of an image
+pub const CROPS_EXTENSION: &str = "crops.json";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationFormat {
+    /// `<image name>.txt` next to each image
+    Yolo,
+    /// `annotations.json` per directory
+    Coco,
+}
+
+/// A COCO dataset; keys this program doesn't know (`info`, `licenses`, ...) are kept.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CocoDataset {
+    #[serde(default)]
+    pub images: Vec<CocoImage>,
+    #[serde(default)]
+    pub annotations: Vec<CocoAnnotation>,
+    #[serde(default)]
+    pub categories: Vec<CocoCategory>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CocoImage {
+    pub id: u64,
+    pub file_name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CocoAnnotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u32,
+    /// `[x, y, width, height]` in pixels
+    pub bbox: [f32; 4],
+    pub area: f32,
+    #[serde(default)]
+    pub iscrowd: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CocoCategory {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Name of class `class`: its entry in `names`, otherwise its number.
+pub fn class_name(class: u32, names: &[String]) -> String {
+    names.get(class as usize).cloned().unwrap_or_else(|| class.to_string())
+}
+
+/// `photo.txt` next to `image`.
+pub fn yolo_path(image: &Path) -> PathBuf {
+    image.with_extension("txt")
+}
+
+/// Another image next to `image` with the same name, e.g. `photo.png` for
+/// `photo.jpg`, whose YOLO annotations would go into the same [`yolo_path`].
+pub fn yolo_clash(image: &Path) -> Option<PathBuf> {
+    let extension = image.extension()?.to_string_lossy().to_ascii_lowercase();
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .filter(|other| **other != extension)
+        .map(|other| image.with_extension(other))
+        .find(|other| other.exists())
+}
+
+/// `annotations.json` in the directory of `image`.
+pub fn coco_path(image: &Path) -> PathBuf {
+    image.with_file_name(COCO_FILE_NAME)
+}
+
+/// `photo.crops.json` next to `image`.
+pub fn crops_path(image: &Path) -> PathBuf {
+    image.with_extension(CROPS_EXTENSION)
+}
+
+/// Selections stored for the image at `path` in the annotations of `format` (if
+/// given) or its `.crops.json` sidecar, scaled to the image displayed at
+/// `reference` size. Returns the file they were read from, `None` if there is none.
+pub fn read_selections(
+    format: Option<AnnotationFormat>,
+    path: &Path,
+    reference: egui::Vec2,
+) -> Result<Option<(PathBuf, Vec<Selection>)>> {
+    let (width, height) = image::image_dimensions(path)
+        .unwrap_or((reference.x.round() as u32, reference.y.round() as u32));
+    let size = egui::vec2(width as f32, height as f32);
+    let found = match format {
+        Some(AnnotationFormat::Yolo) => read_if_exists(&yolo_path(path), |text| parse_yolo(text, size))?,
+        Some(AnnotationFormat::Coco) => {
+            let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            read_if_exists(&coco_path(path), |text| {
+                let dataset: CocoDataset = serde_json::from_str(text)?;
+                Ok(dataset.selections(&file_name))
+            })?
+            .filter(|(_, selections)| !selections.is_empty())
+        }
+        None => None,
+    };
+    let found = match found {
+        Some(found) => Some(found),
+        None => read_if_exists(&crops_path(path), |text| parse_crops(text, width, height))?,
+    };
+    Ok(found.map(|(file, selections)| {
+        let selections = selections.iter().map(|selection| selection.scaled(size, reference)).collect();
+        (file, selections)
+    }))
+}
+
+fn read_if_exists(
+    file: &Path,
+    parse: impl FnOnce(&str) -> Result<Vec<Selection>>,
+) -> Result<Option<(PathBuf, Vec<Selection>)>> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", file.display())),
+    };
+    let selections = parse(&text).with_context(|| format!("Invalid annotations in {}", file.display()))?;
+    Ok(Some((file.to_path_buf(), selections)))
+}
+
+/// Selections of YOLO lines on an image of `size` pixels.
+pub fn parse_yolo(text: &str, size: egui::Vec2) -> Result<Vec<Selection>> {
+    let mut selections = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [class, center_x, center_y, width, height] = fields[..] else {
+            return Err(anyhow!("Expected 'class center_x center_y width height', got '{line}'"));
+        };
+        let class: u32 = class.parse().with_context(|| format!("Invalid class in '{line}'"))?;
+        let mut values = [0.0f32; 4];
+        for (value, field) in values.iter_mut().zip([center_x, center_y, width, height]) {
+            *value = field.parse().with_context(|| format!("Invalid number in '{line}'"))?;
+        }
+        let [center_x, center_y, width, height] = values;
+        selections.push(Selection {
+            class,
+            ..Selection::new(egui::Rect::from_center_size(
+                egui::pos2(center_x * size.x, center_y * size.y),
+                egui::vec2(width * size.x, height * size.y),
+            ))
+        });
+    }
+    Ok(selections)
+}
+
+/// Selections of a `.crops.json` sidecar on a `width` x `height` image: a list of
+/// [`CropSpec`]s, i.e. `[x, y, width, height]` in pixels or `"10%,10%,80%,80%"`.
+pub fn parse_crops(text: &str, width: u32, height: u32) -> Result<Vec<Selection>> {
+    let specs: Vec<CropSpec> = serde_json::from_str(text)?;
+    Ok(specs.iter().map(|spec| spec.selection(width, height)).collect())
+}
+
+/// Write `selections`, drawn on the image at `path` displayed at `reference` size,
+/// as annotations in `format`. Returns the file written.
+pub fn write_annotations(
+    format: AnnotationFormat,
+    path: &Path,
+    reference: egui::Vec2,
+    selections: &[Selection],
+    class_names: &[String],
+) -> Result<PathBuf> {
+    // The displayed image may be a scaled-down preview
+    let (width, height) = image::image_dimensions(path)
+        .unwrap_or((reference.x.round() as u32, reference.y.round() as u32));
+    let size = egui::vec2(width as f32, height as f32);
+    let selections: Vec<Selection> = selections
+        .iter()
+        .map(|selection| selection.scaled(reference, size))
+        .collect();
+    match format {
+        AnnotationFormat::Yolo => write_yolo(path, size, &selections, class_names),
+        AnnotationFormat::Coco => write_coco(path, size, &selections, class_names),
+    }
+}
+
+/// Box of `selection` in annotations: the bounding box of the rotated shape,
+/// within the `size` of the image.
+fn annotation_box(selection: &Selection, size: egui::Vec2) -> egui::Rect {
+    selection
+        .bounding_box()
+        .intersect(egui::Rect::from_min_size(egui::Pos2::ZERO, size))
+}
+
+/// YOLO lines of `selections` on an image of `size` pixels. Rotated selections
+/// are exported as their bounding box.
+pub fn yolo_lines(size: egui::Vec2, selections: &[Selection]) -> String {
+    let mut text = String::new();
+    for selection in selections {
+        let bounds = annotation_box(selection, size);
+        if !bounds.is_positive() {
+            continue;
+        }
+        let (center, extent) = (bounds.center(), bounds.size());
+        text.push_str(&format!(
+            "{} {:.6} {:.6} {:.6} {:.6}\n",
+            selection.class,
+            center.x / size.x,
+            center.y / size.y,
+            extent.x / size.x,
+            extent.y / size.y
+        ));
+    }
+    text
+}
+
+fn write_yolo(path: &Path, size: egui::Vec2, selections: &[Selection], class_names: &[String]) -> Result<PathBuf> {
+    let output = yolo_path(path);
+    if let Some(other) = yolo_clash(path) {
+        return Err(anyhow!(
+            "{} would also hold the annotations of {}; rename one of the images",
+            output.display(),
+            other.display()
+        ));
+    }
+    std::fs::write(&output, yolo_lines(size, selections))
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    if !class_names.is_empty() {
+        let classes = output.with_file_name(YOLO_CLASSES_FILE_NAME);
+        std::fs::write(&classes, class_names.join("\n") + "\n")
+            .with_context(|| format!("Failed to write {}", classes.display()))?;
+    }
+    Ok(output)
+}
+
+impl CocoDataset {
+    /// Selections of the annotations of image `file_name`, in pixels.
+    pub fn selections(&self, file_name: &str) -> Vec<Selection> {
+        let Some(image) = self.images.iter().find(|image| image.file_name == file_name) else {
+            return Vec::new();
+        };
+        self.annotations
+            .iter()
+            .filter(|annotation| annotation.image_id == image.id)
+            .map(|annotation| {
+                let [x, y, width, height] = annotation.bbox;
+                Selection {
+                    class: annotation.category_id,
+                    ..Selection::new(egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(width, height)))
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the entries of the image `file_name` by `selections`, adding
+    /// categories for classes not listed yet.
+    pub fn set_image(&mut self, file_name: &str, size: egui::Vec2, selections: &[Selection], class_names: &[String]) {
+        let image_id = match self.images.iter().find(|image| image.file_name == file_name) {
+            Some(image) => image.id,
+            None => {
+                let id = self.images.iter().map(|image| image.id + 1).max().unwrap_or(1);
+                self.images.push(CocoImage {
+                    id,
+                    file_name: file_name.to_string(),
+                    width: 0,
+                    height: 0,
+                });
+                id
+            }
+        };
+        if let Some(image) = self.images.iter_mut().find(|image| image.id == image_id) {
+            (image.width, image.height) = (size.x.round() as u32, size.y.round() as u32);
+        }
+        self.annotations.retain(|annotation| annotation.image_id != image_id);
+
+        let mut next_id = self.annotations.iter().map(|annotation| annotation.id + 1).max().unwrap_or(1);
+        for selection in selections {
+            // Rotated selections are exported as their bounding box
+            let bounds = Selection::new(annotation_box(selection, size));
+            let Some((x, y, width, height)) = bounds.to_u32_bounds() else { continue };
+            self.annotations.push(CocoAnnotation {
+                id: next_id,
+                image_id,
+                category_id: selection.class,
+                bbox: [x as f32, y as f32, width as f32, height as f32],
+                area: (width * height) as f32,
+                iscrowd: 0,
+            });
+            next_id += 1;
+            if !self.categories.iter().any(|category| category.id == selection.class) {
+                self.categories.push(CocoCategory {
+                    id: selection.class,
+                    name: class_name(selection.class, class_names),
+                });
+                self.categories.sort_by_key(|category| category.id);
+            }
+        }
+    }
+}
+
+fn write_coco(path: &Path, size: egui::Vec2, selections: &[Selection], class_names: &[String]) -> Result<PathBuf> {
+    let output = coco_path(path);
+    let mut dataset: CocoDataset = match std::fs::read_to_string(&output) {
+        Ok(text) => serde_json::from_str(&text).with_context(|| format!("Invalid COCO file {}", output.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => CocoDataset::default(),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", output.display())),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("No filename in {}", path.display()))?
+        .to_string_lossy();
+    dataset.set_image(&file_name, size, selections, class_names);
+    std::fs::write(&output, serde_json::to_string_pretty(&dataset)? + "\n")
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    Ok(output)
+}
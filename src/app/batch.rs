@@ -0,0 +1,231 @@
+use std::{
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use eframe::egui;
+
+use crate::{
+    autocrop::center_crop,
+    crop::{crop_file, crop_file_with, resolve_specs, CropSpec, SaveImage},
+    fs_utils::OutputLocation,
+    image_utils::{CombineStyle, EncodeSettings, OutputFormat},
+    journal::Event,
+    pipeline::SaverOptions,
+    selection::Selection,
+};
+
+use super::saver::Saver;
+
+/// Crop `files` in a background thread. Results arrive in order on the returned channel,
+/// which holds at most `capacity` of them: the thread waits while they aren't taken.
+pub fn spawn_batch_crop(
+    files: Vec<PathBuf>,
+    selections: Vec<Selection>,
+    reference: egui::Vec2,
+    style: CombineStyle,
+    capacity: usize,
+) -> Receiver<(PathBuf, Result<SaveImage>)> {
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    thread::spawn(move || {
+        for path in files {
+            let result = crop_file_with(&path, &selections, Some(reference), &style);
+            if tx.send((path, result)).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Settings of a headless batch crop.
+pub struct BatchOptions {
+    pub encode: EncodeSettings,
+    pub parallel: usize,
+    pub dry_run: bool,
+    pub saver: SaverOptions,
+    pub output: OutputLocation,
+}
+
+/// Region a headless batch crop cuts out of every image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchCrop {
+    /// `--apply-to-all`: the same rectangle
+    Rect(CropSpec),
+    /// `--center-crop`: the largest centered region with this aspect ratio (width / height)
+    Center(f32),
+}
+
+impl BatchCrop {
+    /// The selection for the image at `path`.
+    pub fn selection(&self, path: &Path) -> Result<Selection> {
+        match self {
+            BatchCrop::Rect(spec) => Ok(resolve_specs(path, &[*spec])?.remove(0)),
+            BatchCrop::Center(aspect) => {
+                let (width, height) = image::image_dimensions(path)
+                    .with_context(|| format!("Failed to read the size of {}", path.display()))?;
+                let (x, y, w, h) = center_crop(width, height, *aspect);
+                Ok(CropSpec::from([x, y, w, h]).selection(width, height))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BatchCrop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchCrop::Rect(spec) => write!(f, "{spec}"),
+            BatchCrop::Center(aspect) => write!(f, "the centered {aspect:.3}:1 region"),
+        }
+    }
+}
+
+/// Headless `--apply-to-all` and `--center-crop`: crop every file and save it
+/// like an interactive crop. Returns the number of failed images.
+pub fn apply_to_all(files: &[PathBuf], crop: BatchCrop, options: BatchOptions) -> usize {
+    let BatchOptions {
+        encode,
+        parallel,
+        dry_run,
+        saver,
+        output,
+    } = options;
+    let journal = saver.journal.clone();
+    let mut saver = Saver::with_options(parallel.max(1), saver);
+    let mut failed = 0;
+    for path in files {
+        if dry_run {
+            println!(
+                "Dry run: would crop {} to {crop} and save as {}",
+                path.display(),
+                output.output_path(path, encode.format.extension()).display()
+            );
+            continue;
+        }
+        let queued = crop
+            .selection(path)
+            .and_then(|selection| crop_file(path, &[selection], None))
+            .and_then(|image| {
+                journal.record(path, image.event(&output, encode.format));
+                saver.queue_save(image.into_request(&output, encode))
+            });
+        if let Err(err) = queued {
+            eprintln!("{err:#}");
+            failed += 1;
+        }
+    }
+
+    while !saver.pending_saves.is_empty() {
+        for (path, result, _) in saver.check_completions() {
+            match result {
+                Ok(()) => println!("Saved {}", path.display()),
+                Err(err) => {
+                    eprintln!("Error saving {}: {err:#}", path.display());
+                    failed += 1;
+                }
+            }
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    failed
+}
+
+/// Whether `path` already has the extension of `format` and needs no conversion.
+pub fn is_in_format(path: &Path, format: OutputFormat) -> bool {
+    path.extension()
+        .and_then(|extension| OutputFormat::from_extension(&extension.to_string_lossy()))
+        == Some(format)
+}
+
+/// `[=========>          ] 45/100` on stderr, overwriting the previous line.
+fn print_progress(done: usize, total: usize, failed: usize) {
+    const WIDTH: usize = 30;
+    let filled = (done * WIDTH).checked_div(total).unwrap_or(WIDTH);
+    let bar: String = (0..WIDTH)
+        .map(|i| match i.cmp(&filled) {
+            std::cmp::Ordering::Less => '=',
+            std::cmp::Ordering::Equal => '>',
+            std::cmp::Ordering::Greater => ' ',
+        })
+        .collect();
+    let failed = if failed > 0 { format!(" ({failed} failed)") } else { String::new() };
+    eprint!("\r[{bar}] {done}/{total}{failed}");
+    let _ = std::io::stderr().flush();
+}
+
+/// Count finished saves of `saver` and report failures.
+fn collect_saves(saver: &mut Saver, done: &mut usize, failed: &mut usize) {
+    for (path, result, _) in saver.check_completions() {
+        *done += 1;
+        if let Err(err) = result {
+            eprintln!("\rError saving {}: {err:#}", path.display());
+            *failed += 1;
+        }
+    }
+}
+
+/// Headless `convert`: re-encode every file that isn't in the output format
+/// yet, like `--resave` does in the app. Returns the number of failed images.
+pub fn convert_all(files: &[PathBuf], options: BatchOptions) -> usize {
+    let BatchOptions {
+        encode,
+        parallel,
+        dry_run,
+        saver,
+        output,
+    } = options;
+    let files: Vec<&PathBuf> = files.iter().filter(|path| !is_in_format(path, encode.format)).collect();
+    if dry_run {
+        for path in &files {
+            println!(
+                "Dry run: would convert {} to {}",
+                path.display(),
+                output.output_path(path, encode.format.extension()).display()
+            );
+        }
+        return 0;
+    }
+
+    let journal = saver.journal.clone();
+    let parallel = parallel.max(1);
+    let mut saver = Saver::with_options(parallel, saver);
+    let (mut done, mut failed) = (0, 0);
+    print_progress(0, files.len(), 0);
+    for path in &files {
+        // Decoded images wait in the queue, so don't decode far ahead of the encoders
+        while saver.pending_saves.len() >= parallel * 2 {
+            collect_saves(&mut saver, &mut done, &mut failed);
+            print_progress(done, files.len(), failed);
+            thread::sleep(Duration::from_millis(20));
+        }
+        let queued = crop_file(path, &[], None).and_then(|image| {
+            journal.record(
+                path,
+                Event::Resave {
+                    output: image.output_path(&output, encode.format),
+                },
+            );
+            saver.queue_save(image.into_request(&output, encode))
+        });
+        if let Err(err) = queued {
+            eprintln!("\r{err:#}");
+            done += 1;
+            failed += 1;
+        }
+        collect_saves(&mut saver, &mut done, &mut failed);
+        print_progress(done, files.len(), failed);
+    }
+
+    while !saver.pending_saves.is_empty() {
+        collect_saves(&mut saver, &mut done, &mut failed);
+        print_progress(done, files.len(), failed);
+        thread::sleep(Duration::from_millis(20));
+    }
+    eprintln!();
+    failed
+}
@@ -1,14 +1,65 @@
 use eframe::egui::{self, Color32};
 
 use crate::{
-    selection::{selection_color, HandleDrag, Selection, SelectionHandle},
-    ui::{ImageMetrics, KeyboardState, ARROW_MOVE_STEP},
+    annotations::class_name,
+    image_utils::EdgeMap,
+    selection::{arrange, Arrangement, HandleDrag, MoveDrag, Selection, SelectionHandle, SelectionShape, SelectionStyle},
+    ui::{horizon_angle, ArrowSpeed, GuideMode, ImageMetrics, KeyboardState},
 };
 
+/// Magnification of the loupe shown while dragging, in screen pixels per image pixel
+pub const LOUPE_ZOOM: f32 = 6.0;
+/// Edge length of the loupe in screen pixels
+pub const LOUPE_SIZE: f32 = 180.0;
+/// Largest pinch zoom, relative to the image fitted to the screen
+pub const MAX_ZOOM: f32 = 16.0;
+/// Screen distance between points recorded while tracing a freehand polygon
+pub const FREEHAND_SPACING: f32 = 4.0;
+/// Distance in image pixels within which selection edges snap to image edges
+pub const EDGE_SNAP_RADIUS: u32 = 6;
+/// Offset in image pixels of a duplicated selection from its original
+pub const DUPLICATE_OFFSET: f32 = 20.0;
+
 pub struct Canvas {
     pub selections: Vec<Selection>,
     pub selection_anchor: Option<egui::Pos2>,
     pub active_handle: Option<HandleDrag>,
+    /// Selection currently moved by dragging its body
+    pub active_move: Option<MoveDrag>,
+    /// Index of the selection that was last created, moved or resized
+    pub active: Option<usize>,
+    /// Grid size in image pixels that selection edges snap to, if enabled.
+    pub snap_grid: Option<u32>,
+    /// Snap selection edges to strong nearby image edges
+    pub snap_edges: bool,
+    /// Luminance of the current image for `snap_edges`, computed on first use
+    pub edge_map: Option<EdgeMap>,
+    pub guides: GuideMode,
+    /// While enabled, dragging draws a horizon line instead of a selection
+    pub straighten_mode: bool,
+    /// Horizon line being drawn, in image coordinates
+    pub straighten_line: Option<(egui::Pos2, egui::Pos2)>,
+    straighten_angle: Option<f32>,
+    /// Smart crop candidates for the current image, best first
+    pub suggestions: Vec<Selection>,
+    pub suggestion_index: usize,
+    pub arrow_speed: ArrowSpeed,
+    pub style: SelectionStyle,
+    /// Class of new selections, the one last chosen with the number keys in `--annotate` mode
+    pub current_class: u32,
+    /// Class labels drawn on the selections, `None` outside of `--annotate` mode
+    pub class_names: Option<Vec<String>>,
+    /// Shape of new selections, cycled with E
+    pub shape: SelectionShape,
+    /// Vertices of the polygon being drawn, in image coordinates
+    pub polygon_points: Vec<egui::Pos2>,
+    /// Seconds the arrow keys have been held, for acceleration
+    arrow_held: f32,
+    /// Pinch zoom of the view, 1 fits the image to the screen
+    pub zoom: f32,
+    /// Offset of the zoomed image from the screen center, in screen pixels
+    pub pan: egui::Vec2,
+    long_pressed: bool,
 }
 
 impl Canvas {
@@ -17,13 +68,217 @@ impl Canvas {
             selections: Vec::new(),
             selection_anchor: None,
             active_handle: None,
+            active_move: None,
+            active: None,
+            snap_grid: None,
+            snap_edges: false,
+            edge_map: None,
+            guides: GuideMode::None,
+            straighten_mode: false,
+            straighten_line: None,
+            straighten_angle: None,
+            suggestions: Vec::new(),
+            suggestion_index: 0,
+            arrow_speed: ArrowSpeed::default(),
+            style: SelectionStyle::default(),
+            current_class: 0,
+            class_names: None,
+            shape: SelectionShape::default(),
+            polygon_points: Vec::new(),
+            arrow_held: 0.0,
+            zoom: 1.0,
+            pan: egui::Vec2::ZERO,
+            long_pressed: false,
+        }
+    }
+
+    fn snapped(&self, mut selection: Selection, image_size: egui::Vec2) -> Selection {
+        if let Some(grid) = self.snap_grid {
+            selection.snap_to_grid(grid, image_size);
+        }
+        if let Some(edges) = self.edge_map.as_ref().filter(|_| self.snap_edges) {
+            edges.snap(&mut selection, EDGE_SNAP_RADIUS);
         }
+        selection
     }
 
     pub fn clear(&mut self) {
         self.selections.clear();
         self.selection_anchor = None;
         self.active_handle = None;
+        self.active_move = None;
+        self.active = None;
+        self.straighten_line = None;
+        self.polygon_points.clear();
+        self.edge_map = None;
+        self.suggestions.clear();
+        self.suggestion_index = 0;
+    }
+
+    /// Index of the active selection: the one last interacted with, or the newest one.
+    pub fn active_index(&self) -> Option<usize> {
+        let len = self.selections.len();
+        if len == 0 {
+            return None;
+        }
+        Some(self.active.filter(|index| *index < len).unwrap_or(len - 1))
+    }
+
+    /// Give the active selection (and the ones drawn next) class `class`.
+    pub fn set_class(&mut self, class: u32) {
+        self.current_class = class;
+        if let Some(index) = self.active_index() {
+            self.selections[index].class = class;
+        }
+    }
+
+    /// Cycle the shape of new selections through rectangle, ellipse and polygon.
+    /// The active selection switches along between rectangle and ellipse.
+    /// Returns the new shape.
+    pub fn toggle_shape(&mut self) -> &SelectionShape {
+        self.shape = match self.shape {
+            SelectionShape::Rectangle => SelectionShape::Ellipse,
+            SelectionShape::Ellipse => SelectionShape::Polygon(Vec::new()),
+            SelectionShape::Polygon(_) => SelectionShape::Rectangle,
+        };
+        self.polygon_points.clear();
+        if let Some(index) = self.active_index() {
+            let selection = &mut self.selections[index];
+            if !matches!(selection.shape, SelectionShape::Polygon(_)) && !self.is_polygon_tool() {
+                selection.shape = self.shape.clone();
+            }
+        }
+        &self.shape
+    }
+
+    fn is_polygon_tool(&self) -> bool {
+        matches!(self.shape, SelectionShape::Polygon(_))
+    }
+
+    /// Whether a polygon is being drawn, so Enter closes it instead of cropping.
+    pub fn is_drawing_polygon(&self) -> bool {
+        !self.polygon_points.is_empty()
+    }
+
+    /// Turn the polygon being drawn into a selection, replacing the existing ones
+    /// unless `keep_existing`. Returns `false` if it has fewer than three points.
+    pub fn close_polygon(&mut self, image_size: egui::Vec2, keep_existing: bool) -> bool {
+        let points = std::mem::take(&mut self.polygon_points);
+        let Some(selection) = Selection::polygon(&points, image_size) else {
+            return false;
+        };
+        if !keep_existing {
+            self.selections.clear();
+        }
+        self.selections.push(Selection {
+            class: self.current_class,
+            ..selection
+        });
+        self.active = Some(self.selections.len() - 1);
+        true
+    }
+
+    /// Rotate the active selection clockwise by `degrees`. Returns its new angle,
+    /// `None` without selections.
+    pub fn rotate_active(&mut self, degrees: f32) -> Option<f32> {
+        let index = self.active_index()?;
+        let selection = &mut self.selections[index];
+        selection.rotate_by(degrees);
+        Some(selection.angle)
+    }
+
+    /// Grow the active selection by `amount` pixels on every side (shrink if
+    /// negative). Returns its new size.
+    pub fn grow_active(&mut self, amount: f32, image_size: egui::Vec2) -> Option<egui::Vec2> {
+        let index = self.active_index()?;
+        let selection = &mut self.selections[index];
+        selection.grow(amount, image_size);
+        Some(selection.rect.size())
+    }
+
+    /// Stretch the active selection to the full image width and/or height,
+    /// starting from the full image if there is none. Returns its new size.
+    pub fn stretch_active(&mut self, width: bool, height: bool, image_size: egui::Vec2) -> egui::Vec2 {
+        let index = match self.active_index() {
+            Some(index) => index,
+            None => {
+                self.selections.push(Selection {
+                    class: self.current_class,
+                    ..Selection::new(egui::Rect::from_min_size(egui::Pos2::ZERO, image_size))
+                });
+                self.selections.len() - 1
+            }
+        };
+        let selection = &mut self.selections[index];
+        selection.stretch(width, height, image_size);
+        selection.rect.size()
+    }
+
+    /// Add a copy of the active selection, shifted down and right (or back up and
+    /// left at the image border), and make it active. Returns its size.
+    pub fn duplicate_active(&mut self, image_size: egui::Vec2) -> Option<egui::Vec2> {
+        let index = self.active_index()?;
+        let mut copy = self.selections[index].clone();
+        let original = copy.rect;
+        copy.translate(egui::Vec2::splat(DUPLICATE_OFFSET), image_size);
+        if copy.rect == original {
+            copy.translate(egui::Vec2::splat(-DUPLICATE_OFFSET), image_size);
+        }
+        self.selections.push(copy);
+        self.active = Some(self.selections.len() - 1);
+        Some(original.size())
+    }
+
+    /// Line up the selections after the active one. Returns `false` if there
+    /// are fewer than two selections.
+    pub fn arrange(&mut self, arrangement: Arrangement, image_size: egui::Vec2) -> bool {
+        let Some(index) = self.active_index().filter(|_| self.selections.len() > 1) else {
+            return false;
+        };
+        arrange(&mut self.selections, index, arrangement, image_size);
+        true
+    }
+
+    /// Whether the image was long-pressed on a touchscreen since the last call.
+    pub fn take_long_press(&mut self) -> bool {
+        std::mem::take(&mut self.long_pressed)
+    }
+
+    /// Metrics of the image shown in `rect` at the current zoom.
+    pub fn metrics(&self, rect: egui::Rect, image_size: egui::Vec2) -> ImageMetrics {
+        ImageMetrics::zoomed(rect, image_size, self.zoom, self.pan)
+    }
+
+    pub fn reset_zoom(&mut self) {
+        self.zoom = 1.0;
+        self.pan = egui::Vec2::ZERO;
+    }
+
+    /// Zoom the view in `rect` by `factor`, keeping the image point under
+    /// `center` in place, then move it by `translation` screen pixels.
+    pub fn pinch(
+        &mut self,
+        rect: egui::Rect,
+        image_size: egui::Vec2,
+        factor: f32,
+        center: egui::Pos2,
+        translation: egui::Vec2,
+    ) {
+        let zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+        if zoom <= 1.0 {
+            self.reset_zoom();
+            return;
+        }
+        let before = self.metrics(rect, image_size).image_rect;
+        let factor = zoom / self.zoom;
+        let min = center - (center - before.min) * factor + translation;
+        self.zoom = zoom;
+        self.pan = min + before.size() * factor * 0.5 - rect.center();
+    }
+
+    /// Tilt of the last completed straighten line, if one was drawn since the last call.
+    pub fn take_straighten_angle(&mut self) -> Option<f32> {
+        self.straighten_angle.take()
     }
 
     pub fn handle_pointer(
@@ -33,20 +288,143 @@ impl Canvas {
         image_size: egui::Vec2,
         ctx: &egui::Context,
     ) {
-        let ctrl_down = ctx.input(|i| i.modifiers.ctrl);
+        // Two fingers zoom the view instead of drawing
+        if let Some(touch) = ctx.input(|input| input.multi_touch()) {
+            self.selection_anchor = None;
+            self.active_move = None;
+            self.pinch(response.rect, image_size, touch.zoom_delta, touch.center_pos, touch.translation_delta);
+            return;
+        }
+        if response.long_touched() {
+            self.long_pressed = true;
+        }
+
+        if self.straighten_mode {
+            self.handle_straighten_pointer(response, metrics);
+            return;
+        }
+
+        if self.is_polygon_tool() {
+            self.handle_polygon_pointer(response, metrics, image_size);
+            return;
+        }
+
+        let modifiers = ctx.input(|i| i.modifiers);
+        let ctrl_down = modifiers.ctrl;
+
+        if self.active_move.is_some() {
+            ctx.set_cursor_icon(egui::CursorIcon::Grabbing);
+        } else if let Some(hover) = response.hover_pos() {
+            if !ctrl_down && self.movable_selection_at(hover, metrics).is_some() {
+                ctx.set_cursor_icon(egui::CursorIcon::Move);
+            }
+        }
 
         if response.drag_started() {
             if let Some(pointer) = response.interact_pointer_pos() {
-                self.begin_selection(pointer, metrics, image_size, ctrl_down);
+                // Ctrl always starts a new selection, even inside an existing one
+                if ctrl_down || !self.begin_move(pointer, metrics) {
+                    self.begin_selection(pointer, metrics, image_size, ctrl_down);
+                }
             }
         } else if response.dragged() {
-            if let (Some(anchor), Some(pointer)) =
-                (self.selection_anchor, response.interact_pointer_pos())
-            {
-                self.update_drag(anchor, pointer, metrics, image_size);
+            if let Some(pointer) = response.interact_pointer_pos() {
+                if self.active_move.is_some() {
+                    self.update_move(pointer, metrics, image_size);
+                } else if let Some(anchor) = self.selection_anchor {
+                    self.update_drag(anchor, pointer, modifiers, metrics, image_size);
+                }
             }
         } else if response.drag_stopped() {
             self.selection_anchor = None;
+            self.active_move = None;
+        }
+    }
+
+    /// Polygon tool: clicks add vertices and dragging traces a freehand outline.
+    /// Dragging an existing selection before the first vertex still moves it.
+    fn handle_polygon_pointer(&mut self, response: &egui::Response, metrics: &ImageMetrics, image_size: egui::Vec2) {
+        let Some(pointer) = response.interact_pointer_pos() else {
+            if response.drag_stopped() {
+                self.active_move = None;
+            }
+            return;
+        };
+        let image_pos = metrics.screen_to_image(pointer);
+        let image_pos = egui::pos2(image_pos.x.clamp(0.0, image_size.x), image_pos.y.clamp(0.0, image_size.y));
+        if response.clicked() {
+            self.polygon_points.push(image_pos);
+        } else if response.drag_started() {
+            if self.polygon_points.is_empty() && self.begin_move(pointer, metrics) {
+                return;
+            }
+            self.polygon_points.push(image_pos);
+        } else if response.dragged() {
+            if self.active_move.is_some() {
+                self.update_move(pointer, metrics, image_size);
+            } else if self
+                .polygon_points
+                .last()
+                .is_none_or(|last| (*last - image_pos).length() * metrics.scale >= FREEHAND_SPACING)
+            {
+                self.polygon_points.push(image_pos);
+            }
+        } else if response.drag_stopped() {
+            self.active_move = None;
+        }
+    }
+
+    /// Index of the topmost selection whose body (not a handle) is under `pointer`.
+    fn movable_selection_at(&self, pointer: egui::Pos2, metrics: &ImageMetrics) -> Option<usize> {
+        if self.active_handle.is_some() || self.pointer_over_handle(pointer, metrics) {
+            return None;
+        }
+        self.selections
+            .iter()
+            .rposition(|selection| selection.contains(metrics.screen_to_image(pointer)))
+    }
+
+    /// Start moving the selection under `pointer`. Returns `false` if there is none.
+    pub fn begin_move(&mut self, pointer: egui::Pos2, metrics: &ImageMetrics) -> bool {
+        let Some(index) = self.movable_selection_at(pointer, metrics) else {
+            return false;
+        };
+        self.active_move = Some(MoveDrag {
+            original: self.selections[index].clone(),
+            start_pos: pointer,
+            selection_index: index,
+        });
+        self.active = Some(index);
+        true
+    }
+
+    pub fn update_move(&mut self, pointer: egui::Pos2, metrics: &ImageMetrics, image_size: egui::Vec2) {
+        let Some(active) = &self.active_move else { return };
+        let delta = (pointer - active.start_pos) / metrics.scale;
+        let mut moved = active.original.clone();
+        moved.translate(delta, image_size);
+        if let Some(selection) = self.selections.get_mut(active.selection_index) {
+            *selection = moved;
+        }
+    }
+
+    fn handle_straighten_pointer(&mut self, response: &egui::Response, metrics: &ImageMetrics) {
+        if response.drag_started() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let start = metrics.screen_to_image(pointer);
+                self.straighten_line = Some((start, start));
+            }
+        } else if response.dragged() {
+            if let (Some((start, _)), Some(pointer)) =
+                (self.straighten_line, response.interact_pointer_pos())
+            {
+                self.straighten_line = Some((start, metrics.screen_to_image(pointer)));
+            }
+        } else if response.drag_stopped() {
+            if let Some((start, end)) = self.straighten_line.take() {
+                self.straighten_angle = horizon_angle(start, end);
+                self.straighten_mode = false;
+            }
         }
     }
 
@@ -68,25 +446,41 @@ impl Canvas {
             self.selections.clear();
         }
 
-        self.selections
-            .push(Selection::from_points(image_pos, image_pos, image_size));
+        self.selections.push(Selection {
+            class: self.current_class,
+            shape: self.shape.clone(),
+            ..Selection::from_points(image_pos, image_pos, image_size)
+        });
+        self.active = Some(self.selections.len() - 1);
     }
 
+    /// Alt grows the selection from the anchor as its center, Shift + Alt as a square.
     fn update_drag(
         &mut self,
         anchor: egui::Pos2,
         pointer: egui::Pos2,
+        modifiers: egui::Modifiers,
         metrics: &ImageMetrics,
         image_size: egui::Vec2,
     ) {
         let image_pos = metrics.screen_to_image(pointer);
+        let selection = if modifiers.alt {
+            Selection::from_center(anchor, image_pos, modifiers.shift, image_size)
+        } else {
+            Selection::from_points(anchor, image_pos, image_size)
+        };
+        let selection = self.snapped(selection, image_size);
         if let Some(last) = self.selections.last_mut() {
-            *last = Selection::from_points(anchor, image_pos, image_size);
+            *last = Selection {
+                rect: selection.rect,
+                ..last.clone()
+            };
         }
     }
 
     fn pointer_over_handle(&self, pointer: egui::Pos2, metrics: &ImageMetrics) -> bool {
-        self.selections.iter().any(|selection| {
+        // Rotated selections have no resize handles
+        self.selections.iter().filter(|selection| selection.angle == 0.0).any(|selection| {
             let screen_rect = metrics.selection_rect(selection);
             SelectionHandle::ALL
                 .iter()
@@ -94,26 +488,27 @@ impl Canvas {
         })
     }
 
-    pub fn handle_arrow_movement(&mut self, keys: &KeyboardState, image_size: egui::Vec2) {
-        if self.selections.is_empty() {
-            return;
-        }
-        let mut delta = egui::Vec2::ZERO;
+    /// Move all selections with the arrow keys; `dt` is the frame time in seconds.
+    pub fn handle_arrow_movement(&mut self, keys: &KeyboardState, image_size: egui::Vec2, dt: f32) {
+        let mut direction = egui::Vec2::ZERO;
         if keys.move_up {
-            delta.y -= ARROW_MOVE_STEP;
+            direction.y -= 1.0;
         }
         if keys.move_down {
-            delta.y += ARROW_MOVE_STEP;
+            direction.y += 1.0;
         }
         if keys.move_left {
-            delta.x -= ARROW_MOVE_STEP;
+            direction.x -= 1.0;
         }
         if keys.move_right {
-            delta.x += ARROW_MOVE_STEP;
+            direction.x += 1.0;
         }
-        if delta == egui::Vec2::ZERO {
+        if direction == egui::Vec2::ZERO || self.selections.is_empty() {
+            self.arrow_held = 0.0;
             return;
         }
+        let delta = direction * self.arrow_speed.distance(self.arrow_held, dt);
+        self.arrow_held += dt.min(crate::ui::MAX_ARROW_FRAME_TIME);
         // Move all selections
         for selection in &mut self.selections {
             selection.translate(delta, image_size);
@@ -122,19 +517,135 @@ impl Canvas {
 
     pub fn draw(&mut self, ui: &egui::Ui, painter: &egui::Painter, metrics: &ImageMetrics, image_size: egui::Vec2) {
         self.draw_selection(painter, metrics);
+        self.draw_polygon_points(painter, metrics);
         self.draw_handles(ui, painter, metrics, image_size);
+        self.draw_straighten_line(painter, metrics);
+    }
+
+    /// Whether a selection is currently being drawn or resized.
+    pub fn is_dragging(&self) -> bool {
+        self.selection_anchor.is_some() || self.active_handle.is_some() || self.active_move.is_some()
+    }
+
+    /// Draw a magnified view of the image around `pointer`, placed next to the
+    /// cursor and kept inside `bounds`.
+    pub fn draw_loupe(
+        &self,
+        painter: &egui::Painter,
+        metrics: &ImageMetrics,
+        texture: egui::TextureId,
+        pointer: egui::Pos2,
+        bounds: egui::Rect,
+    ) {
+        let offset = 24.0;
+        let mut min = pointer + egui::vec2(offset, offset);
+        if min.x + LOUPE_SIZE > bounds.max.x {
+            min.x = pointer.x - offset - LOUPE_SIZE;
+        }
+        if min.y + LOUPE_SIZE > bounds.max.y {
+            min.y = pointer.y - offset - LOUPE_SIZE;
+        }
+        let loupe = egui::Rect::from_min_size(min, egui::vec2(LOUPE_SIZE, LOUPE_SIZE));
+
+        // Image region shown in the loupe, clipped to the image
+        let center = metrics.screen_to_image(pointer);
+        let half = LOUPE_SIZE * 0.5 / LOUPE_ZOOM;
+        let region = egui::Rect::from_center_size(center, egui::vec2(half * 2.0, half * 2.0));
+        let image_bounds = egui::Rect::from_min_size(egui::Pos2::ZERO, metrics.image_size);
+        let visible = region.intersect(image_bounds);
+
+        painter.rect_filled(loupe, 0.0, Color32::BLACK);
+        if visible.is_positive() {
+            let dest = egui::Rect::from_min_max(
+                loupe.min + (visible.min - region.min) * LOUPE_ZOOM,
+                loupe.min + (visible.max - region.min) * LOUPE_ZOOM,
+            );
+            let uv = egui::Rect::from_min_max(
+                (visible.min.to_vec2() / metrics.image_size).to_pos2(),
+                (visible.max.to_vec2() / metrics.image_size).to_pos2(),
+            );
+            painter.image(texture, dest, uv, Color32::WHITE);
+        }
+
+        // Crosshair marking the pixel under the cursor
+        let cross = egui::Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 0, 200));
+        painter.hline(loupe.x_range(), loupe.center().y, cross);
+        painter.vline(loupe.center().x, loupe.y_range(), cross);
+        painter.rect_stroke(loupe, 0.0, egui::Stroke::new(2.0, Color32::WHITE), egui::StrokeKind::Outside);
+    }
+
+    fn draw_polygon_points(&self, painter: &egui::Painter, metrics: &ImageMetrics) {
+        if self.polygon_points.is_empty() {
+            return;
+        }
+        let color = self.style.color(self.selections.len());
+        let points: Vec<_> = self.polygon_points.iter().map(|point| metrics.image_to_screen(*point)).collect();
+        painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(self.style.stroke_width, color)));
+        for point in points {
+            painter.circle_filled(point, 3.0, color);
+        }
+    }
+
+    fn draw_straighten_line(&self, painter: &egui::Painter, metrics: &ImageMetrics) {
+        let Some((start, end)) = self.straighten_line else { return };
+        let (a, b) = (metrics.image_to_screen(start), metrics.image_to_screen(end));
+        painter.line_segment([a, b], egui::Stroke::new(2.0, Color32::YELLOW));
+        if let Some(angle) = horizon_angle(start, end) {
+            painter.text(
+                b + egui::vec2(12.0, 12.0),
+                egui::Align2::LEFT_TOP,
+                format!("{angle:.1}°"),
+                egui::FontId::proportional(16.0),
+                Color32::YELLOW,
+            );
+        }
     }
 
     fn draw_selection(&self, painter: &egui::Painter, metrics: &ImageMetrics) {
+        let active = self.active_index();
         for (i, selection) in self.selections.iter().enumerate() {
             let rect = metrics.selection_rect(selection);
-            let color = selection_color(i);
-            painter.rect_filled(
-                rect,
-                0.0,
-                Color32::from_rgba_unmultiplied(255, 255, 255, 24),
-            );
-            painter.rect_stroke(rect, 0.0, egui::Stroke::new(2.0, color), egui::StrokeKind::Middle);
+            let color = self.style.color(i);
+            let width = if active == Some(i) && self.selections.len() > 1 {
+                self.style.active_stroke_width
+            } else {
+                self.style.stroke_width
+            };
+            let stroke = egui::Stroke::new(width, color);
+            let top_left = if selection.angle == 0.0 && selection.shape == SelectionShape::Rectangle {
+                painter.rect_filled(rect, 0.0, self.style.fill());
+                painter.rect_stroke(rect, 0.0, stroke, egui::StrokeKind::Middle);
+                self.draw_guides(painter, rect);
+                rect.left_top()
+            } else {
+                let outline = selection.outline().into_iter().map(|point| metrics.image_to_screen(point)).collect();
+                if let SelectionShape::Polygon(_) = selection.shape {
+                    // May be concave, which the filled convex shape can't draw
+                    painter.add(egui::Shape::closed_line(outline, stroke));
+                } else {
+                    painter.add(egui::Shape::convex_polygon(outline, self.style.fill(), stroke));
+                }
+                metrics.image_to_screen(selection.corners()[0])
+            };
+            if let Some(names) = &self.class_names {
+                painter.text(
+                    top_left + egui::vec2(4.0, 2.0),
+                    egui::Align2::LEFT_TOP,
+                    class_name(selection.class, names),
+                    egui::FontId::proportional(14.0),
+                    color,
+                );
+            }
+        }
+    }
+
+    fn draw_guides(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let stroke = egui::Stroke::new(1.0, Color32::from_white_alpha(140));
+        for &fraction in self.guides.fractions() {
+            let x = rect.min.x + rect.width() * fraction;
+            let y = rect.min.y + rect.height() * fraction;
+            painter.vline(x, rect.y_range(), stroke);
+            painter.hline(rect.x_range(), y, stroke);
         }
     }
 
@@ -146,7 +657,10 @@ impl Canvas {
         // We need to iterate indices to modify specific selections
         for i in 0..self.selections.len() {
             let current_selection = self.selections[i].clone();
-            let color = selection_color(i);
+            if current_selection.angle != 0.0 {
+                continue;
+            }
+            let color = self.style.color(i);
             let handle_color =
                 Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), 160);
 
@@ -167,6 +681,7 @@ impl Canvas {
                             start_pos: pointer_pos,
                             selection_index: i,
                         });
+                        self.active = Some(i);
                     }
                 }
                 if response.dragged() {
@@ -178,12 +693,12 @@ impl Canvas {
                                     total_delta.x / metrics.scale,
                                     total_delta.y / metrics.scale,
                                 );
+                                let adjusted = self.snapped(
+                                    active.original.clone().adjusted(active.handle, delta, image_size),
+                                    image_size,
+                                );
                                 if let Some(sel) = self.selections.get_mut(i) {
-                                    *sel = active.original.clone().adjusted(
-                                        active.handle,
-                                        delta,
-                                        image_size,
-                                    );
+                                    *sel = adjusted;
                                 }
                             }
                         }
@@ -0,0 +1,264 @@
+//! Startup screen for launches without paths, e.g. from a desktop launcher: a
+//! native folder picker, the recently processed folders and the main options,
+//! after which the same window turns into the cropper.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use eframe::{
+    egui::{self, Color32, ViewportCommand},
+    App, Frame,
+};
+
+use super::{AppOptions, ImageCropperApp};
+use crate::{fs_utils::SortOrder, image_utils::OutputFormat, recent::RecentDirs};
+
+/// Choices made on the startup screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchSettings {
+    pub folder: Option<PathBuf>,
+    pub format: OutputFormat,
+    pub quality: u8,
+    pub order: SortOrder,
+    pub recursive: bool,
+    /// Start where the last session in the folder ended
+    pub resume: bool,
+}
+
+/// Turns the chosen settings into the images and options of the session, or
+/// `None` if there is nothing to show in the app.
+pub type StartSession = Box<dyn FnMut(&LaunchSettings) -> Result<Option<(Vec<PathBuf>, AppOptions)>>>;
+
+pub struct Launcher {
+    settings: LaunchSettings,
+    recent: RecentDirs,
+    /// Switch to fullscreen once the session starts
+    fullscreen: bool,
+    start: StartSession,
+    /// Whether the folder picker was opened on its own at startup
+    #[cfg(feature = "folder-picker")]
+    picker_shown: bool,
+    /// Folder path as typed, without a native picker
+    #[cfg(not(feature = "folder-picker"))]
+    folder_input: String,
+    error: Option<String>,
+    app: Option<ImageCropperApp>,
+}
+
+/// Name of `value` as given on the command line.
+fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+impl Launcher {
+    pub fn new(settings: LaunchSettings, recent: RecentDirs, fullscreen: bool, start: StartSession) -> Self {
+        Self {
+            settings,
+            // Offer the recent folders first instead of opening the picker right away
+            #[cfg(feature = "folder-picker")]
+            picker_shown: !recent.entries.is_empty(),
+            #[cfg(not(feature = "folder-picker"))]
+            folder_input: String::new(),
+            recent,
+            fullscreen,
+            start,
+            error: None,
+            app: None,
+        }
+    }
+
+    #[cfg(feature = "folder-picker")]
+    fn pick_folder(&mut self) {
+        let mut dialog = rfd::FileDialog::new().set_title("Folder with images to crop");
+        if let Some(folder) = &self.settings.folder {
+            dialog = dialog.set_directory(folder);
+        }
+        if let Some(folder) = dialog.pick_folder() {
+            self.choose_folder(folder);
+        }
+    }
+
+    /// Use `folder`, resuming where its last session ended if there was one.
+    fn choose_folder(&mut self, folder: PathBuf) {
+        self.settings.resume = self.resume_position(&folder).is_some();
+        #[cfg(not(feature = "folder-picker"))]
+        {
+            self.folder_input = folder.display().to_string();
+        }
+        self.settings.folder = Some(folder);
+        self.error = None;
+    }
+
+    /// Image the last session in `folder` ended at.
+    fn resume_position(&self, folder: &Path) -> Option<&Path> {
+        self.recent.get(folder)?.position.as_deref()
+    }
+
+    fn start_session(&mut self, ctx: &egui::Context, frame: &Frame) {
+        let Some(render_state) = frame.wgpu_render_state() else {
+            return;
+        };
+        let session = (self.start)(&self.settings).and_then(|session| {
+            session
+                .map(|(files, options)| ImageCropperApp::with_render_state(ctx, render_state, files, options))
+                .transpose()
+        });
+        match session {
+            Ok(Some(app)) => {
+                self.app = Some(app);
+                if self.fullscreen {
+                    ctx.send_viewport_cmd(ViewportCommand::Fullscreen(true));
+                }
+            }
+            Ok(None) => ctx.send_viewport_cmd(ViewportCommand::Close),
+            Err(err) => self.error = Some(format!("{err:#}")),
+        }
+    }
+
+    #[cfg(feature = "folder-picker")]
+    fn show_folder(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let folder = match &self.settings.folder {
+                Some(folder) => folder.display().to_string(),
+                None => "None chosen".into(),
+            };
+            ui.label(folder);
+            if ui.button("Choose…").clicked() {
+                self.pick_folder();
+            }
+        });
+    }
+
+    /// Path field for builds without the native picker; only an existing folder can be started.
+    #[cfg(not(feature = "folder-picker"))]
+    fn show_folder(&mut self, ui: &mut egui::Ui) {
+        let field = egui::TextEdit::singleline(&mut self.folder_input).hint_text("Path of a folder with images");
+        if ui.add(field).changed() {
+            let folder = PathBuf::from(self.folder_input.trim());
+            if folder.is_dir() {
+                self.choose_folder(folder);
+            } else {
+                self.settings.folder = None;
+            }
+        }
+    }
+
+    fn show_settings(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("launch_settings")
+            .num_columns(2)
+            .spacing([12.0, 10.0])
+            .show(ui, |ui| {
+                ui.label("Folder");
+                self.show_folder(ui);
+                ui.end_row();
+
+                let resume_at = self
+                    .settings
+                    .folder
+                    .as_deref()
+                    .and_then(|folder| self.resume_position(folder))
+                    .and_then(|position| position.file_name())
+                    .map(|name| name.to_string_lossy().into_owned());
+                if let Some(name) = resume_at {
+                    ui.label("");
+                    ui.checkbox(&mut self.settings.resume, format!("Continue at {name}"));
+                    ui.end_row();
+                }
+
+                ui.label("");
+                ui.checkbox(&mut self.settings.recursive, "Include subfolders");
+                ui.end_row();
+
+                ui.label("Format");
+                egui::ComboBox::from_id_salt("launch_format")
+                    .selected_text(value_name(&self.settings.format))
+                    .show_ui(ui, |ui| {
+                        for format in OutputFormat::value_variants() {
+                            ui.selectable_value(&mut self.settings.format, *format, value_name(format));
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Quality");
+                ui.add_enabled(
+                    self.settings.format.uses_quality(),
+                    egui::Slider::new(&mut self.settings.quality, 1..=100),
+                );
+                ui.end_row();
+
+                ui.label("Order");
+                egui::ComboBox::from_id_salt("launch_order")
+                    .selected_text(value_name(&self.settings.order))
+                    .show_ui(ui, |ui| {
+                        for order in SortOrder::value_variants() {
+                            ui.selectable_value(&mut self.settings.order, *order, value_name(order));
+                        }
+                    });
+                ui.end_row();
+
+                if !self.recent.entries.is_empty() {
+                    ui.label("Recent");
+                    let mut chosen = None;
+                    ui.vertical(|ui| {
+                        for entry in &self.recent.entries {
+                            let label = match entry.position.as_deref().and_then(|position| position.file_name()) {
+                                Some(name) => format!("{} (at {})", entry.dir.display(), name.to_string_lossy()),
+                                None => format!("{} (done)", entry.dir.display()),
+                            };
+                            if ui.link(label).on_hover_text(&entry.time).clicked() {
+                                chosen = Some(entry.dir.clone());
+                            }
+                        }
+                    });
+                    if let Some(folder) = chosen {
+                        self.choose_folder(folder);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+impl App for Launcher {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut Frame) {
+        if let Some(app) = &mut self.app {
+            app.update(ctx, frame);
+            return;
+        }
+        // The picker blocks, so open it once the window has been drawn
+        #[cfg(feature = "folder-picker")]
+        if !self.picker_shown {
+            if ctx.cumulative_frame_nr() > 0 {
+                self.picker_shown = true;
+                self.pick_folder();
+            }
+            ctx.request_repaint();
+        }
+
+        let mut start = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("ImageCropper");
+            ui.add_space(12.0);
+            self.show_settings(ui);
+            ui.add_space(12.0);
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+            let button = egui::Button::new(egui::RichText::new("Start").size(18.0)).min_size(egui::vec2(96.0, 36.0));
+            start = ui.add_enabled(self.settings.folder.is_some(), button).clicked();
+        });
+        if start {
+            self.start_session(ctx, frame);
+        }
+    }
+
+    fn on_exit(&mut self) {
+        if let Some(app) = &mut self.app {
+            app.on_exit();
+        }
+    }
+}
@@ -1,83 +1,470 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     io::Cursor,
-    path::PathBuf,
-    sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{mpsc::{self, Receiver, Sender}, Arc, Condvar, Mutex},
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
+use eframe::egui;
 use fast_image_resize::images::Image;
 use fast_image_resize::{PixelType, ResizeOptions, Resizer};
 use zune_jpeg::JpegDecoder;
 
-use crate::image_utils::PreloadedImage;
+use crate::{
+    image_utils::{dhash, is_high_bit_depth, to_color_image, PreloadedImage},
+    probe::quick_preview,
+    thumbnail_cache::ThumbnailCache,
+    pdf,
+    video::{self, is_video},
+};
+
+/// Longest side of filmstrip thumbnails in pixels
+pub const THUMBNAIL_SIZE: u32 = 160;
+
+/// Default size decoded images are scaled down to fit into, see [`LoaderOptions::max_preview`]
+pub const DEFAULT_MAX_PREVIEW: (u32, u32) = (3840, 2160);
+
+/// Default number of images after the current one that are preloaded
+pub const DEFAULT_PRELOAD_AHEAD: usize = 8;
+/// Default number of images before the current one that are preloaded
+pub const DEFAULT_PRELOAD_BEHIND: usize = 2;
+
+/// Report of the images that couldn't be loaded, written to the working directory at exit
+pub const CORRUPT_REPORT_FILE_NAME: &str = "imagecropper-corrupt-files.txt";
+
+/// Number of recently shown images the performance HUD averages over
+pub const LOAD_STATS_WINDOW: usize = 20;
+
+/// How long the stages of loading one image took, see [`PreloadedImage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoadTimings {
+    pub read: Duration,
+    pub decode: Duration,
+    pub resize: Duration,
+    pub texture: Duration,
+    pub total: Duration,
+}
+
+impl LoadTimings {
+    pub fn of(image: &PreloadedImage) -> Self {
+        Self {
+            read: image.read_duration,
+            decode: image.decode_duration,
+            resize: image.resize_duration,
+            texture: image.texture_gen_duration,
+            total: image.load_duration,
+        }
+    }
+}
+
+/// Load timings of the last [`LOAD_STATS_WINDOW`] images shown, for the
+/// performance HUD (`--perf-hud`, F3). Slow reads point to slow storage, slow
+/// decodes to large or expensive formats.
+#[derive(Default)]
+pub struct LoadStats {
+    recent: VecDeque<LoadTimings>,
+}
+
+impl LoadStats {
+    pub fn record(&mut self, timings: LoadTimings) {
+        if self.recent.len() >= LOAD_STATS_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(timings);
+    }
+
+    pub fn last(&self) -> Option<LoadTimings> {
+        self.recent.back().copied()
+    }
+
+    /// Mean of every stage over the recorded images.
+    pub fn average(&self) -> Option<LoadTimings> {
+        let count = u32::try_from(self.recent.len()).ok().filter(|count| *count > 0)?;
+        let sum = |stage: fn(&LoadTimings) -> Duration| self.recent.iter().map(stage).sum::<Duration>() / count;
+        Some(LoadTimings {
+            read: sum(|t| t.read),
+            decode: sum(|t| t.decode),
+            resize: sum(|t| t.resize),
+            texture: sum(|t| t.texture),
+            total: sum(|t| t.total),
+        })
+    }
+
+    /// Table of the last and average timings in milliseconds.
+    pub fn lines(&self) -> Vec<String> {
+        let row = |label: &str, t: LoadTimings| {
+            let ms = |d: Duration| format!("{:8.1}", d.as_secs_f64() * 1000.0);
+            format!("{label:<8}{}{}{}{}{}", ms(t.read), ms(t.decode), ms(t.resize), ms(t.texture), ms(t.total))
+        };
+        let mut lines = vec![format!("{:<8}{:>8}{:>8}{:>8}{:>8}{:>8}", "ms", "read", "decode", "resize", "texture", "total")];
+        if let (Some(last), Some(average)) = (self.last(), self.average()) {
+            lines.push(row("last", last));
+            lines.push(row(&format!("avg {}", self.recent.len()), average));
+        }
+        lines
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Paths waiting for a worker, most important first
+    paths: VecDeque<PathBuf>,
+    closed: bool,
+}
+
+/// Load requests shared between the [`Loader`] and its decoding threads.
+#[derive(Default)]
+struct LoadQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl LoadQueue {
+    /// Block until a path is queued. `None` once the loader is dropped.
+    fn next(&self) -> Option<PathBuf> {
+        let mut state = self.state.lock().ok()?;
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(path) = state.paths.pop_front() {
+                return Some(path);
+            }
+            state = self.ready.wait(state).ok()?;
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut VecDeque<PathBuf>)) {
+        if let Ok(mut state) = self.state.lock() {
+            f(&mut state.paths);
+        }
+        self.ready.notify_all();
+    }
+
+    fn queued(&self) -> Vec<PathBuf> {
+        self.state
+            .lock()
+            .map(|state| state.paths.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// Settings of the decoding threads.
+#[derive(Clone, Debug)]
+pub struct LoaderOptions {
+    /// Decoded images are scaled down to fit into this size to speed up texture
+    /// upload and save memory; `None` keeps the full resolution
+    pub max_preview: Option<(u32, u32)>,
+    pub thumbnail_cache: Option<ThumbnailCache>,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        Self {
+            max_preview: Some(DEFAULT_MAX_PREVIEW),
+            thumbnail_cache: None,
+        }
+    }
+}
+
+/// Parse `--max-preview-dimension`: `WIDTHxHEIGHT`, a single size for both sides, or `none`.
+pub fn parse_max_preview(value: &str) -> anyhow::Result<Option<(u32, u32)>> {
+    let invalid = || anyhow::anyhow!("Invalid preview size {value:?} (expected e.g. 3840x2160, 5120 or none)");
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    let (w, h) = value.split_once(['x', 'X']).unwrap_or((value, value));
+    let w: u32 = w.trim().parse().map_err(|_| invalid())?;
+    let h: u32 = h.trim().parse().map_err(|_| invalid())?;
+    if w == 0 || h == 0 {
+        return Err(invalid());
+    }
+    Ok(Some((w, h)))
+}
+
+/// Request to the video worker, see [`Loader::load_video_duration`] and
+/// [`Loader::load_video_frame`].
+enum VideoRequest {
+    Duration(PathBuf),
+    Frame(PathBuf, f64),
+}
+
+/// What the video worker read, with the error as text if it failed.
+pub enum VideoLoaded {
+    /// Length of the video in seconds
+    Duration(PathBuf, Result<f64, String>),
+    /// The frame shown at a time in seconds into the video
+    Frame(PathBuf, f64, Result<image::DynamicImage, String>),
+}
 
 pub struct Loader {
-    preload_rx: Receiver<PreloadedImage>,
-    path_tx: Sender<PathBuf>,
+    preload_rx: Receiver<Result<PreloadedImage, (PathBuf, String)>>,
+    queue: Arc<LoadQueue>,
+    thumbnail_rx: Receiver<(PathBuf, egui::ColorImage)>,
+    thumbnail_tx: Sender<PathBuf>,
+    preview_rx: Receiver<(PathBuf, egui::ColorImage)>,
+    preview_tx: Sender<PathBuf>,
+    video_rx: Receiver<VideoLoaded>,
+    video_tx: Sender<VideoRequest>,
+    comparison_rx: Receiver<(PathBuf, Result<egui::ColorImage, String>)>,
+    comparison_tx: Sender<PathBuf>,
+    /// Decoded images; insert and remove through the loader to keep the memory budget
     pub cache: HashMap<PathBuf, PreloadedImage>,
+    /// Cached paths, least recently used first
+    cache_lru: VecDeque<PathBuf>,
+    cache_bytes: u64,
+    /// Memory the cache may use before least recently used images are evicted
+    pub cache_budget: Option<u64>,
     pub history: VecDeque<PreloadedImage>,
     pub loading_active: bool,
+    /// Paths that are queued or being decoded
     pub pending: HashSet<PathBuf>,
+    /// Files that couldn't be read or decoded, with the error; not requested again
+    pub failed: HashMap<PathBuf, String>,
+    /// Thumbnails that were requested (finished or not), to avoid decoding twice
+    pub thumbnails_requested: HashSet<PathBuf>,
+    /// Finished thumbnails waiting to be uploaded by the UI
+    pub thumbnails_ready: Vec<(PathBuf, egui::ColorImage)>,
+    /// Finished quick previews (see [`Loader::load_preview`]) waiting to be shown by the UI
+    pub previews_ready: Vec<(PathBuf, egui::ColorImage)>,
+    /// Finished video requests waiting to be handled by the UI
+    pub videos_ready: Vec<VideoLoaded>,
+    /// Comparison images that were requested and haven't arrived yet
+    pub comparisons_requested: HashSet<PathBuf>,
+    /// Finished comparison images (see [`Loader::load_comparison`]) waiting to be shown by the UI
+    pub comparisons_ready: Vec<(PathBuf, Result<egui::ColorImage, String>)>,
+    /// Size decoded images are scaled down to fit into: [`LoaderOptions::max_preview`]
+    /// within the texture size limit of the GPU
+    pub max_preview: Option<(u32, u32)>,
 }
 
 impl Loader {
     pub fn new() -> Self {
-        let (preload_rx, path_tx) = Self::spawn_preloader(None, None);
+        Self::with_options(LoaderOptions::default())
+    }
+
+    pub fn with_wgpu(device: wgpu::Device, queue: wgpu::Queue, options: LoaderOptions) -> Self {
+        Self::build(Some(device), Some(queue), options)
+    }
+
+    /// Loader without GPU textures.
+    pub fn with_options(options: LoaderOptions) -> Self {
+        Self::build(None, None, options)
+    }
+
+    fn build(device: Option<wgpu::Device>, queue: Option<wgpu::Queue>, options: LoaderOptions) -> Self {
+        // Textures can't be larger than the GPU allows, even without a preview limit
+        let texture_limit = device.as_ref().map(|d| d.limits().max_texture_dimension_2d);
+        let max_preview = match (options.max_preview, texture_limit) {
+            (Some((w, h)), Some(limit)) => Some((w.min(limit), h.min(limit))),
+            (None, Some(limit)) => Some((limit, limit)),
+            (max_preview, None) => max_preview,
+        };
+        let thumbnail_cache = options.thumbnail_cache.map(Arc::new);
+        let (preload_rx, queue) = Self::spawn_preloader(device, queue, max_preview, thumbnail_cache.clone());
+        let (thumbnail_rx, thumbnail_tx) = Self::spawn_thumbnailer(thumbnail_cache.clone());
+        let (preview_rx, preview_tx) = Self::spawn_previewer(thumbnail_cache);
+        let (video_rx, video_tx) = Self::spawn_video_worker();
+        let (comparison_rx, comparison_tx) = Self::spawn_comparer(max_preview);
         Self {
             preload_rx,
-            path_tx,
+            queue,
+            thumbnail_rx,
+            thumbnail_tx,
+            preview_rx,
+            preview_tx,
+            video_rx,
+            video_tx,
+            comparison_rx,
+            comparison_tx,
             cache: HashMap::new(),
+            cache_lru: VecDeque::new(),
+            cache_bytes: 0,
+            cache_budget: None,
             history: VecDeque::with_capacity(10),
             loading_active: false,
             pending: HashSet::new(),
+            failed: HashMap::new(),
+            thumbnails_requested: HashSet::new(),
+            thumbnails_ready: Vec::new(),
+            previews_ready: Vec::new(),
+            videos_ready: Vec::new(),
+            comparisons_requested: HashSet::new(),
+            comparisons_ready: Vec::new(),
+            max_preview,
         }
     }
 
-    pub fn with_wgpu(device: wgpu::Device, queue: wgpu::Queue) -> Self {
-        let (preload_rx, path_tx) = Self::spawn_preloader(Some(device), Some(queue));
-        Self {
-            preload_rx,
-            path_tx,
-            cache: HashMap::new(),
-            history: VecDeque::with_capacity(10),
-            loading_active: false,
-            pending: HashSet::new(),
+    /// Spawn a worker that reads cached or embedded thumbnails for [`Loader::load_preview`].
+    fn spawn_previewer(
+        thumbnail_cache: Option<Arc<ThumbnailCache>>,
+    ) -> (Receiver<(PathBuf, egui::ColorImage)>, Sender<PathBuf>) {
+        let (preview_tx, preview_rx) = mpsc::channel();
+        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+        thread::spawn(move || {
+            for path in path_rx {
+                let preview = thumbnail_cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(&path))
+                    .or_else(|| quick_preview(&path));
+                let Some(preview) = preview else { continue };
+                if preview_tx.send((path, to_color_image(&preview))).is_err() {
+                    break;
+                }
+            }
+        });
+        (preview_rx, path_tx)
+    }
+
+    /// Spawn a worker that runs `ffprobe` and `ffmpeg` for the video scrubber, so
+    /// seeking doesn't block the UI.
+    fn spawn_video_worker() -> (Receiver<VideoLoaded>, Sender<VideoRequest>) {
+        let (loaded_tx, loaded_rx) = mpsc::channel();
+        let (request_tx, request_rx) = mpsc::channel::<VideoRequest>();
+        thread::spawn(move || {
+            for request in request_rx {
+                let loaded = match request {
+                    VideoRequest::Duration(path) => {
+                        let duration = video::duration(&path).map_err(|err| err.to_string());
+                        VideoLoaded::Duration(path, duration)
+                    }
+                    VideoRequest::Frame(path, seconds) => {
+                        let frame = video::grab_frame(&path, seconds).map_err(|err| err.to_string());
+                        VideoLoaded::Frame(path, seconds, frame)
+                    }
+                };
+                if loaded_tx.send(loaded).is_err() {
+                    break;
+                }
+            }
+        });
+        (loaded_rx, request_tx)
+    }
+
+    /// Spawn a worker that decodes the images shown for comparison next to or
+    /// instead of the current one, scaled down to `max_preview`.
+    fn spawn_comparer(
+        max_preview: Option<(u32, u32)>,
+    ) -> (Receiver<(PathBuf, Result<egui::ColorImage, String>)>, Sender<PathBuf>) {
+        let (image_tx, image_rx) = mpsc::channel();
+        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+        thread::spawn(move || {
+            for path in path_rx {
+                let image = match image::open(&path) {
+                    Ok(image) => {
+                        let image = match max_preview {
+                            Some((w, h)) if image.width() > w || image.height() > h => image.thumbnail(w, h),
+                            _ => image,
+                        };
+                        Ok(to_color_image(&image))
+                    }
+                    Err(err) => Err(err.to_string()),
+                };
+                if image_tx.send((path, image)).is_err() {
+                    break;
+                }
+            }
+        });
+        (image_rx, path_tx)
+    }
+
+    /// Spawn a small worker pool that decodes images into filmstrip thumbnails.
+    fn spawn_thumbnailer(
+        thumbnail_cache: Option<Arc<ThumbnailCache>>,
+    ) -> (Receiver<(PathBuf, egui::ColorImage)>, Sender<PathBuf>) {
+        let (thumb_tx, thumb_rx) = mpsc::channel();
+        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+        let path_rx = Arc::new(Mutex::new(path_rx));
+
+        for _ in 0..2 {
+            let path_rx = path_rx.clone();
+            let thumb_tx = thumb_tx.clone();
+            let thumbnail_cache = thumbnail_cache.clone();
+            thread::spawn(move || loop {
+                let path = {
+                    let Ok(rx) = path_rx.lock() else { break };
+                    match rx.recv() {
+                        Ok(p) => p,
+                        Err(_) => break,
+                    }
+                };
+                if let Some(cached) = thumbnail_cache.as_ref().and_then(|cache| cache.get(&path)) {
+                    let thumbnail = cached.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+                    if thumb_tx.send((path, to_color_image(&thumbnail))).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                let image = if let Some((pdf, page)) = pdf::page_of(&path) {
+                    pdf::render_page(&pdf, page)
+                } else if is_video(&path) {
+                    video::grab_frame(&path, 0.0)
+                } else {
+                    image::open(&path)
+                };
+                match image {
+                    Ok(image) => {
+                        // Pages of PDFs have no file whose modification time keys the cache
+                        if let Some(cache) = thumbnail_cache.as_ref().filter(|_| path.exists()) {
+                            if let Err(err) = cache.put(&path, &image) {
+                                eprintln!("Failed to cache thumbnail of {}: {err:#}", path.display());
+                            }
+                        }
+                        let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+                        if thumb_tx.send((path, to_color_image(&thumbnail))).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to create thumbnail for {}: {err:#}", path.display());
+                    }
+                }
+            });
         }
+        (thumb_rx, path_tx)
     }
 
     fn spawn_preloader(
         device: Option<wgpu::Device>,
         queue: Option<wgpu::Queue>,
-    ) -> (Receiver<PreloadedImage>, Sender<PathBuf>) {
+        max_preview: Option<(u32, u32)>,
+        thumbnail_cache: Option<Arc<ThumbnailCache>>,
+    ) -> (Receiver<Result<PreloadedImage, (PathBuf, String)>>, Arc<LoadQueue>) {
         let (preload_tx, preload_rx) = mpsc::channel();
-        let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
-        
-        let path_rx = Arc::new(Mutex::new(path_rx));
+        let load_queue = Arc::new(LoadQueue::default());
+
         let device = device.map(Arc::new);
         let queue = queue.map(Arc::new);
 
         for _ in 0..16 {
-            let path_rx = path_rx.clone();
+            let load_queue = load_queue.clone();
             let preload_tx = preload_tx.clone();
             let device = device.clone();
             let queue = queue.clone();
+            let thumbnail_cache = thumbnail_cache.clone();
 
             thread::spawn(move || {
-                loop {
-                    let path = {
-                        let Ok(rx) = path_rx.lock() else { break };
-                        match rx.recv() {
-                            Ok(p) => p,
-                            Err(_) => break,
-                        }
-                    };
-
+                while let Some(path) = load_queue.next() {
                     let start = Instant::now();
                 
                 let read_start = Instant::now();
-                let file_bytes = std::fs::read(&path);
+                // Videos are shown with their first frame
+                let file_bytes = if let Some((pdf, page)) = pdf::page_of(&path) {
+                    pdf::render_page_png(&pdf, page)
+                } else if is_video(&path) {
+                    video::frame_png(&path, 0.0)
+                } else {
+                    std::fs::read(&path)
+                };
                 let read_duration = read_start.elapsed();
 
                 match file_bytes {
@@ -121,9 +508,9 @@ impl Loader {
                             Ok(mut image) => {
                                 let resize_start = Instant::now();
                                 // Resize if too large to speed up texture upload and save memory
-                                // Assuming 4K max dimension is enough for cropping
-                                if image.width() > 3840 || image.height() > 2160 {
-                                    let (nwidth, nheight) = (3840, 2160);
+                                if let Some((nwidth, nheight)) = max_preview
+                                    .filter(|&(w, h)| image.width() > w || image.height() > h)
+                                {
                                     let ratio = image.width() as f64 / image.height() as f64;
                                     let (new_w, new_h) = if ratio > nwidth as f64 / nheight as f64 {
                                         (nwidth, (nwidth as f64 / ratio) as u32)
@@ -152,6 +539,8 @@ impl Loader {
                                                 PixelType::U8x4,
                                             ).ok()
                                         }
+                                        // Resized below without losing the bit depth
+                                        ref image if is_high_bit_depth(image) => None,
                                         _ => {
                                             // Fallback for other types
                                             let rgba = image.to_rgba8();
@@ -184,6 +573,8 @@ impl Loader {
                                             }
                                             _ => unreachable!("We only created U8x3 or U8x4 images"),
                                         };
+                                    } else {
+                                        image = image.resize_exact(new_w, new_h, image::imageops::FilterType::Triangle);
                                     }
                                 }
                                 let resize_duration = resize_start.elapsed();
@@ -233,11 +624,18 @@ impl Loader {
                                         (None, std::time::Duration::default())
                                     };
 
+                                let hash = Some(dhash(&image));
+                                if let Some(cache) = thumbnail_cache.as_ref().filter(|_| path.exists()) {
+                                    if let Err(err) = cache.put(&path, &image) {
+                                        eprintln!("Failed to cache thumbnail of {}: {err:#}", path.display());
+                                    }
+                                }
                                 let load_duration = start.elapsed();
                                 if preload_tx
-                                    .send(PreloadedImage {
+                                    .send(Ok(PreloadedImage {
                                         path,
                                         image,
+                                        hash,
                                         color_image: None,
                                         texture,
                                         load_duration,
@@ -245,7 +643,7 @@ impl Loader {
                                         decode_duration,
                                         resize_duration,
                                         texture_gen_duration,
-                                    })
+                                    }))
                                     .is_err()
                                 {
                                     break;
@@ -253,36 +651,186 @@ impl Loader {
                             }
                             Err(err) => {
                                 eprintln!("Failed to decode {}: {err:#}", path.display());
+                                if preload_tx.send(Err((path, format!("Can't decode: {err}")))).is_err() {
+                                    break;
+                                }
                             }
                         }
                     }
                     Err(err) => {
                         eprintln!("Failed to read {}: {err:#}", path.display());
+                        if preload_tx.send(Err((path, format!("Can't read: {err}")))).is_err() {
+                            break;
+                        }
                     }
                 }
                 }
             });
         }
-        (preload_rx, path_tx)
+        (preload_rx, load_queue)
     }
 
+    /// Load `path` before everything else that is queued.
     pub fn load_image(&mut self, path: PathBuf) {
-        if self.cache.contains_key(&path) || self.pending.contains(&path) {
+        if self.cache.contains_key(&path) || self.failed.contains_key(&path) {
             return;
         }
-        self.pending.insert(path.clone());
-        let _ = self.path_tx.send(path);
+        let queued = !self.pending.insert(path.clone());
+        self.queue.update(|paths| {
+            if queued {
+                // Already queued or decoding: move it to the front if it is still waiting
+                let Some(index) = paths.iter().position(|p| *p == path) else {
+                    return;
+                };
+                paths.remove(index);
+            }
+            paths.push_front(path);
+        });
+    }
+
+    /// Replace the queue with `window`, most important first.
+    ///
+    /// Queued paths outside the window are dropped, so jumping far ahead doesn't
+    /// wait for images that are no longer needed. Images that are already being
+    /// decoded still end up in the cache.
+    ///
+    /// With a cache budget, images are only requested while the cached part of the
+    /// window fits into it; evicted images are requested again once there is room.
+    pub fn set_preload_window(&mut self, window: &[PathBuf]) {
+        let queued: HashSet<PathBuf> = self.queue.queued().into_iter().collect();
+        let mut in_progress = self.pending.clone();
+        in_progress.retain(|path| !queued.contains(path));
+
+        // The most important images become the most recently used
+        for path in window.iter().rev() {
+            self.touch(path);
+        }
+        let mut window_bytes = 0;
+        let mut wanted = VecDeque::new();
+        for path in window {
+            if self.cache_budget.is_some_and(|budget| window_bytes >= budget) {
+                break;
+            }
+            if let Some(entry) = self.cache.get(path) {
+                window_bytes += entry.memory_size();
+            } else if !in_progress.contains(path) && !self.failed.contains_key(path) {
+                wanted.push_back(path.clone());
+            }
+        }
+        self.pending = in_progress.into_iter().chain(wanted.iter().cloned()).collect();
+        self.queue.update(|paths| *paths = wanted);
+    }
+
+    /// Read a low-resolution preview of `path` (e.g. the EXIF thumbnail) to show
+    /// while the full image is decoded. Images without one are silently skipped.
+    pub fn load_preview(&mut self, path: PathBuf) {
+        let _ = self.preview_tx.send(path);
+    }
+
+    /// Read the length of the video at `path`; it arrives in `videos_ready`.
+    pub fn load_video_duration(&mut self, path: PathBuf) {
+        let _ = self.video_tx.send(VideoRequest::Duration(path));
+    }
+
+    /// Grab the frame `seconds` into the video at `path`; it arrives in `videos_ready`.
+    pub fn load_video_frame(&mut self, path: PathBuf, seconds: f64) {
+        let _ = self.video_tx.send(VideoRequest::Frame(path, seconds));
+    }
+
+    /// Decode `path` to compare it with the current image; it arrives in
+    /// `comparisons_ready`. Requests for an image on its way are ignored.
+    pub fn load_comparison(&mut self, path: PathBuf) {
+        if self.comparisons_requested.insert(path.clone()) {
+            let _ = self.comparison_tx.send(path);
+        }
+    }
+
+    pub fn load_thumbnail(&mut self, path: PathBuf) {
+        if !self.thumbnails_requested.insert(path.clone()) {
+            return;
+        }
+        let _ = self.thumbnail_tx.send(path);
     }
 
     pub fn update(&mut self) {
-        while let Ok(entry) = self.preload_rx.try_recv() {
-            self.pending.remove(&entry.path);
-            self.cache.insert(entry.path.clone(), entry);
+        while let Ok(result) = self.preload_rx.try_recv() {
+            match result {
+                Ok(entry) => {
+                    self.pending.remove(&entry.path);
+                    self.insert_cache(entry);
+                }
+                Err((path, error)) => {
+                    self.pending.remove(&path);
+                    self.failed.insert(path, error);
+                }
+            }
+        }
+        while let Ok(thumbnail) = self.thumbnail_rx.try_recv() {
+            self.thumbnails_ready.push(thumbnail);
+        }
+        while let Ok(preview) = self.preview_rx.try_recv() {
+            self.previews_ready.push(preview);
+        }
+        while let Ok(loaded) = self.video_rx.try_recv() {
+            self.videos_ready.push(loaded);
+        }
+        while let Ok((path, image)) = self.comparison_rx.try_recv() {
+            self.comparisons_requested.remove(&path);
+            self.comparisons_ready.push((path, image));
+        }
+    }
+
+    pub fn get_from_cache(&mut self, path: &Path) -> Option<PreloadedImage> {
+        self.remove_from_cache(path)
+    }
+
+    pub fn remove_from_cache(&mut self, path: &Path) -> Option<PreloadedImage> {
+        let entry = self.cache.remove(path)?;
+        self.cache_bytes = self.cache_bytes.saturating_sub(entry.memory_size());
+        self.cache_lru.retain(|p| p != path);
+        Some(entry)
+    }
+
+    /// Add a decoded image, evicting the least recently used ones if over budget.
+    pub fn insert_cache(&mut self, entry: PreloadedImage) {
+        let path = entry.path.clone();
+        self.remove_from_cache(&path);
+        self.cache_bytes += entry.memory_size();
+        self.cache.insert(path.clone(), entry);
+        self.cache_lru.push_back(path.clone());
+
+        let Some(budget) = self.cache_budget else { return };
+        while self.cache_bytes > budget {
+            // Keep the image that just arrived, it was requested for a reason
+            let Some(oldest) = self.cache_lru.front().filter(|p| **p != path).cloned() else {
+                break;
+            };
+            self.remove_from_cache(&oldest);
         }
     }
 
-    pub fn get_from_cache(&mut self, path: &PathBuf) -> Option<PreloadedImage> {
-        self.cache.remove(path)
+    /// Memory used by cached images in bytes.
+    pub fn cache_bytes(&self) -> u64 {
+        self.cache_bytes
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if let Some(index) = self.cache_lru.iter().position(|p| p == path) {
+            if let Some(path) = self.cache_lru.remove(index) {
+                self.cache_lru.push_back(path);
+            }
+        }
+    }
+
+    /// Write the files that couldn't be loaded and why to `path`, one per line.
+    pub fn write_failure_report(&self, path: &Path) -> anyhow::Result<()> {
+        let mut failed: Vec<_> = self.failed.iter().collect();
+        failed.sort();
+        let report: String = failed
+            .into_iter()
+            .map(|(file, error)| format!("{}\t{error}\n", file.display()))
+            .collect();
+        std::fs::write(path, report).map_err(|err| anyhow::anyhow!("Failed to write {}: {err}", path.display()))
     }
 
     pub fn push_history(&mut self, image: PreloadedImage) {
@@ -297,3 +845,8 @@ impl Loader {
     }
 }
 
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
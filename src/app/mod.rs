@@ -1,8 +1,15 @@
+pub mod batch;
 pub mod canvas;
+pub mod launcher;
 pub mod loader;
 pub mod saver;
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Instant, SystemTime},
+};
 
 use anyhow::{anyhow, Result};
 use eframe::{
@@ -10,40 +17,286 @@ use eframe::{
     egui_wgpu::RenderState,
     App, Frame,
 };
-use image::DynamicImage;
+use image::{DynamicImage, GenericImageView};
 use wgpu;
 
 use crate::{
-    fs_utils::{format_savings_summary, format_deletion_summary, format_overall_summary, format_size, move_with_unique_name, prepare_dir, TRASH_DIR},
-    image_utils::{build_output_image, combine_crops, to_color_image, OutputFormat, PreloadedImage, SaveRequest},
-    ui::{ImageMetrics, KeyboardState},
+    annotations::{class_name, read_selections, write_annotations, AnnotationFormat},
+    archive::write_archive,
+    autocrop::suggest_crops,
+    clipboard::{copy_image, paste_image, pasted_path},
+    config::Preset,
+    contact_sheet::write_contact_sheet,
+    crop::SaveImage,
+    fs_utils::{format_size, sort_into_folder, trash_file, unique_destination, OutputLocation, SessionStats, TrashMode, WorkDirs},
+    journal::{Event, Journal},
+    metadata::{write_rating, RatingTarget, MAX_RATING},
+    pdf,
+    pipeline::{OriginalsMode, SaverOptions},
+    processed::mark_processed,
+    recent::RecentDirs,
+    image_utils::{build_output_image, build_output_image_with, combine_crops_with, labeled_path, mask_path, selection_mask, selection_request, split_into_tiles, CombineStyle, CopyMetadata, ExtraOutput, OutputFormat, tile_path, detect_content_bounds, dhash, hash_distance, straighten, to_color_image, EdgeMap, EncodeSettings, Histogram, PreloadedImage, RedactStyle, SaveRequest, redact, remove_bands, DUPLICATE_HASH_DISTANCE},
+    selection::{Arrangement, Selection, SelectionOutput, SelectionStyle},
+    thumbnail_cache::ThumbnailCache,
+    ui::{draw_histogram, show_help, pixel_readout, selection_readout, ArrowSpeed, ImageMetrics, KeyboardState, Overlays},
+    video::{self, is_video, timestamp},
+};
+
+use self::{
+    batch::spawn_batch_crop,
+    canvas::Canvas,
+    loader::{LoadStats, LoadTimings, Loader, LoaderOptions, VideoLoaded, CORRUPT_REPORT_FILE_NAME},
+    saver::{Saver, SizeEstimate, FAILURE_REPORT_FILE_NAME},
 };
 
-use self::{canvas::Canvas, loader::Loader, saver::Saver};
+/// Change of the output quality per press of + or -
+const QUALITY_STEP: i32 = 5;
+/// Pixels added to (or removed from) every side of the active selection per press of Alt + `+` or `-`
+const GROW_STEP: f32 = 10.0;
+/// Images skipped per press of Page Up or Page Down
+const PAGE_STEP: isize = 10;
+
+/// Startup options for [`ImageCropperApp`], usually derived from the command line.
+#[derive(Clone, Debug)]
+pub struct AppOptions {
+    pub dry_run: bool,
+    pub resave: bool,
+    pub report_sizes: bool,
+    /// Where to write a contact sheet of the images saved this session at exit
+    pub contact_sheet: Option<PathBuf>,
+    /// Zip or tar archive to collect the files written this session in at exit
+    pub export_archive: Option<PathBuf>,
+    /// Output format and encoder settings
+    pub encode: EncodeSettings,
+    pub parallel: usize,
+    /// Number of queued saves at which new crops are held off; unlimited if unset
+    pub max_pending_saves: Option<usize>,
+    /// Flush saved files to disk before they replace the originals
+    pub fsync: bool,
+    /// List cropped, resaved and finished images in `.imagecropper-processed`
+    pub track_processed: bool,
+    pub benchmark: bool,
+    /// Snap selection edges to a grid of this many pixels
+    pub snap: Option<u32>,
+    /// Snap selection edges to strong nearby image edges
+    pub snap_edges: bool,
+    /// Per-channel tolerance used when detecting uniform borders
+    pub trim_tolerance: u8,
+    /// Aspect ratio (width / height) of smart crop suggestions; the image's own ratio if unset
+    pub suggest_aspect: Option<f32>,
+    /// Number of images a batch crop (Shift+Enter) covers; all remaining if unset
+    pub apply_count: Option<usize>,
+    /// Selection presets from the config file, bound to the number keys
+    pub presets: Vec<Preset>,
+    pub arrow_speed: ArrowSpeed,
+    /// Show buttons for the main actions at the top
+    pub toolbar: bool,
+    /// Culling folders bound to the number keys instead of the presets; culling is off if empty
+    pub cull_folders: Vec<PathBuf>,
+    /// Keys of the config's `[move_keys]` and the folders they move images into
+    pub move_keys: Vec<(egui::Key, PathBuf)>,
+    /// Where 0-5 write star ratings; the number keys apply presets if unset
+    pub rating_target: Option<RatingTarget>,
+    /// Save crops as a grid of this many columns x rows of separate tiles
+    pub tiles: Option<(u32, u32)>,
+    /// Arrangement and spacing of multiple selections saved as one image
+    pub combine: CombineStyle,
+    /// Also save a black-and-white mask of the selections as `<name>_mask.png`
+    pub export_mask: bool,
+    /// Write the selections as annotations in this format instead of cropping
+    pub annotate: Option<AnnotationFormat>,
+    /// Hide the selections in the full image this way instead of cropping
+    pub redact: Option<RedactStyle>,
+    /// Color of `--redact fill`
+    pub redact_color: image::Rgba<u8>,
+    /// Remove the selected bands and keep the rest instead of cropping
+    pub remove_bands: bool,
+    /// Names of the annotation classes, bound to the keys 0-9
+    pub class_names: Vec<String>,
+    /// Overlays shown until toggled with Tab
+    pub overlays: Overlays,
+    pub selection_style: SelectionStyle,
+    pub trash_mode: TrashMode,
+    /// Where the trash, originals and temp folders are created
+    pub work_dirs: WorkDirs,
+    /// What happens to source files when they are saved over
+    pub originals: OriginalsMode,
+    /// Where saved images are written
+    pub output: OutputLocation,
+    /// Show load timings of the current image and recent averages
+    pub perf_hud: bool,
+    /// Go to the next image after a crop
+    pub auto_advance: bool,
+    /// Ask before Delete moves an image to the trash
+    pub confirm_delete: bool,
+    /// Delete marks images to be trashed together at the end
+    pub mark_delete: bool,
+    /// Number of images after the current one to preload
+    pub preload_ahead: usize,
+    /// Number of images before the current one to preload
+    pub preload_behind: usize,
+    /// Memory budget of the preload cache in bytes
+    pub cache_budget: Option<u64>,
+    /// On-disk cache of previews of images seen before
+    pub thumbnail_cache: Option<ThumbnailCache>,
+    /// Size decoded images are scaled down to fit into; full resolution if unset
+    pub max_preview: Option<(u32, u32)>,
+    /// Operation log; disabled in dry runs
+    pub journal: Journal,
+    /// Recently processed directories, updated with where this session ends
+    pub recent_dirs: Option<RecentDirs>,
+}
+
+/// Histogram together with the image and region it was computed for.
+pub struct CachedHistogram {
+    pub path: PathBuf,
+    pub image_size: egui::Vec2,
+    pub region: Option<(u32, u32, u32, u32)>,
+    pub histogram: Histogram,
+}
+
+/// Scrubbing state of the video shown instead of an image.
+struct VideoScrub {
+    path: PathBuf,
+    /// Length in seconds
+    duration: f64,
+    /// Time of the shown frame in seconds
+    position: f64,
+}
+
+/// What happens to a grabbed video frame once the saver has written it.
+enum FrameFollowUp {
+    /// Queue the crop of the frame
+    Crop(SaveRequest),
+    /// Put the frame into the queue after this video
+    Enqueue(PathBuf),
+}
 
 pub struct ImageCropperApp {
     pub files: Vec<PathBuf>,
     pub current_index: usize,
     pub dry_run: bool,
-    pub quality: u8,
     pub resave: bool,
-    pub format: OutputFormat,
+    pub encode: EncodeSettings,
+    /// Output quality of the current image chosen with +/-; `encode.quality` if unset
+    pub quality_override: Option<u8>,
     pub image: Option<DynamicImage>,
     pub texture: Option<(egui::TextureId, wgpu::Texture)>,
+    /// Nearest-filtered view of `texture` used by the magnifier loupe
+    pub loupe_texture: Option<egui::TextureId>,
     pub preview_texture: Option<egui::TextureHandle>,
+    /// Output size of the crop shown in preview mode
+    pub size_estimate: Option<SizeEstimate>,
+    /// Low-resolution stand-in for the current image while it is decoded
+    pub quick_preview: Option<(PathBuf, egui::TextureHandle)>,
+    /// Untouched original of the current image for the before/after comparison, by source path
+    pub compare_texture: Option<(PathBuf, egui::TextureHandle)>,
+    /// Show another image next to the current one (B), e.g. to pick the best of a burst
+    pub side_by_side: bool,
+    /// The image shown next to the current one, by path
+    pub side_by_side_texture: Option<(PathBuf, egui::TextureHandle)>,
     pub image_size: egui::Vec2,
+    /// Clockwise quarter turns applied to the current image since it was loaded
+    pub rotation: u8,
+    /// Whether the current image was straightened since it was loaded
+    pub straightened: bool,
     pub canvas: Canvas,
     pub loader: Loader,
     pub saver: Saver,
     pub report_sizes: bool,
     pub benchmark: bool,
+    pub trim_tolerance: u8,
+    pub suggest_aspect: Option<f32>,
+    pub show_filmstrip: bool,
+    pub thumbnail_textures: HashMap<PathBuf, egui::TextureHandle>,
+    pub show_histogram: bool,
+    /// Load timings overlay, toggled with F3
+    pub show_perf_hud: bool,
+    pub load_stats: LoadStats,
+    /// Move on after a crop, toggled with C
+    pub auto_advance: bool,
+    /// Log entries of the crops the saver keeps for the current image, recorded
+    /// once they are queued
+    kept_events: Vec<(PathBuf, Event)>,
+    /// `--confirm-delete`: Delete opens the confirmation dialog instead of trashing right away
+    pub ask_before_delete: bool,
+    /// `--mark-delete`: Delete marks the image instead of trashing it
+    pub mark_delete: bool,
+    /// Images marked with Delete in `--mark-delete` mode, in marking order
+    pub marked_for_deletion: Vec<PathBuf>,
+    /// Whether the review of the marked images is open (X, or when quitting)
+    pub show_deletion_review: bool,
+    /// Quit once the review is done, because it was opened by quitting
+    exit_after_review: bool,
+    /// Frame scrubber of the current image if it is a video
+    video_scrub: Option<VideoScrub>,
+    /// Grabbed video frames being written, by path
+    grabbed_frames: HashMap<PathBuf, FrameFollowUp>,
+    /// Images pasted with Ctrl+V by their generated path, kept in memory until saved
+    pasted: HashMap<PathBuf, DynamicImage>,
+    pub show_help: bool,
+    pub show_toolbar: bool,
+    /// Whether a long press or Delete with `--confirm-delete` opened the dialog confirming deletion
+    pub delete_dialog_open: bool,
+    /// Overlays drawn over the image
+    pub overlays: Overlays,
+    /// Whether Tab hid all overlays
+    pub hide_overlays: bool,
+    pub show_selection_editor: bool,
+    pub histogram: Option<CachedHistogram>,
+    /// Selections of the last committed crop and the size of the image they were drawn on
+    pub last_crop: Option<(Vec<Selection>, egui::Vec2)>,
+    pub apply_count: Option<usize>,
+    /// Cropped images of a running batch crop, in file order
+    pub batch: Option<Receiver<(PathBuf, Result<SaveImage>)>>,
+    pub batch_remaining: usize,
+    pub presets: Vec<Preset>,
+    pub show_presets: bool,
+    pub cull_folders: Vec<PathBuf>,
+    /// Keys of the config's `[move_keys]` and the folders they move images into
+    pub move_keys: Vec<(egui::Key, PathBuf)>,
+    pub rating_target: Option<RatingTarget>,
+    pub tiles: Option<(u32, u32)>,
+    /// Arrangement and spacing of multiple selections in one image; M cycles the layout
+    pub combine: CombineStyle,
+    pub export_mask: bool,
+    pub annotate: Option<AnnotationFormat>,
+    pub redact: Option<RedactStyle>,
+    pub redact_color: image::Rgba<u8>,
+    pub remove_bands: bool,
+    pub trash_mode: TrashMode,
+    pub work_dirs: WorkDirs,
+    pub output: OutputLocation,
+    /// `--track-processed`: D lists the image in `.imagecropper-processed`
+    pub track_processed: bool,
+    /// Perceptual hash of the current image
+    pub current_hash: Option<u64>,
+    /// Hashes of the images shown this session, for duplicate detection
+    pub seen_hashes: Vec<(PathBuf, u64)>,
+    /// Images shown this session, including the ones deleted since
+    viewed: HashSet<PathBuf>,
+    /// Earlier image the current one is a near-duplicate of
+    pub duplicate_of: Option<PathBuf>,
+    pub journal: Journal,
+    /// Recently processed directories, updated with where this session ends
+    recent_dirs: Option<RecentDirs>,
+    contact_sheet: Option<PathBuf>,
+    export_archive: Option<PathBuf>,
+    /// Files written this session, in order: saved images with their extra
+    /// outputs, annotations, video frames and the contact sheet
+    written: Vec<PathBuf>,
+    pub preload_ahead: usize,
+    pub preload_behind: usize,
     pub status: String,
     pub finished: bool,
     pub is_exiting: bool,
     pub exit_attempt_count: usize,
     pub list_completed: bool,
     pub windowed_mode_set: bool,
-    pub completed_conversions: usize,
+    /// Start of the session, for the elapsed time in the summary
+    pub session_start: Instant,
+    pub cropped_images: usize,
+    pub resaved_images: usize,
     pub total_original_bytes: u64,
     pub total_new_bytes: u64,
     pub deleted_files: usize,
@@ -55,34 +308,128 @@ impl ImageCropperApp {
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         files: Vec<PathBuf>,
-        dry_run: bool,
-        quality: u8,
-        resave: bool,
-        report_sizes: bool,
-        format: OutputFormat,
-        parallel: usize,
-        benchmark: bool,
+        options: AppOptions,
     ) -> Result<Self> {
         let wgpu_render_state = cc.wgpu_render_state.as_ref().expect("WGPU enabled");
+        Self::with_render_state(&cc.egui_ctx, wgpu_render_state, files, options)
+    }
+
+    /// Like [`ImageCropperApp::new`], for a window that is already running, e.g.
+    /// after the [`launcher::Launcher`] picked the images.
+    pub fn with_render_state(
+        ctx: &egui::Context,
+        wgpu_render_state: &RenderState,
+        files: Vec<PathBuf>,
+        options: AppOptions,
+    ) -> Result<Self> {
         let device = wgpu_render_state.device.clone();
         let queue = wgpu_render_state.queue.clone();
-        let loader = Loader::with_wgpu(device, queue);
-        let saver = Saver::new(parallel);
-        let canvas = Canvas::new();
+        let mut loader = Loader::with_wgpu(
+            device,
+            queue,
+            LoaderOptions {
+                max_preview: options.max_preview,
+                thumbnail_cache: options.thumbnail_cache.clone(),
+            },
+        );
+        loader.cache_budget = options.cache_budget;
+        let mut saver = Saver::with_options(
+            options.parallel,
+            SaverOptions {
+                work_dirs: options.work_dirs.clone(),
+                originals: options.originals,
+                journal: options.journal.clone(),
+                track_processed: options.track_processed,
+                fsync: options.fsync,
+            },
+        );
+        saver.max_pending = options.max_pending_saves;
+        saver.keep_failed = true;
+        let mut canvas = Canvas::new();
+        canvas.snap_grid = options.snap;
+        canvas.snap_edges = options.snap_edges;
+        canvas.arrow_speed = options.arrow_speed;
+        canvas.style = options.selection_style.clone();
+        if options.annotate.is_some() {
+            canvas.class_names = Some(options.class_names.clone());
+        }
 
         let mut app = Self {
             files,
             current_index: 0,
-            dry_run,
-            quality,
-            resave,
-            report_sizes,
-            benchmark,
-            format,
+            dry_run: options.dry_run,
+            resave: options.resave,
+            report_sizes: options.report_sizes,
+            benchmark: options.benchmark,
+            trim_tolerance: options.trim_tolerance,
+            suggest_aspect: options.suggest_aspect,
+            show_filmstrip: false,
+            thumbnail_textures: HashMap::new(),
+            show_histogram: false,
+            show_perf_hud: options.perf_hud,
+            load_stats: LoadStats::default(),
+            auto_advance: options.auto_advance,
+            kept_events: Vec::new(),
+            ask_before_delete: options.confirm_delete,
+            mark_delete: options.mark_delete,
+            marked_for_deletion: Vec::new(),
+            show_deletion_review: false,
+            exit_after_review: false,
+            video_scrub: None,
+            grabbed_frames: HashMap::new(),
+            pasted: HashMap::new(),
+            show_help: false,
+            show_toolbar: options.toolbar,
+            delete_dialog_open: false,
+            overlays: options.overlays,
+            hide_overlays: false,
+            show_selection_editor: false,
+            last_crop: None,
+            apply_count: options.apply_count,
+            batch: None,
+            batch_remaining: 0,
+            presets: options.presets.clone(),
+            show_presets: false,
+            cull_folders: options.cull_folders.clone(),
+            move_keys: options.move_keys.clone(),
+            rating_target: options.rating_target,
+            tiles: options.tiles,
+            combine: options.combine,
+            export_mask: options.export_mask,
+            annotate: options.annotate,
+            redact: options.redact,
+            redact_color: options.redact_color,
+            remove_bands: options.remove_bands,
+            trash_mode: options.trash_mode,
+            work_dirs: options.work_dirs.clone(),
+            output: options.output.clone(),
+            track_processed: options.track_processed,
+            current_hash: None,
+            seen_hashes: Vec::new(),
+            viewed: HashSet::new(),
+            duplicate_of: None,
+            journal: options.journal.clone(),
+            recent_dirs: options.recent_dirs.clone(),
+            contact_sheet: options.contact_sheet.clone(),
+            export_archive: options.export_archive.clone(),
+            written: Vec::new(),
+            preload_ahead: options.preload_ahead,
+            preload_behind: options.preload_behind,
+            histogram: None,
+            encode: options.encode,
+            quality_override: None,
             image: None,
             texture: None,
+            loupe_texture: None,
             preview_texture: None,
+            size_estimate: None,
+            quick_preview: None,
+            compare_texture: None,
+            side_by_side: false,
+            side_by_side_texture: None,
             image_size: egui::Vec2::new(1.0, 1.0),
+            rotation: 0,
+            straightened: false,
             canvas,
             loader,
             saver,
@@ -92,39 +439,37 @@ impl ImageCropperApp {
             exit_attempt_count: 0,
             list_completed: false,
             windowed_mode_set: false,
-            completed_conversions: 0,
+            session_start: Instant::now(),
+            cropped_images: 0,
+            resaved_images: 0,
             total_original_bytes: 0,
             total_new_bytes: 0,
             deleted_files: 0,
             total_deleted_bytes: 0,
             exit_summary_printed: false,
         };
-        app.load_current_image(&cc.egui_ctx, Some(wgpu_render_state))?;
+        app.load_current_image(ctx, Some(wgpu_render_state))?;
         Ok(app)
     }
 
-fn conversion_summary(&self) -> String {
-         if self.completed_conversions == 0 {
-             "Total conversion savings: 0 B".to_string()
-         } else {
-             format_savings_summary(self.total_original_bytes, self.total_new_bytes)
-         }
-     }
-
-    fn deletion_summary(&self) -> String {
-        if self.deleted_files == 0 {
-            "Total deleted file size: 0 B".to_string()
-        } else {
-            format_deletion_summary(self.total_deleted_bytes)
+    /// Counters of this session for the summary screen and exit output.
+    fn session_stats(&self) -> SessionStats {
+        SessionStats {
+            viewed: self.viewed.len(),
+            cropped: self.cropped_images,
+            resaved: self.resaved_images,
+            deleted: self.deleted_files,
+            original_bytes: self.total_original_bytes,
+            new_bytes: self.total_new_bytes,
+            deleted_bytes: self.total_deleted_bytes,
+            elapsed: self.session_start.elapsed(),
         }
     }
 
-    fn exit_summary(&self) -> String {
-        format_overall_summary(
-            self.total_original_bytes,
-            self.total_new_bytes,
-            self.total_deleted_bytes,
-        )
+    fn show_session_summary(&self, ui: &mut egui::Ui) {
+        for line in self.session_stats().summary_lines() {
+            ui.label(line);
+        }
     }
 
     fn print_exit_summary(&mut self) {
@@ -132,10 +477,80 @@ fn conversion_summary(&self) -> String {
             return;
         }
 
-        println!("{}", self.exit_summary());
+        for line in self.session_stats().summary_lines() {
+            println!("{line}");
+        }
+        if !self.loader.failed.is_empty() {
+            let report = Path::new(CORRUPT_REPORT_FILE_NAME);
+            match self.loader.write_failure_report(report) {
+                Ok(()) => println!("{} images couldn't be loaded, see {}", self.loader.failed.len(), report.display()),
+                Err(err) => eprintln!("{err:#}"),
+            }
+        }
+        if !self.saver.failed.is_empty() {
+            let report = Path::new(FAILURE_REPORT_FILE_NAME);
+            match self.saver.write_failure_report(report) {
+                Ok(()) => println!("{} saves failed, see {}", self.saver.failed.len(), report.display()),
+                Err(err) => eprintln!("{err:#}"),
+            }
+        }
+        if let Some(sheet) = &self.contact_sheet {
+            if self.dry_run {
+                println!("Dry run: would write a contact sheet to {}", sheet.display());
+            } else {
+                // Only images, and they may have been moved or deleted since
+                let is_image = |path: &Path| {
+                    let extension = path.extension().and_then(|ext| ext.to_str());
+                    extension.and_then(OutputFormat::from_extension).is_some()
+                };
+                let outputs: Vec<PathBuf> =
+                    self.written.iter().filter(|path| is_image(path) && path.exists()).cloned().collect();
+                match write_contact_sheet(&outputs, sheet) {
+                    Ok(()) => {
+                        println!("Contact sheet of {} images written to {}", outputs.len(), sheet.display());
+                        self.written.push(sheet.clone());
+                    }
+                    Err(err) => eprintln!("{err:#}"),
+                }
+            }
+        }
+        if let Some(archive) = &self.export_archive {
+            if self.dry_run {
+                println!("Dry run: would collect the written files in {}", archive.display());
+            } else {
+                let mut roots = self.output.roots.clone();
+                roots.extend(self.output.dir.clone());
+                match write_archive(archive, &self.written, &roots, &self.work_dirs) {
+                    Ok(count) => println!("{count} files collected in {}", archive.display()),
+                    Err(err) => eprintln!("{err:#}"),
+                }
+            }
+        }
         self.exit_summary_printed = true;
     }
 
+    /// Add `path` to the files written this session, once even if it was saved again.
+    fn note_written(&mut self, path: PathBuf) {
+        if !self.written.contains(&path) {
+            self.written.push(path);
+        }
+    }
+
+    /// Remember the input directories, with the image shown now to resume at
+    /// unless all images were processed.
+    fn remember_directories(&mut self) {
+        let position = self.current_path().filter(|_| !self.list_completed).map(Path::to_path_buf);
+        let Some(recent) = self.recent_dirs.as_mut().filter(|_| !self.output.roots.is_empty()) else {
+            return;
+        };
+        for root in &self.output.roots {
+            recent.remember(root, position.as_deref().filter(|position| position.starts_with(root)));
+        }
+        if let Err(err) = recent.save() {
+            eprintln!("Failed to remember recent directories: {err:#}");
+        }
+    }
+
     fn finalize_shutdown(&mut self, ctx: &egui::Context) {
         self.print_exit_summary();
         ctx.send_viewport_cmd(ViewportCommand::Close);
@@ -153,6 +568,20 @@ fn conversion_summary(&self) -> String {
             .ok_or_else(|| anyhow!("No images remaining"))?
             .to_path_buf();
 
+        if let Some(image) = self.pasted.get(&path).cloned() {
+            self.image_size = egui::Vec2::new(image.width() as f32, image.height() as f32);
+            self.canvas.clear();
+            self.reset_transform();
+            self.quality_override = None;
+            self.replace_texture(render_state, &image);
+            self.note_image_hash(&path, Some(dhash(&image)));
+            self.image = Some(image);
+            self.quick_preview = None;
+            self.status = format!("Pasted {} ({}/{})", path.display(), self.current_index + 1, self.files.len());
+            self.loader.loading_active = false;
+            return Ok(());
+        }
+
         if let Some(preloaded) = self.loader.get_from_cache(&path) {
             if self.benchmark {
                 println!(
@@ -165,18 +594,16 @@ fn conversion_summary(&self) -> String {
                     preloaded.texture_gen_duration
                 );
             }
+            self.load_stats.record(LoadTimings::of(&preloaded));
             self.image_size =
                 egui::Vec2::new(preloaded.image.width() as f32, preloaded.image.height() as f32);
             self.canvas.clear();
+            self.reset_transform();
+            self.quality_override = None;
             
             let texture_start = std::time::Instant::now();
             
-            // Free previous texture
-            if let Some((id, _)) = self.texture.take() {
-                if let Some(rs) = render_state {
-                    rs.renderer.write().free_texture(&id);
-                }
-            }
+            self.free_texture(render_state);
 
             if let Some(texture) = preloaded.texture {
                 if let Some(rs) = render_state {
@@ -191,12 +618,15 @@ fn conversion_summary(&self) -> String {
             }
 
             self.image = Some(preloaded.image);
+            self.quick_preview = None;
+            self.note_image_hash(&path, preloaded.hash);
             self.status = format!(
                 "Loaded {} ({}/{})",
                 path.display(),
                 self.current_index + 1,
                 self.files.len()
             );
+            self.import_selections(&path);
             self.loader.loading_active = false;
         } else {
             if self.benchmark {
@@ -204,7 +634,11 @@ fn conversion_summary(&self) -> String {
             }
             // Not in cache, start loading if not already
             self.image = None;
-            self.texture = None;
+            self.current_hash = None;
+            self.duplicate_of = None;
+            self.free_texture(render_state);
+            self.reset_transform();
+            self.quality_override = None;
             self.status = format!(
                 "Loading {} ({}/{})",
                 path.display(),
@@ -217,6 +651,7 @@ fn conversion_summary(&self) -> String {
             }
 
             self.loader.load_image(path.clone());
+            self.loader.load_preview(path.clone());
         }
         
         if self.benchmark {
@@ -225,76 +660,401 @@ fn conversion_summary(&self) -> String {
         Ok(())
     }
 
+    /// Show the selections stored in existing annotations or a `.crops.json`
+    /// sidecar of `path`, so they can be reviewed and adjusted.
+    fn import_selections(&mut self, path: &Path) {
+        match read_selections(self.annotate, path, self.image_size) {
+            Ok(Some((file, selections))) if !selections.is_empty() => {
+                self.status = format!("{}, {} selections from {}", self.status, selections.len(), file.display());
+                self.canvas.selections = selections;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("{err:#}");
+                self.status = format!("{err:#}");
+            }
+        }
+    }
+
+    /// Queue the current image, then the next `preload_ahead` and the previous
+    /// `preload_behind` images, dropping requests for images outside that window.
+    fn update_preload_window(&mut self) {
+        if self.files.is_empty() || self.list_completed {
+            return;
+        }
+        let index = self.current_index.min(self.files.len() - 1);
+        let ahead = self.files[index..].iter().take(self.preload_ahead + 1);
+        let behind = self.files[..index].iter().rev().take(self.preload_behind);
+        // Pasted images aren't on disk for the loader to read
+        let window: Vec<PathBuf> = ahead
+            .chain(behind)
+            .filter(|path| !self.pasted.contains_key(*path))
+            .cloned()
+            .collect();
+        self.loader.set_preload_window(&window);
+    }
+
+    /// Whether saves are queued or a batch crop is still producing images.
+    fn has_pending_work(&self) -> bool {
+        !self.saver.pending_saves.is_empty() || self.batch.is_some()
+    }
+
+    /// Count the image at `path` as viewed, remember its hash and look for an
+    /// earlier near-duplicate. Without a hash there is nothing to compare.
+    fn note_image_hash(&mut self, path: &Path, hash: Option<u64>) {
+        self.viewed.insert(path.to_path_buf());
+        self.current_hash = hash;
+        let Some(hash) = hash else {
+            self.duplicate_of = None;
+            return;
+        };
+        self.duplicate_of = self
+            .seen_hashes
+            .iter()
+            .filter(|(seen, _)| seen != path && self.files.contains(seen))
+            .map(|(seen, seen_hash)| (seen, hash_distance(hash, *seen_hash)))
+            .filter(|(_, distance)| *distance <= DUPLICATE_HASH_DISTANCE)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(seen, _)| seen.clone());
+        if !self.seen_hashes.iter().any(|(seen, _)| seen == path) {
+            self.seen_hashes.push((path.to_path_buf(), hash));
+        }
+    }
+
+    fn jump_to_duplicate(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let Some(index) = self
+            .duplicate_of
+            .as_ref()
+            .and_then(|duplicate| self.files.iter().position(|p| p == duplicate))
+        else {
+            self.status = "No duplicate of this image seen".into();
+            return;
+        };
+        self.jump_to(ctx, render_state, index);
+    }
+
+    /// Move the earlier near-duplicate of the current image, or the image shown
+    /// side by side with it, to the trash.
+    fn delete_duplicate(&mut self) {
+        let other = if self.side_by_side {
+            self.side_by_side_target()
+        } else {
+            self.duplicate_of.clone()
+        };
+        let Some(duplicate) = other else {
+            self.status = "No duplicate of this image seen".into();
+            return;
+        };
+        let Some(index) = self.files.iter().position(|p| *p == duplicate) else {
+            return;
+        };
+        if self.dry_run {
+            println!("Dry run: would move {} to the trash", duplicate.display());
+            self.status = format!("Dry run: skipped deleting {}", duplicate.display());
+            return;
+        }
+        let size = std::fs::metadata(&duplicate).map(|meta| meta.len()).ok();
+        match trash_file(&duplicate, self.trash_mode, &self.work_dirs) {
+            Ok(destination) => {
+                self.journal.record(
+                    &duplicate,
+                    Event::Delete {
+                        destination: destination.clone(),
+                        bytes: size,
+                    },
+                );
+                if let Some(size) = size {
+                    self.deleted_files += 1;
+                    self.total_deleted_bytes = self.total_deleted_bytes.saturating_add(size);
+                }
+                self.files.remove(index);
+                if index < self.current_index {
+                    self.current_index -= 1;
+                }
+                self.loader.remove_from_cache(&duplicate);
+                self.seen_hashes.retain(|(seen, _)| *seen != duplicate);
+                self.duplicate_of = None;
+                self.side_by_side_texture = None;
+                self.status = format!("Moved duplicate {} to {}", duplicate.display(), destination);
+            }
+            Err(err) => self.status = format!("Failed to delete duplicate: {err:#}"),
+        }
+    }
+
+    /// Overlays to draw this frame: none while hidden with Tab, otherwise the configured ones.
+    fn visible_overlays(&self) -> Overlays {
+        if self.hide_overlays {
+            Overlays::none()
+        } else {
+            self.overlays
+        }
+    }
+
     fn request_shutdown(&mut self, ctx: &egui::Context) {
+        if !self.marked_for_deletion.is_empty() {
+            // Decide on the marked images before leaving
+            self.show_deletion_review = true;
+            self.exit_after_review = true;
+            return;
+        }
+        self.queue_kept_crop();
+        // A pasted image only exists in memory until it is saved
+        if self.current_path().is_some_and(|path| self.pasted.contains_key(path)) {
+            if self.held_by_full_queue() {
+                return;
+            }
+            self.save_pending_changes();
+        }
         self.finished = true;
-        if self.saver.pending_saves.is_empty() {
+        if !self.has_pending_work() {
             self.finalize_shutdown(ctx);
         }
     }
 
-    fn handle_keyboard(ctx: &egui::Context) -> KeyboardState {
+    fn handle_keyboard(&self, ctx: &egui::Context) -> KeyboardState {
+        // Don't trigger shortcuts while typing into a text field
+        if ctx.wants_keyboard_input() {
+            return KeyboardState::default();
+        }
         ctx.input(|input| KeyboardState {
             next_image: input.key_pressed(egui::Key::Space),
             prev_image: input.key_pressed(egui::Key::Backspace),
-            save_selection: input.key_pressed(egui::Key::Enter),
-            delete: input.key_pressed(egui::Key::Delete),
+            jump_by: match (input.key_pressed(egui::Key::PageDown), input.key_pressed(egui::Key::PageUp)) {
+                (true, false) => PAGE_STEP,
+                (false, true) => -PAGE_STEP,
+                _ => 0,
+            },
+            first_image: input.key_pressed(egui::Key::Home),
+            last_image: input.key_pressed(egui::Key::End),
+            save_selection: input.key_pressed(egui::Key::Enter) && !input.modifiers.shift,
+            batch_crop: input.key_pressed(egui::Key::Enter) && input.modifiers.shift,
+            delete: input.key_pressed(egui::Key::Delete) && !input.modifiers.shift,
+            delete_duplicate: input.key_pressed(egui::Key::Delete) && input.modifiers.shift,
+            jump_to_duplicate: input.key_pressed(egui::Key::J),
+            mark_done: input.key_pressed(egui::Key::D) && !input.modifiers.ctrl,
+            duplicate_selection: input.key_pressed(egui::Key::D) && input.modifiers.ctrl,
             escape: input.key_pressed(egui::Key::Escape),
             move_up: input.key_down(egui::Key::ArrowUp),
             move_down: input.key_down(egui::Key::ArrowDown),
             move_left: input.key_down(egui::Key::ArrowLeft),
             move_right: input.key_down(egui::Key::ArrowRight),
             preview: input.key_down(egui::Key::P),
+            compare: input.key_down(egui::Key::O),
+            quality_up: (input.key_pressed(egui::Key::Plus) || input.key_pressed(egui::Key::Equals))
+                && !input.modifiers.alt,
+            quality_down: input.key_pressed(egui::Key::Minus) && !input.modifiers.alt,
+            grow_selection: match (
+                input.modifiers.alt && (input.key_pressed(egui::Key::Plus) || input.key_pressed(egui::Key::Equals)),
+                input.modifiers.alt && input.key_pressed(egui::Key::Minus),
+            ) {
+                (true, false) => GROW_STEP,
+                (false, true) => -GROW_STEP,
+                _ => 0.0,
+            },
             rotate_cw: input.key_pressed(egui::Key::R) && !input.modifiers.shift,
             rotate_ccw: input.key_pressed(egui::Key::R) && input.modifiers.shift,
+            rotate_selection: {
+                let step = if input.modifiers.shift { 0.1 } else { 1.0 };
+                match (input.key_pressed(egui::Key::OpenBracket), input.key_pressed(egui::Key::CloseBracket)) {
+                    (true, false) => -step,
+                    (false, true) => step,
+                    _ => 0.0,
+                }
+            },
+            cycle_guides: input.key_pressed(egui::Key::G),
+            toggle_shape: input.key_pressed(egui::Key::E),
+            cycle_layout: input.key_pressed(egui::Key::M),
+            straighten: input.key_pressed(egui::Key::T) && !input.modifiers.alt,
+            stretch_width: (input.key_pressed(egui::Key::W) && !input.modifiers.shift)
+                || (input.key_pressed(egui::Key::A) && input.modifiers.ctrl),
+            stretch_height: (input.key_pressed(egui::Key::W) && input.modifiers.shift)
+                || (input.key_pressed(egui::Key::A) && input.modifiers.ctrl),
+            auto_trim: input.key_pressed(egui::Key::A) && !input.modifiers.ctrl,
+            arrange: [
+                (egui::Key::S, Arrangement::SameSize),
+                (egui::Key::T, Arrangement::AlignTops),
+                (egui::Key::L, Arrangement::AlignLefts),
+                (egui::Key::H, Arrangement::DistributeHorizontally),
+                (egui::Key::V, Arrangement::DistributeVertically),
+            ]
+            .into_iter()
+            .find(|(key, _)| input.modifiers.alt && input.key_pressed(*key))
+            .map(|(_, arrangement)| arrangement),
+            suggest_crop: input.key_pressed(egui::Key::S) && !input.modifiers.alt,
+            toggle_filmstrip: input.key_pressed(egui::Key::F),
+            toggle_histogram: input.key_pressed(egui::Key::H) && !input.modifiers.alt,
+            toggle_cursor_readout: input.key_pressed(egui::Key::I),
+            toggle_selection_editor: input.key_pressed(egui::Key::N),
+            repeat_last_crop: input.key_pressed(egui::Key::L) && !input.modifiers.alt,
+            toggle_presets: input.key_pressed(egui::Key::K),
+            toggle_help: input.key_pressed(egui::Key::F1) || input.key_pressed(egui::Key::Questionmark),
+            toggle_overlays: input.key_pressed(egui::Key::Tab),
+            toggle_fullscreen: input.key_pressed(egui::Key::F11),
+            toggle_perf_hud: input.key_pressed(egui::Key::F3),
+            toggle_auto_advance: input.key_pressed(egui::Key::C),
+            review_marked: input.key_pressed(egui::Key::X),
+            toggle_side_by_side: input.key_pressed(egui::Key::B),
+            retry_failed_saves: input.key_pressed(egui::Key::F5),
+            // egui turns pressing Ctrl+V into a text paste, or nothing if the
+            // clipboard holds an image, so react to releasing V instead
+            paste: input.modifiers.command && input.key_released(egui::Key::V),
+            // Ctrl+C arrives as a copy command rather than a key press
+            copy_crop: input.events.contains(&egui::Event::Copy) && !input.modifiers.shift,
+            copy_image: input.events.contains(&egui::Event::Copy) && input.modifiers.shift,
+            quit: false,
+            move_to: self.move_keys.iter().position(|(key, _)| input.key_pressed(*key)),
+            preset: [
+                egui::Key::Num1,
+                egui::Key::Num2,
+                egui::Key::Num3,
+                egui::Key::Num4,
+                egui::Key::Num5,
+                egui::Key::Num6,
+                egui::Key::Num7,
+                egui::Key::Num8,
+                egui::Key::Num9,
+            ]
+            .iter()
+            .position(|key| input.key_pressed(*key)),
+            zero: input.key_pressed(egui::Key::Num0),
+        })
+    }
+
+    /// Whether `--resave` converts the image at `path` to the output format.
+    fn needs_conversion(&self, path: &Path) -> bool {
+        self.resave
+            && path
+                .extension()
+                .map_or(false, |e| e.to_ascii_lowercase() != self.encode.format.extension())
+    }
+
+    /// Whether [`Self::save_pending_changes`] has a new save to queue for the current image.
+    fn has_pending_changes(&self) -> bool {
+        // Videos are only ever read, their frames are saved with Enter
+        self.current_path().filter(|path| !is_video(path)).is_some_and(|path| {
+            // A pasted image is saved as it is unless it was cropped
+            self.needs_conversion(path) || self.has_pending_transform() || self.pasted.contains_key(path)
         })
     }
 
+    /// Queue a save of the current image if it was rotated/straightened or
+    /// `--resave` converts it to the output format. Returns whether a save was queued.
+    fn save_pending_changes(&mut self) -> bool {
+        if self.saver.kept_count() > 0 {
+            return self.queue_kept_crop();
+        }
+        if !self.has_pending_changes() || self.held_by_full_queue() {
+            return false;
+        }
+        let (Some(path), Some(image)) = (self.current_path().map(Path::to_path_buf), self.image.clone()) else {
+            return false;
+        };
+        let needs_conversion = self.needs_conversion(&path);
+        let output_path = self.output.output_path(&path, self.encode.format.extension());
+        let request = SaveRequest {
+            image,
+            path: output_path.clone(),
+            original_path: path.clone(),
+            settings: self.save_settings(),
+            extra_outputs: Vec::new(),
+        };
+
+        match self.saver.queue_save(request) {
+            Ok(_) => {
+                self.resaved_images += 1;
+                self.journal.record(
+                    &path,
+                    Event::Resave {
+                        output: output_path.clone(),
+                    },
+                );
+                self.reset_transform();
+                self.pasted.remove(&path);
+                if let Some(p) = self.files.get_mut(self.current_index) {
+                    *p = output_path.clone();
+                }
+                self.status = if needs_conversion {
+                    format!(
+                        "Converting {} to {}...",
+                        output_path.display(),
+                        self.encode.format.extension().to_uppercase()
+                    )
+                } else {
+                    format!("Saving edited {}...", output_path.display())
+                };
+                true
+            }
+            Err(err) => {
+                let msg = format!("Failed to queue save: {err:#}");
+                eprintln!("{}", msg);
+                self.status = msg;
+                false
+            }
+        }
+    }
+
+    /// Encoder settings for saving the current image, with its quality override.
+    fn save_settings(&self) -> EncodeSettings {
+        EncodeSettings {
+            quality: self.quality_override.unwrap_or(self.encode.quality),
+            ..self.encode
+        }
+    }
+
+    /// Change the output quality of the current image by `step`.
+    fn adjust_quality(&mut self, step: i32) {
+        if !self.encode.format.uses_quality() {
+            self.status = format!(
+                "{} output has no quality setting",
+                self.encode.format.extension().to_uppercase()
+            );
+            return;
+        }
+        let settings = self.save_settings().with_quality_step(step);
+        self.quality_override = (settings.quality != self.encode.quality).then_some(settings.quality);
+        self.status = format!("Quality for this image: {} (default {})", settings.quality, self.encode.quality);
+    }
+
+    /// Jump directly to the image at `index`, saving pending changes first.
+    fn jump_to(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>, index: usize) {
+        if index >= self.files.len() || index == self.current_index || self.held_by_full_queue() {
+            return;
+        }
+        self.save_pending_changes();
+        self.current_index = index;
+        self.list_completed = false;
+        // Drop the preloads around the old position before asking for this one
+        self.update_preload_window();
+        if let Err(err) = self.load_current_image(ctx, render_state) {
+            self.status = format!("{err:#}");
+        }
+    }
+
+    /// Move on without cropping. Pending edits and `--resave` conversions are still saved.
+    fn skip(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        if self.held_by_full_queue() {
+            return;
+        }
+        if let Some(path) = self.current_path().map(Path::to_path_buf) {
+            if !self.save_pending_changes() {
+                self.journal.record(&path, Event::Skip);
+            }
+        }
+        self.advance(ctx, render_state);
+    }
+
     fn advance(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
         let start = std::time::Instant::now();
         if self.files.is_empty() {
             self.request_shutdown(ctx);
             return;
         }
-
-        // Check if we need to resave the current image
-        if self.resave {
-            if let Some(path) = self.current_path().map(Path::to_path_buf) {
-                if path
-                    .extension()
-                    .map_or(false, |e| e.to_ascii_lowercase() != self.format.extension())
-                {
-                    if let Some(image) = self.image.clone() {
-                        let output_path = path.with_extension(self.format.extension());
-                        let request = SaveRequest {
-                            image,
-                            path: output_path.clone(),
-                            original_path: path.clone(),
-                            quality: self.quality,
-                            format: self.format,
-                        };
-
-                        match self.saver.queue_save(request) {
-                            Ok(_) => {
-                                if let Some(p) = self.files.get_mut(self.current_index) {
-                                    *p = output_path.clone();
-                                }
-                                self.status = format!(
-                                    "Converting {} to {}...",
-                                    output_path.display(),
-                                    self.format.extension().to_uppercase()
-                                );
-                            }
-                            Err(err) => {
-                                let msg = format!("Failed to queue save: {err:#}");
-                                eprintln!("{}", msg);
-                                self.status = msg;
-                            }
-                        }
-                    }
-                }
-            }
+        if self.held_by_full_queue() {
+            return;
         }
 
+        self.save_pending_changes();
+
         // Cache current image before moving
         if let (Some(path), Some(image), Some((_id, texture))) = (
             self.current_path().map(Path::to_path_buf),
@@ -308,6 +1068,7 @@ fn conversion_summary(&self) -> String {
             self.loader.push_history(PreloadedImage {
                 path,
                 image,
+                hash: self.current_hash,
                 color_image: None,
                 texture: Some(texture),
                 load_duration: std::time::Duration::default(),
@@ -320,6 +1081,7 @@ fn conversion_summary(&self) -> String {
 
         if self.current_index + 1 >= self.files.len() {
             self.list_completed = true;
+            self.canvas.clear();
             self.status = "All images processed".into();
             return;
         }
@@ -337,6 +1099,7 @@ fn conversion_summary(&self) -> String {
         if self.files.is_empty() {
             return;
         }
+        self.queue_kept_crop();
 
         // Try to pop from history first
         if let Some(entry) = self.loader.pop_history() {
@@ -355,13 +1118,10 @@ fn conversion_summary(&self) -> String {
                 self.image_size =
                     egui::Vec2::new(entry.image.width() as f32, entry.image.height() as f32);
                 self.canvas.clear();
+                self.reset_transform();
+                self.quality_override = None;
                 
-                // Free previous texture
-                if let Some((id, _)) = self.texture.take() {
-                    if let Some(rs) = render_state {
-                        rs.renderer.write().free_texture(&id);
-                    }
-                }
+                self.free_texture(render_state);
 
                 if let Some(texture) = entry.texture {
                     if let Some(rs) = render_state {
@@ -372,6 +1132,7 @@ fn conversion_summary(&self) -> String {
                 }
 
                 self.image = Some(entry.image);
+                self.note_image_hash(&entry.path, entry.hash);
                 self.status = format!(
                     "Loaded {} ({}/{})",
                     self.files[prev_index].display(),
@@ -395,47 +1156,282 @@ fn conversion_summary(&self) -> String {
         }
     }
 
+    /// Record the current image as processed without changing it and go to the next one.
+    fn mark_done(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        if let Some(path) = self.current_path().map(Path::to_path_buf) {
+            if !self.track_processed {
+                self.status = "Run with --track-processed or --skip-processed to mark images as done".into();
+                return;
+            } else if self.dry_run {
+                println!("Dry run: would mark {} as done", path.display());
+            } else if let Err(err) = mark_processed(&path) {
+                self.status = format!("Failed to mark as done: {err:#}");
+                return;
+            } else {
+                self.journal.record(&path, Event::Done);
+            }
+        }
+        self.advance(ctx, render_state);
+    }
+
     fn delete_current(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
         let Some(path) = self.current_path().map(Path::to_path_buf) else {
             self.status = "No image selected".into();
             return;
         };
+        // Crops kept while staying on the image go to the trash with it
+        self.saver.discard_kept();
+        self.kept_events.clear();
+
+        // Pages of PDFs and pasted images aren't files of their own, so they only leave the queue
+        if pdf::page_of(&path).is_some() || self.pasted.remove(&path).is_some() {
+            self.status = format!("Dropped {}", path.display());
+            self.remove_current(ctx, render_state, &path);
+            return;
+        }
 
         if self.dry_run {
-            println!("Dry run: would move {} to {}", path.display(), TRASH_DIR);
+            let destination = match self.trash_mode {
+                TrashMode::Folder => "the trash folder",
+                TrashMode::System => "the system trash",
+            };
+            println!("Dry run: would move {} to {}", path.display(), destination);
             self.status = format!("Dry run: skipped deleting {}", path.display());
             self.advance(ctx, render_state);
             return;
         }
 
         // record deletion statistics
-        if let Ok(meta) = std::fs::metadata(&path) {
+        let size = std::fs::metadata(&path).map(|meta| meta.len()).ok();
+        if let Some(size) = size {
             self.deleted_files += 1;
-            self.total_deleted_bytes = self.total_deleted_bytes.saturating_add(meta.len());
+            self.total_deleted_bytes = self.total_deleted_bytes.saturating_add(size);
             if self.report_sizes {
                 let msg = format!(
                     "Deleted {} ({})",
                     path.display(),
-                    format_size(meta.len())
+                    format_size(size)
                 );
                 println!("{}", msg);
                 self.status = msg.clone();
             }
         }
 
-        let parent = path.parent().unwrap_or_else(|| Path::new("."));
-        let Ok(target_dir) = prepare_dir(parent, TRASH_DIR) else {
-            self.status = "Unable to prepare trash directory".into();
+        let destination = match trash_file(&path, self.trash_mode, &self.work_dirs) {
+            Ok(destination) => destination,
+            Err(err) => {
+                self.status = format!("Failed to delete: {err:#}");
+                return;
+            }
+        };
+
+        self.journal.record(
+            &path,
+            Event::Delete {
+                destination: destination.clone(),
+                bytes: size,
+            },
+        );
+        self.status = format!("Moved {} to {}", path.display(), destination);
+        self.remove_current(ctx, render_state, &path);
+    }
+
+    /// `--mark-delete`: mark the current image for deletion and go to the next one,
+    /// or unmark it if it already is.
+    fn toggle_mark_current(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let Some(path) = self.current_path().map(Path::to_path_buf) else {
+            self.status = "No image selected".into();
+            return;
+        };
+        if let Some(index) = self.marked_for_deletion.iter().position(|marked| *marked == path) {
+            self.marked_for_deletion.remove(index);
+            self.status = format!("Unmarked {} ({} marked)", path.display(), self.marked_for_deletion.len());
+            return;
+        }
+        self.marked_for_deletion.push(path.clone());
+        self.advance(ctx, render_state);
+        self.status = format!(
+            "Marked {} for deletion ({} marked, X: review)",
+            path.display(),
+            self.marked_for_deletion.len()
+        );
+    }
+
+    /// Move all images marked for deletion to the trash and take them out of the list.
+    fn trash_marked(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let current = self.current_path().map(Path::to_path_buf);
+        let (mut moved, mut failed) = (0, 0);
+        for path in std::mem::take(&mut self.marked_for_deletion) {
+            if self.dry_run {
+                println!("Dry run: would move {} to the trash", path.display());
+                continue;
+            }
+            // Cropped or moved in the meantime
+            if !path.exists() {
+                continue;
+            }
+            let size = std::fs::metadata(&path).map(|meta| meta.len()).ok();
+            match trash_file(&path, self.trash_mode, &self.work_dirs) {
+                Ok(destination) => {
+                    self.journal.record(
+                        &path,
+                        Event::Delete {
+                            destination,
+                            bytes: size,
+                        },
+                    );
+                    if let Some(size) = size {
+                        self.deleted_files += 1;
+                        self.total_deleted_bytes = self.total_deleted_bytes.saturating_add(size);
+                    }
+                    if let Some(index) = self.files.iter().position(|p| *p == path) {
+                        self.files.remove(index);
+                        if index < self.current_index {
+                            self.current_index -= 1;
+                        }
+                    }
+                    self.loader.remove_from_cache(&path);
+                    self.seen_hashes.retain(|(seen, _)| *seen != path);
+                    moved += 1;
+                }
+                Err(err) => {
+                    eprintln!("Failed to delete {}: {err:#}", path.display());
+                    failed += 1;
+                }
+            }
+        }
+        self.status = match failed {
+            0 => format!("Moved {moved} marked images to the trash"),
+            _ => format!("Moved {moved} marked images to the trash, {failed} failed (see terminal)"),
+        };
+        if current.is_some_and(|current| !self.files.contains(&current)) {
+            self.canvas.clear();
+            if self.current_index >= self.files.len() {
+                self.list_completed = true;
+            } else if let Err(err) = self.load_current_image(ctx, render_state) {
+                self.status = format!("{err:#}");
+            }
+        }
+    }
+
+    /// Window listing the images marked for deletion, to trash them together,
+    /// keep some or all of them, or go back.
+    fn show_deletion_review_window(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let mut unmark = None;
+        let mut answer = None;
+        egui::Window::new(format!("{} images marked for deletion", self.marked_for_deletion.len()))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    for (index, path) in self.marked_for_deletion.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.small_button("Keep").clicked() {
+                                unmark = Some(index);
+                            }
+                            ui.label(path.display().to_string());
+                        });
+                    }
+                });
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    let trash = format!("Move {} to trash", self.marked_for_deletion.len());
+                    for (label, choice) in [(trash.as_str(), Some(true)), ("Keep all", Some(false)), ("Back", None)] {
+                        let button =
+                            egui::Button::new(egui::RichText::new(label).size(18.0)).min_size(egui::vec2(110.0, 40.0));
+                        if ui.add(button).clicked() {
+                            answer = Some(choice);
+                        }
+                    }
+                });
+            });
+        if let Some(index) = unmark {
+            self.marked_for_deletion.remove(index);
+        }
+        let Some(choice) = answer else { return };
+        self.show_deletion_review = false;
+        match choice {
+            Some(true) => self.trash_marked(ctx, render_state),
+            Some(false) => {
+                self.marked_for_deletion.clear();
+                self.status = "Kept all marked images".into();
+            }
+            None => {
+                self.exit_after_review = false;
+                return;
+            }
+        }
+        if std::mem::take(&mut self.exit_after_review) {
+            self.request_shutdown(ctx);
+        }
+    }
+
+    /// Move the current image into culling folder `index`.
+    fn sort_current(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>, index: usize) {
+        let Some(path) = self.current_path().map(Path::to_path_buf) else {
+            self.status = "No image selected".into();
+            return;
+        };
+        let Some(folder) = self.cull_folders.get(index).cloned() else {
+            self.status = format!("No culling folder {}", index + 1);
+            return;
+        };
+        self.move_current(ctx, render_state, &path, &folder);
+    }
+
+    /// Move the current image at `path` into `folder` and show the next one.
+    fn move_current(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>, path: &Path, folder: &Path) {
+        if self.dry_run {
+            println!("Dry run: would move {} to {}", path.display(), folder.display());
+            self.status = format!("Dry run: skipped sorting {}", path.display());
+            self.advance(ctx, render_state);
+            return;
+        }
+
+        match sort_into_folder(path, folder) {
+            Ok(destination) => {
+                self.journal.record(
+                    path,
+                    Event::Sort {
+                        destination: destination.clone(),
+                    },
+                );
+                self.status = format!("Moved {} to {}", path.display(), folder.display());
+                self.remove_current(ctx, render_state, path);
+            }
+            Err(err) => self.status = format!("Failed to move: {err:#}"),
+        }
+    }
+
+    /// Write star rating `rating` of the current image to `target`.
+    fn rate_current(&mut self, rating: u8, target: RatingTarget) {
+        let Some(path) = self.current_path().map(Path::to_path_buf) else {
+            self.status = "No image selected".into();
             return;
         };
-        if let Err(err) = move_with_unique_name(&path, &target_dir) {
-            self.status = format!("Failed to delete: {err:#}");
+        if rating > MAX_RATING {
+            self.status = format!("Ratings go from 0 to {MAX_RATING}");
             return;
         }
+        if self.dry_run {
+            println!("Dry run: would rate {} with {rating} stars", path.display());
+            self.status = format!("Dry run: skipped rating {}", path.display());
+            return;
+        }
+        match write_rating(&path, rating, target) {
+            Ok(file) => {
+                self.status = format!("Rating: {rating}/{MAX_RATING} ({})", file.display());
+                self.journal.record(&path, Event::Rate { rating, file });
+            }
+            Err(err) => self.status = format!("Failed to write rating: {err:#}"),
+        }
+    }
 
-        self.status = format!("Moved {} to {}", path.display(), TRASH_DIR);
+    /// Drop `path`, the current image that was moved away, from the list and show the next one.
+    fn remove_current(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>, path: &Path) {
         self.canvas.clear();
-        self.loader.cache.remove(&path);
+        self.loader.remove_from_cache(path);
         self.files.remove(self.current_index);
         if self.files.is_empty() {
             self.list_completed = true;
@@ -452,8 +1448,35 @@ fn conversion_summary(&self) -> String {
         }
     }
 
-    fn crop_selections(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) -> bool {
-        let Some(image) = self.image.clone() else {
+    fn queue_full_status(&self) -> String {
+        if self.saver.failed.is_empty() {
+            format!("Save queue full ({}), wait for the encoder to catch up", self.saver.queue_summary())
+        } else {
+            format!(
+                "Save queue full ({}), press F5 to retry the {} failed saves",
+                self.saver.queue_summary(),
+                self.saver.failed.len()
+            )
+        }
+    }
+
+    /// Whether to stay on the current image because its changes can't be queued
+    /// while the save queue is full.
+    fn held_by_full_queue(&mut self) -> bool {
+        if self.saver.is_full() && self.has_pending_changes() {
+            self.status = self.queue_full_status();
+            return true;
+        }
+        false
+    }
+
+    fn crop_selections(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) -> bool {
+        if self.saver.is_full() {
+            // Each queued crop holds its pixels until encoded, so wait instead of piling up
+            self.status = self.queue_full_status();
+            return false;
+        }
+        let Some(image) = self.image.clone() else {
             self.status = "Image not loaded".into();
             return false;
         };
@@ -462,32 +1485,97 @@ fn conversion_summary(&self) -> String {
             return false;
         };
 
-        let Some(final_image) = build_output_image(&image, &self.canvas.selections) else {
-            self.status = "Selections too small".into();
-            return false;
-        };
-
-        let output_path = path.with_extension(self.format.extension());
-
-        // Send to background saver
-        let request = SaveRequest {
+        let mut output_path = self.output.output_path(&path, self.encode.format.extension());
+        let kept = self.saver.kept_count();
+        if kept > 0 {
+            // Further variants of an image we stayed on: photo_2.avif, photo_3.avif, ...
+            output_path = labeled_path(&output_path, &(kept + 1).to_string(), self.encode.format.extension());
+        }
+        let full_request = |final_image| SaveRequest {
             image: final_image,
             path: output_path.clone(),
             original_path: path.clone(),
-            quality: self.quality,
-            format: self.format,
+            settings: self.save_settings(),
+            extra_outputs: Vec::new(),
         };
-
-        if let Err(err) = self.saver.queue_save(request) {
-            let msg = format!("Failed to queue save: {err:#}");
-            eprintln!("{}", msg);
-            self.status = msg;
+        let request = if self.redact.is_some() || self.remove_bands {
+            if self.canvas.selections.is_empty() {
+                self.status = "Select the regions to remove first".into();
+                return false;
+            }
+            match self.redact {
+                Some(style) => {
+                    let mut request = full_request(redact(&image, &self.canvas.selections, style, self.redact_color));
+                    request.settings.metadata = CopyMetadata::ColorProfile;
+                    Some(request)
+                }
+                None => match remove_bands(&image, &self.canvas.selections) {
+                    Some(final_image) => Some(full_request(final_image)),
+                    None => {
+                        self.status = "Nothing of the image would be left".into();
+                        return false;
+                    }
+                },
+            }
+        } else if let Some((columns, rows)) = self.tiles {
+            self.tile_request(&image, &path, &output_path, columns, rows)
+        } else if self.canvas.selections.iter().any(|selection| selection.output.is_some()) {
+            selection_request(&image, &self.canvas.selections, &self.combine, self.save_settings(), &path, &output_path)
+        } else {
+            build_output_image_with(&image, &self.canvas.selections, &self.combine).map(full_request)
+        };
+        let Some(mut request) = request else {
+            self.status = "Selections too small".into();
             return false;
+        };
+        if self.export_mask {
+            request.extra_outputs.push(ExtraOutput {
+                path: mask_path(&output_path),
+                image: selection_mask(image.width(), image.height(), &self.canvas.selections),
+                settings: EncodeSettings::mask(),
+            });
         }
-
-        // Update the file list to point to the new file
-        if let Some(p) = self.files.get_mut(self.current_index) {
-            *p = output_path.clone();
+        let output_path = request.path.clone();
+
+        let queued = self.auto_advance && kept == 0;
+        if queued {
+            if let Err(err) = self.saver.queue_save(request) {
+                let msg = format!("Failed to queue save: {err:#}");
+                eprintln!("{}", msg);
+                self.status = msg;
+                return false;
+            }
+            // Update the file list to point to the new file
+            self.pasted.remove(&path);
+            if let Some(p) = self.files.get_mut(self.current_index) {
+                *p = output_path.clone();
+            }
+        } else {
+            self.saver.keep(request);
+        }
+        self.cropped_images += 1;
+        let selections = self.source_selections(&path);
+        let event = if self.redact.is_some() {
+            Event::redact(&selections, &output_path)
+        } else if self.remove_bands {
+            Event::exclude(&selections, &output_path)
+        } else {
+            Event::crop(&selections, &output_path)
+        };
+        // Kept crops are only logged once they are queued, they may still be deleted
+        if queued {
+            self.journal.record(&path, event);
+        } else {
+            self.kept_events.push((path.clone(), event));
+        }
+        self.last_crop = Some((self.canvas.selections.clone(), self.image_size));
+        // Rotation/straightening is part of the saved crop, don't save it again on advance
+        self.reset_transform();
+
+        if !self.auto_advance {
+            let count = self.saver.kept_count();
+            self.status = format!("Crop {count} of this image kept, saved when you move on (C: move on after crops)");
+            return true;
         }
 
         // Skip to next image immediately
@@ -497,9 +1585,224 @@ fn conversion_summary(&self) -> String {
         true
     }
 
+    /// `--annotate`: write the selections of the current image as annotations and
+    /// move on to the next image.
+    fn annotate_current(
+        &mut self,
+        ctx: &egui::Context,
+        render_state: Option<&RenderState>,
+        format: AnnotationFormat,
+    ) -> bool {
+        let Some(path) = self.current_path().map(Path::to_path_buf) else {
+            self.status = "No image selected".into();
+            return false;
+        };
+        if self.rotation != 0 || self.straightened {
+            self.status = "Annotations can't be written for a rotated or straightened image".into();
+            return false;
+        }
+        let names = self.canvas.class_names.as_deref().unwrap_or_default();
+        match write_annotations(format, &path, self.image_size, &self.canvas.selections, names) {
+            Ok(output) => {
+                self.journal.record(&path, Event::annotate(&self.source_selections(&path), &output));
+                self.note_written(output.clone());
+                self.advance(ctx, render_state);
+                self.status = format!("Annotations written to {}", output.display());
+                true
+            }
+            Err(err) => {
+                self.status = format!("{err:#}");
+                false
+            }
+        }
+    }
+
+    /// Save request for `--tiles`: every selection (or the whole image) split into
+    /// a grid, the first tile as the main output.
+    fn tile_request(
+        &self,
+        image: &DynamicImage,
+        path: &Path,
+        output_path: &Path,
+        columns: u32,
+        rows: u32,
+    ) -> Option<SaveRequest> {
+        let regions = if self.canvas.selections.is_empty() {
+            vec![image.clone()]
+        } else {
+            self.canvas
+                .selections
+                .iter()
+                .filter_map(|selection| build_output_image(image, std::slice::from_ref(selection)))
+                .collect()
+        };
+        let numbered = regions.len() > 1;
+        let mut tiles = Vec::new();
+        for (index, region) in regions.iter().enumerate() {
+            let grid = split_into_tiles(region, columns, rows);
+            let grid_columns = columns.clamp(1, region.width().max(1));
+            for (i, tile) in grid.into_iter().enumerate() {
+                let (row, column) = (i as u32 / grid_columns, i as u32 % grid_columns);
+                tiles.push(ExtraOutput {
+                    path: tile_path(output_path, numbered.then_some(index), row, column),
+                    image: tile,
+                    settings: self.save_settings(),
+                });
+            }
+        }
+        if tiles.is_empty() {
+            return None;
+        }
+        let first = tiles.remove(0);
+        Some(SaveRequest {
+            image: first.image,
+            path: first.path,
+            original_path: path.to_path_buf(),
+            settings: first.settings,
+            extra_outputs: tiles,
+        })
+    }
+
+    /// Queue the crops kept for the current image and log them. Returns whether
+    /// there were any and they were queued.
+    fn queue_kept_crop(&mut self) -> bool {
+        match self.saver.queue_kept() {
+            Some(Ok((output_path, count))) => {
+                for (path, event) in std::mem::take(&mut self.kept_events) {
+                    self.journal.record(&path, event);
+                }
+                if let Some(p) = self.files.get_mut(self.current_index) {
+                    *p = output_path.clone();
+                }
+                self.status = format!("Saving {count} crops of {} in background...", output_path.display());
+                true
+            }
+            Some(Err(err)) => {
+                self.kept_events.clear();
+                let msg = format!("Failed to queue save: {err:#}");
+                eprintln!("{}", msg);
+                self.status = msg;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Release the GPU texture of the current image and any derived texture ids.
+    fn free_texture(&mut self, render_state: Option<&RenderState>) {
+        let loupe_texture = self.loupe_texture.take();
+        if let Some((id, _)) = self.texture.take() {
+            if let Some(rs) = render_state {
+                let mut renderer = rs.renderer.write();
+                renderer.free_texture(&id);
+                if let Some(loupe_id) = loupe_texture {
+                    renderer.free_texture(&loupe_id);
+                }
+            }
+        }
+    }
+
+    /// Texture id of the current image sampled with nearest filtering, so
+    /// magnified pixels stay crisp. Registered on first use.
+    fn loupe_texture_id(&mut self, render_state: Option<&RenderState>) -> Option<egui::TextureId> {
+        if self.loupe_texture.is_none() {
+            let (rs, (_, texture)) = (render_state?, self.texture.as_ref()?);
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let id = rs.renderer.write().register_native_texture(&rs.device, &view, wgpu::FilterMode::Nearest);
+            self.loupe_texture = Some(id);
+        }
+        self.loupe_texture
+    }
+
+    /// Replace the GPU texture of the current image with one generated from `image`.
+    fn replace_texture(&mut self, render_state: Option<&RenderState>, image: &DynamicImage) {
+        self.free_texture(render_state);
+
+        // Create new texture
+        if let Some(rs) = render_state {
+            let rgba = image.to_rgba8();
+            let width = rgba.width();
+            let height = rgba.height();
+
+            let texture_size = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            };
+
+            let texture = rs.device.create_texture(&wgpu::TextureDescriptor {
+                size: texture_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                label: Some("edited_image_texture"),
+                view_formats: &[],
+            });
+
+            rs.queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &rgba,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                texture_size,
+            );
+
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let id = rs.renderer.write().register_native_texture(&rs.device, &view, wgpu::FilterMode::Linear);
+            self.texture = Some((id, texture));
+        }
+    }
+
+    /// The selections in pixels of the file at `path`, which the loader may have
+    /// scaled down for display, as the journal records them.
+    fn source_selections(&self, path: &Path) -> Vec<Selection> {
+        let source = if self.pasted.contains_key(path) {
+            None
+        } else {
+            image::image_dimensions(path).ok()
+        };
+        let Some((width, height)) = source else {
+            return self.canvas.selections.clone();
+        };
+        // The selections were drawn on the rotated image
+        let size = if self.rotation % 2 == 1 {
+            egui::vec2(height as f32, width as f32)
+        } else {
+            egui::vec2(width as f32, height as f32)
+        };
+        self.canvas
+            .selections
+            .iter()
+            .map(|selection| selection.scaled(self.image_size, size))
+            .collect()
+    }
+
+    /// Forget rotation/straightening applied to the previous image.
+    fn reset_transform(&mut self) {
+        self.rotation = 0;
+        self.straightened = false;
+        self.canvas.reset_zoom();
+    }
+
+    /// Whether the current image was rotated or straightened and must be saved.
+    fn has_pending_transform(&self) -> bool {
+        self.rotation != 0 || self.straightened
+    }
+
     fn rotate_current_image(&mut self, _ctx: &egui::Context, render_state: Option<&RenderState>, cw: bool) {
         if let Some(image) = &self.image {
             let start = std::time::Instant::now();
+            let old_size = self.image_size;
             let new_image = if cw {
                 image.rotate90()
             } else {
@@ -507,63 +1810,819 @@ fn conversion_summary(&self) -> String {
             };
 
             self.image_size = egui::Vec2::new(new_image.width() as f32, new_image.height() as f32);
+            self.replace_texture(render_state, &new_image);
+
+            self.image = Some(new_image);
+            self.rotation = if cw { (self.rotation + 1) % 4 } else { (self.rotation + 3) % 4 };
+
+            // Keep selections on the same image content
+            self.canvas.selection_anchor = None;
+            self.canvas.active_handle = None;
+            self.canvas.edge_map = None;
+            for selection in &mut self.canvas.selections {
+                *selection = selection.rotated_90(cw, old_size);
+            }
             
-            // Free previous texture
-            if let Some((id, _)) = self.texture.take() {
-                if let Some(rs) = render_state {
-                    rs.renderer.write().free_texture(&id);
+            if self.benchmark {
+                println!("[Benchmark] Rotation took {:?}", start.elapsed());
+            }
+        }
+    }
+
+    fn straighten_current_image(&mut self, render_state: Option<&RenderState>, degrees: f32) {
+        let Some(image) = &self.image else { return };
+        let start = std::time::Instant::now();
+        let new_image = straighten(image, degrees);
+
+        self.image_size = egui::Vec2::new(new_image.width() as f32, new_image.height() as f32);
+        self.replace_texture(render_state, &new_image);
+        self.image = Some(new_image);
+        self.straightened = true;
+        // Selections don't survive the inner crop
+        self.canvas.clear();
+        self.status = format!("Straightened by {:.1}°", degrees);
+
+        if self.benchmark {
+            println!("[Benchmark] Straighten took {:?}", start.elapsed());
+        }
+    }
+
+    fn suggest_border_trim(&mut self) {
+        let Some(image) = &self.image else {
+            self.status = "Image not loaded".into();
+            return;
+        };
+        match detect_content_bounds(image, self.trim_tolerance) {
+            Some((x, y, w, h)) => {
+                let rect = egui::Rect::from_min_size(
+                    egui::pos2(x as f32, y as f32),
+                    egui::vec2(w as f32, h as f32),
+                );
+                self.canvas.clear();
+                self.canvas.selections.push(Selection::new(rect));
+                self.status = format!("Suggested trim: {w}x{h} at {x},{y}");
+            }
+            None => {
+                self.status = "No uniform border detected".into();
+            }
+        }
+    }
+
+    /// Shift+Enter: crop this and the following images (`--apply-count`, or all
+    /// remaining) with the current selections in the background, and move past them.
+    fn start_batch_crop(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        if self.batch.is_some() {
+            self.status = "A batch crop is already running".into();
+            return;
+        }
+        if self.canvas.selections.is_empty() {
+            self.status = "Draw a selection to apply first".into();
+            return;
+        }
+        if self.redact.is_some() || self.remove_bands {
+            self.status = "Batch crop isn't available with --redact or --remove-bands".into();
+            return;
+        }
+        if self.has_pending_transform() {
+            self.status = "Batch crop works on the files on disk; undo rotation/straightening first".into();
+            return;
+        }
+
+        let start = self.current_index;
+        let end = self
+            .apply_count
+            .map_or(self.files.len(), |count| (start + count.max(1)).min(self.files.len()));
+        let files = self.files[start..end].to_vec();
+        let selections = self.canvas.selections.clone();
+        self.last_crop = Some((selections.clone(), self.image_size));
+        self.batch_remaining = files.len();
+        // Finished crops wait in the channel while the save queue is full, so bound it the same way
+        let capacity = self.saver.max_pending.unwrap_or(files.len());
+        self.batch = Some(spawn_batch_crop(files, selections, self.image_size, self.combine.clone(), capacity));
+        self.canvas.clear();
+        self.status = format!("Batch cropping {} images in background...", end - start);
+
+        // Skip past the batch; the current image is part of it, so don't resave it
+        if end >= self.files.len() {
+            self.list_completed = true;
+        } else {
+            self.current_index = end;
+            if let Err(err) = self.load_current_image(ctx, render_state) {
+                self.status = format!("{err:#}");
+            }
+        }
+    }
+
+    /// Queue saves for images the running batch crop has finished.
+    fn poll_batch(&mut self) {
+        let Some(rx) = &self.batch else { return };
+        let mut results = Vec::new();
+        let done = loop {
+            // Leave finished crops with the batch until the save queue has room
+            if self.saver.max_pending.is_some_and(|max| self.saver.held_saves() + results.len() >= max) {
+                break false;
+            }
+            match rx.try_recv() {
+                Ok(result) => results.push(result),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        for (path, result) in results {
+            self.batch_remaining = self.batch_remaining.saturating_sub(1);
+            let queued = result.and_then(|image| {
+                self.journal.record(&path, image.event(&self.output, self.encode.format));
+                let request = image.into_request(&self.output, self.encode);
+                let output_path = request.path.clone();
+                self.saver.queue_save(request).map(|()| output_path)
+            });
+            match queued {
+                Ok(output_path) => {
+                    self.cropped_images += 1;
+                    if let Some(p) = self.files.iter_mut().find(|p| **p == path) {
+                        *p = output_path;
+                    }
+                }
+                Err(err) => {
+                    let msg = format!("Batch crop failed: {err:#}");
+                    eprintln!("{}", msg);
+                    self.status = msg;
                 }
             }
+        }
 
-            // Create new texture
-            if let Some(rs) = render_state {
-                let rgba = new_image.to_rgba8();
-                let width = rgba.width();
-                let height = rgba.height();
-                
-                let texture_size = wgpu::Extent3d {
-                    width,
-                    height,
-                    depth_or_array_layers: 1,
+        if done {
+            self.batch = None;
+            self.batch_remaining = 0;
+        }
+    }
+
+    /// Replace the selections with a centered selection from preset `index`.
+    fn apply_preset(&mut self, index: usize) {
+        let Some(preset) = self.presets.get(index) else {
+            self.status = format!("No preset {}", index + 1);
+            return;
+        };
+        if self.image.is_none() {
+            self.status = "Image not loaded".into();
+            return;
+        }
+        let Some(selection) = preset.selection(self.image_size) else {
+            self.status = format!("Preset \"{}\" is invalid", preset.name);
+            return;
+        };
+        self.status = format!("Preset: {}", preset.name);
+        self.canvas.clear();
+        self.canvas.selections = vec![selection];
+    }
+
+    /// Popup listing the presets. Returns the index of a clicked preset.
+    fn show_presets_popup(&mut self, ctx: &egui::Context) -> Option<usize> {
+        let mut chosen = None;
+        egui::Window::new("Presets")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                if self.presets.is_empty() {
+                    ui.label("No presets configured");
+                }
+                for (i, preset) in self.presets.iter().enumerate() {
+                    let label = if i < 9 {
+                        format!("{}: {}", i + 1, preset.name)
+                    } else {
+                        preset.name.clone()
+                    };
+                    if ui.button(label).clicked() {
+                        chosen = Some(i);
+                    }
+                }
+            });
+        chosen
+    }
+
+    /// Re-apply the selections of the last committed crop, scaled to the current image.
+    fn repeat_last_crop(&mut self) {
+        let Some((selections, size)) = &self.last_crop else {
+            self.status = "No previous crop to repeat".into();
+            return;
+        };
+        if self.image.is_none() {
+            self.status = "Image not loaded".into();
+            return;
+        }
+        self.canvas.clear();
+        self.canvas.selections = selections
+            .iter()
+            .map(|selection| selection.scaled(*size, self.image_size))
+            .collect();
+        self.status = format!(
+            "Repeated last crop ({} selection(s), Enter: accept)",
+            self.canvas.selections.len()
+        );
+    }
+
+    /// Show the next smart crop suggestion, analyzing the image on first use.
+    fn cycle_crop_suggestion(&mut self) {
+        let Some(image) = &self.image else {
+            self.status = "Image not loaded".into();
+            return;
+        };
+        if self.canvas.suggestions.is_empty() {
+            let aspect = self
+                .suggest_aspect
+                .unwrap_or(self.image_size.x / self.image_size.y.max(1.0));
+            self.canvas.suggestions = suggest_crops(image, aspect, 5)
+                .into_iter()
+                .map(|(x, y, w, h)| {
+                    Selection::new(egui::Rect::from_min_size(
+                        egui::pos2(x as f32, y as f32),
+                        egui::vec2(w as f32, h as f32),
+                    ))
+                })
+                .collect();
+            self.canvas.suggestion_index = 0;
+        } else {
+            self.canvas.suggestion_index =
+                (self.canvas.suggestion_index + 1) % self.canvas.suggestions.len();
+        }
+
+        let Some(suggestion) = self.canvas.suggestions.get(self.canvas.suggestion_index).cloned() else {
+            self.status = "No crop suggestions for this image".into();
+            return;
+        };
+        self.canvas.selections = vec![suggestion];
+        self.status = format!(
+            "Crop suggestion {}/{} (S: next, Enter: accept)",
+            self.canvas.suggestion_index + 1,
+            self.canvas.suggestions.len()
+        );
+    }
+
+    /// Low-resolution texture of the current image to show while it is loading.
+    fn loading_preview(&self) -> Option<&egui::TextureHandle> {
+        let path = self.current_path()?;
+        self.quick_preview
+            .as_ref()
+            .filter(|(preview_path, _)| preview_path == path)
+            .map(|(_, texture)| texture)
+            .or_else(|| self.thumbnail_textures.get(path))
+    }
+
+    /// Insert the image on the clipboard after the current one and show it. It
+    /// gets a name next to the current image and is written when it is cropped
+    /// or skipped.
+    fn paste_image(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        if self.held_by_full_queue() {
+            return;
+        }
+        let image = match paste_image() {
+            Ok(image) => image,
+            Err(err) => {
+                self.status = format!("{err:#}");
+                return;
+            }
+        };
+        let dir = self.current_path().and_then(Path::parent).map(Path::to_path_buf).unwrap_or_default();
+        let taken = |path: &Path| path.exists() || self.files.iter().any(|file| file == path);
+        let path = pasted_path(&dir, SystemTime::now(), taken);
+        let thumbnail = image.thumbnail(loader::THUMBNAIL_SIZE, loader::THUMBNAIL_SIZE);
+        let texture = ctx.load_texture(
+            format!("thumbnail-{}", path.display()),
+            to_color_image(&thumbnail),
+            egui::TextureOptions::LINEAR,
+        );
+        self.thumbnail_textures.insert(path.clone(), texture);
+        self.pasted.insert(path.clone(), image);
+
+        let index = if self.list_completed {
+            self.files.len()
+        } else {
+            (self.current_index + 1).min(self.files.len())
+        };
+        self.files.insert(index, path);
+        self.save_pending_changes();
+        self.current_index = index;
+        self.list_completed = false;
+        if let Err(err) = self.load_current_image(ctx, render_state) {
+            self.status = format!("{err:#}");
+        }
+    }
+
+    /// Copy the crop of the selections, or with `whole_image` (or without
+    /// selections) the current image, to the clipboard without saving anything.
+    fn copy_to_clipboard(&mut self, whole_image: bool) {
+        let Some(image) = &self.image else {
+            self.status = "Image not loaded".into();
+            return;
+        };
+        let copied = if whole_image || self.canvas.selections.is_empty() {
+            Some(image.clone())
+        } else {
+            build_output_image_with(image, &self.canvas.selections, &self.combine)
+        };
+        let Some(copied) = copied else {
+            self.status = "Selections too small".into();
+            return;
+        };
+        self.status = match copy_image(&copied) {
+            Ok(()) => format!("Copied {}x{} image to the clipboard", copied.width(), copied.height()),
+            Err(err) => format!("Failed to copy: {err:#}"),
+        };
+    }
+
+    /// Draw the scrubber below a video, showing the frame at the chosen time once
+    /// the slider is released. The loader reads the length and frames in the background.
+    fn show_video_scrubber(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let Some(path) = self.current_path().filter(|path| is_video(path)).map(Path::to_path_buf) else {
+            self.video_scrub = None;
+            self.loader.videos_ready.clear();
+            return;
+        };
+        if self.video_scrub.as_ref().is_none_or(|scrub| scrub.path != path) {
+            self.loader.load_video_duration(path.clone());
+            self.video_scrub = Some(VideoScrub {
+                path,
+                duration: 0.0,
+                position: 0.0,
+            });
+        }
+        for loaded in std::mem::take(&mut self.loader.videos_ready) {
+            let Some(scrub) = self.video_scrub.as_mut() else { break };
+            match loaded {
+                VideoLoaded::Duration(video, duration) if video == scrub.path => match duration {
+                    Ok(duration) => scrub.duration = duration,
+                    Err(err) => self.status = err,
+                },
+                // Frames of earlier seeks are outdated
+                VideoLoaded::Frame(video, seconds, frame) if video == scrub.path && seconds == scrub.position => {
+                    match frame {
+                        Ok(frame) => self.show_video_frame(render_state, frame),
+                        Err(err) => self.status = format!("Failed to grab frame: {err}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(scrub) = self.video_scrub.as_mut() else {
+            return;
+        };
+        let mut seek = false;
+        egui::TopBottomPanel::bottom("video_scrubber").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(timestamp(scrub.position, ':'));
+                ui.spacing_mut().slider_width = (ui.available_width() - 220.0).max(100.0);
+                let response = ui.add(egui::Slider::new(&mut scrub.position, 0.0..=scrub.duration).show_value(false));
+                // Decoding a frame takes a while, so only seek once dragging stops
+                seek = response.drag_stopped() || (response.changed() && !response.dragged());
+                ui.label(timestamp(scrub.duration, ':'));
+                ui.label("Enter: grab frame");
+            });
+        });
+        if !seek || self.image.is_none() {
+            return;
+        }
+        let (path, position) = (scrub.path.clone(), scrub.position);
+        self.loader.load_video_frame(path, position);
+        self.status = format!("Seeking to {}...", timestamp(position, ':'));
+    }
+
+    /// Show `frame` of the current video in place of the image.
+    fn show_video_frame(&mut self, render_state: Option<&RenderState>, frame: DynamicImage) {
+        self.image_size = egui::Vec2::new(frame.width() as f32, frame.height() as f32);
+        self.replace_texture(render_state, &frame);
+        self.image = Some(frame);
+        self.canvas.edge_map = None;
+        for selection in &mut self.canvas.selections {
+            selection.clamp_within(self.image_size);
+        }
+        if let Some(scrub) = &self.video_scrub {
+            self.status = format!("{} at {}", scrub.path.display(), timestamp(scrub.position, ':'));
+        }
+    }
+
+    /// Save the shown frame of the current video as PNG next to it. The saver
+    /// writes it; then it is queued right after the video, or with selections
+    /// their crop of the frame is saved.
+    fn grab_video_frame(&mut self) -> bool {
+        let (Some(image), Some(scrub)) = (&self.image, &self.video_scrub) else {
+            self.status = "Frame not loaded".into();
+            return false;
+        };
+        let mut frame_path = video::frame_path(&scrub.path, scrub.position);
+        if self.grabbed_frames.contains_key(&frame_path) {
+            self.status = format!("{} is being saved already", frame_path.display());
+            return false;
+        }
+        if frame_path.exists() {
+            let dir = frame_path.parent().map(Path::to_path_buf).unwrap_or_default();
+            frame_path = unique_destination(&dir, frame_path.file_name().unwrap_or_default());
+        }
+        let crop = if self.canvas.selections.is_empty() {
+            None
+        } else {
+            match build_output_image_with(image, &self.canvas.selections, &self.combine) {
+                Some(crop) => Some(crop),
+                None => {
+                    self.status = "Selections too small".into();
+                    return false;
+                }
+            }
+        };
+        if self.dry_run {
+            self.status = format!("Dry run: would grab {}", frame_path.display());
+            return true;
+        }
+        let video_path = scrub.path.clone();
+        // A frame has no file of its own to copy metadata from or back up
+        let frame = SaveRequest {
+            image: image.clone(),
+            path: frame_path.clone(),
+            original_path: frame_path.clone(),
+            settings: EncodeSettings {
+                metadata: CopyMetadata::Nothing,
+                ..EncodeSettings::new(OutputFormat::Png)
+            },
+            extra_outputs: Vec::new(),
+        };
+        if let Err(err) = self.saver.queue_save(frame) {
+            self.status = format!("Failed to queue save: {err:#}");
+            return false;
+        }
+        self.journal.record(&video_path, Event::crop(&[], &frame_path));
+        let follow_up = match crop {
+            Some(crop) => {
+                let output_path = self.output.output_path(&frame_path, self.encode.format.extension());
+                self.journal.record(&frame_path, Event::crop(&self.canvas.selections, &output_path));
+                self.cropped_images += 1;
+                self.status = format!("Grabbing {} and cropping it...", frame_path.display());
+                FrameFollowUp::Crop(SaveRequest {
+                    image: crop,
+                    path: output_path,
+                    original_path: frame_path.clone(),
+                    settings: self.save_settings(),
+                    extra_outputs: Vec::new(),
+                })
+            }
+            None => {
+                self.status = format!("Grabbing {}, it follows the video in the queue", frame_path.display());
+                FrameFollowUp::Enqueue(video_path)
+            }
+        };
+        self.grabbed_frames.insert(frame_path, follow_up);
+        true
+    }
+
+    /// Go on with a grabbed video frame once it is written: queue its crop, or
+    /// put it into the queue after the video and the earlier frames grabbed from it.
+    fn frame_written(&mut self, frame: PathBuf, follow_up: FrameFollowUp) {
+        match follow_up {
+            FrameFollowUp::Crop(request) => {
+                if let Err(err) = self.saver.queue_save(request) {
+                    self.status = format!("Failed to queue save: {err:#}");
+                }
+            }
+            FrameFollowUp::Enqueue(video_path) => {
+                let index = match self.files.iter().position(|path| *path == video_path) {
+                    // Frames are named by their time, so they stay in video order
+                    Some(video_index) => {
+                        let earlier = self.files[video_index + 1..]
+                            .iter()
+                            .take_while(|path| video::is_frame_of(&video_path, path) && **path < frame)
+                            .count();
+                        video_index + 1 + earlier
+                    }
+                    None => self.files.len(),
                 };
+                if index <= self.current_index && !self.files.is_empty() {
+                    self.current_index += 1;
+                }
+                self.files.insert(index, frame);
+            }
+        }
+    }
+
+    /// Draw the thumbnail strip of nearby images and return the index that was clicked.
+    fn show_filmstrip_panel(&mut self, ctx: &egui::Context) -> Option<usize> {
+        const RADIUS: usize = 8;
+        let start = self.current_index.saturating_sub(RADIUS);
+        let end = (self.current_index + RADIUS + 1).min(self.files.len());
+
+        // Pasted images got their thumbnail when they were pasted
+        for path in self.files[start..end].iter().filter(|path| !self.pasted.contains_key(*path)) {
+            self.loader.load_thumbnail(path.clone());
+        }
+        for (path, color_image) in self.loader.thumbnails_ready.drain(..) {
+            let name = format!("thumbnail-{}", path.display());
+            let texture = ctx.load_texture(name, color_image, egui::TextureOptions::LINEAR);
+            self.thumbnail_textures.insert(path, texture);
+        }
 
-                let texture = rs.device.create_texture(&wgpu::TextureDescriptor {
-                    size: texture_size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    label: Some("rotated_image_texture"),
-                    view_formats: &[],
+        let mut clicked = None;
+        let thumb_size = loader::THUMBNAIL_SIZE as f32 * 0.5;
+        egui::TopBottomPanel::bottom("filmstrip")
+            .exact_height(thumb_size + 16.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::horizontal().show(ui, |ui| {
+                    ui.horizontal_centered(|ui| {
+                        for index in start..end {
+                            let path = &self.files[index];
+                            let selected = index == self.current_index;
+                            let response = match self.thumbnail_textures.get(path) {
+                                Some(texture) => {
+                                    let size = texture.size_vec2() * (thumb_size / loader::THUMBNAIL_SIZE as f32);
+                                    ui.add(egui::Button::image(egui::Image::new((texture.id(), size))).selected(selected))
+                                }
+                                None => ui.add_sized(
+                                    [thumb_size, thumb_size],
+                                    egui::Button::new("…").selected(selected),
+                                ),
+                            };
+                            let response = response.on_hover_text(path.display().to_string());
+                            if response.clicked() {
+                                clicked = Some(index);
+                            }
+                            if selected {
+                                response.scroll_to_me(Some(egui::Align::Center));
+                            }
+                        }
+                    });
                 });
+            });
+        clicked
+    }
 
-                rs.queue.write_texture(
-                    wgpu::TexelCopyTextureInfo {
-                        texture: &texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d::ZERO,
-                        aspect: wgpu::TextureAspect::All,
-                    },
-                    &rgba,
-                    wgpu::TexelCopyBufferLayout {
-                        offset: 0,
-                        bytes_per_row: Some(4 * width),
-                        rows_per_image: Some(height),
-                    },
-                    texture_size,
-                );
+    /// Histogram of the active selection (or the whole image), recomputed only
+    /// when the image or the selection changes.
+    fn current_histogram(&mut self) -> Option<&Histogram> {
+        let image = self.image.as_ref()?;
+        let path = self.files.get(self.current_index)?;
+        let region = self
+            .canvas
+            .selections
+            .last()
+            .and_then(|selection| selection.to_u32_bounds());
+        let stale = !matches!(
+            &self.histogram,
+            Some(cached) if cached.path == *path
+                && cached.image_size == self.image_size
+                && cached.region == region
+        );
+        if stale {
+            self.histogram = Some(CachedHistogram {
+                path: path.clone(),
+                image_size: self.image_size,
+                region,
+                histogram: Histogram::from_image(image, region),
+            });
+        }
+        self.histogram.as_ref().map(|cached| &cached.histogram)
+    }
 
-                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-                let id = rs.renderer.write().register_native_texture(&rs.device, &view, wgpu::FilterMode::Linear);
-                self.texture = Some((id, texture));
+    /// Buttons for the main actions, for use without a keyboard. Clicks set the
+    /// same flags in `keys` as the corresponding shortcuts.
+    fn show_toolbar_panel(ctx: &egui::Context, keys: &mut KeyboardState) {
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let buttons = [
+                    ("Prev", "Backspace", &mut keys.prev_image),
+                    ("Next", "Space", &mut keys.next_image),
+                    ("Crop", "Enter", &mut keys.save_selection),
+                    ("Rotate", "R", &mut keys.rotate_cw),
+                    ("Delete", "Delete", &mut keys.delete),
+                    ("Quit", "Exits once pending saves finish", &mut keys.quit),
+                ];
+                for (label, hint, flag) in buttons {
+                    let button =
+                        egui::Button::new(egui::RichText::new(label).size(18.0)).min_size(egui::vec2(96.0, 36.0));
+                    if ui.add(button).on_hover_text(hint).clicked() {
+                        *flag = true;
+                    }
+                }
+            });
+        });
+    }
+
+    /// Dialog opened by a long press on a touchscreen or by Delete with
+    /// `--confirm-delete`, asking whether to trash the current image.
+    fn show_delete_confirmation(&mut self, ctx: &egui::Context, render_state: Option<&RenderState>) {
+        let name = self
+            .current_path()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut answer = ctx.input(|input| {
+            if input.key_pressed(egui::Key::Y) {
+                Some(true)
+            } else if input.key_pressed(egui::Key::N) || input.key_pressed(egui::Key::Escape) {
+                Some(false)
+            } else {
+                None
+            }
+        });
+        egui::Window::new("Move to trash?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Delete {name}? Y/N"));
+                ui.horizontal(|ui| {
+                    for (label, delete) in [("Delete", true), ("Cancel", false)] {
+                        let button =
+                            egui::Button::new(egui::RichText::new(label).size(20.0)).min_size(egui::vec2(120.0, 48.0));
+                        if ui.add(button).clicked() {
+                            answer = Some(delete);
+                        }
+                    }
+                });
+            });
+        if let Some(delete) = answer {
+            self.delete_dialog_open = false;
+            if delete {
+                self.exit_attempt_count = 0;
+                self.delete_current(ctx, render_state);
             }
+        }
+    }
 
-            self.image = Some(new_image);
-            self.canvas.clear(); // Clear selections as they are now invalid
-            
-            if self.benchmark {
-                println!("[Benchmark] Rotation took {:?}", start.elapsed());
+    /// Side panel with editable pixel geometry of the active selection and the
+    /// file it is saved to.
+    fn show_selection_editor_panel(&mut self, ctx: &egui::Context) {
+        let image_size = self.image_size;
+        let image_settings = self.save_settings();
+        let active = self.canvas.active_index();
+        egui::SidePanel::right("selection_editor")
+            .resizable(false)
+            .default_width(180.0)
+            .show(ctx, |ui| {
+                ui.heading("Selection");
+                let Some(index) = active else {
+                    ui.label("Drag on the image to create a selection.");
+                    return;
+                };
+                ui.label(format!("#{} of {}", index + 1, self.canvas.selections.len()));
+                ui.add_space(6.0);
+
+                let selection = &mut self.canvas.selections[index];
+                let rect = selection.rect;
+                let (mut x, mut y) = (rect.min.x.round(), rect.min.y.round());
+                let (mut w, mut h) = (rect.width().round(), rect.height().round());
+                let mut ratio = w / h.max(1.0);
+                let mut changed = false;
+
+                egui::Grid::new("selection_editor_grid").num_columns(2).show(ui, |ui| {
+                    let mut field = |ui: &mut egui::Ui, label: &str, value: &mut f32, max: f32| {
+                        ui.label(label);
+                        changed |= ui
+                            .add(egui::DragValue::new(value).range(0.0..=max).speed(1.0).max_decimals(0))
+                            .changed();
+                        ui.end_row();
+                    };
+                    field(ui, "X", &mut x, image_size.x);
+                    field(ui, "Y", &mut y, image_size.y);
+                    field(ui, "W", &mut w, image_size.x);
+                    field(ui, "H", &mut h, image_size.y);
+
+                    ui.label("Ratio");
+                    if ui
+                        .add(egui::DragValue::new(&mut ratio).range(0.05..=20.0).speed(0.01).max_decimals(3))
+                        .changed()
+                    {
+                        // Keep the width, derive the height from the new ratio
+                        h = (w / ratio).round();
+                        changed = true;
+                    }
+                    ui.end_row();
+                });
+
+                if changed {
+                    selection.rect = egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(w.max(1.0), h.max(1.0)));
+                    selection.clamp_within(image_size);
+                }
+
+                ui.add_space(10.0);
+                let mut separate = selection.output.is_some();
+                if ui.checkbox(&mut separate, "Save as its own file").changed() {
+                    selection.output = separate.then(SelectionOutput::default);
+                }
+                let Some(output) = &mut selection.output else { return };
+                egui::Grid::new("selection_output_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Label");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut output.label)
+                            .hint_text((index + 1).to_string())
+                            .desired_width(100.0),
+                    );
+                    ui.end_row();
+
+                    ui.label("Format");
+                    egui::ComboBox::from_id_salt("selection_output_format")
+                        .selected_text(output.format.map_or("as image", |format| format.extension()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut output.format, None, "as image");
+                            for format in [
+                                OutputFormat::Jpg,
+                                OutputFormat::Png,
+                                OutputFormat::Webp,
+                                OutputFormat::Avif,
+                                OutputFormat::Jxl,
+                                OutputFormat::Tiff,
+                            ] {
+                                if format != OutputFormat::Jxl || cfg!(feature = "jxl") {
+                                    ui.selectable_value(&mut output.format, Some(format), format.extension());
+                                }
+                            }
+                        });
+                    ui.end_row();
+
+                    if output.settings(image_settings).format.uses_quality() {
+                        ui.label("Quality");
+                        let mut quality = output.quality.unwrap_or(image_settings.quality);
+                        if ui.add(egui::DragValue::new(&mut quality).range(1..=100)).changed() {
+                            output.quality = Some(quality);
+                        }
+                        ui.end_row();
+                    }
+                });
+                let settings = output.settings(image_settings);
+                ui.weak(format!("…_{}.{}", output.file_label(index + 1), settings.format.extension()));
+            });
+    }
+
+    /// The untouched original of the current image: its backup if it was saved
+    /// this session, otherwise the file on disk.
+    fn compare_source(&self) -> Option<PathBuf> {
+        let path = self.current_path()?;
+        Some(self.saver.backups.get(path).cloned().unwrap_or_else(|| path.to_path_buf()))
+    }
+
+    /// Request the original of the current image for comparison, see
+    /// [`Self::compare_source`]. Returns whether it is loaded.
+    fn load_compare_texture(&mut self) -> bool {
+        let Some(source) = self.compare_source() else { return false };
+        if self.compare_texture.as_ref().is_some_and(|(loaded, _)| *loaded == source) {
+            return true;
+        }
+        self.loader.load_comparison(source);
+        false
+    }
+
+    /// The image compared side by side with the current one: the near-duplicate
+    /// seen earlier if there is one, otherwise the previous image.
+    fn side_by_side_target(&self) -> Option<PathBuf> {
+        self.duplicate_of
+            .clone()
+            .or_else(|| self.current_index.checked_sub(1).and_then(|index| self.files.get(index).cloned()))
+    }
+
+    /// Load the texture of [`Self::side_by_side_target`] from the preload cache if
+    /// it is still there, otherwise request it. Returns whether there is one to show.
+    fn load_side_by_side_texture(&mut self, ctx: &egui::Context) -> bool {
+        let Some(target) = self.side_by_side_target() else {
+            self.side_by_side_texture = None;
+            return false;
+        };
+        if self.side_by_side_texture.as_ref().is_some_and(|(loaded, _)| *loaded == target) {
+            return true;
+        }
+        let Some(cached) = self.loader.cache.get(&target) else {
+            self.loader.load_comparison(target);
+            return false;
+        };
+        let texture = ctx.load_texture(
+            "side-by-side-texture",
+            to_color_image(&cached.image),
+            egui::TextureOptions::LINEAR,
+        );
+        self.side_by_side_texture = Some((target, texture));
+        true
+    }
+
+    /// Turn the comparison images decoded by the loader into the textures of the
+    /// original or the image side by side, whichever they are still wanted for.
+    fn receive_comparisons(&mut self, ctx: &egui::Context) {
+        for (path, image) in std::mem::take(&mut self.loader.comparisons_ready) {
+            let original = self.compare_source().is_some_and(|source| source == path);
+            let side_by_side = self.side_by_side && self.side_by_side_target().is_some_and(|target| target == path);
+            if !original && !side_by_side {
+                continue;
+            }
+            match image {
+                Ok(color_image) => {
+                    let texture = ctx.load_texture("compare-texture", color_image, egui::TextureOptions::LINEAR);
+                    if original {
+                        self.compare_texture = Some((path.clone(), texture.clone()));
+                    }
+                    if side_by_side {
+                        self.side_by_side_texture = Some((path, texture));
+                    }
+                }
+                Err(err) if original => self.status = format!("Unable to load original {}: {err}", path.display()),
+                Err(err) => {
+                    self.status = format!("Unable to load {}: {err}", path.display());
+                    self.side_by_side = false;
+                }
             }
         }
     }
@@ -587,7 +2646,7 @@ fn conversion_summary(&self) -> String {
         let final_image = if crops.len() == 1 {
             crops[0].clone()
         } else {
-            combine_crops(crops)
+            combine_crops_with(crops, &self.combine)
         };
 
         let color_image = to_color_image(&final_image);
@@ -596,6 +2655,7 @@ fn conversion_summary(&self) -> String {
             color_image,
             egui::TextureOptions::LINEAR,
         ));
+        self.size_estimate = Some(SizeEstimate::spawn(final_image, self.save_settings()));
     }
 }
 
@@ -603,21 +2663,28 @@ impl App for ImageCropperApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut Frame) {
         let render_state = frame.wgpu_render_state();
 
+        // Closing the window goes through the same shutdown as Q, so kept crops are
+        // saved first; `finalize_shutdown` closes it once the saves are done
+        if ctx.input(|input| input.viewport().close_requested()) && !self.exit_summary_printed {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.request_shutdown(ctx);
+        }
+
         self.loader.update();
 
-        // Preload next 64 images
-        if self.image.is_some() {
-            let start = self.current_index + 1;
-            let end = (start + 64).min(self.files.len());
-            for i in start..end {
-                if let Some(path) = self.files.get(i) {
-                    self.loader.load_image(path.clone());
-                }
-            }
-        }
+        self.update_preload_window();
 
         // Check for save completions
-        for (path, result, sizes) in self.saver.check_completions() {
+        let completions = self.saver.check_completions();
+        for path in self.saver.take_written() {
+            self.note_written(path);
+        }
+        for (path, result, sizes) in completions {
+            if let Some(follow_up) = self.grabbed_frames.remove(&path) {
+                if result.is_ok() {
+                    self.frame_written(path.clone(), follow_up);
+                }
+            }
             match result {
                 Err(err) => {
                     let msg = format!("Error saving {}: {err:#}", path.display());
@@ -626,7 +2693,6 @@ impl App for ImageCropperApp {
                 }
                 Ok(()) => {
                     if let Some((original, new)) = sizes {
-                        self.completed_conversions += 1;
                         self.total_original_bytes = self.total_original_bytes.saturating_add(original);
                         self.total_new_bytes = self.total_new_bytes.saturating_add(new);
                     }
@@ -660,11 +2726,25 @@ impl App for ImageCropperApp {
             }
         }
 
-        if self.exit_attempt_count > 0 && self.saver.pending_saves.is_empty() {
+        self.poll_batch();
+        if self.batch.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        if self.exit_attempt_count > 0 && !self.has_pending_work() {
             self.request_shutdown(ctx);
             return;
         }
 
+        self.receive_comparisons(ctx);
+
+        for (path, color_image) in std::mem::take(&mut self.loader.previews_ready) {
+            if self.image.is_none() && self.current_path() == Some(path.as_path()) {
+                let texture = ctx.load_texture("quick-preview", color_image, egui::TextureOptions::LINEAR);
+                self.quick_preview = Some((path, texture));
+            }
+        }
+
         // If image is not loaded, check if it arrived in cache
         if self.image.is_none() {
             if let Some(path) = self.current_path().map(Path::to_path_buf) {
@@ -679,7 +2759,7 @@ impl App for ImageCropperApp {
         }
 
         if self.is_exiting {
-            if self.saver.pending_saves.is_empty() {
+            if !self.has_pending_work() {
                 self.finalize_shutdown(ctx);
             } else {
                 if !self.windowed_mode_set {
@@ -692,11 +2772,10 @@ impl App for ImageCropperApp {
                         ui.vertical_centered(|ui| {
                             ui.heading(format!(
                                 "Finishing background tasks... ({} remaining)",
-                                self.saver.pending_saves.len()
+                                self.saver.pending_saves.len() + self.batch_remaining
                             ));
                             ui.add_space(8.0);
-                            ui.label(self.conversion_summary());
-                            ui.label(self.deletion_summary());
+                            self.show_session_summary(ui);
                         });
                     });
                 });
@@ -705,18 +2784,25 @@ impl App for ImageCropperApp {
             return;
         }
 
+        if self.show_deletion_review {
+            self.show_deletion_review_window(ctx, render_state);
+        }
+
         if self.list_completed {
+            let mut quit = false;
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.centered_and_justified(|ui| {
                     ui.vertical_centered(|ui| {
                         ui.heading("All images processed!");
-                        if !self.saver.pending_saves.is_empty() {
+                        if self.has_pending_work() {
                             ui.add_space(10.0);
-                            ui.label(format!("Processing {} images...", self.saver.pending_saves.len()));
+                            ui.label(format!(
+                                "Processing {} images...",
+                                self.saver.pending_saves.len() + self.batch_remaining
+                            ));
                         }
                         ui.add_space(10.0);
-                        ui.label(self.conversion_summary());
-                        ui.label(self.deletion_summary());
+                        self.show_session_summary(ui);
                         ui.add_space(20.0);
                         if ui.button("Start Over").clicked() {
                             self.list_completed = false;
@@ -725,25 +2811,108 @@ impl App for ImageCropperApp {
                                 self.status = format!("{err:#}");
                             }
                         }
+                        if !self.marked_for_deletion.is_empty() && ui.button("Review marked images").clicked() {
+                            self.show_deletion_review = true;
+                        }
                         ui.add_space(10.0);
                         if ui.button("Quit").clicked() {
-                            self.finished = true;
+                            quit = true;
                         }
                     });
                 });
             });
+            if quit {
+                self.request_shutdown(ctx);
+            }
+            return;
+        }
+
+        // The delete confirmation takes its own keys
+        let mut keys = if self.delete_dialog_open || self.show_deletion_review {
+            KeyboardState::default()
+        } else {
+            self.handle_keyboard(ctx)
+        };
+        if self.show_toolbar {
+            Self::show_toolbar_panel(ctx, &mut keys);
+        }
+
+        if keys.quit {
+            self.request_shutdown(ctx);
             return;
         }
 
-        let keys = Self::handle_keyboard(ctx);
+        if keys.toggle_help {
+            self.show_help = !self.show_help;
+        }
+
+        if keys.toggle_overlays {
+            self.hide_overlays = !self.hide_overlays;
+        }
+
+        if keys.copy_crop || keys.copy_image {
+            self.copy_to_clipboard(keys.copy_image);
+        }
+
+        if keys.paste {
+            self.exit_attempt_count = 0;
+            self.paste_image(ctx, render_state);
+        }
+
+        if keys.retry_failed_saves && !self.saver.failed.is_empty() {
+            self.status = match self.saver.retry_failed() {
+                Ok(count) => format!("Retrying {count} failed saves..."),
+                Err(err) => format!("{err:#}"),
+            };
+        }
+
+        if keys.toggle_perf_hud {
+            self.show_perf_hud = !self.show_perf_hud;
+        }
+
+        if keys.toggle_side_by_side {
+            self.side_by_side = !self.side_by_side;
+            self.status = match self.side_by_side_target().filter(|_| self.side_by_side) {
+                Some(other) => format!("Comparing with {}", other.display()),
+                None if self.side_by_side => "No other image to compare with yet".into(),
+                None => "Side-by-side comparison off".into(),
+            };
+        }
+
+        if keys.review_marked {
+            if self.marked_for_deletion.is_empty() {
+                self.status = "No images marked for deletion".into();
+            } else {
+                self.show_deletion_review = true;
+            }
+        }
+
+        if keys.toggle_auto_advance {
+            self.auto_advance = !self.auto_advance;
+            self.status = if self.auto_advance {
+                "Moving on to the next image after each crop".into()
+            } else {
+                "Staying on the image after crops, Space saves them and moves on".into()
+            };
+        }
+
+        if keys.toggle_fullscreen {
+            let fullscreen = ctx.input(|input| input.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(ViewportCommand::Fullscreen(!fullscreen));
+        }
 
-        if keys.escape {
+        if keys.escape && self.show_help {
+            self.show_help = false;
+        } else if keys.escape && self.canvas.is_drawing_polygon() {
+            self.canvas.polygon_points.clear();
+            self.status = "Polygon cancelled".into();
+        } else if keys.escape {
             if !self.canvas.selections.is_empty() {
                 self.canvas.clear();
                 self.status = "Selection cleared".into();
                 self.exit_attempt_count = 0;
             } else {
-                if self.saver.pending_saves.is_empty() {
+                if !self.has_pending_work() {
                     self.request_shutdown(ctx);
                     return;
                 } else {
@@ -762,17 +2931,32 @@ impl App for ImageCropperApp {
             }
         }
 
-        if keys.save_selection {
+        if keys.save_selection && self.canvas.is_drawing_polygon() {
+            // Ctrl keeps the other selections, as when drawing rectangles
+            let keep_existing = ctx.input(|input| input.modifiers.ctrl);
+            self.status = if self.canvas.close_polygon(self.image_size, keep_existing) {
+                "Polygon closed, press Enter to save".into()
+            } else {
+                "A polygon needs at least 3 points".into()
+            };
+        } else if keys.save_selection {
+            self.exit_attempt_count = 0;
+            // Both advance to the next image, which replaces the selections
+            match self.annotate {
+                _ if self.video_scrub.is_some() => self.grab_video_frame(),
+                Some(format) => self.annotate_current(ctx, render_state, format),
+                None => self.crop_selections(ctx, render_state),
+            };
+        }
+
+        if keys.batch_crop {
             self.exit_attempt_count = 0;
-            if self.crop_selections(ctx, render_state) {
-                // crop_selections now advances automatically
-                self.canvas.clear();
-            }
+            self.start_batch_crop(ctx, render_state);
         }
 
         if keys.next_image {
             self.exit_attempt_count = 0;
-            self.advance(ctx, render_state);
+            self.skip(ctx, render_state);
         }
 
         if keys.prev_image {
@@ -780,9 +2964,44 @@ impl App for ImageCropperApp {
             self.go_back(ctx, render_state);
         }
 
+        if keys.jump_by != 0 && !self.files.is_empty() {
+            self.exit_attempt_count = 0;
+            let index = self.current_index.saturating_add_signed(keys.jump_by);
+            self.jump_to(ctx, render_state, index.min(self.files.len() - 1));
+        }
+
+        if keys.first_image {
+            self.exit_attempt_count = 0;
+            self.jump_to(ctx, render_state, 0);
+        }
+
+        if keys.last_image {
+            self.exit_attempt_count = 0;
+            self.jump_to(ctx, render_state, self.files.len().saturating_sub(1));
+        }
+
         if keys.delete {
+            if self.mark_delete {
+                self.toggle_mark_current(ctx, render_state);
+            } else if self.ask_before_delete {
+                self.delete_dialog_open = true;
+            } else {
+                self.exit_attempt_count = 0;
+                self.delete_current(ctx, render_state);
+            }
+        }
+
+        if keys.mark_done {
             self.exit_attempt_count = 0;
-            self.delete_current(ctx, render_state);
+            self.mark_done(ctx, render_state);
+        }
+
+        if keys.delete_duplicate {
+            self.delete_duplicate();
+        }
+
+        if keys.jump_to_duplicate {
+            self.jump_to_duplicate(ctx, render_state);
         }
 
         if keys.rotate_cw {
@@ -793,13 +3012,168 @@ impl App for ImageCropperApp {
             self.rotate_current_image(ctx, render_state, false);
         }
 
-        self.canvas.handle_arrow_movement(&keys, self.image_size);
+        if keys.rotate_selection != 0.0 {
+            if let Some(angle) = self.canvas.rotate_active(keys.rotate_selection) {
+                // A cached preview shows the old angle
+                self.preview_texture = None;
+                self.status = format!("Selection rotated to {angle:.1}°");
+            }
+        }
+
+        if keys.grow_selection != 0.0 {
+            if let Some(size) = self.canvas.grow_active(keys.grow_selection, self.image_size) {
+                self.preview_texture = None;
+                self.status = format!("Selection resized to {:.0}x{:.0}", size.x, size.y);
+            }
+        }
+
+        if keys.stretch_width || keys.stretch_height {
+            let size = self.canvas.stretch_active(keys.stretch_width, keys.stretch_height, self.image_size);
+            self.preview_texture = None;
+            self.status = format!("Selection stretched to {:.0}x{:.0}", size.x, size.y);
+        }
+
+        if keys.duplicate_selection {
+            self.status = match self.canvas.duplicate_active(self.image_size) {
+                Some(size) => {
+                    self.preview_texture = None;
+                    format!("Selection duplicated ({:.0}x{:.0}), drag it into place", size.x, size.y)
+                }
+                None => "No selection to duplicate".into(),
+            };
+        }
+
+        if let Some(arrangement) = keys.arrange {
+            self.status = if self.canvas.arrange(arrangement, self.image_size) {
+                self.preview_texture = None;
+                format!("Selections {}", arrangement.label())
+            } else {
+                "Draw several selections with Ctrl + Drag to line them up".into()
+            };
+        }
+
+        if keys.toggle_shape {
+            let shape = self.canvas.toggle_shape().label();
+            self.preview_texture = None;
+            self.status = format!("Selection shape: {shape}");
+        }
+
+        if keys.straighten {
+            self.canvas.straighten_mode = !self.canvas.straighten_mode;
+            self.canvas.straighten_line = None;
+            self.status = if self.canvas.straighten_mode {
+                "Straighten: drag along a line that should be horizontal or vertical".into()
+            } else {
+                "Straighten mode off".into()
+            };
+        }
+
+        if keys.auto_trim {
+            self.suggest_border_trim();
+        }
+
+        if keys.suggest_crop {
+            self.cycle_crop_suggestion();
+        }
+
+        if keys.repeat_last_crop {
+            self.repeat_last_crop();
+        }
+
+        if keys.toggle_presets {
+            self.show_presets = !self.show_presets;
+        }
+
+        let mut preset = keys.preset;
+        if self.show_presets {
+            if let Some(index) = self.show_presets_popup(ctx) {
+                preset = Some(index);
+            }
+        }
+        let number = if keys.zero { Some(0) } else { keys.preset.map(|index| index + 1) };
+        if let (Some(_), Some(class)) = (self.annotate, number) {
+            self.canvas.set_class(class as u32);
+            let names = self.canvas.class_names.as_deref().unwrap_or_default();
+            self.status = format!("Class: {}", class_name(class as u32, names));
+        } else if let (Some(target), Some(rating)) = (self.rating_target, number) {
+            self.rate_current(rating as u8, target);
+        } else if let Some(index) = keys.preset.filter(|_| !self.cull_folders.is_empty()) {
+            self.exit_attempt_count = 0;
+            self.sort_current(ctx, render_state, index);
+        } else if let Some(index) = preset {
+            self.show_presets = false;
+            self.apply_preset(index);
+        }
+
+        if let Some((_, folder)) = keys.move_to.and_then(|index| self.move_keys.get(index)).cloned() {
+            self.exit_attempt_count = 0;
+            match self.current_path().map(Path::to_path_buf) {
+                Some(path) => self.move_current(ctx, render_state, &path, &folder),
+                None => self.status = "No image selected".into(),
+            }
+        }
+
+        if keys.toggle_filmstrip {
+            self.show_filmstrip = !self.show_filmstrip;
+        }
+
+        if keys.toggle_histogram {
+            self.show_histogram = !self.show_histogram;
+        }
+
+        if keys.toggle_cursor_readout {
+            self.overlays.cursor = !self.overlays.cursor;
+        }
+
+        if keys.quality_up {
+            self.adjust_quality(QUALITY_STEP);
+        }
+
+        if keys.quality_down {
+            self.adjust_quality(-QUALITY_STEP);
+        }
+
+        if keys.cycle_guides {
+            self.canvas.guides = self.canvas.guides.next();
+            self.status = format!("Composition guides: {}", self.canvas.guides.label());
+        }
+
+        if keys.cycle_layout {
+            self.combine.layout = self.combine.layout.next();
+            // Show the new arrangement if the preview is open
+            self.preview_texture = None;
+            self.status = format!("Layout of multiple selections: {}", self.combine.layout.label());
+        }
+
+        let dt = ctx.input(|input| input.stable_dt);
+        self.canvas.handle_arrow_movement(&keys, self.image_size, dt);
+        if keys.move_up || keys.move_down || keys.move_left || keys.move_right {
+            // Keep animating while held instead of waiting for key repeat events
+            ctx.request_repaint();
+        }
+
+        if keys.toggle_selection_editor {
+            self.show_selection_editor = !self.show_selection_editor;
+        }
+
+        if self.show_selection_editor {
+            self.show_selection_editor_panel(ctx);
+        }
+
+        self.show_video_scrubber(ctx, render_state);
+
+        if self.show_filmstrip {
+            if let Some(index) = self.show_filmstrip_panel(ctx) {
+                self.jump_to(ctx, render_state, index);
+            }
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             let (response, painter) =
                 ui.allocate_painter(ui.available_size(), egui::Sense::hover());
             painter.rect_filled(response.rect, 0.0, Color32::BLACK);
 
+            let overlays = self.visible_overlays();
             let draw_text_with_bg = |pos: egui::Pos2, align: egui::Align2, text: String, font: egui::FontId, color: Color32| {
                 let galley = ctx.fonts_mut(|fonts| fonts.layout_no_wrap(text, font, color));
                 let rect = align.anchor_size(pos, galley.size());
@@ -807,8 +3181,30 @@ impl App for ImageCropperApp {
                 painter.galley(rect.min, galley, Color32::WHITE);
             };
 
-            if keys.preview && !self.canvas.selections.is_empty() {
-                if self.preview_texture.is_none() {
+            if keys.compare && self.load_compare_texture() {
+                if let Some((_, texture)) = &self.compare_texture {
+                    let metrics = ImageMetrics::new(response.rect, texture.size_vec2());
+                    painter.image(
+                        texture.id(),
+                        metrics.image_rect,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+
+                    draw_text_with_bg(
+                        response.rect.left_top() + egui::vec2(10.0, 10.0),
+                        egui::Align2::LEFT_TOP,
+                        "ORIGINAL".to_string(),
+                        egui::FontId::proportional(20.0),
+                        Color32::YELLOW,
+                    );
+                }
+            } else if keys.preview && !self.canvas.selections.is_empty() {
+                let settings_changed = self
+                    .size_estimate
+                    .as_ref()
+                    .is_some_and(|estimate| estimate.settings != self.save_settings());
+                if self.preview_texture.is_none() || settings_changed {
                     self.generate_preview(ctx);
                 }
 
@@ -828,12 +3224,33 @@ impl App for ImageCropperApp {
                         egui::FontId::proportional(20.0),
                         Color32::YELLOW,
                     );
+                    if let Some(estimate) = &mut self.size_estimate {
+                        draw_text_with_bg(
+                            response.rect.left_top() + egui::vec2(10.0, 44.0),
+                            egui::Align2::LEFT_TOP,
+                            estimate.label(),
+                            egui::FontId::proportional(18.0),
+                            Color32::WHITE,
+                        );
+                    }
                 }
             } else {
                 self.preview_texture = None;
+                self.size_estimate = None;
+
+                // Side by side the current image takes the left half, the other one the right
+                let (image_area, other_area) = if self.side_by_side && self.load_side_by_side_texture(ctx) {
+                    let middle = response.rect.center().x;
+                    (
+                        egui::Rect::from_min_max(response.rect.min, egui::pos2(middle, response.rect.max.y)),
+                        Some(egui::Rect::from_min_max(egui::pos2(middle, response.rect.min.y), response.rect.max)),
+                    )
+                } else {
+                    (response.rect, None)
+                };
 
                 if let Some((id, _)) = &self.texture {
-                    let metrics = ImageMetrics::new(response.rect, self.image_size);
+                    let metrics = self.canvas.metrics(image_area, self.image_size);
                     painter.image(
                         *id,
                         metrics.image_rect,
@@ -841,13 +3258,105 @@ impl App for ImageCropperApp {
                         Color32::WHITE,
                     );
 
+                    if let (Some(area), Some((path, texture))) = (other_area, &self.side_by_side_texture) {
+                        // Same zoom and pan as the current image, covering what spills over from it
+                        let other = self.canvas.metrics(area, texture.size_vec2());
+                        let clipped = painter.with_clip_rect(area);
+                        clipped.rect_filled(area, 0.0, Color32::BLACK);
+                        clipped.image(
+                            texture.id(),
+                            other.image_rect,
+                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                            Color32::WHITE,
+                        );
+                        painter.vline(area.min.x, area.y_range(), egui::Stroke::new(2.0, Color32::from_gray(90)));
+                        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                        draw_text_with_bg(
+                            area.center_bottom() + egui::vec2(0.0, -44.0),
+                            egui::Align2::CENTER_BOTTOM,
+                            format!("{name} (Shift+Delete: trash it)"),
+                            egui::FontId::proportional(18.0),
+                            Color32::WHITE,
+                        );
+                    }
+
                     let image_response = ui.interact(
-                        response.rect,
+                        image_area,
                         ui.id().with("image_drag_area"),
                         egui::Sense::click_and_drag(),
                     );
+                    if self.canvas.snap_edges && self.canvas.edge_map.is_none() {
+                        if let Some(image) = &self.image {
+                            self.canvas.edge_map = Some(EdgeMap::new(image));
+                        }
+                    }
                     self.canvas.handle_pointer(&image_response, &metrics, self.image_size, ctx);
                     self.canvas.draw(ui, &painter, &metrics, self.image_size);
+
+                    let dragged = self.canvas.active_index().filter(|_| self.canvas.is_dragging());
+                    if let Some(selection) = dragged.map(|index| &self.canvas.selections[index]) {
+                        // Below the bottom right corner, kept on screen
+                        let corner =
+                            metrics.image_to_screen(selection.bounding_box().right_bottom()) + egui::vec2(8.0, 8.0);
+                        draw_text_with_bg(
+                            corner.min(response.rect.right_bottom() - egui::vec2(260.0, 30.0)),
+                            egui::Align2::LEFT_TOP,
+                            selection_readout(selection.rect.size()),
+                            egui::FontId::monospace(14.0),
+                            Color32::WHITE,
+                        );
+                    }
+
+                    if self.canvas.is_dragging() {
+                        if let (Some(pointer), Some(loupe_id)) =
+                            (ctx.pointer_latest_pos(), self.loupe_texture_id(render_state))
+                        {
+                            self.canvas.draw_loupe(&painter, &metrics, loupe_id, pointer, image_area);
+                        }
+                    }
+
+                    let hovered = image_response.hover_pos().filter(|_| overlays.cursor);
+                    if let (Some(pointer), Some(image)) = (hovered, &self.image) {
+                        let image_pos = metrics.screen_to_image(pointer);
+                        let (x, y) = (image_pos.x.floor(), image_pos.y.floor());
+                        if x >= 0.0 && y >= 0.0 && (x as u32) < image.width() && (y as u32) < image.height() {
+                            let [r, g, b, _] = image.get_pixel(x as u32, y as u32).0;
+                            draw_text_with_bg(
+                                pointer + egui::vec2(16.0, 20.0),
+                                egui::Align2::LEFT_TOP,
+                                pixel_readout(x as u32, y as u32, [r, g, b]),
+                                egui::FontId::monospace(14.0),
+                                Color32::WHITE,
+                            );
+                        }
+                    }
+                } else if let Some((path, error)) =
+                    self.current_path().and_then(|path| self.loader.failed.get_key_value(path))
+                {
+                    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+                    draw_text_with_bg(
+                        response.rect.center(),
+                        egui::Align2::CENTER_CENTER,
+                        format!("{name}\n{error}\n\nSpace: skip   Delete: move to trash"),
+                        egui::FontId::proportional(20.0),
+                        Color32::from_rgb(255, 80, 80),
+                    );
+                } else if let Some(texture) = self.loading_preview() {
+                    // Embedded or filmstrip thumbnail until the full image is decoded
+                    let metrics = ImageMetrics::new(response.rect, texture.size_vec2());
+                    painter.image(
+                        texture.id(),
+                        metrics.image_rect,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                    draw_text_with_bg(
+                        response.rect.center_top() + egui::vec2(0.0, 10.0),
+                        egui::Align2::CENTER_TOP,
+                        "Loading...".to_string(),
+                        egui::FontId::proportional(20.0),
+                        Color32::WHITE,
+                    );
                 } else {
                     painter.text(
                         response.rect.center(),
@@ -859,15 +3368,43 @@ impl App for ImageCropperApp {
                 }
             }
 
+            let marked = self.current_path().is_some_and(|path| self.marked_for_deletion.iter().any(|p| p == path));
+            if marked && !self.hide_overlays {
+                painter.rect_stroke(
+                    response.rect.shrink(2.0),
+                    0.0,
+                    egui::Stroke::new(4.0, Color32::from_rgb(230, 40, 40)),
+                    egui::StrokeKind::Inside,
+                );
+                draw_text_with_bg(
+                    response.rect.center_top() + egui::vec2(0.0, 12.0),
+                    egui::Align2::CENTER_TOP,
+                    "MARKED FOR DELETION (Delete: unmark)".to_string(),
+                    egui::FontId::proportional(20.0),
+                    Color32::from_rgb(255, 80, 80),
+                );
+            }
+
+            // Stays until retried, unlike the status line
+            if !self.saver.failed.is_empty() {
+                draw_text_with_bg(
+                    response.rect.right_bottom() + egui::vec2(-12.0, -68.0),
+                    egui::Align2::RIGHT_BOTTOM,
+                    format!("{} saves failed (F5: retry)", self.saver.failed.len()),
+                    egui::FontId::proportional(16.0),
+                    Color32::from_rgb(255, 80, 80),
+                );
+            }
+
             // Draw spinner if saving
-            if !self.saver.pending_saves.is_empty() {
+            if overlays.saving && !self.saver.pending_saves.is_empty() {
                 let text = if self.saver.pending_saves.len() <= 3 {
                     let names: Vec<_> = self.saver.pending_saves.iter()
                         .filter_map(|p| p.file_name().map(|s| s.to_string_lossy()))
                         .collect();
                     format!("Saving: {}", names.join(", "))
                 } else {
-                    format!("Saving {} images...", self.saver.pending_saves.len())
+                    format!("Saving {}...", self.saver.queue_summary())
                 };
 
                 draw_text_with_bg(
@@ -879,32 +3416,108 @@ impl App for ImageCropperApp {
                 );
             }
 
-            draw_text_with_bg(
-                response.rect.left_bottom() + egui::vec2(12.0, -12.0),
-                egui::Align2::LEFT_BOTTOM,
-                self.status.clone(),
-                egui::FontId::monospace(16.0),
-                Color32::WHITE,
-            );
+            if overlays.status {
+                let mut status = match self.quality_override {
+                    Some(quality) => format!("{} | Quality {quality}", self.status),
+                    None => self.status.clone(),
+                };
+                if !self.auto_advance {
+                    status.push_str(" | Stay after crop");
+                }
+                draw_text_with_bg(
+                    response.rect.left_bottom() + egui::vec2(12.0, -12.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    status,
+                    egui::FontId::monospace(16.0),
+                    Color32::WHITE,
+                );
+            }
 
-            draw_text_with_bg(
-                response.rect.right_bottom() + egui::vec2(-12.0, -12.0),
-                egui::Align2::RIGHT_BOTTOM,
-                "Enter: Save | Space: Next | Backspace: Prev | Delete: Trash | R: Rotate | P: Preview | Esc: Clear/Quit".to_string(),
-                egui::FontId::monospace(16.0),
-                Color32::from_gray(200),
-            );
+            if overlays.hints {
+                draw_text_with_bg(
+                    response.rect.right_bottom() + egui::vec2(-12.0, -12.0),
+                    egui::Align2::RIGHT_BOTTOM,
+                    "F1 / ?: Keyboard shortcuts".to_string(),
+                    egui::FontId::monospace(16.0),
+                    Color32::from_gray(200),
+                );
+            }
+
+            if self.show_histogram && !self.hide_overlays {
+                let histogram_rect = egui::Rect::from_min_size(
+                    response.rect.right_top() + egui::vec2(-12.0 - 264.0, 12.0),
+                    egui::vec2(264.0, 110.0),
+                );
+                if let Some(histogram) = self.current_histogram() {
+                    draw_histogram(&painter, histogram_rect, histogram);
+                }
+            }
+
+            if self.show_perf_hud && !self.hide_overlays {
+                let mut lines = self.load_stats.lines();
+                lines.push(format!(
+                    "cache   {} images, {:.0} MB",
+                    self.loader.cache.len(),
+                    self.loader.cache_bytes() as f64 / (1024.0 * 1024.0)
+                ));
+                draw_text_with_bg(
+                    response.rect.left_top() + egui::vec2(12.0, 84.0),
+                    egui::Align2::LEFT_TOP,
+                    lines.join("\n"),
+                    egui::FontId::monospace(14.0),
+                    Color32::WHITE,
+                );
+            }
 
             // Image X of Y indicator
-            draw_text_with_bg(
-                response.rect.left_top() + egui::vec2(12.0, 12.0),
-                egui::Align2::LEFT_TOP,
-                format!("Image {} of {}", self.current_index + 1, self.files.len()),
-                egui::FontId::proportional(20.0),
-                Color32::WHITE,
-            );
+            if overlays.counter {
+                draw_text_with_bg(
+                    response.rect.left_top() + egui::vec2(12.0, 12.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("Image {} of {}", self.current_index + 1, self.files.len()),
+                    egui::FontId::proportional(20.0),
+                    Color32::WHITE,
+                );
+            }
+
+            if let Some(duplicate) = self.duplicate_of.as_ref().filter(|_| overlays.duplicate) {
+                let name = duplicate.file_name().map_or_else(
+                    || duplicate.display().to_string(),
+                    |name| name.to_string_lossy().into_owned(),
+                );
+                draw_text_with_bg(
+                    response.rect.left_top() + egui::vec2(12.0, 48.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("Possible duplicate of {name} (J: jump, Shift+Delete: trash it)"),
+                    egui::FontId::proportional(18.0),
+                    Color32::from_rgb(255, 170, 0),
+                );
+            }
+
+            if self.show_help {
+                show_help(ctx, response.rect, &mut self.show_help, &self.move_keys);
+            }
         });
 
+        if self.canvas.take_long_press() {
+            self.delete_dialog_open = true;
+        }
+        if self.delete_dialog_open {
+            self.show_delete_confirmation(ctx, render_state);
+        }
+
+        if let Some(angle) = self.canvas.take_straighten_angle() {
+            // Rotate against the tilt of the drawn line to level it
+            self.straighten_current_image(render_state, -angle);
+        }
+
         ctx.request_repaint();
     }
+
+    fn on_exit(&mut self) {
+        // Closes that bypassed `finalize_shutdown` still get the summary, contact
+        // sheet and archive
+        self.print_exit_summary();
+        self.remember_directories();
+    }
 }
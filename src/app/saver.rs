@@ -1,5 +1,8 @@
 use std::{
-    path::PathBuf,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
     sync::{
         mpsc::{self, Receiver, Sender},
         Arc, Mutex,
@@ -8,43 +11,81 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
-use image::codecs::avif::AvifEncoder;
-
-use img_parts::{ImageEXIF, ImageICC};
-use img_parts::jpeg::Jpeg;
-use img_parts::png::Png;
-use img_parts::webp::WebP;
+use image::DynamicImage;
 
 use crate::{
-    fs_utils::{backup_original, prepare_dir, TEMP_DIR},
-    image_utils::{OutputFormat, SaveRequest, SaveStatus},
+    fs_utils::format_size,
+    image_utils::{encode, EncodeSettings, ExtraOutput, SaveRequest, SaveStatus},
+    pipeline::{save, SaverOptions},
 };
 
+/// Default for `--max-pending-saves`
+pub const DEFAULT_MAX_PENDING_SAVES: usize = 32;
+
+/// Report of the saves that still failed at exit, written to the working directory
+pub const FAILURE_REPORT_FILE_NAME: &str = "imagecropper-failed-saves.txt";
+
+/// A save that failed, kept so it can be retried.
+pub struct FailedSave {
+    pub request: SaveRequest,
+    pub error: String,
+}
+
 pub struct Saver {
     save_tx: Sender<SaveRequest>,
-    save_status_rx: Receiver<SaveStatus>,
+    /// Outcome of each save, with the request if it failed
+    save_status_rx: Receiver<(SaveStatus, Option<SaveRequest>)>,
     pub pending_saves: Vec<PathBuf>,
+    /// Memory held by each pending save, in the order of `pending_saves`
+    pending_bytes: Vec<u64>,
+    /// Number of pending saves at which [`Saver::is_full`] holds off new ones;
+    /// unlimited if unset
+    pub max_pending: Option<usize>,
+    /// Keep failed saves in `failed` for [`Saver::retry_failed`]
+    pub keep_failed: bool,
+    pub failed: Vec<FailedSave>,
+    /// Backed up original of each file saved this session, by output path
+    pub backups: HashMap<PathBuf, PathBuf>,
+    /// Files written by the successful saves since the last [`Saver::take_written`]
+    written: Vec<PathBuf>,
+    /// Crops kept back while staying on an image, and how many, until [`Saver::queue_kept`]
+    kept: Option<(SaveRequest, usize)>,
 }
 
 impl Saver {
     pub fn new(concurrency: usize) -> Self {
+        Self::with_options(concurrency, SaverOptions::default())
+    }
+
+    pub fn with_options(concurrency: usize, options: SaverOptions) -> Self {
         let (save_tx, save_rx) = mpsc::channel();
         let (save_status_tx, save_status_rx) = mpsc::channel();
 
         let rx = Arc::new(Mutex::new(save_rx));
 
         for _ in 0..concurrency {
-            Self::spawn_saver_thread(rx.clone(), save_status_tx.clone());
+            Self::spawn_saver_thread(rx.clone(), save_status_tx.clone(), options.clone());
         }
 
         Self {
             save_tx,
             save_status_rx,
             pending_saves: Vec::new(),
+            pending_bytes: Vec::new(),
+            max_pending: None,
+            keep_failed: false,
+            failed: Vec::new(),
+            backups: HashMap::new(),
+            written: Vec::new(),
+            kept: None,
         }
     }
 
-    fn spawn_saver_thread(rx: Arc<Mutex<Receiver<SaveRequest>>>, tx: Sender<SaveStatus>) {
+    fn spawn_saver_thread(
+        rx: Arc<Mutex<Receiver<SaveRequest>>>,
+        tx: Sender<(SaveStatus, Option<SaveRequest>)>,
+        options: SaverOptions,
+    ) {
         thread::spawn(move || {
             loop {
                 let req = {
@@ -55,156 +96,16 @@ impl Saver {
                     }
                 };
 
-                let mut original_size: Option<u64> = None;
-                let mut new_size: Option<u64> = None;
-
-                let result = (|| -> Result<()> {
-                    // capture original size if possible before backup moves the file
-                    if let Ok(meta) = std::fs::metadata(&req.original_path) {
-                        original_size = Some(meta.len());
-                    }
-
-                    let backed_up_path = backup_original(&req.original_path)?;
-
-                    // Save to temp file first
-                    let parent = req.path.parent().unwrap_or_else(|| std::path::Path::new("."));
-                    let temp_dir = prepare_dir(parent, TEMP_DIR)?;
-                    let file_name = req
-                        .path
-                        .file_name()
-                        .ok_or_else(|| anyhow!("No filename"))?;
-                    let temp_path = temp_dir.join(file_name);
-
-                    {
-                        let file = std::fs::File::create(&temp_path)?;
-                        let writer = std::io::BufWriter::new(file);
-                        match req.format {
-                            OutputFormat::Jpg => {
-                                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
-                                    writer,
-                                    req.quality,
-                                );
-                                req.image.write_with_encoder(encoder)?;
-                            }
-                            OutputFormat::Png => {
-                                let encoder = image::codecs::png::PngEncoder::new(writer);
-                                req.image.write_with_encoder(encoder)?;
-                            }
-                            OutputFormat::Webp => {
-                                let encoder = image::codecs::webp::WebPEncoder::new_lossless(writer);
-                                req.image.write_with_encoder(encoder)?;
-                            }
-                            OutputFormat::Avif => {
-                                let encoder =
-                                    AvifEncoder::new_with_speed_quality(writer, 4, req.quality);
-                                req.image.write_with_encoder(encoder)?;
-                            }
-                        }
-                    } // Close file
-
-                    // Move to final destination
-                    // std::fs::rename(&temp_path, &req.path)?; // We do this later now
-
-                    // Try to copy EXIF/ICC from original to new file
-                    // We read the temp file, inject metadata, and write to final path.
-                    // If injection fails, we just move the temp file.
-                    
-                    let copy_metadata = || -> Result<()> {
-                        let input_data = std::fs::read(&backed_up_path)?;
-                        let temp_data = std::fs::read(&temp_path)?;
-                        
-                        // Detect input format and extract metadata
-                        let (exif, icc) = if let Ok(input_jpeg) = Jpeg::from_bytes(input_data.clone().into()) {
-                            (input_jpeg.exif(), input_jpeg.icc_profile())
-                        } else if let Ok(input_png) = Png::from_bytes(input_data.clone().into()) {
-                            (input_png.exif(), input_png.icc_profile())
-                        } else if let Ok(input_webp) = WebP::from_bytes(input_data.clone().into()) {
-                            (input_webp.exif(), input_webp.icc_profile())
-                        } else {
-                            (None, None)
-                        };
-
-                        if exif.is_none() && icc.is_none() {
-                            // No metadata to copy, just move file
-                            std::fs::rename(&temp_path, &req.path)?;
-                            return Ok(());
-                        }
-
-                        // Inject into output
-                        let output_bytes = match req.format {
-                            OutputFormat::Jpg => {
-                                if let Ok(mut out_jpeg) = Jpeg::from_bytes(temp_data.into()) {
-                                    if let Some(exif) = exif { out_jpeg.set_exif(Some(exif)); }
-                                    if let Some(icc) = icc { out_jpeg.set_icc_profile(Some(icc)); }
-                                    let mut out = Vec::new();
-                                    out_jpeg.encoder().write_to(&mut out)?;
-                                    Some(out)
-                                } else { None }
-                            }
-                            OutputFormat::Png => {
-                                if let Ok(mut out_png) = Png::from_bytes(temp_data.into()) {
-                                    if let Some(exif) = exif { out_png.set_exif(Some(exif)); }
-                                    if let Some(icc) = icc { out_png.set_icc_profile(Some(icc)); }
-                                    let mut out = Vec::new();
-                                    out_png.encoder().write_to(&mut out)?;
-                                    Some(out)
-                                } else { None }
-                            }
-                            OutputFormat::Webp => {
-                                if let Ok(mut out_webp) = WebP::from_bytes(temp_data.into()) {
-                                    if let Some(exif) = exif { out_webp.set_exif(Some(exif)); }
-                                    if let Some(icc) = icc { out_webp.set_icc_profile(Some(icc)); }
-                                    let mut out = Vec::new();
-                                    out_webp.encoder().write_to(&mut out)?;
-                                    Some(out)
-                                } else { None }
-                            }
-                            OutputFormat::Avif => {
-                                // img-parts doesn't support AVIF yet?
-                                // AVIF is based on ISOBMFF (HEIF). img-parts has some support?
-                                // It seems img-parts 0.3 doesn't have explicit AVIF support.
-                                // So we skip AVIF metadata copy for now.
-                                None
-                            }
-                        };
-
-                        if let Some(bytes) = output_bytes {
-                            std::fs::write(&req.path, bytes)?;
-                            std::fs::remove_file(&temp_path)?;
-                        } else {
-                            std::fs::rename(&temp_path, &req.path)?;
-                        }
-                        Ok(())
-                    };
-
-                    if let Err(e) = copy_metadata() {
-                        eprintln!("Failed to copy metadata: {}", e);
-                        // Fallback: just move the file if it hasn't been moved yet
-                        if temp_path.exists() {
-                            std::fs::rename(&temp_path, &req.path)?;
-                        }
-                    }
-
-                    // capture new file size if possible
-
-                    // capture new file size if possible
-                    if let Ok(meta) = std::fs::metadata(&req.path) {
-                        new_size = Some(meta.len());
-                    }
-                    Ok(())
-                })();
-                let _ = tx.send(SaveStatus {
-                    path: req.path,
-                    result,
-                    original_size,
-                    new_size,
-                });
+                let status = save(&req, &options);
+                let failed = status.result.is_err().then_some(req);
+                let _ = tx.send((status, failed));
             }
         });
     }
 
     pub fn queue_save(&mut self, request: SaveRequest) -> Result<()> {
         self.pending_saves.push(request.path.clone());
+        self.pending_bytes.push(request.memory_size());
         self.save_tx
             .send(request)
             .map_err(|e| anyhow!("Failed to send save request: {}", e))
@@ -212,9 +113,23 @@ impl Saver {
 
     pub fn check_completions(&mut self) -> Vec<(PathBuf, Result<()>, Option<(u64, u64)>)> {
         let mut completed = Vec::new();
-        while let Ok(status) = self.save_status_rx.try_recv() {
+        while let Ok((status, failed)) = self.save_status_rx.try_recv() {
             if let Some(idx) = self.pending_saves.iter().position(|p| *p == status.path) {
                 self.pending_saves.remove(idx);
+                self.pending_bytes.remove(idx);
+            }
+            if status.result.is_ok() {
+                self.written.push(status.path.clone());
+                self.written.extend(status.extra_paths);
+            }
+            if let (Ok(()), Some(backup_path)) = (&status.result, status.backup_path) {
+                self.backups.insert(status.path.clone(), backup_path);
+            }
+            if let (Err(err), Some(request), true) = (&status.result, failed, self.keep_failed) {
+                self.failed.push(FailedSave {
+                    request,
+                    error: format!("{err:#}"),
+                });
             }
             let sizes = match (status.original_size, status.new_size) {
                 (Some(original), Some(new)) => Some((original, new)),
@@ -224,5 +139,138 @@ impl Saver {
         }
         completed
     }
+
+    /// Hold `request` back until [`Saver::queue_kept`], e.g. while staying on an image
+    /// to cut several variants out of it. The first kept crop is the main output,
+    /// later ones are written as extra outputs after the same backup.
+    pub fn keep(&mut self, request: SaveRequest) {
+        match &mut self.kept {
+            Some((kept, count)) => {
+                kept.extra_outputs.push(ExtraOutput {
+                    path: request.path,
+                    image: request.image,
+                    settings: request.settings,
+                });
+                kept.extra_outputs.extend(request.extra_outputs);
+                *count += 1;
+            }
+            None => self.kept = Some((request, 1)),
+        }
+    }
+
+    /// Number of crops held back by [`Saver::keep`].
+    pub fn kept_count(&self) -> usize {
+        self.kept.as_ref().map_or(0, |(_, count)| *count)
+    }
+
+    /// Queue the kept crops as one save. Returns its output path and how many
+    /// crops it holds, `None` if nothing was kept.
+    pub fn queue_kept(&mut self) -> Option<Result<(PathBuf, usize)>> {
+        let (request, count) = self.kept.take()?;
+        let output_path = request.path.clone();
+        Some(self.queue_save(request).map(|()| (output_path, count)))
+    }
+
+    /// Drop the kept crops without saving them.
+    pub fn discard_kept(&mut self) {
+        self.kept = None;
+    }
+
+    /// Files written by the saves completed since the last call, with their extra
+    /// outputs, in order.
+    pub fn take_written(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.written)
+    }
+
+    /// Number of saves holding their pixels: the queued ones, the failed ones
+    /// kept for a retry and the crops kept back until their image is left.
+    pub fn held_saves(&self) -> usize {
+        self.pending_saves.len() + self.failed.len() + self.kept_count()
+    }
+
+    /// Whether `max_pending` saves are queued, being encoded, kept back or failed
+    /// and kept, so no more should be added until some finish or are retried.
+    pub fn is_full(&self) -> bool {
+        self.max_pending.is_some_and(|max| self.held_saves() >= max)
+    }
+
+    /// Memory held by the images waiting to be saved, kept back or retried, in bytes.
+    pub fn pending_bytes(&self) -> u64 {
+        let failed: u64 = self.failed.iter().map(|failure| failure.request.memory_size()).sum();
+        let kept = self.kept.as_ref().map_or(0, |(request, _)| request.memory_size());
+        self.pending_bytes.iter().sum::<u64>() + failed + kept
+    }
+
+    /// Queue all failed saves again. Returns how many were queued.
+    pub fn retry_failed(&mut self) -> Result<usize> {
+        let failed = std::mem::take(&mut self.failed);
+        let count = failed.len();
+        for failure in failed {
+            self.queue_save(failure.request)?;
+        }
+        Ok(count)
+    }
+
+    /// Write the paths and errors of the failed saves to `path`, one per line.
+    pub fn write_failure_report(&self, path: &Path) -> Result<()> {
+        let mut report = String::new();
+        for failure in &self.failed {
+            let _ = writeln!(report, "{}\t{}", failure.request.original_path.display(), failure.error);
+        }
+        fs::write(path, report).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Number and memory of pending and failed saves, e.g. `5 images, 120.0 MB`.
+    pub fn queue_summary(&self) -> String {
+        format!("{} images, {}", self.held_saves(), format_size(self.pending_bytes()))
+    }
 }
 
+
+/// Output file size of a crop, measured by encoding it in a background thread.
+pub struct SizeEstimate {
+    /// Settings the crop is encoded with
+    pub settings: EncodeSettings,
+    /// Size of the uncompressed pixels
+    pub raw_bytes: u64,
+    rx: Receiver<Result<u64>>,
+    result: Option<Result<u64>>,
+}
+
+impl SizeEstimate {
+    pub fn spawn(image: DynamicImage, settings: EncodeSettings) -> Self {
+        let raw_bytes = image.as_bytes().len() as u64;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(encode(&image, &settings).map(|data| data.len() as u64));
+        });
+        Self {
+            settings,
+            raw_bytes,
+            rx,
+            result: None,
+        }
+    }
+
+    /// Encoded size in bytes, or `None` while the encode is running.
+    pub fn bytes(&mut self) -> Option<&Result<u64>> {
+        if self.result.is_none() {
+            self.result = self.rx.try_recv().ok();
+        }
+        self.result.as_ref()
+    }
+
+    /// Text for the preview, e.g. `Estimated size: 1.2 MB (ratio 9.4:1)`.
+    pub fn label(&mut self) -> String {
+        let raw_bytes = self.raw_bytes;
+        match self.bytes() {
+            None => "Estimating output size...".to_string(),
+            Some(Ok(bytes)) => format!(
+                "Estimated size: {} (ratio {:.1}:1)",
+                format_size(*bytes),
+                raw_bytes as f64 / (*bytes).max(1) as f64
+            ),
+            Some(Err(err)) => format!("Size estimate failed: {err:#}"),
+        }
+    }
+}
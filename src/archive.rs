@@ -0,0 +1,143 @@
+//! Archive of the files written in a session (`--export-zip`), to hand the
+//! results off in one piece. The format follows the extension of the archive:
+//! `.zip` or `.tar`. Images are stored as they are, since they are compressed
+//! already.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::fs_utils::{WorkDirs, HELPER_DIRS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    /// Format named by the extension of `path`.
+    pub fn of(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("zip") => Ok(Self::Zip),
+            Some("tar") => Ok(Self::Tar),
+            _ => Err(anyhow!("{} isn't a .zip or .tar file", path.display())),
+        }
+    }
+}
+
+/// Parse the archive path of `--export-zip`, which must end in `.zip` or `.tar`.
+pub fn parse_archive_path(value: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(value);
+    ArchiveFormat::of(&path)?;
+    Ok(path)
+}
+
+/// Whether `path` lies in a trash, originals or temp folder, by their default
+/// names or the folders configured in `dirs`.
+pub fn in_helper_dir(path: &Path, dirs: &WorkDirs) -> bool {
+    let by_name = path.parent().into_iter().flat_map(Path::components).any(|component| {
+        matches!(component, Component::Normal(name) if name.to_str().is_some_and(|name| HELPER_DIRS.contains(&name)))
+    });
+    // Relative folders are created next to each image, so match them at any depth
+    let configured = [&dirs.trash, &dirs.originals, &dirs.temp]
+        .into_iter()
+        .flatten()
+        .any(|dir| path.ancestors().skip(1).any(|ancestor| ancestor.ends_with(dir)));
+    by_name || configured
+}
+
+/// Name of `path` in the archive: relative to the innermost of `roots`
+/// containing it, or just the file name, with `/` between the components.
+pub fn archive_name(path: &Path, roots: &[PathBuf]) -> String {
+    let relative = roots
+        .iter()
+        .filter_map(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .min_by_key(|relative| relative.components().count())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(path.file_name().unwrap_or_default()));
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `name`, numbered like `photo_2.avif` if `taken` already contains it.
+fn unique_name(name: String, taken: &HashSet<String>) -> String {
+    if !taken.contains(&name) {
+        return name;
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !stem.ends_with('/') => (stem, format!(".{extension}")),
+        _ => (name.as_str(), String::new()),
+    };
+    (2..)
+        .map(|index| format!("{stem}_{index}{extension}"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+/// Write `files` into the archive `destination`, named by [`archive_name`].
+/// Files in helper folders and duplicates are left out. Returns the number of
+/// files archived.
+pub fn write_archive(destination: &Path, files: &[PathBuf], roots: &[PathBuf], dirs: &WorkDirs) -> Result<usize> {
+    let format = ArchiveFormat::of(destination)?;
+    let mut seen = HashSet::new();
+    let mut taken = HashSet::new();
+    let mut entries = Vec::new();
+    for path in files {
+        if in_helper_dir(path, dirs) || !path.is_file() || !seen.insert(path) {
+            continue;
+        }
+        let name = unique_name(archive_name(path, roots), &taken);
+        taken.insert(name.clone());
+        entries.push((path, name));
+    }
+    if entries.is_empty() {
+        return Err(anyhow!("No files were written this session"));
+    }
+
+    let result = (|| -> Result<()> {
+        let file = BufWriter::new(File::create(destination)?);
+        match format {
+            ArchiveFormat::Zip => {
+                let mut zip = zip::ZipWriter::new(file);
+                let options =
+                    zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+                for (path, name) in &entries {
+                    zip.start_file(name.as_str(), options)?;
+                    io::copy(&mut File::open(path)?, &mut zip)?;
+                }
+                zip.finish()?.flush()?;
+            }
+            ArchiveFormat::Tar => {
+                let mut tar = tar::Builder::new(file);
+                for (path, name) in &entries {
+                    tar.append_path_with_name(path, name)?;
+                }
+                tar.into_inner()?.flush()?;
+            }
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        // Don't leave a truncated archive behind
+        let _ = fs::remove_file(destination);
+        return Err(err).with_context(|| format!("Unable to write {}", destination.display()));
+    }
+    Ok(entries.len())
+}
@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, DynamicImage, GrayImage};
+
+/// Longest side of the analysis image; saliency is computed on a thumbnail.
+const ANALYSIS_SIZE: u32 = 256;
+/// Window sizes tried, relative to the largest crop of the requested ratio.
+const SCALES: [f32; 4] = [1.0, 0.85, 0.7, 0.55];
+/// Candidates overlapping an already chosen crop by more than this are dropped.
+const MAX_OVERLAP: f32 = 0.6;
+
+/// Parse an aspect ratio given as `W:H`, `W/H` or a plain number like `1.5`.
+pub fn parse_aspect_ratio(spec: &str) -> Result<f32> {
+    let spec = spec.trim();
+    let ratio = if let Some((w, h)) = spec.split_once([':', '/']) {
+        let w: f32 = w.trim().parse().with_context(|| format!("Invalid aspect ratio: {spec}"))?;
+        let h: f32 = h.trim().parse().with_context(|| format!("Invalid aspect ratio: {spec}"))?;
+        if h == 0.0 {
+            return Err(anyhow!("Invalid aspect ratio: {spec}"));
+        }
+        w / h
+    } else {
+        spec.parse().with_context(|| format!("Invalid aspect ratio: {spec}"))?
+    };
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return Err(anyhow!("Invalid aspect ratio: {spec}"));
+    }
+    Ok(ratio)
+}
+
+/// Largest centered crop of a `width` x `height` image with the given aspect
+/// ratio (width / height), as `(x, y, width, height)`.
+pub fn center_crop(width: u32, height: u32, aspect: f32) -> (u32, u32, u32, u32) {
+    let (w, h) = if width as f32 / height.max(1) as f32 > aspect {
+        (((height as f32 * aspect).round() as u32).clamp(1, width.max(1)), height)
+    } else {
+        (width, ((width as f32 / aspect).round() as u32).clamp(1, height.max(1)))
+    };
+    ((width - w.min(width)) / 2, (height - h.min(height)) / 2, w, h)
+}
+
+/// Propose up to `count` crops with the given aspect ratio (width / height),
+/// best first, as `(x, y, width, height)` in image pixels.
+///
+/// Crops are scored by the amount of edge detail (a cheap saliency measure)
+/// they contain, slightly favoring larger windows.
+pub fn suggest_crops(image: &DynamicImage, aspect: f32, count: usize) -> Vec<(u32, u32, u32, u32)> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 || count == 0 || aspect <= 0.0 {
+        return Vec::new();
+    }
+
+    let scale = (ANALYSIS_SIZE as f32 / width.max(height) as f32).min(1.0);
+    let small_w = ((width as f32 * scale).round() as u32).max(1);
+    let small_h = ((height as f32 * scale).round() as u32).max(1);
+    let gray = image
+        .resize_exact(small_w, small_h, FilterType::Triangle)
+        .to_luma8();
+    let integral = IntegralImage::new(&edge_energy(&gray));
+
+    // Largest window with the requested ratio inside the analysis image
+    let (max_w, max_h) = if small_w as f32 / small_h as f32 > aspect {
+        (small_h as f32 * aspect, small_h as f32)
+    } else {
+        (small_w as f32, small_w as f32 / aspect)
+    };
+
+    let mut candidates = Vec::new();
+    for window_scale in SCALES {
+        let win_w = ((max_w * window_scale).round() as u32).clamp(1, small_w);
+        let win_h = ((max_h * window_scale).round() as u32).clamp(1, small_h);
+        let step = (win_w.min(win_h) / 16).max(1);
+        let mut y = 0;
+        while y + win_h <= small_h {
+            let mut x = 0;
+            while x + win_w <= small_w {
+                let energy = integral.sum(x, y, win_w, win_h);
+                // Mild preference for bigger windows so we don't zoom in on noise
+                let score = energy * window_scale.sqrt() as f64;
+                candidates.push((score, (x, y, win_w, win_h)));
+                x += step;
+            }
+            y += step;
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut chosen: Vec<(u32, u32, u32, u32)> = Vec::new();
+    for (_, rect) in candidates {
+        if chosen.iter().all(|other| overlap(rect, *other) <= MAX_OVERLAP) {
+            chosen.push(rect);
+            if chosen.len() >= count {
+                break;
+            }
+        }
+    }
+
+    chosen
+        .into_iter()
+        .map(|(x, y, w, h)| {
+            let to_full = |v: u32, limit: u32| ((v as f32 / scale).round() as u32).min(limit);
+            let fx = to_full(x, width);
+            let fy = to_full(y, height);
+            let fw = to_full(w, width - fx).max(1);
+            let fh = to_full(h, height - fy).max(1);
+            (fx, fy, fw, fh)
+        })
+        .collect()
+}
+
+/// Gradient magnitude of the luminance, a simple stand-in for visual saliency.
+fn edge_energy(gray: &GrayImage) -> Vec<Vec<f64>> {
+    let (w, h) = gray.dimensions();
+    let px = |x: i64, y: i64| -> f64 {
+        let x = x.clamp(0, w as i64 - 1) as u32;
+        let y = y.clamp(0, h as i64 - 1) as u32;
+        gray.get_pixel(x, y).0[0] as f64
+    };
+    (0..h as i64)
+        .map(|y| {
+            (0..w as i64)
+                .map(|x| {
+                    let gx = px(x + 1, y) - px(x - 1, y);
+                    let gy = px(x, y + 1) - px(x, y - 1);
+                    (gx * gx + gy * gy).sqrt()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Intersection area divided by the smaller rectangle's area.
+fn overlap(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> f32 {
+    let x0 = a.0.max(b.0);
+    let y0 = a.1.max(b.1);
+    let x1 = (a.0 + a.2).min(b.0 + b.2);
+    let y1 = (a.1 + a.3).min(b.1 + b.3);
+    if x1 <= x0 || y1 <= y0 {
+        return 0.0;
+    }
+    let intersection = ((x1 - x0) * (y1 - y0)) as f32;
+    let smaller = (a.2 * a.3).min(b.2 * b.3).max(1) as f32;
+    intersection / smaller
+}
+
+struct IntegralImage {
+    width: usize,
+    sums: Vec<f64>,
+}
+
+impl IntegralImage {
+    fn new(values: &[Vec<f64>]) -> Self {
+        let height = values.len();
+        let width = values.first().map_or(0, |row| row.len());
+        let mut sums = vec![0.0; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row_sum = 0.0;
+            for x in 0..width {
+                row_sum += values[y][x];
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row_sum;
+            }
+        }
+        Self { width, sums }
+    }
+
+    fn sum(&self, x: u32, y: u32, w: u32, h: u32) -> f64 {
+        let stride = self.width + 1;
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = (x0 + w as usize, y0 + h as usize);
+        self.sums[y1 * stride + x1] - self.sums[y0 * stride + x1] - self.sums[y1 * stride + x0]
+            + self.sums[y0 * stride + x0]
+    }
+}
@@ -0,0 +1,407 @@
+//! Metadata injection for AVIF files.
+//!
+//! AVIF stores metadata as HEIF items: EXIF is an `Exif` item that describes the
+//! primary image (a `cdsc` reference), the ICC profile is a `colr` property
+//! associated with it. Both live in the `meta` box, whose item locations (`iloc`)
+//! are absolute file offsets that move when the box grows.
+
+use crate::image_utils::bmff_box;
+
+/// A box inside a parsed buffer.
+struct BmffBox<'a> {
+    kind: [u8; 4],
+    /// The whole box including its header
+    data: &'a [u8],
+    payload: &'a [u8],
+    /// Offset of the box end in the parsed buffer
+    end: usize,
+}
+
+/// Split `data` into consecutive boxes. Returns `None` for malformed input.
+fn parse_boxes(data: &[u8]) -> Option<Vec<BmffBox<'_>>> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let rest = &data[pos..];
+        let size = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as u64;
+        let kind: [u8; 4] = rest.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (8, rest.len() as u64),
+            1 => (16, u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?)),
+            size => (8, size),
+        };
+        let size = usize::try_from(size).ok()?;
+        if size < header || size > rest.len() {
+            return None;
+        }
+        boxes.push(BmffBox {
+            kind,
+            data: &rest[..size],
+            payload: &rest[header..size],
+            end: pos + size,
+        });
+        pos += size;
+    }
+    Some(boxes)
+}
+
+/// Big-endian reader for the variable-width fields of full boxes.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Read an unsigned integer of `bytes` (0-8) bytes.
+    fn uint(&mut self, bytes: usize) -> Option<u64> {
+        let value = self.data.get(self.pos..self.pos + bytes)?;
+        self.pos += bytes;
+        Some(value.iter().fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte)))
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+}
+
+/// Append `value` as a `bytes` wide big-endian integer, or `None` if it doesn't fit.
+fn push_uint(out: &mut Vec<u8>, value: u64, bytes: usize) -> Option<()> {
+    if bytes < 8 && value >> (bytes * 8) != 0 {
+        return None;
+    }
+    out.extend(&value.to_be_bytes()[8 - bytes..]);
+    Some(())
+}
+
+struct IlocExtent {
+    index: u64,
+    offset: u64,
+    length: u64,
+}
+
+struct IlocItem {
+    id: u64,
+    construction_method: u64,
+    data_reference_index: u64,
+    base_offset: u64,
+    extents: Vec<IlocExtent>,
+}
+
+/// The item location box.
+struct Iloc {
+    version: u8,
+    offset_size: usize,
+    length_size: usize,
+    base_offset_size: usize,
+    index_size: usize,
+    items: Vec<IlocItem>,
+}
+
+impl Iloc {
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(payload);
+        let version = r.uint(1)? as u8;
+        r.uint(3)?;
+        let sizes = r.uint(1)?;
+        let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 15) as usize);
+        let sizes = r.uint(1)?;
+        let base_offset_size = (sizes >> 4) as usize;
+        let index_size = if version > 0 { (sizes & 15) as usize } else { 0 };
+        let id_size = if version < 2 { 2 } else { 4 };
+        let count = r.uint(id_size)?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            let id = r.uint(id_size)?;
+            let construction_method = if version > 0 { r.uint(2)? & 15 } else { 0 };
+            let data_reference_index = r.uint(2)?;
+            let base_offset = r.uint(base_offset_size)?;
+            let extent_count = r.uint(2)?;
+            let extents = (0..extent_count)
+                .map(|_| {
+                    Some(IlocExtent {
+                        index: r.uint(index_size)?,
+                        offset: r.uint(offset_size)?,
+                        length: r.uint(length_size)?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            items.push(IlocItem {
+                id,
+                construction_method,
+                data_reference_index,
+                base_offset,
+                extents,
+            });
+        }
+        Some(Self {
+            version,
+            offset_size,
+            length_size,
+            base_offset_size,
+            index_size,
+            items,
+        })
+    }
+
+    /// Move file offsets at or after `from` by `delta` bytes.
+    fn shift_offsets(&mut self, from: u64, delta: u64) {
+        for item in &mut self.items {
+            if item.construction_method != 0 || item.data_reference_index != 0 {
+                continue;
+            }
+            if self.base_offset_size > 0 && item.base_offset >= from {
+                item.base_offset += delta;
+                continue;
+            }
+            for extent in &mut item.extents {
+                if item.base_offset + extent.offset >= from {
+                    extent.offset += delta;
+                }
+            }
+        }
+    }
+
+    fn to_box(&self) -> Option<Vec<u8>> {
+        let mut out = vec![self.version, 0, 0, 0];
+        out.push(((self.offset_size << 4) | self.length_size) as u8);
+        out.push(((self.base_offset_size << 4) | self.index_size) as u8);
+        let id_size = if self.version < 2 { 2 } else { 4 };
+        push_uint(&mut out, self.items.len() as u64, id_size)?;
+        for item in &self.items {
+            push_uint(&mut out, item.id, id_size)?;
+            if self.version > 0 {
+                push_uint(&mut out, item.construction_method, 2)?;
+            }
+            push_uint(&mut out, item.data_reference_index, 2)?;
+            push_uint(&mut out, item.base_offset, self.base_offset_size)?;
+            push_uint(&mut out, item.extents.len() as u64, 2)?;
+            for extent in &item.extents {
+                push_uint(&mut out, extent.index, self.index_size)?;
+                push_uint(&mut out, extent.offset, self.offset_size)?;
+                push_uint(&mut out, extent.length, self.length_size)?;
+            }
+        }
+        Some(bmff_box(b"iloc", &[&out]))
+    }
+}
+
+/// Version, entry count and entries of the item info box.
+fn parse_iinf(payload: &[u8]) -> Option<(u8, u64, Vec<BmffBox<'_>>)> {
+    let mut r = Reader::new(payload);
+    let version = r.uint(1)? as u8;
+    r.uint(3)?;
+    let count = r.uint(if version == 0 { 2 } else { 4 })?;
+    Some((version, count, parse_boxes(r.rest())?))
+}
+
+/// Item id of an `infe` entry.
+fn infe_id(payload: &[u8]) -> Option<u64> {
+    let mut r = Reader::new(payload);
+    let version = r.uint(1)?;
+    r.uint(3)?;
+    r.uint(if version == 3 { 4 } else { 2 })
+}
+
+/// Association of `property` (1-based index into `ipco`) with the primary item,
+/// added in front of its other associations.
+fn ipma_with_property(payload: &[u8], primary: u64, property: u64) -> Option<Vec<u8>> {
+    let mut r = Reader::new(payload);
+    let version = r.uint(1)?;
+    let flags = r.uint(3)?;
+    let id_size = if version < 1 { 2 } else { 4 };
+    let index_size = if flags & 1 != 0 { 2 } else { 1 };
+    let count = r.uint(4)?;
+    // Indices have one bit less than their field, the top bit marks essential properties
+    if property >> (index_size * 8 - 1) != 0 {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut found = false;
+    for _ in 0..count {
+        let id = r.uint(id_size)?;
+        let associations = r.uint(1)?;
+        let mut values = (0..associations)
+            .map(|_| r.uint(index_size))
+            .collect::<Option<Vec<_>>>()?;
+        if id == primary {
+            values.insert(0, property);
+            found = true;
+        }
+        entries.push((id, values));
+    }
+    if !found {
+        entries.push((primary, vec![property]));
+    }
+
+    let mut out = Vec::new();
+    push_uint(&mut out, version, 1)?;
+    push_uint(&mut out, flags, 3)?;
+    push_uint(&mut out, entries.len() as u64, 4)?;
+    for (id, values) in entries {
+        push_uint(&mut out, id, id_size)?;
+        push_uint(&mut out, values.len() as u64, 1)?;
+        for value in values {
+            push_uint(&mut out, value, index_size)?;
+        }
+    }
+    Some(bmff_box(b"ipma", &[&out]))
+}
+
+/// `iprp` with an ICC profile property for the primary item, or `None` if
+/// it already has one.
+fn iprp_with_icc(payload: &[u8], primary: u64, icc: &[u8]) -> Option<Vec<u8>> {
+    let children = parse_boxes(payload)?;
+    let ipco = children.iter().find(|b| &b.kind == b"ipco")?;
+    let properties = parse_boxes(ipco.payload)?;
+    let has_profile = properties.iter().any(|p| {
+        &p.kind == b"colr" && matches!(p.payload.get(..4), Some(b"prof") | Some(b"rICC"))
+    });
+    if has_profile {
+        return None;
+    }
+    let property = properties.len() as u64 + 1;
+
+    let mut out = Vec::new();
+    let mut ipma_done = false;
+    for child in &children {
+        match &child.kind {
+            b"ipco" => out.extend(bmff_box(
+                b"ipco",
+                &[ipco.payload, &bmff_box(b"colr", &[b"prof", icc])],
+            )),
+            b"ipma" if !ipma_done => {
+                out.extend(ipma_with_property(child.payload, primary, property)?);
+                ipma_done = true;
+            }
+            _ => out.extend(child.data),
+        }
+    }
+    ipma_done.then_some(out)
+}
+
+/// Add `exif` (TIFF data) as an `Exif` item and `icc` as the color profile of
+/// the primary image of an AVIF file.
+///
+/// The EXIF data is appended in a new `mdat` box. Returns `None` if `avif` isn't
+/// a HEIF file this can handle; an existing ICC profile is kept.
+pub fn add_avif_metadata(avif: &[u8], exif: Option<&[u8]>, icc: Option<&[u8]>) -> Option<Vec<u8>> {
+    let boxes = parse_boxes(avif)?;
+    if boxes.first().map(|b| &b.kind) != Some(b"ftyp") {
+        return None;
+    }
+    let meta = boxes.iter().find(|b| &b.kind == b"meta")?;
+    // A last box extending to the end of the file can't be followed by another one
+    if exif.is_some() && boxes.last()?.data.starts_with(&[0, 0, 0, 0]) {
+        return None;
+    }
+    let meta_start = meta.end - meta.data.len();
+    let meta_header = meta.payload.get(..4)?;
+    let children = parse_boxes(&meta.payload[4..])?;
+
+    let find = |kind: &[u8; 4]| children.iter().find(|b| &b.kind == kind);
+    let mut r = Reader::new(find(b"pitm")?.payload);
+    let pitm_version = r.uint(1)?;
+    r.uint(3)?;
+    let primary = r.uint(if pitm_version == 0 { 2 } else { 4 })?;
+    let (iinf_version, iinf_count, entries) = parse_iinf(find(b"iinf")?.payload)?;
+    let iloc = Iloc::parse(find(b"iloc")?.payload)?;
+    let exif_id = entries
+        .iter()
+        .filter_map(|entry| infe_id(entry.payload))
+        .chain(iloc.items.iter().map(|item| item.id))
+        .chain([primary])
+        .max()?
+        + 1;
+
+    // The item starts with the offset of the TIFF header, which follows directly
+    let exif_payload = exif.map(|exif| [&[0u8, 0, 0, 0][..], exif].concat());
+    let tail_len = avif.len() - meta.end;
+
+    // The size of the new meta box doesn't depend on the offsets written into it,
+    // so build it once to measure and again with the final offsets
+    let build_meta = |delta: u64, exif_offset: u64| -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut has_iref = false;
+        for child in &children {
+            match &child.kind {
+                b"iinf" if exif_payload.is_some() => {
+                    let mut header = vec![iinf_version, 0, 0, 0];
+                    push_uint(&mut header, iinf_count + 1, if iinf_version == 0 { 2 } else { 4 })?;
+                    let infe_version: u8 = if exif_id > 0xFFFF { 3 } else { 2 };
+                    let mut infe = vec![infe_version, 0, 0, 0];
+                    push_uint(&mut infe, exif_id, if infe_version == 3 { 4 } else { 2 })?;
+                    infe.extend([0, 0]);
+                    infe.extend(b"Exif\0");
+                    let mut payload = header;
+                    for entry in &entries {
+                        payload.extend(entry.data);
+                    }
+                    payload.extend(bmff_box(b"infe", &[&infe]));
+                    out.extend(bmff_box(b"iinf", &[&payload]));
+                }
+                b"iloc" => {
+                    let mut iloc = Iloc::parse(child.payload)?;
+                    iloc.shift_offsets(meta.end as u64, delta);
+                    if let Some(exif) = &exif_payload {
+                        iloc.items.push(IlocItem {
+                            id: exif_id,
+                            construction_method: 0,
+                            data_reference_index: 0,
+                            base_offset: 0,
+                            extents: vec![IlocExtent {
+                                index: 0,
+                                offset: exif_offset,
+                                length: exif.len() as u64,
+                            }],
+                        });
+                    }
+                    out.extend(iloc.to_box()?);
+                }
+                b"iref" if exif_payload.is_some() => {
+                    has_iref = true;
+                    let version = *child.payload.first()?;
+                    let id_size = if version == 0 { 2 } else { 4 };
+                    let mut reference = Vec::new();
+                    push_uint(&mut reference, exif_id, id_size)?;
+                    push_uint(&mut reference, 1, 2)?;
+                    push_uint(&mut reference, primary, id_size)?;
+                    out.extend(bmff_box(b"iref", &[child.payload, &bmff_box(b"cdsc", &[&reference])]));
+                }
+                b"iprp" => match icc.and_then(|icc| iprp_with_icc(child.payload, primary, icc)) {
+                    Some(iprp) => out.extend(bmff_box(b"iprp", &[&iprp])),
+                    None => out.extend(child.data),
+                },
+                _ => out.extend(child.data),
+            }
+        }
+        if exif_payload.is_some() && !has_iref {
+            let version: u8 = if exif_id > 0xFFFF { 1 } else { 0 };
+            let id_size = if version == 0 { 2 } else { 4 };
+            let mut reference = Vec::new();
+            push_uint(&mut reference, exif_id, id_size)?;
+            push_uint(&mut reference, 1, 2)?;
+            push_uint(&mut reference, primary, id_size)?;
+            out.extend(bmff_box(b"iref", &[&[version, 0, 0, 0], &bmff_box(b"cdsc", &[&reference])]));
+        }
+        Some(bmff_box(b"meta", &[meta_header, &out]))
+    };
+
+    let measured = build_meta(0, 0)?;
+    let delta = (measured.len() as u64).checked_sub(meta.data.len() as u64)?;
+    // The EXIF payload follows the header of the appended mdat
+    let exif_offset = (meta_start + measured.len() + tail_len + 8) as u64;
+    let new_meta = build_meta(delta, exif_offset)?;
+
+    let mut out = avif[..meta_start].to_vec();
+    out.extend(new_meta);
+    out.extend(&avif[meta.end..]);
+    if let Some(exif) = &exif_payload {
+        out.extend(bmff_box(b"mdat", &[exif]));
+    }
+    Some(out)
+}
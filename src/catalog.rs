@@ -0,0 +1,183 @@
+//! SQLite catalog of everything done to the images.
+//!
+//! Unlike the JSON Lines log, the catalog keeps one row per image with its
+//! latest status, crop rectangles and sizes, so projects that span many sessions
+//! and tens of thousands of images can be queried with `imagecropper report`.
+//! Every log event is stored as well. Needs the `catalog` feature.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::journal::{Entry, Event};
+
+/// Name of the catalog inside the platform data directory
+pub const CATALOG_FILE_NAME: &str = "catalog.sqlite";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    time TEXT NOT NULL,
+    path TEXT NOT NULL,
+    action TEXT NOT NULL,
+    details TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS files (
+    path TEXT PRIMARY KEY,
+    status TEXT NOT NULL,
+    rects TEXT,
+    rating INTEGER,
+    original_bytes INTEGER,
+    new_bytes INTEGER,
+    first_seen TEXT NOT NULL,
+    updated TEXT NOT NULL
+);
+";
+
+#[derive(Debug)]
+pub struct Catalog {
+    connection: Connection,
+}
+
+/// Statistics of the images below a directory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub files: usize,
+    /// Number of images by their latest status, e.g. `cropped` or `deleted`
+    pub by_status: BTreeMap<String, usize>,
+    /// Images whose output was written, with their sizes before and after
+    pub saved_files: usize,
+    pub original_bytes: u64,
+    pub new_bytes: u64,
+    /// Timestamps of the first and the last recorded operation
+    pub first: Option<String>,
+    pub last: Option<String>,
+}
+
+impl Catalog {
+    /// `<data dir>/imagecropper/catalog.sqlite`, e.g. `~/.local/share/imagecropper/catalog.sqlite` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("imagecropper").join(CATALOG_FILE_NAME))
+    }
+
+    /// Open or create the catalog at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("Unable to create {}", parent.display()))?;
+        }
+        let connection =
+            Connection::open(path).with_context(|| format!("Failed to open catalog {}", path.display()))?;
+        Self::with_connection(connection)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA).context("Failed to create the catalog tables")?;
+        Ok(Self { connection })
+    }
+
+    /// Store `entry` and update the row of its image.
+    pub fn record(&self, entry: &Entry) -> Result<()> {
+        let path = std::path::absolute(&entry.path).unwrap_or_else(|_| entry.path.clone());
+        let path = path.to_string_lossy();
+        let details = serde_json::to_value(&entry.event)?;
+        let action = details["action"].as_str().unwrap_or_default().to_string();
+        self.connection.execute(
+            "INSERT INTO events (time, path, action, details) VALUES (?1, ?2, ?3, ?4)",
+            params![entry.time, path, action, details.to_string()],
+        )?;
+
+        // Saves and ratings add details to the image but don't change what happened to it
+        let status = match &entry.event {
+            Event::Crop { .. } => Some("cropped"),
+            Event::Annotate { .. } => Some("annotated"),
+            Event::Redact { .. } => Some("redacted"),
+            Event::Exclude { .. } => Some("excluded"),
+            Event::Resave { .. } => Some("resaved"),
+            Event::Skip => Some("skipped"),
+            Event::Done => Some("done"),
+            Event::Delete { .. } => Some("deleted"),
+            Event::Sort { .. } => Some("sorted"),
+            Event::SaveFailed { .. } => Some("failed"),
+            Event::Rate { .. } | Event::Saved { .. } => None,
+        };
+        self.connection.execute(
+            "INSERT INTO files (path, status, first_seen, updated) VALUES (?1, COALESCE(?2, 'seen'), ?3, ?3)
+             ON CONFLICT(path) DO UPDATE SET status = COALESCE(?2, status), updated = ?3",
+            params![path, status, entry.time],
+        )?;
+        match &entry.event {
+            Event::Crop { rects, .. }
+            | Event::Annotate { rects, .. }
+            | Event::Redact { rects, .. }
+            | Event::Exclude { rects, .. } => {
+                self.connection.execute(
+                    "UPDATE files SET rects = ?2 WHERE path = ?1",
+                    params![path, serde_json::to_string(rects)?],
+                )?;
+            }
+            Event::Rate { rating, .. } => {
+                self.connection
+                    .execute("UPDATE files SET rating = ?2 WHERE path = ?1", params![path, rating])?;
+            }
+            Event::Saved {
+                original_bytes,
+                new_bytes,
+                ..
+            } => {
+                self.connection.execute(
+                    "UPDATE files SET original_bytes = ?2, new_bytes = ?3 WHERE path = ?1",
+                    params![path, original_bytes, new_bytes],
+                )?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Statistics of the images in `dir` and its subdirectories.
+    pub fn report(&self, dir: &Path) -> Result<Report> {
+        let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let mut statement = self
+            .connection
+            .prepare("SELECT path, status, original_bytes, new_bytes, first_seen, updated FROM files")?;
+        let rows = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<u64>>(2)?,
+                row.get::<_, Option<u64>>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+        let mut report = Report::default();
+        for row in rows {
+            let (path, status, original_bytes, new_bytes, first_seen, updated) = row?;
+            if !Path::new(&path).starts_with(&dir) {
+                continue;
+            }
+            report.files += 1;
+            *report.by_status.entry(status).or_default() += 1;
+            if let (Some(original), Some(new)) = (original_bytes, new_bytes) {
+                report.saved_files += 1;
+                report.original_bytes += original;
+                report.new_bytes += new;
+            }
+            if report.first.as_ref().map_or(true, |first| first_seen < *first) {
+                report.first = Some(first_seen);
+            }
+            if report.last.as_ref().map_or(true, |last| updated > *last) {
+                report.last = Some(updated);
+            }
+        }
+        Ok(report)
+    }
+}
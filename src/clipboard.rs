@@ -0,0 +1,84 @@
+//! Images on the system clipboard. Pasted images get a generated path next to
+//! the current image that isn't written until they are saved, like the pages
+//! of PDFs; copied images skip the save pipeline entirely.
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use image::DynamicImage;
+
+/// Run `f` with the system clipboard, which stays open for the rest of the
+/// session: on X11 a copied image is only available while it is.
+#[cfg(feature = "clipboard")]
+fn with_clipboard<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<T>) -> Result<T> {
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+    }
+    CLIPBOARD.with_borrow_mut(|slot| {
+        let clipboard = match slot.take() {
+            Some(clipboard) => clipboard,
+            None => arboard::Clipboard::new()?,
+        };
+        f(slot.insert(clipboard))
+    })
+}
+
+/// The image on the system clipboard.
+#[cfg(feature = "clipboard")]
+pub fn paste_image() -> Result<DynamicImage> {
+    let image = with_clipboard(|clipboard| {
+        clipboard
+            .get_image()
+            .map_err(|err| anyhow::anyhow!("No image in the clipboard ({err})"))
+    })?;
+    let rgba = image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())
+        .ok_or_else(|| anyhow::anyhow!("Clipboard image has an unexpected size"))?;
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn paste_image() -> Result<DynamicImage> {
+    Err(anyhow::anyhow!(
+        "This build has no clipboard support; rebuild with `--features clipboard`"
+    ))
+}
+
+/// Put `image` on the system clipboard.
+#[cfg(feature = "clipboard")]
+pub fn copy_image(image: &DynamicImage) -> Result<()> {
+    let rgba = image.to_rgba8();
+    let data = arboard::ImageData {
+        width: rgba.width() as usize,
+        height: rgba.height() as usize,
+        bytes: rgba.into_raw().into(),
+    };
+    with_clipboard(|clipboard| Ok(clipboard.set_image(data)?))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy_image(_image: &DynamicImage) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "This build has no clipboard support; rebuild with `--features clipboard`"
+    ))
+}
+
+/// Path for an image pasted at `time` into `dir`, e.g. `pasted-2024-05-01T12-30-00.png`,
+/// numbered if `is_taken` already claims it.
+pub fn pasted_path(dir: &Path, time: SystemTime, is_taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let stamp = humantime::format_rfc3339_seconds(time).to_string();
+    // Colons aren't allowed in file names on Windows
+    let stamp = stamp.trim_end_matches('Z').replace(':', "-");
+    let mut candidate = dir.join(format!("pasted-{stamp}.png"));
+    for index in 2.. {
+        if !is_taken(&candidate) {
+            break;
+        }
+        candidate = dir.join(format!("pasted-{stamp}-{index}.png"));
+    }
+    candidate
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    autocrop::parse_aspect_ratio,
+    fs_utils::WorkDirs,
+    image_utils::{parse_fill_color, CombineStyle, EncodeSettings, OutputFormat, PngCompression, TiffCompression},
+    selection::{Selection, SelectionStyle},
+    ui::{ArrowSpeed, Overlays},
+};
+
+/// Name of the configuration file inside the platform config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Settings read from `config.toml`. Missing keys fall back to the defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Selection presets, selectable with the number keys in the listed order
+    pub presets: Vec<Preset>,
+    /// Arrow-key movement of selections
+    pub arrow_keys: ArrowSpeed,
+    /// Locations of the trash, originals and temp folders
+    pub dirs: WorkDirs,
+    /// Encoder settings per output format
+    pub encode: EncodeConfig,
+    /// Overlays shown at startup
+    pub overlays: Overlays,
+    /// Stroke, fill and colors of selections
+    pub selection: SelectionStyle,
+    /// Folders of the `--cull` mode
+    pub culling: Culling,
+    /// Function keys that move the current image into a folder
+    pub move_keys: MoveKeys,
+    /// Arrangement of several selections saved as one image
+    pub combine: CombineStyle,
+    /// Classes of the `--annotate` mode
+    pub annotations: Annotations,
+    /// Fill color of `--redact fill`
+    pub redact: Redaction,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            presets: vec![
+                Preset::ratio("Square 1:1", "1:1"),
+                Preset::ratio("Instagram 4:5", "4:5"),
+                Preset::ratio("Widescreen 16:9", "16:9"),
+                Preset::size("YouTube thumb 1280x720", 1280, 720),
+            ],
+            arrow_keys: ArrowSpeed::default(),
+            dirs: WorkDirs::default(),
+            encode: EncodeConfig::default(),
+            overlays: Overlays::default(),
+            selection: SelectionStyle::default(),
+            culling: Culling::default(),
+            move_keys: MoveKeys::default(),
+            combine: CombineStyle::default(),
+            annotations: Annotations::default(),
+            redact: Redaction::default(),
+        }
+    }
+}
+
+impl Config {
+    /// `<config dir>/imagecropper/config.toml`, e.g. `~/.config/imagecropper/config.toml` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("imagecropper").join(CONFIG_FILE_NAME))
+    }
+
+    /// Load `path`, or the default location if `None`.
+    ///
+    /// An explicitly given file must exist; a missing default file yields the defaults.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        for preset in &config.presets {
+            preset.validate()?;
+        }
+        config.encode.validate()?;
+        config.selection.validate()?;
+        config.culling.validate()?;
+        config.move_keys.bindings()?;
+        config.combine.validate()?;
+        config.redact.validate()?;
+        Ok(config)
+    }
+}
+
+/// The `[encode.<format>]` tables, e.g. `[encode.avif] quality = 55`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncodeConfig {
+    pub jpg: FormatConfig,
+    pub png: FormatConfig,
+    pub webp: FormatConfig,
+    pub avif: FormatConfig,
+    pub jxl: FormatConfig,
+    pub tiff: FormatConfig,
+}
+
+/// Encoder settings of one format. Unset keys keep the defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    /// 1-100 (JPEG, AVIF, JPEG XL)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    /// AVIF encoder speed, 1 (slowest, smallest) to 10
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u8>,
+    /// Progressive JPEG
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progressive: Option<bool>,
+    /// PNG or TIFF compression
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    /// JPEG XL encoder effort, 1 (fastest) to 9 (smallest)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<u8>,
+}
+
+impl FormatConfig {
+    /// Check that the keys set apply to `format` and are in range.
+    fn validate(&self, format: OutputFormat) -> Result<()> {
+        use OutputFormat::*;
+        let name = format.extension();
+        let check = |key: &str, value: Option<u8>, formats: &[OutputFormat], range: RangeInclusive<u8>| {
+            let Some(value) = value else { return Ok(()) };
+            if !formats.contains(&format) {
+                return Err(anyhow!("`{key}` doesn't apply to [encode.{name}]"));
+            }
+            if !range.contains(&value) {
+                return Err(anyhow!(
+                    "[encode.{name}] {key} must be between {} and {}",
+                    range.start(),
+                    range.end()
+                ));
+            }
+            Ok(())
+        };
+        check("quality", self.quality, &[Jpg, Avif, Jxl], 1..=100)?;
+        check("speed", self.speed, &[Avif], 1..=10)?;
+        check("progressive", self.progressive.map(u8::from), &[Jpg], 0..=1)?;
+        match (self.compression, format) {
+            (None, _) | (Some(Compression::Png(_)), Png) | (Some(Compression::Tiff(_)), Tiff) => {}
+            (Some(_), Png | Tiff) => return Err(anyhow!("[encode.{name}] compression isn't a {name} compression")),
+            (Some(_), _) => return Err(anyhow!("`compression` doesn't apply to [encode.{name}]")),
+        }
+        check("effort", self.effort, &[Jxl], 1..=9)
+    }
+
+    /// Keys set here win over the ones in `fallback`.
+    pub fn or(&self, fallback: &FormatConfig) -> FormatConfig {
+        FormatConfig {
+            quality: self.quality.or(fallback.quality),
+            speed: self.speed.or(fallback.speed),
+            progressive: self.progressive.or(fallback.progressive),
+            compression: self.compression.or(fallback.compression),
+            effort: self.effort.or(fallback.effort),
+        }
+    }
+}
+
+/// The `compression` key, whose values depend on the format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Compression {
+    Png(PngCompression),
+    Tiff(TiffCompression),
+}
+
+impl EncodeConfig {
+    pub fn format(&self, format: OutputFormat) -> &FormatConfig {
+        match format {
+            OutputFormat::Jpg => &self.jpg,
+            OutputFormat::Png => &self.png,
+            OutputFormat::Webp => &self.webp,
+            OutputFormat::Avif => &self.avif,
+            OutputFormat::Jxl => &self.jxl,
+            OutputFormat::Tiff => &self.tiff,
+        }
+    }
+
+    /// Settings for `format`: keys in `overrides` (the command line) win over
+    /// the config, which wins over the defaults.
+    pub fn settings(&self, format: OutputFormat, overrides: &FormatConfig) -> EncodeSettings {
+        let config = overrides.or(self.format(format));
+        let defaults = EncodeSettings::new(format);
+        EncodeSettings {
+            format,
+            quality: config.quality.unwrap_or(defaults.quality),
+            avif_speed: config.speed.unwrap_or(defaults.avif_speed),
+            progressive: config.progressive.unwrap_or(defaults.progressive),
+            png_compression: match config.compression {
+                Some(Compression::Png(compression)) => compression,
+                _ => defaults.png_compression,
+            },
+            tiff_compression: match config.compression {
+                Some(Compression::Tiff(compression)) => compression,
+                _ => defaults.tiff_compression,
+            },
+            jxl_effort: config.effort.unwrap_or(defaults.jxl_effort),
+            metadata: defaults.metadata,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        use OutputFormat::*;
+        for format in [Jpg, Png, Webp, Avif, Jxl, Tiff] {
+            self.format(format).validate(format)?;
+        }
+        Ok(())
+    }
+}
+
+/// Folders images are sorted into with the number keys in `--cull` mode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Culling {
+    /// Bound to the keys 1-9 in order; relative paths are resolved against the image's directory
+    pub folders: Vec<PathBuf>,
+}
+
+impl Default for Culling {
+    fn default() -> Self {
+        Self {
+            folders: ["keep", "maybe", "reject"].map(PathBuf::from).to_vec(),
+        }
+    }
+}
+
+impl Culling {
+    fn validate(&self) -> Result<()> {
+        if !(1..=9).contains(&self.folders.len()) {
+            return Err(anyhow!("[culling] needs between 1 and 9 folders"));
+        }
+        Ok(())
+    }
+}
+
+/// The `[move_keys]` table, e.g. `F6 = "sorted/landscapes"`: function keys that
+/// move the current image into a folder and show the next one, in any mode.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MoveKeys(pub BTreeMap<String, PathBuf>);
+
+/// Function keys that already have an action (help, performance HUD, retrying saves, fullscreen)
+const BOUND_FUNCTION_KEYS: [egui::Key; 4] = [egui::Key::F1, egui::Key::F3, egui::Key::F5, egui::Key::F11];
+
+impl MoveKeys {
+    /// The keys and their folders, which are relative to the image's directory
+    /// unless absolute.
+    pub fn bindings(&self) -> Result<Vec<(egui::Key, PathBuf)>> {
+        self.0
+            .iter()
+            .map(|(name, folder)| {
+                let is_function_key = name
+                    .strip_prefix('F')
+                    .is_some_and(|number| number.parse::<u8>().is_ok());
+                let key = egui::Key::from_name(name)
+                    .filter(|_| is_function_key)
+                    .ok_or_else(|| anyhow!("[move_keys] {name} isn't a function key such as F6"))?;
+                if BOUND_FUNCTION_KEYS.contains(&key) {
+                    return Err(anyhow!("[move_keys] {name} is already bound to another action"));
+                }
+                Ok((key, folder.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Class names of the `--annotate` mode, bound to the keys 0-9 in order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Annotations {
+    /// Classes without a name are written and shown by their number
+    pub classes: Vec<String>,
+}
+
+/// Settings of the `--redact` mode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Redaction {
+    /// Color of `--redact fill`: `black`, `white` or `#rrggbb`
+    pub color: String,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self {
+            color: "black".to_string(),
+        }
+    }
+}
+
+impl Redaction {
+    fn validate(&self) -> Result<()> {
+        parse_fill_color(&self.color).map_err(|err| anyhow!("[redact] color: {err}"))?;
+        Ok(())
+    }
+}
+
+/// A named selection shape: either a fixed pixel size or an aspect ratio.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// Aspect ratio such as `4:5`; the selection is as large as the image allows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+impl Preset {
+    pub fn ratio(name: &str, ratio: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ratio: Some(ratio.to_string()),
+            width: None,
+            height: None,
+        }
+    }
+
+    pub fn size(name: &str, width: u32, height: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            ratio: None,
+            width: Some(width),
+            height: Some(height),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match (&self.ratio, self.width, self.height) {
+            (Some(ratio), None, None) => parse_aspect_ratio(ratio).map(|_| ()),
+            (None, Some(w), Some(h)) if w > 0 && h > 0 => Ok(()),
+            _ => Err(anyhow!(
+                "Preset \"{}\" needs either `ratio` or non-zero `width` and `height`",
+                self.name
+            )),
+        }
+    }
+
+    /// Centered selection for an image of `image_size`.
+    ///
+    /// Fixed sizes that don't fit are scaled down, keeping their aspect ratio.
+    pub fn selection(&self, image_size: egui::Vec2) -> Option<Selection> {
+        let size = match (&self.ratio, self.width, self.height) {
+            (Some(ratio), _, _) => {
+                let ratio = parse_aspect_ratio(ratio).ok()?;
+                if image_size.x / image_size.y > ratio {
+                    egui::vec2(image_size.y * ratio, image_size.y)
+                } else {
+                    egui::vec2(image_size.x, image_size.x / ratio)
+                }
+            }
+            (None, Some(w), Some(h)) => {
+                let size = egui::vec2(w as f32, h as f32);
+                let fit = (image_size.x / size.x).min(image_size.y / size.y).min(1.0);
+                size * fit
+            }
+            _ => return None,
+        };
+        let min = ((image_size - size) / 2.0).round();
+        let mut selection = Selection::new(egui::Rect::from_min_size(min.to_pos2(), size.round()));
+        selection.clamp_within(image_size);
+        Some(selection)
+    }
+}
@@ -0,0 +1,143 @@
+//! Contact sheet of the images saved in a session (`--contact-sheet`):
+//! thumbnails with their file names below them, arranged in a grid the way
+//! several crops are combined into one image.
+
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Context, Result};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::image_utils::{combine_crops_with, CombineLayout, CombineStyle};
+
+/// Longest side of the thumbnails
+pub const THUMBNAIL_SIZE: u32 = 320;
+/// Pixel height of the file names
+const CAPTION_SIZE: f32 = 16.0;
+/// Space above and below the file names
+const CAPTION_MARGIN: u32 = 4;
+
+/// Grid in the order the images were saved, on white.
+pub fn sheet_style() -> CombineStyle {
+    CombineStyle {
+        layout: CombineLayout::Grid,
+        gap: 12,
+        padding: 12,
+        background: "white".to_string(),
+        ..CombineStyle::default()
+    }
+}
+
+/// Width of `text` set in `font`.
+fn text_width(font: &impl ScaleFont<impl Font>, text: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// `text`, shortened with an ellipsis at the end if it is wider than `max_width`.
+fn fit_text(font: &impl ScaleFont<impl Font>, text: &str, max_width: f32) -> String {
+    if text_width(font, text) <= max_width {
+        return text.to_string();
+    }
+    let mut chars: Vec<char> = text.chars().collect();
+    while chars.pop().is_some() {
+        let shortened: String = chars.iter().chain(['…'].iter()).collect();
+        if text_width(font, &shortened) <= max_width {
+            return shortened;
+        }
+    }
+    String::new()
+}
+
+/// Draw `text` in black onto the white `image`, starting at `x` on the baseline `y`.
+fn draw_text(image: &mut RgbaImage, font: &impl ScaleFont<impl Font>, text: &str, x: f32, y: f32) {
+    let mut caret = x;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(font.scale(), point(caret, y));
+        caret += font.h_advance(id);
+        previous = Some(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|glyph_x, glyph_y, coverage| {
+            let px = bounds.min.x as i64 + glyph_x as i64;
+            let py = bounds.min.y as i64 + glyph_y as i64;
+            if px < 0 || py < 0 {
+                return;
+            }
+            if let Some(pixel) = image.get_pixel_mut_checked(px as u32, py as u32) {
+                for channel in &mut pixel.0[..3] {
+                    *channel = (*channel as f32 * (1.0 - coverage.clamp(0.0, 1.0))).round() as u8;
+                }
+            }
+        });
+    }
+}
+
+/// `image` scaled to fit into [`THUMBNAIL_SIZE`] on white, with `name` written
+/// below it. Names wider than the thumbnail are shortened.
+pub fn labeled_thumbnail(image: &DynamicImage, name: &str) -> RgbaImage {
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8();
+    // Hack is one of egui's default fonts, so nothing has to be installed
+    let font = FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR).expect("bundled font is valid");
+    let font = font.as_scaled(PxScale::from(CAPTION_SIZE));
+
+    // Narrow images get room for at least a few characters of their name
+    let width = thumbnail.width().max(THUMBNAIL_SIZE / 2);
+    let caption_height = font.height().ceil() as u32 + 2 * CAPTION_MARGIN;
+    let mut labeled = RgbaImage::from_pixel(width, thumbnail.height() + caption_height, Rgba([255; 4]));
+    imageops::overlay(&mut labeled, &thumbnail, ((width - thumbnail.width()) / 2) as i64, 0);
+
+    let text = fit_text(&font, name, width as f32);
+    let x = (width as f32 - text_width(&font, &text)) / 2.0;
+    let baseline = (thumbnail.height() + CAPTION_MARGIN) as f32 + font.ascent();
+    draw_text(&mut labeled, &font, &text, x, baseline);
+    labeled
+}
+
+/// Contact sheet of the images at `paths`. Files that can't be read, e.g. JPEG XL
+/// output, are left out with a warning.
+pub fn contact_sheet(paths: &[PathBuf]) -> Result<DynamicImage> {
+    let thumbnails: Vec<DynamicImage> = paths
+        .iter()
+        .filter_map(|path| match image::open(path) {
+            Ok(image) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                Some(DynamicImage::ImageRgba8(labeled_thumbnail(&image, &name)))
+            }
+            Err(err) => {
+                eprintln!("Leaving {} off the contact sheet: {err}", path.display());
+                None
+            }
+        })
+        .collect();
+    if thumbnails.is_empty() {
+        return Err(anyhow!("No saved images for the contact sheet"));
+    }
+    Ok(combine_crops_with(thumbnails, &sheet_style()))
+}
+
+/// Write the contact sheet of `paths` to `destination`, in the format its
+/// extension names.
+pub fn write_contact_sheet(paths: &[PathBuf], destination: &Path) -> Result<()> {
+    let sheet = contact_sheet(paths)?;
+    // Opaque, so it can be written as JPEG as well
+    DynamicImage::ImageRgb8(sheet.to_rgb8())
+        .save(destination)
+        .with_context(|| format!("Unable to write {}", destination.display()))
+}
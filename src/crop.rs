@@ -0,0 +1,262 @@
+//! Crop rectangles as given on the command line (`--rect`, `--apply-to-all`)
+//! and in `.crops.json` sidecars, and cropping files with them outside the
+//! interactive app.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fs_utils::OutputLocation,
+    image_utils::{build_output_image_with, CombineStyle, EncodeSettings, OutputFormat, SaveRequest},
+    journal::Event,
+    selection::Selection,
+};
+
+/// One value of a [`CropSpec`]: pixels, or a percentage of the image width or height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Length {
+    /// Pixels of this length in an image side of `total` pixels.
+    pub fn resolve(self, total: u32) -> u32 {
+        match self {
+            Length::Pixels(pixels) => pixels,
+            Length::Percent(percent) => (total as f32 * percent / 100.0).round() as u32,
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        match self {
+            Length::Pixels(pixels) => pixels == 0,
+            Length::Percent(percent) => percent <= 0.0,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        match value.strip_suffix('%') {
+            Some(percent) => {
+                let percent: f32 = percent.trim().parse()?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(anyhow!("Percentages go from 0 to 100: {value}"));
+                }
+                Ok(Length::Percent(percent))
+            }
+            None => Ok(Length::Pixels(value.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{pixels}"),
+            Length::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// A crop rectangle `X,Y,W,H` whose values are pixels or percentages of the
+/// image size, e.g. `10%,10%,80%,80%`, so one spec fits images of any resolution.
+/// Serialized as that string; an array `[x, y, width, height]` of pixels is read as well.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SpecValue", into = "String")]
+pub struct CropSpec {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl CropSpec {
+    /// `[x, y, width, height]` in pixels of a `width` x `height` image.
+    pub fn resolve(&self, width: u32, height: u32) -> [u32; 4] {
+        [
+            self.x.resolve(width),
+            self.y.resolve(height),
+            self.width.resolve(width).max(1),
+            self.height.resolve(height).max(1),
+        ]
+    }
+
+    /// Whether the rectangle depends on the image size.
+    pub fn is_relative(&self) -> bool {
+        [self.x, self.y, self.width, self.height]
+            .iter()
+            .any(|length| matches!(length, Length::Percent(_)))
+    }
+
+    /// The rectangle as a selection in pixels of a `width` x `height` image.
+    pub fn selection(&self, width: u32, height: u32) -> Selection {
+        let [x, y, w, h] = self.resolve(width, height);
+        Selection::new(egui::Rect::from_min_size(
+            egui::pos2(x as f32, y as f32),
+            egui::vec2(w as f32, h as f32),
+        ))
+    }
+}
+
+impl From<[u32; 4]> for CropSpec {
+    fn from([x, y, width, height]: [u32; 4]) -> Self {
+        Self {
+            x: Length::Pixels(x),
+            y: Length::Pixels(y),
+            width: Length::Pixels(width),
+            height: Length::Pixels(height),
+        }
+    }
+}
+
+impl FromStr for CropSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let values = spec
+            .split(',')
+            .map(Length::from_str)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid crop geometry (expected X,Y,W,H in pixels or %): {spec}"))?;
+        match values[..] {
+            [x, y, width, height] if !width.is_zero() && !height.is_zero() => Ok(Self { x, y, width, height }),
+            _ => Err(anyhow!("Invalid crop geometry (expected X,Y,W,H in pixels or %): {spec}")),
+        }
+    }
+}
+
+impl TryFrom<String> for CropSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(spec: String) -> Result<Self> {
+        spec.parse()
+    }
+}
+
+/// A [`CropSpec`] as found in JSON.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpecValue {
+    Spec(String),
+    Pixels([f32; 4]),
+}
+
+impl TryFrom<SpecValue> for CropSpec {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SpecValue) -> Result<Self> {
+        match value {
+            SpecValue::Spec(spec) => spec.parse(),
+            SpecValue::Pixels(rect) if rect.iter().all(|value| *value >= 0.0) && rect[2] >= 0.5 && rect[3] >= 0.5 => {
+                Ok(Self::from(rect.map(|value| value.round() as u32)))
+            }
+            SpecValue::Pixels(rect) => Err(anyhow!("Invalid crop rectangle: {rect:?}")),
+        }
+    }
+}
+
+impl From<CropSpec> for String {
+    fn from(spec: CropSpec) -> Self {
+        spec.to_string()
+    }
+}
+
+impl fmt::Display for CropSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Parse a `--rect`/`--apply-to-all` value, see [`CropSpec`].
+pub fn parse_crop_spec(spec: &str) -> Result<CropSpec> {
+    spec.parse()
+}
+
+/// Selections of `specs` for the image at `path`, whose header is only read if
+/// a spec is relative to the image size.
+pub fn resolve_specs(path: &Path, specs: &[CropSpec]) -> Result<Vec<Selection>> {
+    let (width, height) = if specs.iter().any(CropSpec::is_relative) {
+        image::image_dimensions(path).with_context(|| format!("Failed to read the size of {}", path.display()))?
+    } else {
+        (u32::MAX, u32::MAX)
+    };
+    Ok(specs.iter().map(|spec| spec.selection(width, height)).collect())
+}
+
+/// Load `path` at full resolution and cut out `selections`.
+///
+/// If `reference` is given, the selections were drawn on an image of that size
+/// and are scaled to the loaded image; otherwise they are used as pixel values.
+pub fn crop_file(path: &Path, selections: &[Selection], reference: Option<egui::Vec2>) -> Result<SaveImage> {
+    crop_file_with(path, selections, reference, &CombineStyle::default())
+}
+
+/// [`crop_file`] with several selections combined as given by `style`.
+pub fn crop_file_with(
+    path: &Path,
+    selections: &[Selection],
+    reference: Option<egui::Vec2>,
+    style: &CombineStyle,
+) -> Result<SaveImage> {
+    let image = image::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let size = egui::vec2(image.width() as f32, image.height() as f32);
+    let selections: Vec<Selection> = selections
+        .iter()
+        .map(|selection| match reference {
+            Some(reference) => selection.scaled(reference, size),
+            None => {
+                let mut selection = selection.clone();
+                selection.clamp_within(size);
+                selection
+            }
+        })
+        .collect();
+    build_output_image_with(&image, &selections, style)
+        .map(|image| SaveImage {
+            path: path.to_path_buf(),
+            image,
+            selections,
+        })
+        .ok_or_else(|| anyhow!("Selections too small for {}", path.display()))
+}
+
+/// A cropped image ready to be written over `path`.
+pub struct SaveImage {
+    pub path: PathBuf,
+    pub image: image::DynamicImage,
+    /// The selections in pixels of the full-resolution image
+    pub selections: Vec<Selection>,
+}
+
+impl SaveImage {
+    pub fn output_path(&self, output: &OutputLocation, format: OutputFormat) -> PathBuf {
+        output.output_path(&self.path, format.extension())
+    }
+
+    /// Log entry for this crop.
+    pub fn event(&self, output: &OutputLocation, format: OutputFormat) -> Event {
+        Event::crop(&self.selections, &self.output_path(output, format))
+    }
+
+    pub fn into_request(self, output: &OutputLocation, settings: EncodeSettings) -> SaveRequest {
+        SaveRequest {
+            path: self.output_path(output, settings.format),
+            original_path: self.path,
+            image: self.image,
+            settings,
+            extra_outputs: Vec::new(),
+        }
+    }
+}
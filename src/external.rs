@@ -0,0 +1,20 @@
+//! External programs run by the optional video and PDF support, e.g. `ffmpeg`
+//! and `pdftoppm`.
+
+use std::{io, process::Command};
+
+/// Run `command` of the external `package` and return its output, or what it
+/// printed as error if it fails.
+pub fn run(command: &mut Command, package: &str) -> io::Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("Can't run {program} (is {package} installed?): {err}")))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
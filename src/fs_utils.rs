@@ -1,23 +1,34 @@
 use std::{
+    collections::HashSet,
     ffi::OsStr,
     fs,
+    io::BufRead,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{anyhow, Context, Result};
 use clap::ValueEnum;
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use rand::seq::SliceRandom;
 use regex::RegexSet;
+use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
 
 pub const TRASH_DIR: &str = ".imagecropper-trash";
 pub const ORIGINALS_DIR: &str = ".imagecropper-originals";
 pub const TEMP_DIR: &str = ".imagecropper-tmp";
 
+/// Helper folders ImageCropper creates next to images
+pub const HELPER_DIRS: &[&str] = &[TRASH_DIR, ORIGINALS_DIR, TEMP_DIR];
+
 pub const SUPPORTED_EXTENSIONS: &[&str] = &[
     "png", "jpg", "jpeg", "bmp", "gif", "webp", "tiff", "tif", "ico", "avif",
 ];
 
+/// Extension of XMP sidecar files written by RAW developers such as darktable or Lightroom
+pub const SIDECAR_EXTENSION: &str = "xmp";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum FilterSyntax {
     Glob,
@@ -67,6 +78,7 @@ impl PatternMatcher {
 }
 
 pub struct PathFilter {
+    include: PatternMatcher,
     whitelist: PatternMatcher,
     blacklist: PatternMatcher,
 }
@@ -77,17 +89,36 @@ impl PathFilter {
         whitelist_patterns: &[String],
         blacklist_patterns: &[String],
     ) -> Result<Option<Self>> {
-        if whitelist_patterns.is_empty() && blacklist_patterns.is_empty() {
+        Self::compile_with_include(syntax, &[], whitelist_patterns, blacklist_patterns)
+    }
+
+    /// Like [`PathFilter::compile`], but if `include_patterns` is not empty only
+    /// paths matching one of them are considered at all.
+    pub fn compile_with_include(
+        syntax: FilterSyntax,
+        include_patterns: &[String],
+        whitelist_patterns: &[String],
+        blacklist_patterns: &[String],
+    ) -> Result<Option<Self>> {
+        if include_patterns.is_empty()
+            && whitelist_patterns.is_empty()
+            && blacklist_patterns.is_empty()
+        {
             return Ok(None);
         }
 
         Ok(Some(Self {
+            include: PatternMatcher::compile(syntax, include_patterns)?,
             whitelist: PatternMatcher::compile(syntax, whitelist_patterns)?,
             blacklist: PatternMatcher::compile(syntax, blacklist_patterns)?,
         }))
     }
 
     pub fn matches(&self, path: &Path) -> bool {
+        if !matches!(self.include, PatternMatcher::None) && !self.include.matches(path) {
+            return false;
+        }
+
         if self.whitelist.matches(path) {
             return true;
         }
@@ -100,6 +131,30 @@ fn normalize_filter_path(path: &Path) -> String {
     path.to_string_lossy().replace('\\', "/")
 }
 
+/// Order in which images are processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    Filename,
+    Randomize,
+    Modified,
+    Size,
+}
+
+impl SortOrder {
+    /// Sort `files` in this order, reversed with `inverse` unless randomized.
+    pub fn sort(self, files: &mut [PathBuf], inverse: bool) {
+        match self {
+            Self::Filename => files.sort(),
+            Self::Randomize => files.shuffle(&mut rand::thread_rng()),
+            Self::Modified => files.sort_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok()),
+            Self::Size => files.sort_by_key(|path| fs::metadata(path).map(|m| m.len()).unwrap_or(0)),
+        }
+        if inverse && self != Self::Randomize {
+            files.reverse();
+        }
+    }
+}
+
 pub fn collect_images(paths: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
     collect_images_with_filter(paths, recursive, None)
 }
@@ -121,9 +176,11 @@ pub fn collect_images_with_filter(
             }
         } else if path.is_dir() {
             if recursive {
+                // Backups, trash and temp files aren't part of the queue
                 for entry in WalkDir::new(path)
                     .follow_links(false)
                     .into_iter()
+                    .filter_entry(|entry| entry.depth() == 0 || !is_helper_dir(entry))
                     .filter_map(|e| e.ok())
                 {
                     if entry.file_type().is_file()
@@ -150,19 +207,50 @@ pub fn collect_images_with_filter(
             }
         }
     }
+    // A file given on its own may also be inside one of the given directories
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
     Ok(files)
 }
 
+/// Read newline-separated paths for `--files-from`, ignoring empty lines.
+pub fn read_file_list(reader: impl BufRead) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line.context("Failed to read file list")?;
+        let line = line.trim_end_matches('\r');
+        if !line.trim().is_empty() {
+            paths.push(PathBuf::from(line));
+        }
+    }
+    Ok(paths)
+}
+
+/// Position of `name` in `files` for `--start-at`.
+///
+/// `name` may be a path, a file name or a file name without extension, so a
+/// session can be resumed at an image that has since been converted.
+pub fn find_start(files: &[PathBuf], name: &Path) -> Result<usize> {
+    let stem = |path: &Path| path.file_stem().map(OsStr::to_os_string);
+    files
+        .iter()
+        .position(|path| path == name)
+        .or_else(|| files.iter().position(|path| path.file_name() == Some(name.as_os_str())))
+        .or_else(|| files.iter().position(|path| stem(path) == Some(name.as_os_str().to_os_string())))
+        .ok_or_else(|| anyhow!("{} is not among the images to process", name.display()))
+}
+
 fn is_supported_image(path: &Path) -> bool {
     matches!(
         path.extension()
             .and_then(|ext| ext.to_str())
             .map(|s| s.to_ascii_lowercase()),
         Some(ref ext) if SUPPORTED_EXTENSIONS.contains(&ext.as_str())
-    )
+    ) || crate::video::is_video(path)
+        || crate::pdf::is_pdf(path)
 }
 
-pub fn prepare_dir(base: &Path, name: &str) -> Result<PathBuf> {
+pub fn prepare_dir(base: &Path, name: impl AsRef<Path>) -> Result<PathBuf> {
     let dir = base.join(name);
     fs::create_dir_all(&dir).with_context(|| format!("Unable to create {}", dir.display()))?;
     Ok(dir)
@@ -173,14 +261,86 @@ pub fn move_with_unique_name(source: &Path, target_dir: &Path) -> Result<PathBuf
         .file_name()
         .ok_or_else(|| anyhow!("{} has no file name", source.display()))?;
     let destination = unique_destination(target_dir, file_name);
-    fs::rename(source, &destination).with_context(|| {
+    move_file(source, &destination)?;
+    Ok(destination)
+}
+
+/// Rename `source` to `destination`, copying and deleting if they are on different file systems.
+pub fn move_file(source: &Path, destination: &Path) -> Result<()> {
+    move_file_with(source, destination, false)
+}
+
+/// [`move_file`] for `--fsync`: the data is on disk before it replaces `destination`
+/// and the new directory entry is flushed afterwards, so a power loss leaves either
+/// the old or the complete new file.
+pub fn move_file_durable(source: &Path, destination: &Path) -> Result<()> {
+    move_file_with(source, destination, true)
+}
+
+fn move_file_with(source: &Path, destination: &Path, durable: bool) -> Result<()> {
+    let context = || {
         format!(
             "Unable to move {} to {}",
             source.display(),
             destination.display()
         )
-    })?;
-    Ok(destination)
+    };
+    if durable {
+        sync_file(source).with_context(context)?;
+    }
+    if fs::rename(source, destination).is_err() {
+        // Copy next to the destination first, so it is replaced in one step
+        // and never left half-written
+        let partial = partial_path(destination);
+        let copied = fs::copy(source, &partial)
+            .and_then(|_| if durable { sync_file(&partial) } else { Ok(()) })
+            .and_then(|()| fs::rename(&partial, destination));
+        if let Err(err) = copied {
+            let _ = fs::remove_file(&partial);
+            return Err(err).with_context(context);
+        }
+        fs::remove_file(source).with_context(context)?;
+    }
+    if durable {
+        if let Some(parent) = destination.parent() {
+            sync_dir(parent).with_context(context)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `data` to `path` through a temp file next to it, so `path` is replaced in
+/// one step and never left half-written.
+pub fn write_atomically(path: &Path, data: impl AsRef<[u8]>) -> Result<()> {
+    let temp = partial_path(path);
+    if let Err(err) = fs::write(&temp, data) {
+        let _ = fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("Unable to write {}", path.display()));
+    }
+    move_file(&temp, path)
+}
+
+/// Hidden name next to `destination` for a file that is still being copied.
+fn partial_path(destination: &Path) -> PathBuf {
+    let name = destination.file_name().unwrap_or_default().to_string_lossy();
+    destination.with_file_name(format!(".{name}.imagecropper-partial"))
+}
+
+/// Flush the contents of the file at `path` to disk.
+fn sync_file(path: &Path) -> std::io::Result<()> {
+    fs::OpenOptions::new().write(true).open(path)?.sync_all()
+}
+
+/// Flush the entries of directory `dir`, e.g. after a rename into it.
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        fs::File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
 }
 
 pub fn unique_destination(dir: &Path, file_name: &OsStr) -> PathBuf {
@@ -212,10 +372,277 @@ pub fn split_name(file_name: &OsStr) -> (String, Option<String>) {
     }
 }
 
-pub fn backup_original(path: &Path) -> Result<PathBuf> {
+/// XMP sidecars of `image`: `photo.jpg.xmp` (darktable) and `photo.xmp` (Lightroom).
+pub fn find_sidecars(image: &Path) -> Vec<PathBuf> {
+    let Some(name) = image.file_name() else {
+        return Vec::new();
+    };
+    let extensions = [SIDECAR_EXTENSION.to_string(), SIDECAR_EXTENSION.to_ascii_uppercase()];
+    let full_name = |ext: &String| {
+        let mut sidecar = name.to_os_string();
+        sidecar.push(".");
+        sidecar.push(ext);
+        image.with_file_name(sidecar)
+    };
+    let mut sidecars: Vec<PathBuf> = Vec::new();
+    // Only the first spelling that exists, they are the same file on case-insensitive file systems
+    for candidate in [
+        extensions.iter().map(full_name).find(|p| p.is_file()),
+        extensions.iter().map(|ext| image.with_extension(ext)).find(|p| p.is_file()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if !sidecars.contains(&candidate) {
+            sidecars.push(candidate);
+        }
+    }
+    sidecars
+}
+
+/// Where `sidecar` of `image` belongs once the image is at `destination`.
+pub fn sidecar_destination(sidecar: &Path, image: &Path, destination: &Path) -> PathBuf {
+    let ext = sidecar.extension().unwrap_or(OsStr::new(SIDECAR_EXTENSION));
+    if image.file_name().is_some_and(|name| sidecar.file_stem() == Some(name)) {
+        let mut name = destination.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(ext);
+        destination.with_file_name(name)
+    } else {
+        destination.with_extension(ext)
+    }
+}
+
+/// Move the sidecars of `image` next to `destination`, or copy them if `copy`
+/// is set because the image itself stays. Returns the new sidecar paths.
+pub fn move_sidecars(image: &Path, destination: &Path, copy: bool) -> Result<Vec<PathBuf>> {
+    let mut moved = Vec::new();
+    for sidecar in find_sidecars(image) {
+        let target = sidecar_destination(&sidecar, image, destination);
+        if target == sidecar {
+            continue;
+        }
+        if copy {
+            fs::copy(&sidecar, &target).with_context(|| {
+                format!("Unable to copy {} to {}", sidecar.display(), target.display())
+            })?;
+        } else {
+            move_file(&sidecar, &target)?;
+        }
+        moved.push(target);
+    }
+    Ok(moved)
+}
+
+/// Where deleted images go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TrashMode {
+    /// Move into a `.imagecropper-trash` folder next to the image
+    #[default]
+    Folder,
+    /// Use the desktop's trash / recycle bin, falling back to the folder if that fails
+    System,
+}
+
+/// Locations of the trash, originals and temp folders.
+///
+/// By default each folder is created next to the image it is used for. `workdir`
+/// moves all of them below one directory (e.g. on a scratch disk). Each folder can
+/// also be overridden; relative overrides are resolved against `workdir` if set,
+/// otherwise against the image's directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkDirs {
+    pub workdir: Option<PathBuf>,
+    pub trash: Option<PathBuf>,
+    pub originals: Option<PathBuf>,
+    pub temp: Option<PathBuf>,
+}
+
+impl WorkDirs {
+    fn prepare(&self, custom: Option<&Path>, default_name: &str, image: &Path) -> Result<PathBuf> {
+        let parent = image.parent().unwrap_or_else(|| Path::new("."));
+        let base = self.workdir.as_deref().unwrap_or(parent);
+        prepare_dir(base, custom.unwrap_or(Path::new(default_name)))
+    }
+
+    /// Create and return the trash folder used for `image`.
+    pub fn trash_dir(&self, image: &Path) -> Result<PathBuf> {
+        self.prepare(self.trash.as_deref(), TRASH_DIR, image)
+    }
+
+    /// Create and return the folder backed up originals of `image` are moved to.
+    pub fn originals_dir(&self, image: &Path) -> Result<PathBuf> {
+        self.prepare(self.originals.as_deref(), ORIGINALS_DIR, image)
+    }
+
+    /// Create and return the folder new files for `image` are written to before being moved in place.
+    pub fn temp_dir(&self, image: &Path) -> Result<PathBuf> {
+        self.prepare(self.temp.as_deref(), TEMP_DIR, image)
+    }
+
+    pub fn backup_original(&self, path: &Path) -> Result<PathBuf> {
+        let dir = self.originals_dir(path)?;
+        move_with_unique_name(path, &dir)
+    }
+}
+
+/// Where saved images are written: next to their source, or into a separate
+/// directory that mirrors the structure below the input directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputLocation {
+    /// Separate output directory; `None` writes next to the source
+    pub dir: Option<PathBuf>,
+    /// Input directories whose structure is mirrored below `dir`
+    pub roots: Vec<PathBuf>,
+}
+
+impl OutputLocation {
+    /// Path the image loaded from `source` is saved to with the given extension.
+    pub fn output_path(&self, source: &Path, extension: &str) -> PathBuf {
+        let Some(dir) = &self.dir else {
+            return source.with_extension(extension);
+        };
+        let relative = self
+            .roots
+            .iter()
+            .filter_map(|root| source.strip_prefix(root).ok())
+            .min_by_key(|relative| relative.components().count())
+            .filter(|relative| relative.file_name().is_some())
+            .map(Path::to_path_buf)
+            .or_else(|| source.file_name().map(PathBuf::from))
+            .unwrap_or_default();
+        dir.join(relative).with_extension(extension)
+    }
+
+    /// Whether sources stay untouched because results go to a separate directory.
+    pub fn is_separate(&self) -> bool {
+        self.dir.is_some()
+    }
+}
+
+/// Move `path` to the trash and return a short description of where it went.
+///
+/// XMP sidecars of the image go along with it.
+pub fn trash_file(path: &Path, mode: TrashMode, dirs: &WorkDirs) -> Result<String> {
+    if mode == TrashMode::System {
+        match trash::delete(path) {
+            Ok(()) => {
+                for sidecar in find_sidecars(path) {
+                    if let Err(err) = trash::delete(&sidecar) {
+                        eprintln!("Unable to move {} to the system trash: {err}", sidecar.display());
+                    }
+                }
+                return Ok("the system trash".to_string());
+            }
+            Err(err) => eprintln!(
+                "System trash unavailable for {} ({err}), using the trash folder",
+                path.display()
+            ),
+        }
+    }
+    let target_dir = dirs.trash_dir(path).context("Unable to prepare trash directory")?;
+    let destination = move_with_unique_name(path, &target_dir)?;
+    // The image is gone already, so a sidecar left behind is not worth failing for
+    if let Err(err) = move_sidecars(path, &destination, false) {
+        eprintln!("{err:#}");
+    }
+    Ok(target_dir.display().to_string())
+}
+
+/// Move `path` into the culling folder `folder`, which is relative to the
+/// image's directory unless absolute. Returns the new path of the image.
+pub fn sort_into_folder(path: &Path, folder: &Path) -> Result<PathBuf> {
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
-    let dir = prepare_dir(parent, ORIGINALS_DIR)?;
-    move_with_unique_name(path, &dir)
+    let target_dir = prepare_dir(parent, folder)?;
+    let destination = move_with_unique_name(path, &target_dir)?;
+    if let Err(err) = move_sidecars(path, &destination, false) {
+        eprintln!("{err:#}");
+    }
+    Ok(destination)
+}
+
+pub fn backup_original(path: &Path) -> Result<PathBuf> {
+    WorkDirs::default().backup_original(path)
+}
+
+/// A helper folder found by [`find_helper_dirs`] and the files inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelperDirUsage {
+    pub path: PathBuf,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Whether `entry` is a trash, originals or temp folder.
+fn is_helper_dir(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_dir()
+        && entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| HELPER_DIRS.contains(&name))
+}
+
+/// Recursively find trash, originals and temp folders below `root`.
+pub fn find_helper_dirs(root: &Path) -> Result<Vec<HelperDirUsage>> {
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+    let mut found = Vec::new();
+    let mut entries = WalkDir::new(root).follow_links(false).into_iter();
+    while let Some(entry) = entries.next() {
+        let entry = entry.with_context(|| format!("Unable to scan {}", root.display()))?;
+        if is_helper_dir(&entry) {
+            let (files, bytes) = files_older_than(entry.path(), Duration::ZERO)?
+                .iter()
+                .fold((0, 0), |(count, total), (_, size)| (count + 1, total + size));
+            found.push(HelperDirUsage {
+                path: entry.path().to_path_buf(),
+                files,
+                bytes,
+            });
+            entries.skip_current_dir();
+        }
+    }
+    Ok(found)
+}
+
+/// Delete files in `dir` last modified at least `min_age` ago.
+///
+/// Returns the number of files and bytes removed (or that would be removed with `dry_run`).
+pub fn purge_helper_dir(dir: &Path, min_age: Duration, dry_run: bool) -> Result<(usize, u64)> {
+    let mut removed = (0, 0);
+    for (path, size) in files_older_than(dir, min_age)? {
+        if !dry_run {
+            fs::remove_file(&path).with_context(|| format!("Unable to delete {}", path.display()))?;
+        }
+        removed.0 += 1;
+        removed.1 += size;
+    }
+    Ok(removed)
+}
+
+fn files_older_than(dir: &Path, min_age: Duration) -> Result<Vec<(PathBuf, u64)>> {
+    let now = SystemTime::now();
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).follow_links(false) {
+        let entry = entry.with_context(|| format!("Unable to scan {}", dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let meta = entry
+            .metadata()
+            .with_context(|| format!("Unable to read {}", entry.path().display()))?;
+        let age = meta
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age >= min_age {
+            files.push((entry.path().to_path_buf(), meta.len()));
+        }
+    }
+    Ok(files)
 }
 
 /// Format bytes into a short human readable string using 1024-based units.
@@ -281,3 +708,47 @@ pub fn format_overall_summary(original_bytes: u64, new_bytes: u64, deleted_bytes
     }
 }
 
+/// Format a duration as e.g. `1h 02m 05s`, `3m 20s` or `42s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m {seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// What happened during a session, shown when the list is completed and printed at exit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionStats {
+    pub viewed: usize,
+    pub cropped: usize,
+    pub resaved: usize,
+    pub deleted: usize,
+    /// Sizes of the sources and results of all completed saves
+    pub original_bytes: u64,
+    pub new_bytes: u64,
+    pub deleted_bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl SessionStats {
+    pub fn summary_lines(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Viewed {} images in {}: {} cropped, {} resaved, {} deleted",
+                self.viewed,
+                format_duration(self.elapsed),
+                self.cropped,
+                self.resaved,
+                self.deleted
+            ),
+            format_savings_summary(self.original_bytes, self.new_bytes),
+            format_deletion_summary(self.deleted_bytes),
+        ]
+    }
+}
+
@@ -1,11 +1,19 @@
-use std::path::PathBuf;
+use std::{
+    borrow::Cow,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::ValueEnum;
 use eframe::egui;
-use image::{DynamicImage, GenericImage, RgbaImage};
+use image::{
+    codecs::avif::AvifEncoder, ColorType, DynamicImage, GenericImage, GenericImageView, GrayImage, ImageBuffer, Luma,
+    Pixel, Rgba, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::selection::Selection;
+use crate::selection::{parse_hex_color, Selection, SelectionShape};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 pub enum OutputFormat {
@@ -13,6 +21,9 @@ pub enum OutputFormat {
     Png,
     Webp,
     Avif,
+    /// JPEG XL, requires building with the `jxl` feature
+    Jxl,
+    Tiff,
 }
 
 impl OutputFormat {
@@ -22,13 +33,371 @@ impl OutputFormat {
             OutputFormat::Png => "png",
             OutputFormat::Webp => "webp",
             OutputFormat::Avif => "avif",
+            OutputFormat::Jxl => "jxl",
+            OutputFormat::Tiff => "tiff",
         }
     }
+
+    /// Format of a file with this extension, e.g. `jpeg` or `TIF`.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(OutputFormat::Jpg),
+            "png" => Some(OutputFormat::Png),
+            "webp" => Some(OutputFormat::Webp),
+            "avif" => Some(OutputFormat::Avif),
+            "jxl" => Some(OutputFormat::Jxl),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            _ => None,
+        }
+    }
+
+    /// Whether images with 16 bits per channel are written without reducing them to 8.
+    pub fn supports_16bit(&self) -> bool {
+        matches!(self, OutputFormat::Png | OutputFormat::Tiff)
+    }
+
+    /// Whether the encoder of this format takes a quality setting.
+    pub fn uses_quality(&self) -> bool {
+        matches!(self, OutputFormat::Jpg | OutputFormat::Avif | OutputFormat::Jxl)
+    }
+}
+
+/// Speed/size trade-off of PNG output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngCompression {
+    /// Fast deflate with a fixed filter, for large batches
+    Fast,
+    /// The encoder's balanced defaults
+    #[default]
+    Default,
+    /// Strongest deflate level; also optimized with oxipng if built with the `oxipng` feature
+    Best,
+}
+
+impl PngCompression {
+    fn settings(self) -> (image::codecs::png::CompressionType, image::codecs::png::FilterType) {
+        use image::codecs::png::{CompressionType, FilterType};
+        match self {
+            PngCompression::Fast => (CompressionType::Fast, FilterType::Sub),
+            PngCompression::Default => (CompressionType::Default, FilterType::Adaptive),
+            PngCompression::Best => (CompressionType::Best, FilterType::Adaptive),
+        }
+    }
+}
+
+/// Encode `image` as PNG.
+pub fn encode_png(image: &DynamicImage, compression: PngCompression) -> Result<Vec<u8>> {
+    let (level, filter) = compression.settings();
+    let mut data = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new_with_quality(&mut data, level, filter);
+    image.write_with_encoder(encoder)?;
+    #[cfg(feature = "oxipng")]
+    if compression == PngCompression::Best {
+        data = oxipng::optimize_from_memory(&data, &oxipng::Options::default())?;
+    }
+    Ok(data)
+}
+
+/// Lossless compression of TIFF output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TiffCompression {
+    /// Uncompressed, readable by every TIFF reader
+    #[default]
+    None,
+    Lzw,
+    /// Usually smaller than LZW, but slower to write
+    Deflate,
+}
+
+impl TiffCompression {
+    fn method(self) -> tiff::encoder::Compression {
+        use tiff::encoder::{Compression, DeflateLevel};
+        match self {
+            TiffCompression::None => Compression::Uncompressed,
+            TiffCompression::Lzw => Compression::Lzw,
+            TiffCompression::Deflate => Compression::Deflate(DeflateLevel::Balanced),
+        }
+    }
+}
+
+/// Encode `image` as TIFF, embedding the ICC profile `icc` if given.
+pub fn encode_tiff(image: &DynamicImage, compression: TiffCompression, icc: Option<&[u8]>) -> Result<Vec<u8>> {
+    use tiff::encoder::{colortype, TiffEncoder};
+
+    let mut data = std::io::Cursor::new(Vec::new());
+    {
+        let mut encoder = TiffEncoder::new(&mut data)?.with_compression(compression.method());
+        let (width, height) = (image.width(), image.height());
+        match image {
+            DynamicImage::ImageLuma8(gray) => {
+                write_tiff::<_, colortype::Gray8>(&mut encoder, width, height, gray.as_raw(), icc)?
+            }
+            DynamicImage::ImageLuma16(gray) => {
+                write_tiff::<_, colortype::Gray16>(&mut encoder, width, height, gray.as_raw(), icc)?
+            }
+            DynamicImage::ImageRgb8(rgb) => {
+                write_tiff::<_, colortype::RGB8>(&mut encoder, width, height, rgb.as_raw(), icc)?
+            }
+            DynamicImage::ImageRgb16(rgb) => {
+                write_tiff::<_, colortype::RGB16>(&mut encoder, width, height, rgb.as_raw(), icc)?
+            }
+            image if is_high_bit_depth(image) => {
+                write_tiff::<_, colortype::RGBA16>(&mut encoder, width, height, image.to_rgba16().as_raw(), icc)?
+            }
+            image => write_tiff::<_, colortype::RGBA8>(&mut encoder, width, height, image.to_rgba8().as_raw(), icc)?,
+        }
+    }
+    Ok(data.into_inner())
+}
+
+fn write_tiff<W, C>(
+    encoder: &mut tiff::encoder::TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    pixels: &[C::Inner],
+    icc: Option<&[u8]>,
+) -> Result<()>
+where
+    W: std::io::Write + std::io::Seek,
+    C: tiff::encoder::colortype::ColorType,
+    [C::Inner]: tiff::encoder::TiffValue,
+{
+    let mut image = encoder.new_image::<C>(width, height)?;
+    if let Some(icc) = icc {
+        image.encoder().write_tag(tiff::tags::Tag::IccProfile, icc)?;
+    }
+    image.write_data(pixels)?;
+    Ok(())
+}
+
+/// Default JPEG XL encoder effort (1 = fastest, 9 = smallest files)
+pub const DEFAULT_JXL_EFFORT: u8 = 7;
+
+/// Output quality used unless configured
+pub const DEFAULT_QUALITY: u8 = 70;
+
+/// Default AVIF encoder speed (1 = slowest and smallest, 10 = fastest)
+pub const DEFAULT_AVIF_SPEED: u8 = 4;
+
+/// Metadata of the original copied into a saved image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CopyMetadata {
+    /// EXIF data and ICC profile
+    #[default]
+    All,
+    /// Only the ICC profile, for redacted images: the EXIF thumbnail or GPS
+    /// position could give away what was hidden
+    ColorProfile,
+    /// Nothing, for selection masks: an RGB profile or the camera's EXIF data
+    /// don't belong into a black-and-white PNG
+    Nothing,
+}
+
+/// How a saved image is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeSettings {
+    pub format: OutputFormat,
+    /// 1-100, used by the lossy formats (JPEG, AVIF, JPEG XL)
+    pub quality: u8,
+    /// AVIF encoder speed, 1-10
+    pub avif_speed: u8,
+    /// Write progressive JPEGs
+    pub progressive: bool,
+    pub png_compression: PngCompression,
+    pub tiff_compression: TiffCompression,
+    /// JPEG XL encoder effort, 1-9
+    pub jxl_effort: u8,
+    pub metadata: CopyMetadata,
+}
+
+impl EncodeSettings {
+    /// `format` with the default settings.
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            quality: DEFAULT_QUALITY,
+            avif_speed: DEFAULT_AVIF_SPEED,
+            progressive: false,
+            png_compression: PngCompression::default(),
+            tiff_compression: TiffCompression::default(),
+            jxl_effort: DEFAULT_JXL_EFFORT,
+            metadata: CopyMetadata::All,
+        }
+    }
+
+    /// Settings of a selection mask: PNG without metadata.
+    pub fn mask() -> Self {
+        Self {
+            metadata: CopyMetadata::Nothing,
+            ..Self::new(OutputFormat::Png)
+        }
+    }
+
+    /// These settings with the quality changed by `step`, kept within 1-100.
+    pub fn with_quality_step(self, step: i32) -> Self {
+        Self {
+            quality: (self.quality as i32 + step).clamp(1, 100) as u8,
+            ..self
+        }
+    }
+}
+
+/// Whether `image` has more than 8 bits per channel.
+pub fn is_high_bit_depth(image: &DynamicImage) -> bool {
+    let color = image.color();
+    color.bytes_per_pixel() > color.channel_count()
+}
+
+/// `image` in a pixel format the encoder of `format` accepts: 16 bits per
+/// channel if the source has more than 8 and the format supports it, otherwise 8.
+pub fn encodable(image: &DynamicImage, format: OutputFormat) -> Cow<'_, DynamicImage> {
+    match image.color() {
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => Cow::Borrowed(image),
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 if format.supports_16bit() => {
+            Cow::Borrowed(image)
+        }
+        color if format.supports_16bit() && color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba16(image.to_rgba16())),
+        _ if format.supports_16bit() => Cow::Owned(DynamicImage::ImageRgb16(image.to_rgb16())),
+        ColorType::L16 => Cow::Owned(DynamicImage::ImageLuma8(image.to_luma8())),
+        ColorType::La16 => Cow::Owned(DynamicImage::ImageLumaA8(image.to_luma_alpha8())),
+        color if color.has_alpha() => Cow::Owned(DynamicImage::ImageRgba8(image.to_rgba8())),
+        _ => Cow::Owned(DynamicImage::ImageRgb8(image.to_rgb8())),
+    }
+}
+
+/// Encode `image` into the file format of `settings`.
+pub fn encode(image: &DynamicImage, settings: &EncodeSettings) -> Result<Vec<u8>> {
+    let image = &*encodable(image, settings.format);
+    let mut data = Vec::new();
+    match settings.format {
+        OutputFormat::Jpg if settings.progressive => {
+            let rgb = image.to_rgb8();
+            let (width, height) = (u16::try_from(rgb.width()), u16::try_from(rgb.height()));
+            let (Ok(width), Ok(height)) = (width, height) else {
+                return Err(anyhow::anyhow!("Image too large for JPEG"));
+            };
+            let mut encoder = jpeg_encoder::Encoder::new(&mut data, settings.quality);
+            encoder.set_progressive(true);
+            encoder.encode(rgb.as_raw(), width, height, jpeg_encoder::ColorType::Rgb)?;
+        }
+        OutputFormat::Jpg => {
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut data, settings.quality);
+            image.write_with_encoder(encoder)?;
+        }
+        OutputFormat::Png => data = encode_png(image, settings.png_compression)?,
+        OutputFormat::Webp => {
+            let encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut data);
+            image.write_with_encoder(encoder)?;
+        }
+        OutputFormat::Avif => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut data, settings.avif_speed, settings.quality);
+            image.write_with_encoder(encoder)?;
+        }
+        OutputFormat::Jxl => data = encode_jxl(image, settings.quality, settings.jxl_effort)?,
+        OutputFormat::Tiff => data = encode_tiff(image, settings.tiff_compression, None)?,
+    }
+    Ok(data)
+}
+
+/// Butteraugli distance for a 1-100 quality, mapped like libjxl's
+/// `JxlEncoderDistanceFromQuality` (100 is lossless, 90 about visually lossless).
+pub fn jxl_distance(quality: u8) -> f32 {
+    let q = quality.min(100) as f32;
+    if q >= 100.0 {
+        0.0
+    } else if q >= 30.0 {
+        0.1 + (100.0 - q) * 0.09
+    } else {
+        53.0 / 3000.0 * q * q - 23.0 / 20.0 * q + 25.0
+    }
+}
+
+/// Encode `image` as JPEG XL.
+#[cfg(feature = "jxl")]
+pub fn encode_jxl(image: &DynamicImage, quality: u8, effort: u8) -> Result<Vec<u8>> {
+    use jpegxl_rs::encode::{encoder_builder, EncoderResult, EncoderSpeed};
+
+    let speed = match effort {
+        0 | 1 => EncoderSpeed::Lightning,
+        2 => EncoderSpeed::Thunder,
+        3 => EncoderSpeed::Falcon,
+        4 => EncoderSpeed::Cheetah,
+        5 => EncoderSpeed::Hare,
+        6 => EncoderSpeed::Wombat,
+        7 => EncoderSpeed::Squirrel,
+        8 => EncoderSpeed::Kitten,
+        _ => EncoderSpeed::Tortoise,
+    };
+    let has_alpha = image.color().has_alpha();
+    let mut encoder = encoder_builder()
+        .has_alpha(has_alpha)
+        .lossless(quality >= 100)
+        .quality(jxl_distance(quality))
+        .speed(speed)
+        .build()?;
+    let result: EncoderResult<u8> = if has_alpha {
+        encoder.encode::<u8, u8>(image.to_rgba8().as_raw(), image.width(), image.height())?
+    } else {
+        encoder.encode::<u8, u8>(image.to_rgb8().as_raw(), image.width(), image.height())?
+    };
+    Ok(result.data)
+}
+
+#[cfg(not(feature = "jxl"))]
+pub fn encode_jxl(_image: &DynamicImage, _quality: u8, _effort: u8) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!(
+        "This build has no JPEG XL support; rebuild with `--features jxl`"
+    ))
+}
+
+/// Signature box that starts a JPEG XL file in the ISOBMFF container format
+const JXL_CONTAINER_SIGNATURE: [u8; 12] = [0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A];
+
+/// An ISOBMFF box of type `kind` holding the concatenated `payload`.
+pub(crate) fn bmff_box(kind: &[u8; 4], payload: &[&[u8]]) -> Vec<u8> {
+    let len: usize = 8 + payload.iter().map(|p| p.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(len);
+    out.extend((len as u32).to_be_bytes());
+    out.extend(kind);
+    for part in payload {
+        out.extend(*part);
+    }
+    out
+}
+
+/// Add an `Exif` box with `exif` (TIFF data) to a JPEG XL file.
+///
+/// A bare codestream is wrapped in the container format; in an existing container
+/// the box is inserted after `ftyp`. Returns `None` if `jxl` isn't JPEG XL.
+pub fn add_jxl_exif(jxl: &[u8], exif: &[u8]) -> Option<Vec<u8>> {
+    // The box payload starts with the offset of the TIFF header, which follows directly
+    let exif_box = bmff_box(b"Exif", &[&[0, 0, 0, 0], exif]);
+    if jxl.starts_with(&[0xFF, 0x0A]) {
+        let mut out = JXL_CONTAINER_SIGNATURE.to_vec();
+        out.extend(bmff_box(b"ftyp", &[b"jxl ", &[0, 0, 0, 0], b"jxl "]));
+        out.extend(exif_box);
+        out.extend(bmff_box(b"jxlc", &[jxl]));
+        return Some(out);
+    }
+    let rest = jxl.strip_prefix(&JXL_CONTAINER_SIGNATURE)?;
+    let ftyp_len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    if rest.get(4..8)? != b"ftyp" || ftyp_len < 8 || ftyp_len > rest.len() {
+        return None;
+    }
+    let mut out = JXL_CONTAINER_SIGNATURE.to_vec();
+    out.extend(&rest[..ftyp_len]);
+    out.extend(exif_box);
+    out.extend(&rest[ftyp_len..]);
+    Some(out)
 }
 
 pub struct PreloadedImage {
     pub path: PathBuf,
     pub image: DynamicImage,
+    /// Perceptual hash of the image, see [`dhash`]; `None` if it wasn't computed
+    pub hash: Option<u64>,
     pub color_image: Option<egui::ColorImage>,
     pub texture: Option<wgpu::Texture>,
     pub load_duration: std::time::Duration,
@@ -38,12 +407,45 @@ pub struct PreloadedImage {
     pub texture_gen_duration: std::time::Duration,
 }
 
+impl PreloadedImage {
+    /// Approximate memory used by the decoded pixels and GPU texture.
+    pub fn memory_size(&self) -> u64 {
+        let pixels = self.image.as_bytes().len() as u64;
+        let texture = self
+            .texture
+            .as_ref()
+            .map_or(0, |t| t.width() as u64 * t.height() as u64 * 4);
+        let color_image = self
+            .color_image
+            .as_ref()
+            .map_or(0, |c| c.pixels.len() as u64 * 4);
+        pixels + texture + color_image
+    }
+}
+
 pub struct SaveRequest {
     pub image: DynamicImage,
     pub path: PathBuf,
     pub original_path: PathBuf,
-    pub quality: u8,
-    pub format: OutputFormat,
+    pub settings: EncodeSettings,
+    /// More images written after the same backup, e.g. tiles or a mask
+    pub extra_outputs: Vec<ExtraOutput>,
+}
+
+impl SaveRequest {
+    /// Memory held by the pixels of all images to be written.
+    pub fn memory_size(&self) -> u64 {
+        let extra: usize = self.extra_outputs.iter().map(|output| output.image.as_bytes().len()).sum();
+        (self.image.as_bytes().len() + extra) as u64
+    }
+}
+
+/// A further file of a [`SaveRequest`], with the metadata of the original copied
+/// into it like into the main output unless its settings leave it out.
+pub struct ExtraOutput {
+    pub path: PathBuf,
+    pub image: DynamicImage,
+    pub settings: EncodeSettings,
 }
 
 pub struct SaveStatus {
@@ -53,6 +455,93 @@ pub struct SaveStatus {
     pub original_size: Option<u64>,
     /// Size of the newly-written file (in bytes), if available
     pub new_size: Option<u64>,
+    /// Where the replaced original was backed up to, if it was
+    pub backup_path: Option<PathBuf>,
+    /// Files written along with the image, e.g. its mask
+    pub extra_paths: Vec<PathBuf>,
+}
+
+/// Images whose [`dhash`] differs in at most this many bits are considered near-duplicates
+pub const DUPLICATE_HASH_DISTANCE: u32 = 6;
+
+/// 64-bit difference hash: compares neighboring pixels of a 9x8 grayscale thumbnail.
+///
+/// Resized, recompressed or slightly edited copies of an image get the same or a
+/// very similar hash; compare with [`hash_distance`].
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// Number of differing bits between two [`dhash`] values.
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Per-channel value counts of an image or image region.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+    pub luma: [u32; 256],
+}
+
+impl Histogram {
+    /// Upper bound on the number of sampled pixels; larger inputs are subsampled.
+    const MAX_SAMPLES: u64 = 262_144;
+
+    /// Compute the histogram of `image`, restricted to `region` (`x, y, width, height`) if given.
+    pub fn from_image(image: &DynamicImage, region: Option<(u32, u32, u32, u32)>) -> Self {
+        let mut histogram = Self {
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+            luma: [0; 256],
+        };
+        let (x0, y0, w, h) = region.unwrap_or((0, 0, image.width(), image.height()));
+        let x1 = (x0 + w).min(image.width());
+        let y1 = (y0 + h).min(image.height());
+        if x1 <= x0 || y1 <= y0 {
+            return histogram;
+        }
+
+        let pixels = (x1 - x0) as u64 * (y1 - y0) as u64;
+        let step = ((pixels as f64 / Self::MAX_SAMPLES as f64).sqrt().ceil() as u32).max(1);
+        // Read only the sampled pixels: converting the whole image would cost a frame while dragging
+        for y in (y0..y1).step_by(step as usize) {
+            for x in (x0..x1).step_by(step as usize) {
+                let [r, g, b, _] = image.get_pixel(x, y).0;
+                histogram.red[r as usize] += 1;
+                histogram.green[g as usize] += 1;
+                histogram.blue[b as usize] += 1;
+                // Rec. 601 luma
+                let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round();
+                histogram.luma[luma as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    pub fn max_count(&self) -> u32 {
+        [&self.red, &self.green, &self.blue, &self.luma]
+            .iter()
+            .flat_map(|channel| channel.iter())
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
 }
 
 pub fn to_color_image(img: &DynamicImage) -> egui::ColorImage {
@@ -62,66 +551,368 @@ pub fn to_color_image(img: &DynamicImage) -> egui::ColorImage {
     egui::ColorImage::from_rgba_unmultiplied(size, &pixels)
 }
 
-pub fn combine_crops(mut crops: Vec<DynamicImage>) -> DynamicImage {
-    // Simple shelf packing or just horizontal stacking if few?
-    // User wants to "minimize empty space".
-    // Let's sort by height descending.
-    crops.sort_by(|a, b| b.height().cmp(&a.height()));
+/// How [`combine_crops`] arranges several crops into one image.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CombineLayout {
+    /// Side by side in selection order
+    Horizontal,
+    /// Below each other in selection order
+    Vertical,
+    /// Rows of a roughly square grid in selection order
+    Grid,
+    /// Packed as tightly as possible, minimizing empty space
+    #[default]
+    Pack,
+}
+
+impl CombineLayout {
+    pub const ALL: [CombineLayout; 4] = [
+        CombineLayout::Horizontal,
+        CombineLayout::Vertical,
+        CombineLayout::Grid,
+        CombineLayout::Pack,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CombineLayout::Horizontal => "horizontal",
+            CombineLayout::Vertical => "vertical",
+            CombineLayout::Grid => "grid",
+            CombineLayout::Pack => "pack",
+        }
+    }
+
+    /// The layout after this one, for cycling with a key.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|layout| *layout == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 
-    // Calculate total area to estimate canvas size
-    let total_area: u64 = crops.iter().map(|i| i.width() as u64 * i.height() as u64).sum();
-    let max_width = (total_area as f64).sqrt().ceil() as u32 * 2; // Heuristic: start with something wider
+    /// Top-left corner of each of the crops with the given sizes, and the size
+    /// of the canvas they are placed on.
+    pub fn arrange(self, sizes: &[(u32, u32)]) -> (Vec<(u32, u32)>, (u32, u32)) {
+        let mut positions = vec![(0, 0); sizes.len()];
+        let (mut canvas_width, mut canvas_height) = (0, 0);
+        match self {
+            CombineLayout::Horizontal => {
+                for (position, &(width, height)) in positions.iter_mut().zip(sizes) {
+                    *position = (canvas_width, 0);
+                    canvas_width += width;
+                    canvas_height = canvas_height.max(height);
+                }
+            }
+            CombineLayout::Vertical => {
+                for (position, &(width, height)) in positions.iter_mut().zip(sizes) {
+                    *position = (0, canvas_height);
+                    canvas_width = canvas_width.max(width);
+                    canvas_height += height;
+                }
+            }
+            CombineLayout::Grid => {
+                let columns = (sizes.len() as f64).sqrt().ceil().max(1.0) as usize;
+                let rows = sizes.len().div_ceil(columns);
+                // Each column is as wide as its widest crop, each row as high as its highest
+                let mut column_widths = vec![0; columns];
+                let mut row_heights = vec![0; rows];
+                for (i, &(width, height)) in sizes.iter().enumerate() {
+                    column_widths[i % columns] = column_widths[i % columns].max(width);
+                    row_heights[i / columns] = row_heights[i / columns].max(height);
+                }
+                for (i, position) in positions.iter_mut().enumerate() {
+                    *position = (
+                        column_widths[..i % columns].iter().sum(),
+                        row_heights[..i / columns].iter().sum(),
+                    );
+                }
+                canvas_width = column_widths.iter().sum();
+                canvas_height = row_heights.iter().sum();
+            }
+            CombineLayout::Pack => return pack_crops(sizes),
+        }
+        (positions, (canvas_width, canvas_height))
+    }
+}
 
-    // Simple shelf algorithm
-    let mut canvas_width = 0;
-    let mut canvas_height = 0;
+/// Axis-aligned rectangle of free space while packing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FreeRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
 
-    struct PlacedImage {
-        x: u32,
-        y: u32,
-        img: DynamicImage,
+impl FreeRect {
+    fn contains(&self, other: &FreeRect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
     }
+}
+
+/// Positions of the crops and the canvas size, as returned by [`CombineLayout::arrange`]
+type Arrangement = (Vec<(u32, u32)>, (u32, u32));
 
-    let mut placed = Vec::new();
-    let mut current_x = 0;
-    let mut current_y = 0;
-    let mut row_height = 0;
+/// MaxRects packing of crops into a canvas `bin_width` wide. Each crop goes to
+/// the free spot where its bottom edge ends highest, then furthest left.
+fn max_rects(sizes: &[(u32, u32)], bin_width: u32) -> Option<Arrangement> {
+    let bin_height = sizes.iter().map(|&(_, height)| height).sum();
+    let mut free = vec![FreeRect {
+        x: 0,
+        y: 0,
+        width: bin_width,
+        height: bin_height,
+    }];
+    // Large crops first, the small ones fill the gaps
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| {
+        let (width, height) = sizes[i];
+        std::cmp::Reverse((width.max(height), width as u64 * height as u64))
+    });
 
-    // First pass: determine positions and canvas size
-    for img in crops {
-        if current_x + img.width() > max_width && current_x > 0 {
-            // New row
-            current_x = 0;
-            current_y += row_height;
-            row_height = 0;
+    let mut positions = vec![(0, 0); sizes.len()];
+    for i in order {
+        let (width, height) = sizes[i];
+        let (x, y) = free
+            .iter()
+            .filter(|spot| width <= spot.width && height <= spot.height)
+            .min_by_key(|spot| (spot.y + height, spot.x))
+            .map(|spot| (spot.x, spot.y))?;
+        positions[i] = (x, y);
+
+        // Split every free rectangle overlapping the placed crop into the parts around it
+        let mut split = Vec::new();
+        for spot in free {
+            if x >= spot.x + spot.width || x + width <= spot.x || y >= spot.y + spot.height || y + height <= spot.y {
+                split.push(spot);
+                continue;
+            }
+            if x > spot.x {
+                split.push(FreeRect { width: x - spot.x, ..spot });
+            }
+            if x + width < spot.x + spot.width {
+                split.push(FreeRect {
+                    x: x + width,
+                    width: spot.x + spot.width - x - width,
+                    ..spot
+                });
+            }
+            if y > spot.y {
+                split.push(FreeRect { height: y - spot.y, ..spot });
+            }
+            if y + height < spot.y + spot.height {
+                split.push(FreeRect {
+                    y: y + height,
+                    height: spot.y + spot.height - y - height,
+                    ..spot
+                });
+            }
         }
+        // Drop free rectangles inside others (of two equal ones, keep the first)
+        free = split
+            .iter()
+            .enumerate()
+            .filter(|&(a, spot)| {
+                !split
+                    .iter()
+                    .enumerate()
+                    .any(|(b, other)| a != b && other.contains(spot) && (other != spot || b < a))
+            })
+            .map(|(_, spot)| *spot)
+            .collect();
+    }
 
-        placed.push(PlacedImage {
-            x: current_x,
-            y: current_y,
-            img: img.clone(),
-        });
+    let canvas = positions.iter().zip(sizes).fold((0, 0), |(w, h), (&(x, y), &(width, height))| {
+        (w.max(x + width), h.max(y + height))
+    });
+    Some((positions, canvas))
+}
+
+/// [`CombineLayout::Pack`]: MaxRects packing with the canvas width giving the
+/// smallest canvas area (the squarer canvas on ties).
+fn pack_crops(sizes: &[(u32, u32)]) -> Arrangement {
+    let Some(widest) = sizes.iter().map(|&(width, _)| width).max() else {
+        return (Vec::new(), (0, 0));
+    };
+    // Widths where another crop fits next to the wider ones, and multiples of the
+    // side of a square with the total area
+    let mut widths: Vec<u32> = sizes.iter().map(|&(width, _)| width).collect();
+    widths.sort_unstable_by(|a, b| b.cmp(a));
+    let mut candidates: Vec<u32> = widths
+        .iter()
+        .scan(0, |sum, width| {
+            *sum += width;
+            Some(*sum)
+        })
+        .collect();
+    let total_area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+    for factor in [1.0, 1.25, 1.5, 2.0] {
+        candidates.push(widest.max(((total_area as f64).sqrt() * factor).ceil() as u32));
+    }
+    candidates.sort_unstable();
+    candidates.dedup();
+
+    candidates
+        .into_iter()
+        .filter_map(|width| max_rects(sizes, width))
+        .min_by_key(|(_, (width, height))| (*width as u64 * *height as u64, (*width).max(*height)))
+        .unwrap_or_default()
+}
+
+/// How several crops are combined into one image, the `[combine]` table of the config.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CombineStyle {
+    /// `horizontal`, `vertical`, `grid` or `pack`
+    pub layout: CombineLayout,
+    /// Pixels between neighboring crops
+    pub gap: u32,
+    /// Pixels around the combined crops
+    pub padding: u32,
+    /// Color of the gaps and the padding: `transparent`, `white`, `black` or `#rrggbb`.
+    /// Transparent areas turn black in JPEG files.
+    pub background: String,
+    /// Width of the lines drawn around each crop in the middle of the gaps, 0 for none.
+    /// The gap is widened to the line width.
+    pub separator_width: u32,
+    /// Color of the separator lines, as for `background`
+    pub separator_color: String,
+}
+
+impl Default for CombineStyle {
+    fn default() -> Self {
+        Self {
+            layout: CombineLayout::default(),
+            gap: 0,
+            padding: 0,
+            background: "transparent".to_string(),
+            separator_width: 0,
+            separator_color: "black".to_string(),
+        }
+    }
+}
+
+impl CombineStyle {
+    pub fn validate(&self) -> Result<()> {
+        parse_fill_color(&self.background).map_err(|err| anyhow!("[combine] background: {err}"))?;
+        parse_fill_color(&self.separator_color).map_err(|err| anyhow!("[combine] separator_color: {err}"))?;
+        Ok(())
+    }
 
-        row_height = row_height.max(img.height());
-        current_x += img.width();
+    fn background_color(&self) -> Rgba<u8> {
+        parse_fill_color(&self.background).unwrap_or(Rgba([0, 0, 0, 0]))
+    }
 
-        canvas_width = canvas_width.max(current_x);
-        canvas_height = canvas_height.max(current_y + row_height);
+    fn separator_color(&self) -> Rgba<u8> {
+        parse_fill_color(&self.separator_color).unwrap_or(Rgba([0, 0, 0, 255]))
     }
+}
+
+/// Parse `transparent`, `white`, `black` or a `#rrggbb` color.
+pub fn parse_fill_color(text: &str) -> Result<Rgba<u8>> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "transparent" => Ok(Rgba([0, 0, 0, 0])),
+        "white" => Ok(Rgba([255, 255, 255, 255])),
+        "black" => Ok(Rgba([0, 0, 0, 255])),
+        _ => parse_hex_color(text.trim()).map(|color| Rgba([color.r(), color.g(), color.b(), 255])),
+    }
+}
 
-    let mut final_image = RgbaImage::new(canvas_width, canvas_height);
+/// Combine several crops into one image with the default style.
+pub fn combine_crops(crops: Vec<DynamicImage>) -> DynamicImage {
+    combine_crops_with(crops, &CombineStyle::default())
+}
 
-    for p in placed {
-        // Copy pixels
-        // We can use image::GenericImage::copy_from but we need to be careful about types.
-        // DynamicImage implements GenericImage.
-        let _ = final_image.copy_from(&p.img, p.x, p.y);
+/// Combine several crops into one image arranged and spaced as given by `style`.
+pub fn combine_crops_with(crops: Vec<DynamicImage>, style: &CombineStyle) -> DynamicImage {
+    let gap = if style.separator_width > 0 {
+        style.gap.max(style.separator_width)
+    } else {
+        style.gap
+    };
+    // Every crop is arranged with the gap to its right and below it
+    let sizes: Vec<_> = crops.iter().map(|crop| (crop.width() + gap, crop.height() + gap)).collect();
+    let (positions, (width, height)) = style.layout.arrange(&sizes);
+    let positions: Vec<_> = positions
+        .into_iter()
+        .map(|(x, y)| (x + style.padding, y + style.padding))
+        .collect();
+    let canvas_width = width.saturating_sub(gap) + 2 * style.padding;
+    let canvas_height = height.saturating_sub(gap) + 2 * style.padding;
+    let lines = if style.separator_width > 0 {
+        separator_lines(&crops, &positions, gap, style.separator_width)
+    } else {
+        Vec::new()
+    };
+    let (background, separator) = (style.background_color(), style.separator_color());
+
+    // Keep 16-bit crops at their depth instead of going through 8-bit pixels
+    if crops.iter().any(is_high_bit_depth) {
+        let widen = |color: Rgba<u8>| Rgba(color.0.map(|channel| channel as u16 * 257));
+        let mut final_image = ImageBuffer::from_pixel(canvas_width, canvas_height, widen(background));
+        for (crop, &(x, y)) in crops.iter().zip(&positions) {
+            let _ = final_image.copy_from(&crop.to_rgba16(), x, y);
+        }
+        for &line in &lines {
+            fill_rect(&mut final_image, line, widen(separator));
+        }
+        return DynamicImage::ImageRgba16(final_image);
     }
 
+    let mut final_image = RgbaImage::from_pixel(canvas_width, canvas_height, background);
+    for (crop, &(x, y)) in crops.iter().zip(&positions) {
+        let _ = final_image.copy_from(crop, x, y);
+    }
+    for &line in &lines {
+        fill_rect(&mut final_image, line, separator);
+    }
     DynamicImage::ImageRgba8(final_image)
 }
 
+/// `[x, y, width, height]` of the lines framing each crop. They run through the
+/// middle of the gaps, so the frames of neighboring crops share their lines.
+fn separator_lines(crops: &[DynamicImage], positions: &[(u32, u32)], gap: u32, line_width: u32) -> Vec<[i64; 4]> {
+    let (gap, line_width) = (gap as i64, line_width as i64);
+    let mut lines = Vec::new();
+    for (crop, &(x, y)) in crops.iter().zip(positions) {
+        let left = x as i64 - (gap + 1) / 2 - line_width / 2;
+        let top = y as i64 - (gap + 1) / 2 - line_width / 2;
+        let right = (x + crop.width()) as i64 + gap / 2 - line_width / 2 + line_width;
+        let bottom = (y + crop.height()) as i64 + gap / 2 - line_width / 2 + line_width;
+        lines.extend([
+            [left, top, right - left, line_width],
+            [left, bottom - line_width, right - left, line_width],
+            [left, top, line_width, bottom - top],
+            [right - line_width, top, line_width, bottom - top],
+        ]);
+    }
+    lines
+}
+
+/// Fill `[x, y, width, height]` of `image` with `color`, clipped to the image.
+fn fill_rect<P: Pixel>(image: &mut ImageBuffer<P, Vec<P::Subpixel>>, [x, y, width, height]: [i64; 4], color: P) {
+    let columns = x.max(0)..(x + width).min(image.width() as i64);
+    for row in y.max(0)..(y + height).min(image.height() as i64) {
+        for column in columns.clone() {
+            image.put_pixel(column as u32, row as u32, color);
+        }
+    }
+}
+
+/// Cut `selections` out of `image`, combined with the default style.
 pub fn build_output_image(image: &DynamicImage, selections: &[Selection]) -> Option<DynamicImage> {
+    build_output_image_with(image, selections, &CombineStyle::default())
+}
+
+/// Cut `selections` out of `image`; several are combined into one image as given
+/// by `style`. No selections give the whole image.
+pub fn build_output_image_with(
+    image: &DynamicImage,
+    selections: &[Selection],
+    style: &CombineStyle,
+) -> Option<DynamicImage> {
     if selections.is_empty() {
         return Some(image.clone());
     }
@@ -130,7 +921,12 @@ pub fn build_output_image(image: &DynamicImage, selections: &[Selection]) -> Opt
     for selection in selections {
         if let Some((x, y, w, h)) = selection.to_u32_bounds() {
             if w > 0 && h > 0 {
-                crops.push(image.crop_imm(x, y, w, h));
+                let crop = if selection.angle == 0.0 {
+                    image.crop_imm(x, y, w, h)
+                } else {
+                    crop_rotated(image, selection)
+                };
+                crops.push(clear_outside_shape(crop, selection));
             }
         }
     }
@@ -140,7 +936,641 @@ pub fn build_output_image(image: &DynamicImage, selections: &[Selection]) -> Opt
     } else if crops.len() == 1 {
         Some(crops.remove(0))
     } else {
-        Some(combine_crops(crops))
+        Some(combine_crops_with(crops, style))
+    }
+}
+
+/// How `--redact` hides the selected regions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactStyle {
+    /// Strong gaussian blur
+    Blur,
+    /// Coarse blocks of the average color
+    Pixelate,
+    /// A solid color, black unless configured otherwise
+    Fill,
+}
+
+/// Gaussian blur of `image` strong enough to hide faces and text: sigma grows
+/// with the size of the region.
+pub fn blur_region(image: &DynamicImage) -> DynamicImage {
+    let sigma = (image.width().max(image.height()) as f32 / 10.0).max(3.0);
+    image.blur(sigma)
+}
+
+/// `image` as blocks of its average color, about 12 blocks along the longer side.
+pub fn pixelate_region(image: &DynamicImage) -> DynamicImage {
+    use image::imageops::FilterType;
+    let (width, height) = (image.width(), image.height());
+    let block = (width.max(height) / 12).max(2);
+    image
+        .resize_exact(width.div_ceil(block), height.div_ceil(block), FilterType::Triangle)
+        .resize_exact(width, height, FilterType::Nearest)
+}
+
+/// Pixels of a `width` x `height` image covered by the bounding box of the
+/// rotated `selection`, as `(x, y, width, height)`. `None` if they are empty.
+fn pixel_bounds(selection: &Selection, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let bounds = selection.bounding_box();
+    let x0 = bounds.min.x.max(0.0).round() as u32;
+    let y0 = bounds.min.y.max(0.0).round() as u32;
+    let x1 = (bounds.max.x.max(0.0).round() as u32).min(width);
+    let y1 = (bounds.max.y.max(0.0).round() as u32).min(height);
+    (x1 > x0 && y1 > y0).then(|| (x0, y0, x1 - x0, y1 - y0))
+}
+
+/// `image` with `selections` blurred, pixelated or filled with `color`; the rest
+/// stays untouched. Rotated and non-rectangular selections only change the
+/// pixels whose centers lie inside their shape.
+pub fn redact(image: &DynamicImage, selections: &[Selection], style: RedactStyle, color: Rgba<u8>) -> DynamicImage {
+    let mut output = image.clone();
+    for selection in selections {
+        let Some((x, y, w, h)) = pixel_bounds(selection, image.width(), image.height()) else { continue };
+        let region = image.crop_imm(x, y, w, h);
+        let region = match style {
+            RedactStyle::Blur => blur_region(&region),
+            RedactStyle::Pixelate => pixelate_region(&region),
+            RedactStyle::Fill => {
+                DynamicImage::ImageRgba8(RgbaImage::from_pixel(region.width(), region.height(), color))
+            }
+        };
+        if selection.angle == 0.0 && selection.shape == SelectionShape::Rectangle {
+            // The region was cut from the same place, so it always fits
+            let _ = output.copy_from(&region, x, y);
+            continue;
+        }
+        for (px, py, pixel) in region.pixels() {
+            if selection.contains(egui::pos2((x + px) as f32 + 0.5, (y + py) as f32 + 0.5)) {
+                output.put_pixel(x + px, y + py, pixel);
+            }
+        }
+    }
+    output
+}
+
+/// Whether `selection` on a `width` x `height` image marks a horizontal band
+/// (rows) rather than a vertical one (columns): its bounding box covers
+/// relatively more of the image's width than of its height.
+pub fn is_horizontal_band(selection: &Selection, width: u32, height: u32) -> bool {
+    let bounds = selection.bounding_box();
+    bounds.width() / width.max(1) as f32 >= bounds.height() / height.max(1) as f32
+}
+
+/// `image` with the bands marked by `selections` removed and the remaining parts
+/// stitched back together, e.g. to cut a watermark bar out of a screenshot. See
+/// [`is_horizontal_band`] for which rows or columns a selection removes. `None`
+/// if nothing would be left.
+pub fn remove_bands(image: &DynamicImage, selections: &[Selection]) -> Option<DynamicImage> {
+    let (width, height) = (image.width(), image.height());
+    let mut keep_rows = vec![true; height as usize];
+    let mut keep_columns = vec![true; width as usize];
+    for selection in selections {
+        // A rotated selection removes all rows or columns it touches
+        let Some((x, y, w, h)) = pixel_bounds(selection, width, height) else { continue };
+        let (keep, start, length) = if is_horizontal_band(selection, width, height) {
+            (&mut keep_rows, y, h)
+        } else {
+            (&mut keep_columns, x, w)
+        };
+        for kept in keep.iter_mut().skip(start as usize).take(length as usize) {
+            *kept = false;
+        }
+    }
+    let (rows, columns) = (kept_runs(&keep_rows), kept_runs(&keep_columns));
+    if rows.is_empty() || columns.is_empty() {
+        return None;
+    }
+
+    // Keep 16-bit images at their depth instead of going through 8-bit pixels
+    Some(if is_high_bit_depth(image) {
+        DynamicImage::ImageRgba16(stitch(&image.to_rgba16(), &rows, &columns))
+    } else {
+        DynamicImage::ImageRgba8(stitch(&image.to_rgba8(), &rows, &columns))
+    })
+}
+
+/// Ranges of consecutive `true` entries in `keep`.
+fn kept_runs(keep: &[bool]) -> Vec<Range<u32>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (index, &kept) in keep.iter().chain([&false]).enumerate() {
+        match (kept, start) {
+            (true, None) => start = Some(index as u32),
+            (false, Some(first)) => {
+                runs.push(first..index as u32);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// The parts of `image` in the given `rows` and `columns`, placed next to each other.
+fn stitch<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    rows: &[Range<u32>],
+    columns: &[Range<u32>],
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let length = |range: &Range<u32>| range.end - range.start;
+    let width = columns.iter().map(length).sum();
+    let height = rows.iter().map(length).sum();
+    let mut output = ImageBuffer::new(width, height);
+    let mut y = 0;
+    for row in rows {
+        let mut x = 0;
+        for column in columns {
+            let part = image::imageops::crop_imm(image, column.start, row.start, length(column), length(row));
+            let _ = output.copy_from(&part.to_image(), x, y);
+            x += length(column);
+        }
+        y += length(row);
+    }
+    output
+}
+
+/// Parse a tile grid given as `COLUMNSxROWS`, e.g. `4x3`, or a single number for both.
+pub fn parse_tile_grid(spec: &str) -> Result<(u32, u32)> {
+    let spec = spec.trim();
+    let (columns, rows) = spec.split_once(['x', 'X']).unwrap_or((spec, spec));
+    let parse = |value: &str| value.trim().parse::<u32>().ok().filter(|value| *value > 0);
+    match (parse(columns), parse(rows)) {
+        (Some(columns), Some(rows)) => Ok((columns, rows)),
+        _ => Err(anyhow!("Invalid tile grid (expected COLUMNSxROWS, e.g. 4x3): {spec}")),
+    }
+}
+
+/// Split `image` into a grid of `columns` x `rows` tiles, row by row.
+///
+/// Tiles are as equal as possible: if the size isn't divisible, they differ by
+/// at most one pixel. Never more tiles than pixels are made.
+pub fn split_into_tiles(image: &DynamicImage, columns: u32, rows: u32) -> Vec<DynamicImage> {
+    let (width, height) = (image.width(), image.height());
+    let (columns, rows) = (columns.clamp(1, width.max(1)), rows.clamp(1, height.max(1)));
+    let edge = |total: u32, count: u32, i: u32| (total as u64 * i as u64 / count as u64) as u32;
+    let mut tiles = Vec::new();
+    for row in 0..rows {
+        let (y0, y1) = (edge(height, rows, row), edge(height, rows, row + 1));
+        for column in 0..columns {
+            let (x0, x1) = (edge(width, columns, column), edge(width, columns, column + 1));
+            tiles.push(image.crop_imm(x0, y0, x1 - x0, y1 - y0));
+        }
+    }
+    tiles
+}
+
+/// Black-and-white mask of `selections` on a `width` x `height` image: white
+/// inside the selections, black elsewhere. No selections cover the whole image.
+pub fn selection_mask(width: u32, height: u32, selections: &[Selection]) -> DynamicImage {
+    if selections.is_empty() {
+        return DynamicImage::ImageLuma8(GrayImage::from_pixel(width, height, Luma([255])));
+    }
+    let mut mask = GrayImage::new(width, height);
+    for selection in selections {
+        if selection.angle != 0.0 || selection.shape != SelectionShape::Rectangle {
+            // Pixels whose centers lie inside the rotated shape
+            let bounds = selection.bounding_box();
+            let columns = bounds.min.x.max(0.0) as u32..(bounds.max.x.ceil() as u32).min(width);
+            for y in bounds.min.y.max(0.0) as u32..(bounds.max.y.ceil() as u32).min(height) {
+                for x in columns.clone() {
+                    if selection.contains(egui::pos2(x as f32 + 0.5, y as f32 + 0.5)) {
+                        mask.put_pixel(x, y, Luma([255]));
+                    }
+                }
+            }
+        } else if let Some((x, y, w, h)) = selection.to_u32_bounds() {
+            fill_rect(&mut mask, [x as i64, y as i64, w as i64, h as i64], Luma([255]));
+        }
+    }
+    DynamicImage::ImageLuma8(mask)
+}
+
+/// Output path of the selection mask of `path`: `photo_mask.png`.
+pub fn mask_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}_mask.png"))
+}
+
+/// Output path of a selection written to its own file: `photo_logo.png` for
+/// label `logo` and extension `png`.
+pub fn labeled_path(path: &Path, label: &str, extension: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}_{label}.{extension}"))
+}
+
+/// Save request for selections with outputs of their own: the other selections
+/// are combined into `output_path` with `settings`, each of these is cropped into
+/// its own [`labeled_path`]. Without other selections the first of them is the
+/// main output. A label whose file is already taken by another output or the
+/// mask gets a number, e.g. `photo_logo_2.png`.
+pub fn selection_request(
+    image: &DynamicImage,
+    selections: &[Selection],
+    style: &CombineStyle,
+    settings: EncodeSettings,
+    original_path: &Path,
+    output_path: &Path,
+) -> Option<SaveRequest> {
+    let combined: Vec<Selection> = selections
+        .iter()
+        .filter(|selection| selection.output.is_none())
+        .cloned()
+        .collect();
+    let mut outputs = Vec::new();
+    if !combined.is_empty() {
+        outputs.push(ExtraOutput {
+            path: output_path.to_path_buf(),
+            image: build_output_image_with(image, &combined, style)?,
+            settings,
+        });
+    }
+    let mut taken = vec![output_path.to_path_buf(), mask_path(output_path)];
+    for (index, selection) in selections.iter().enumerate() {
+        let Some(output) = &selection.output else { continue };
+        let settings = output.settings(settings);
+        let label = output.file_label(index + 1);
+        let extension = settings.format.extension();
+        let mut path = labeled_path(output_path, &label, extension);
+        for number in 2.. {
+            if !taken.contains(&path) {
+                break;
+            }
+            path = labeled_path(output_path, &format!("{label}_{number}"), extension);
+        }
+        taken.push(path.clone());
+        outputs.push(ExtraOutput {
+            path,
+            image: build_output_image(image, std::slice::from_ref(selection))?,
+            settings,
+        });
+    }
+    if outputs.is_empty() {
+        return None;
+    }
+    let main = outputs.remove(0);
+    Some(SaveRequest {
+        image: main.image,
+        path: main.path,
+        original_path: original_path.to_path_buf(),
+        settings: main.settings,
+        extra_outputs: outputs,
+    })
+}
+
+/// Output path of a tile: `photo_r2_c3.avif` (zero-based row and column), with
+/// the selection number (`photo_1_r2_c3.avif`) if several selections are tiled.
+pub fn tile_path(path: &Path, selection: Option<usize>, row: u32, column: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let selection = selection.map(|index| format!("_{}", index + 1)).unwrap_or_default();
+    let mut name = format!("{stem}{selection}_r{row}_c{column}");
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// Size of the largest axis-aligned rectangle that fits inside a `width` x
+/// `height` rectangle rotated by `degrees`.
+pub fn inscribed_size(width: f32, height: f32, degrees: f32) -> (f32, f32) {
+    if width <= 0.0 || height <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let angle = degrees.to_radians();
+    let (sin_a, cos_a) = (angle.sin().abs(), angle.cos().abs());
+    let width_is_longer = width >= height;
+    let (long, short) = if width_is_longer { (width, height) } else { (height, width) };
+
+    if short <= 2.0 * sin_a * cos_a * long || (sin_a - cos_a).abs() < 1e-6 {
+        // Half constrained: two corners of the inscribed rect touch the longer side
+        let x = 0.5 * short;
+        if width_is_longer {
+            (x / sin_a, x / cos_a)
+        } else {
+            (x / cos_a, x / sin_a)
+        }
+    } else {
+        // Fully constrained: all four corners touch the rotated rectangle
+        let cos_2a = cos_a * cos_a - sin_a * sin_a;
+        (
+            (width * cos_a - height * sin_a) / cos_2a,
+            (height * cos_a - width * sin_a) / cos_2a,
+        )
+    }
+}
+
+/// Rotate `image` clockwise by `degrees` around its center and crop the result
+/// to the largest inner rectangle, so no empty corners remain.
+pub fn straighten(image: &DynamicImage, degrees: f32) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let (src_w, src_h) = (width as f32, height as f32);
+    let (out_w, out_h) = inscribed_size(src_w, src_h, degrees);
+    let out_w = (out_w.floor() as u32).clamp(1, width);
+    let out_h = (out_h.floor() as u32).clamp(1, height);
+
+    let angle = degrees.to_radians();
+    let (sin_a, cos_a) = angle.sin_cos();
+    let (src_cx, src_cy) = (src_w * 0.5, src_h * 0.5);
+    let (out_cx, out_cy) = (out_w as f32 * 0.5, out_h as f32 * 0.5);
+
+    // Map the output pixel center back into the unrotated source
+    let source_pos = |x: u32, y: u32| {
+        let dx = x as f32 + 0.5 - out_cx;
+        let dy = y as f32 + 0.5 - out_cy;
+        (
+            src_cx + cos_a * dx + sin_a * dy - 0.5,
+            src_cy - sin_a * dx + cos_a * dy - 0.5,
+        )
+    };
+
+    if is_high_bit_depth(image) {
+        let source = image.to_rgba16();
+        let output = ImageBuffer::from_fn(out_w, out_h, |x, y| {
+            let (sx, sy) = source_pos(x, y);
+            let pixel = sample_bilinear(width, height, |x, y| source.get_pixel(x, y).0.map(f32::from), sx, sy);
+            Rgba(pixel.map(|c| c.round() as u16))
+        });
+        return DynamicImage::ImageRgba16(output);
+    }
+    let source = image.to_rgba8();
+    let output = RgbaImage::from_fn(out_w, out_h, |x, y| {
+        let (sx, sy) = source_pos(x, y);
+        let pixel = sample_bilinear(width, height, |x, y| source.get_pixel(x, y).0.map(f32::from), sx, sy);
+        Rgba(pixel.map(|c| c.round() as u8))
+    });
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Color underneath the transparent area outside a selection's shape, which
+/// formats without transparency such as JPEG show instead
+pub const OUTSIDE_SHAPE_COLOR: Rgba<u8> = Rgba([255, 255, 255, 0]);
+
+/// Make the pixels of `crop`, cut out of the rectangle of `selection`, that lie
+/// outside the selection's shape transparent. Rectangles are returned as is.
+pub fn clear_outside_shape(crop: DynamicImage, selection: &Selection) -> DynamicImage {
+    if selection.shape == SelectionShape::Rectangle {
+        return crop;
+    }
+    let (width, height) = (crop.width(), crop.height());
+    let rect = selection.rect;
+    // Pixel centers of the upright crop relative to the unrotated rectangle
+    let inside = |x: u32, y: u32| {
+        selection.shape_contains(egui::pos2(
+            rect.min.x + (x as f32 + 0.5) / width as f32 * rect.width(),
+            rect.min.y + (y as f32 + 0.5) / height as f32 * rect.height(),
+        ))
+    };
+    if is_high_bit_depth(&crop) {
+        let mut output = crop.to_rgba16();
+        let outside = Rgba(OUTSIDE_SHAPE_COLOR.0.map(|channel| channel as u16 * 257));
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            if !inside(x, y) {
+                *pixel = outside;
+            }
+        }
+        return DynamicImage::ImageRgba16(output);
     }
+    let mut output = crop.to_rgba8();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        if !inside(x, y) {
+            *pixel = OUTSIDE_SHAPE_COLOR;
+        }
+    }
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Cut the rectangle of `selection`, rotated by its angle, out of `image` and
+/// turn it upright. Parts outside the image are transparent.
+pub fn crop_rotated(image: &DynamicImage, selection: &Selection) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let size = selection.rect.size();
+    let (out_w, out_h) = ((size.x.round() as u32).max(1), (size.y.round() as u32).max(1));
+    let center = selection.rect.center();
+    let (sin_a, cos_a) = selection.angle.to_radians().sin_cos();
+
+    // Map the output pixel center into the source, None outside of it
+    let source_pos = |x: u32, y: u32| {
+        let dx = x as f32 + 0.5 - out_w as f32 * 0.5;
+        let dy = y as f32 + 0.5 - out_h as f32 * 0.5;
+        let (sx, sy) = (center.x + cos_a * dx - sin_a * dy, center.y + sin_a * dx + cos_a * dy);
+        let inside = (0.0..=width as f32).contains(&sx) && (0.0..=height as f32).contains(&sy);
+        inside.then_some((sx - 0.5, sy - 0.5))
+    };
+
+    if is_high_bit_depth(image) {
+        let source = image.to_rgba16();
+        let output = ImageBuffer::from_fn(out_w, out_h, |x, y| match source_pos(x, y) {
+            Some((sx, sy)) => {
+                let pixel = sample_bilinear(width, height, |x, y| source.get_pixel(x, y).0.map(f32::from), sx, sy);
+                Rgba(pixel.map(|c| c.round() as u16))
+            }
+            None => Rgba([0; 4]),
+        });
+        return DynamicImage::ImageRgba16(output);
+    }
+    let source = image.to_rgba8();
+    let output = RgbaImage::from_fn(out_w, out_h, |x, y| match source_pos(x, y) {
+        Some((sx, sy)) => {
+            let pixel = sample_bilinear(width, height, |x, y| source.get_pixel(x, y).0.map(f32::from), sx, sy);
+            Rgba(pixel.map(|c| c.round() as u8))
+        }
+        None => Rgba([0; 4]),
+    });
+    DynamicImage::ImageRgba8(output)
 }
 
+/// Interpolate the channels of the `width` x `height` image read by `pixel` at `x`, `y`.
+fn sample_bilinear(width: u32, height: u32, pixel: impl Fn(u32, u32) -> [f32; 4], x: f32, y: f32) -> [f32; 4] {
+    let max_x = width.saturating_sub(1) as f32;
+    let max_y = height.saturating_sub(1) as f32;
+    let x = x.clamp(0.0, max_x);
+    let y = y.clamp(0.0, max_y);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(max_x as u32), (y0 + 1).min(max_y as u32));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let p00 = pixel(x0, y0);
+    let p10 = pixel(x1, y0);
+    let p01 = pixel(x0, y1);
+    let p11 = pixel(x1, y1);
+    let mut out = [0.0; 4];
+    for c in 0..4 {
+        let top = p00[c] * (1.0 - fx) + p10[c] * fx;
+        let bottom = p01[c] * (1.0 - fx) + p11[c] * fx;
+        out[c] = top * (1.0 - fy) + bottom * fy;
+    }
+    out
+}
+
+/// Detect uniform borders (scanner margins, letterboxing) around the image
+/// content and return the content bounds as `(x, y, width, height)`.
+///
+/// A row or column counts as border while every pixel differs by at most
+/// `tolerance` per channel from the corner color of that side. Returns `None`
+/// when there is nothing to trim or the whole image is uniform.
+pub fn detect_content_bounds(image: &DynamicImage, tolerance: u8) -> Option<(u32, u32, u32, u32)> {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let top_left = *rgba.get_pixel(0, 0);
+    let bottom_right = *rgba.get_pixel(width - 1, height - 1);
+    let similar = |pixel: &Rgba<u8>, reference: &Rgba<u8>| {
+        pixel
+            .0
+            .iter()
+            .zip(reference.0.iter())
+            .take(3)
+            .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    };
+    let row_is_border = |y: u32, x0: u32, x1: u32, reference: &Rgba<u8>| {
+        (x0..x1).all(|x| similar(rgba.get_pixel(x, y), reference))
+    };
+    let column_is_border = |x: u32, y0: u32, y1: u32, reference: &Rgba<u8>| {
+        (y0..y1).all(|y| similar(rgba.get_pixel(x, y), reference))
+    };
+
+    let (mut top, mut bottom) = (0, height);
+    while top < bottom && row_is_border(top, 0, width, &top_left) {
+        top += 1;
+    }
+    while bottom > top && row_is_border(bottom - 1, 0, width, &bottom_right) {
+        bottom -= 1;
+    }
+    if top >= bottom {
+        return None;
+    }
+
+    let (mut left, mut right) = (0, width);
+    while left < right && column_is_border(left, top, bottom, &top_left) {
+        left += 1;
+    }
+    while right > left && column_is_border(right - 1, top, bottom, &bottom_right) {
+        right -= 1;
+    }
+    if left >= right {
+        return None;
+    }
+
+    if (left, top, right, bottom) == (0, 0, width, height) {
+        return None;
+    }
+    Some((left, top, right - left, bottom - top))
+}
+
+/// Average Sobel response along a selection edge above which it snaps to an
+/// image edge, about a step of 20 gray levels
+const EDGE_SNAP_THRESHOLD: f32 = 80.0;
+
+/// Luminance of an image whose Sobel gradients are used to snap selection edges
+/// to strong nearby image edges (`--snap-edges`). Gradients are only computed
+/// for the pixels around the edges of the selection being snapped.
+pub struct EdgeMap {
+    width: u32,
+    height: u32,
+    gray: GrayImage,
+}
+
+impl EdgeMap {
+    pub fn new(image: &DynamicImage) -> Self {
+        let gray = image.to_luma8();
+        let (width, height) = gray.dimensions();
+        Self { width, height, gray }
+    }
+
+    fn luminance(&self, x: i64, y: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.gray.get_pixel(x, y).0[0] as f32
+    }
+
+    /// |Gx| at pixel `x`, `y`, strong along vertical image edges.
+    fn horizontal_gradient(&self, x: u32, y: u32) -> f32 {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let px = |x, y| self.luminance(x, y);
+        let gx = px(x + 1, y - 1) + 2.0 * px(x + 1, y) + px(x + 1, y + 1)
+            - px(x - 1, y - 1)
+            - 2.0 * px(x - 1, y)
+            - px(x - 1, y + 1);
+        gx.abs()
+    }
+
+    /// |Gy| at pixel `x`, `y`, strong along horizontal image edges.
+    fn vertical_gradient(&self, x: u32, y: u32) -> f32 {
+        let (x, y) = (i64::from(x), i64::from(y));
+        let px = |x, y| self.luminance(x, y);
+        let gy = px(x - 1, y + 1) + 2.0 * px(x, y + 1) + px(x + 1, y + 1)
+            - px(x - 1, y - 1)
+            - 2.0 * px(x, y - 1)
+            - px(x + 1, y - 1);
+        gy.abs()
+    }
+
+    /// Move each edge of an unrotated selection to the strongest image edge
+    /// within `radius` pixels, if there is one strong enough.
+    pub fn snap(&self, selection: &mut Selection, radius: u32) {
+        if selection.angle != 0.0 {
+            return;
+        }
+        let rect = selection.rect;
+        let rows = rect.min.y.max(0.0).floor() as u32..(rect.max.y.ceil() as u32).min(self.height);
+        let columns = rect.min.x.max(0.0).floor() as u32..(rect.max.x.ceil() as u32).min(self.width);
+        let min = egui::pos2(
+            self.snap_edge(rect.min.x, &rows, true, radius),
+            self.snap_edge(rect.min.y, &columns, false, radius),
+        );
+        let max = egui::pos2(
+            self.snap_edge(rect.max.x, &rows, true, radius),
+            self.snap_edge(rect.max.y, &columns, false, radius),
+        );
+        // Both edges snapping to the same image edge would collapse the selection
+        if max.x > min.x && max.y > min.y {
+            selection.rect = egui::Rect::from_min_max(min, max);
+        }
+    }
+
+    /// `position` of a vertical (or horizontal) selection edge spanning the rows
+    /// (or columns) `span`, moved to the strongest image edge nearby.
+    fn snap_edge(&self, position: f32, span: &Range<u32>, vertical: bool, radius: u32) -> f32 {
+        let limit = i64::from(if vertical { self.width } else { self.height });
+        let center = position.round() as i64;
+        (center - radius as i64..=center + radius as i64)
+            .filter(|boundary| (0..=limit).contains(boundary))
+            .map(|boundary| (boundary, self.edge_strength(boundary as u32, span, vertical)))
+            .filter(|(_, strength)| *strength >= EDGE_SNAP_THRESHOLD)
+            // The strongest edge, the nearest one on ties
+            .max_by(|a, b| {
+                a.1.total_cmp(&b.1)
+                    .then_with(|| (b.0 - center).abs().cmp(&(a.0 - center).abs()))
+            })
+            .map_or(position, |(boundary, _)| boundary as f32)
+    }
+
+    /// Mean gradient across the pixel boundary `boundary` along `span`.
+    fn edge_strength(&self, boundary: u32, span: &Range<u32>, vertical: bool) -> f32 {
+        if span.is_empty() {
+            return 0.0;
+        }
+        // `across` counts pixels perpendicular to the edge, `along` parallel to it
+        let gradient = |across: u32, along: u32| {
+            if vertical && across < self.width {
+                self.horizontal_gradient(across, along)
+            } else if !vertical && across < self.height {
+                self.vertical_gradient(along, across)
+            } else {
+                0.0
+            }
+        };
+        let sum: f32 = span
+            .clone()
+            .map(|along| {
+                let before = boundary.checked_sub(1).map_or(0.0, |before| gradient(before, along));
+                (before + gradient(boundary, along)) / 2.0
+            })
+            .sum();
+        sum / span.len() as f32
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "catalog")]
+use crate::catalog::Catalog;
+use crate::selection::Selection;
+
+/// Default name of the operation log, created in the current directory
+pub const DEFAULT_LOG_FILE: &str = "imagecropper-log.jsonl";
+
+/// Something that happened to an image, as written to the log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Event {
+    /// Selections (`[x, y, width, height]` in pixels of the source image, after any rotation) queued for saving
+    Crop { rects: Vec<[u32; 4]>, output: PathBuf },
+    /// Selections written as annotations with their classes, in pixels of the source image
+    Annotate {
+        rects: Vec<[u32; 4]>,
+        classes: Vec<u32>,
+        output: PathBuf,
+    },
+    /// Selections (in pixels of the source image) blurred, pixelated or filled in the saved image
+    Redact { rects: Vec<[u32; 4]>, output: PathBuf },
+    /// Bands (in pixels of the source image) cut out of the saved image
+    Exclude { rects: Vec<[u32; 4]>, output: PathBuf },
+    /// Converted to the output format or saved after rotating/straightening
+    Resave { output: PathBuf },
+    Skip,
+    /// Marked as finished without changes
+    Done,
+    Delete { destination: String, bytes: Option<u64> },
+    /// Moved into a culling folder
+    Sort { destination: PathBuf },
+    /// Star rating written to `file`, the image or its XMP sidecar
+    Rate { rating: u8, file: PathBuf },
+    /// A queued crop or resave was written to disk
+    Saved {
+        output: PathBuf,
+        original_bytes: Option<u64>,
+        new_bytes: Option<u64>,
+        /// Bytes saved compared to the original; negative if the file grew
+        saved_bytes: Option<i64>,
+        backup: Option<PathBuf>,
+    },
+    SaveFailed { error: String },
+}
+
+impl Event {
+    pub fn crop(selections: &[Selection], output: &Path) -> Self {
+        Self::Crop {
+            rects: rects(selections),
+            output: output.to_path_buf(),
+        }
+    }
+
+    pub fn redact(selections: &[Selection], output: &Path) -> Self {
+        Self::Redact {
+            rects: rects(selections),
+            output: output.to_path_buf(),
+        }
+    }
+
+    pub fn exclude(selections: &[Selection], output: &Path) -> Self {
+        Self::Exclude {
+            rects: rects(selections),
+            output: output.to_path_buf(),
+        }
+    }
+
+    pub fn annotate(selections: &[Selection], output: &Path) -> Self {
+        Self::Annotate {
+            rects: rects(selections),
+            classes: selections.iter().map(|s| s.class).collect(),
+            output: output.to_path_buf(),
+        }
+    }
+
+    pub fn saved(output: &Path, sizes: (Option<u64>, Option<u64>), backup: Option<PathBuf>) -> Self {
+        let (original_bytes, new_bytes) = sizes;
+        Self::Saved {
+            output: output.to_path_buf(),
+            original_bytes,
+            new_bytes,
+            saved_bytes: original_bytes.zip(new_bytes).map(|(o, n)| o as i64 - n as i64),
+            backup,
+        }
+    }
+}
+
+fn rects(selections: &[Selection]) -> Vec<[u32; 4]> {
+    selections
+        .iter()
+        .map(|s| {
+            let (min, size) = (s.rect.min.round(), s.rect.size().round());
+            [min.x as u32, min.y as u32, size.x as u32, size.y as u32]
+        })
+        .collect()
+}
+
+/// One line of the log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// RFC 3339 UTC timestamp
+    pub time: String,
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Append-only JSON Lines log of everything done to the images.
+///
+/// Clones share the same file, so the app and the saver threads can all record
+/// into it. With the `catalog` feature, entries can also go to a SQLite
+/// [`Catalog`]. The default journal records nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    file: Option<Arc<Mutex<File>>>,
+    #[cfg(feature = "catalog")]
+    catalog: Option<Arc<Mutex<Catalog>>>,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file {}", path.display()))?;
+        Ok(Self {
+            file: Some(Arc::new(Mutex::new(file))),
+            ..Self::default()
+        })
+    }
+
+    /// Also record into `catalog`.
+    #[cfg(feature = "catalog")]
+    pub fn with_catalog(self, catalog: Catalog) -> Self {
+        Self {
+            catalog: Some(Arc::new(Mutex::new(catalog))),
+            ..self
+        }
+    }
+
+    fn is_recording(&self) -> bool {
+        #[cfg(feature = "catalog")]
+        if self.catalog.is_some() {
+            return true;
+        }
+        self.file.is_some()
+    }
+
+    /// Append `event` for `path`. Failures are reported but don't interrupt the work.
+    pub fn record(&self, path: &Path, event: Event) {
+        if !self.is_recording() {
+            return;
+        }
+        let entry = Entry {
+            time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            path: path.to_path_buf(),
+            event,
+        };
+        #[cfg(feature = "catalog")]
+        if let Some(catalog) = &self.catalog {
+            let result = catalog
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Catalog lock poisoned"))
+                .and_then(|catalog| catalog.record(&entry));
+            if let Err(err) = result {
+                eprintln!("Failed to write catalog entry: {err:#}");
+            }
+        }
+        let Some(file) = &self.file else { return };
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = file.lock().map_err(|_| anyhow::anyhow!("Log file lock poisoned"))?;
+                // One write per line so entries from several threads don't interleave
+                file.write_all(format!("{line}\n").as_bytes())?;
+                Ok(())
+            });
+        if let Err(err) = result {
+            eprintln!("Failed to write log entry: {err:#}");
+        }
+    }
+}
+
+/// Read all entries of a log file.
+pub fn read_log(path: &Path) -> Result<Vec<Entry>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read log file {}", path.display()))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid entry on line {} of {}", i + 1, path.display()))
+        })
+        .collect()
+}
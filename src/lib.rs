@@ -1,5 +1,25 @@
+pub mod annotations;
 pub mod app;
+pub mod archive;
+pub mod autocrop;
+pub mod avif;
+#[cfg(feature = "catalog")]
+pub mod catalog;
+pub mod clipboard;
+pub mod config;
+pub mod contact_sheet;
+pub mod crop;
+pub mod external;
 pub mod fs_utils;
 pub mod image_utils;
+pub mod journal;
+pub mod metadata;
+pub mod pdf;
+pub mod pipeline;
+pub mod probe;
+pub mod processed;
+pub mod recent;
 pub mod selection;
+pub mod thumbnail_cache;
 pub mod ui;
+pub mod video;
@@ -1,41 +1,186 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use eframe::egui;
-use rand::seq::SliceRandom;
-
-use imagecropper::app::ImageCropperApp;
-use imagecropper::fs_utils::{collect_images_with_filter, FilterSyntax, PathFilter};
-use imagecropper::image_utils::OutputFormat;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
-enum SortOrder {
-    Filename,
-    Randomize,
-    Modified,
-    Size,
+
+use imagecropper::annotations::AnnotationFormat;
+use imagecropper::app::batch::{apply_to_all, convert_all, BatchCrop, BatchOptions};
+use imagecropper::app::loader::{parse_max_preview, DEFAULT_PRELOAD_AHEAD, DEFAULT_PRELOAD_BEHIND};
+use imagecropper::app::saver::DEFAULT_MAX_PENDING_SAVES;
+use imagecropper::app::launcher::{LaunchSettings, Launcher};
+use imagecropper::app::{AppOptions, ImageCropperApp};
+use imagecropper::archive::parse_archive_path;
+use imagecropper::autocrop::parse_aspect_ratio;
+use imagecropper::config::{Compression, Config, FormatConfig};
+use imagecropper::crop::{parse_crop_spec, CropSpec};
+use imagecropper::fs_utils::{
+    collect_images_with_filter, find_helper_dirs, find_start, format_size, purge_helper_dir, FilterSyntax,
+    read_file_list, OutputLocation, PathFilter, SortOrder, TrashMode,
+};
+use imagecropper::image_utils::{
+    parse_fill_color, parse_tile_grid, CombineLayout, CombineStyle, OutputFormat, PngCompression, RedactStyle,
+    TiffCompression,
+};
+use imagecropper::journal::{Journal, DEFAULT_LOG_FILE};
+use imagecropper::metadata::RatingTarget;
+use imagecropper::pipeline::{CropJob, OriginalsMode, SaverOptions};
+use imagecropper::probe::{parse_file_size, parse_pixel_count, SizeFilter};
+use imagecropper::processed::retain_unprocessed;
+use imagecropper::recent::RecentDirs;
+use imagecropper::thumbnail_cache::ThumbnailCache;
+use imagecropper::pdf::{self, is_pdf};
+use imagecropper::video::is_video;
+
+#[derive(clap::Subcommand, Debug, Clone)]
+enum Command {
+    /// Report the size of trash, originals and temp folders and optionally purge old files
+    Clean {
+        /// Directories to search recursively
+        #[arg(value_name = "DIRS", required = true)]
+        dirs: Vec<PathBuf>,
+
+        /// Delete files in these folders last modified more than N days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+
+        /// Only report what would be deleted
+        #[arg(short = 'd', long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Crop a single image without opening the UI, with the same backups and metadata handling
+    Crop {
+        /// Image to crop
+        #[arg(value_name = "INPUT")]
+        input: PathBuf,
+
+        /// X,Y,W,H rectangle in pixels or percent (e.g. 10%,10%,80%,80%); several are combined into one image
+        #[arg(long, value_name = "X,Y,W,H", value_parser = parse_crop_spec, required = true)]
+        rect: Vec<CropSpec>,
+
+        /// Output file; the input is kept unchanged. Without it, the input is replaced
+        /// (and backed up) by a file in --format
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Output format [default: from the extension of --output, otherwise avif]
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+
+        /// Quality of the output image (1-100) [default: 70, or `quality` in the config's [encode.<format>]]
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: Option<u8>,
+
+        /// Replace the input without keeping it in .imagecropper-originals
+        #[arg(long, default_value_t = false)]
+        no_backup: bool,
+
+        /// Also write a black-and-white mask of the rectangles as <output name>_mask.png
+        #[arg(long, default_value_t = false)]
+        mask: bool,
+
+        /// Append a JSON line for the crop and the save to this file
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
+
+        /// Configuration file (default: <config dir>/imagecropper/config.toml)
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+    },
+    /// Recursively re-encode all images in directories without opening the UI, like --resave
+    Convert {
+        /// Directories or files to convert
+        #[arg(value_name = "PATHS", required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Output format; images already in it are skipped
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Avif)]
+        format: OutputFormat,
+
+        /// Quality of the output image (1-100) [default: 70, or `quality` in the config's [encode.<format>]]
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
+        quality: Option<u8>,
+
+        /// Number of parallel encoding threads
+        #[arg(short = 'j', long = "parallel", default_value_t = 16)]
+        parallel: usize,
+
+        /// Write the results into this directory instead of next to the sources
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Replace the sources without keeping them in .imagecropper-originals
+        #[arg(long, default_value_t = false)]
+        no_backup: bool,
+
+        /// Only list what would be converted
+        #[arg(short = 'd', long, default_value_t = false)]
+        dry_run: bool,
+
+        /// List the converted images in .imagecropper-processed for a later --skip-processed
+        #[arg(long, default_value_t = false)]
+        track_processed: bool,
+
+        /// Append a JSON line for every conversion to this file
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
+
+        /// Configuration file (default: <config dir>/imagecropper/config.toml)
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+    },
+    /// Print statistics from the catalog for the images in a directory
+    Report {
+        /// Directory whose images (including subdirectories) are counted
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+
+        /// Catalog to read (default: <data dir>/imagecropper/catalog.sqlite)
+        #[arg(long, value_name = "FILE")]
+        catalog: Option<PathBuf>,
+    },
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(
     author,
     version,
-    about = "Fullscreen image cropper with deletion workflow"
+    about = "Fullscreen image cropper with deletion workflow",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
-    /// Directories or files to process
-    #[arg(value_name = "PATHS", required = true)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directories or files to process; without any, a folder is picked in the app
+    #[arg(value_name = "PATHS")]
     paths: Vec<PathBuf>,
 
-    /// Quality of the output image (1-100)
-    #[arg(short, long, default_value_t = 70)]
-    quality: u8,
+    /// Also process the newline-separated image paths in this file ("-" for stdin)
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Quality of the output image (1-100) [default: 70, or `quality` in the config's [encode.<format>]]
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
 
     /// Output format for saved images
     #[arg(short, long, value_enum, default_value_t = OutputFormat::Avif)]
     format: OutputFormat,
 
+    /// JPEG XL encoder effort, from 1 (fastest) to 9 (smallest files) [default: 7]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=9))]
+    jxl_effort: Option<u8>,
+
+    /// PNG compression: trade encoding speed for file size [default: default]
+    #[arg(long, value_enum)]
+    png_compression: Option<PngCompression>,
+
+    /// TIFF compression [default: none]
+    #[arg(long, value_enum)]
+    tiff_compression: Option<TiffCompression>,
+
     /// Automatically resave images to the selected format when navigating away
     #[arg(long, default_value_t = false)]
     resave: bool,
@@ -44,6 +189,14 @@ struct Args {
     #[arg(long, default_value_t = false)]
     report_sizes: bool,
 
+    /// At exit, write a contact sheet (thumbnails with file names) of all images saved this session to FILE, e.g. sheet.jpg
+    #[arg(long, value_name = "FILE")]
+    contact_sheet: Option<PathBuf>,
+
+    /// At exit, collect all files written this session in a .zip or .tar archive (without the trash and originals folders)
+    #[arg(long, value_name = "FILE", value_parser = parse_archive_path)]
+    export_zip: Option<PathBuf>,
+
     /// Skip destructive operations and just print what would happen
     #[arg(short = 'd', long, default_value_t = false)]
     dry_run: bool,
@@ -52,6 +205,14 @@ struct Args {
     #[arg(short = 'j', long = "parallel", default_value_t = 16)]
     parallel: usize,
 
+    /// Hold off new crops while N saves are waiting to be encoded, to bound their memory (0: unlimited)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_PENDING_SAVES)]
+    max_pending_saves: usize,
+
+    /// Flush every saved file to disk before it replaces the original, so a power loss can't leave it half-written
+    #[arg(long, global = true, default_value_t = false)]
+    fsync: bool,
+
     /// Recurse into subdirectories to find images (disabled by default)
     #[arg(short = 'r', long = "recursive", default_value_t = false)]
     recursive: bool,
@@ -60,14 +221,30 @@ struct Args {
     #[arg(long, value_enum, default_value_t = FilterSyntax::Glob)]
     filter_syntax: FilterSyntax,
 
+    /// Only process paths matching one of these filters, e.g. "*.jpg"
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
     /// Include paths matching this filter even if they also match a blacklist
     #[arg(long, value_name = "PATTERN")]
     whitelist: Vec<String>,
 
     /// Exclude paths matching this filter unless they also match a whitelist
-    #[arg(long, value_name = "PATTERN")]
+    #[arg(long, visible_alias = "exclude", value_name = "PATTERN")]
     blacklist: Vec<String>,
 
+    /// Skip images with fewer pixels, e.g. 250000, 2MP or 800x600
+    #[arg(long, value_name = "PIXELS", value_parser = parse_pixel_count)]
+    min_pixels: Option<u64>,
+
+    /// Skip files smaller than this, e.g. 100K
+    #[arg(long, value_name = "SIZE", value_parser = parse_file_size)]
+    min_size: Option<u64>,
+
+    /// Skip files larger than this, e.g. 20M
+    #[arg(long, value_name = "SIZE", value_parser = parse_file_size)]
+    max_size: Option<u64>,
+
     /// Invert order of processed images (ignored for randomize)
     #[arg(short = 'i', long = "inverse-order", default_value_t = false)]
     inverse: bool,
@@ -76,54 +253,494 @@ struct Args {
     #[arg(short, long, value_enum, default_value_t = SortOrder::Filename)]
     order: SortOrder,
 
+    /// Start at this image (path, file name or name without extension), skipping the ones sorted before it
+    #[arg(long, value_name = "FILE")]
+    start_at: Option<PathBuf>,
+
+    /// Process at most N images
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Snap selection edges to a grid of N pixels (e.g. 8 for JPEG blocks)
+    #[arg(long, value_name = "N")]
+    snap: Option<u32>,
+
+    /// Snap selection edges to strong nearby image edges, e.g. the borders of scanned documents
+    #[arg(long)]
+    snap_edges: bool,
+
+    /// Per-channel color tolerance (0-255) for the automatic border trim (A key)
+    #[arg(long, default_value_t = 16)]
+    trim_tolerance: u8,
+
+    /// Aspect ratio for smart crop suggestions (S key), e.g. 4:5 or 1.5
+    #[arg(long, value_name = "RATIO", value_parser = parse_aspect_ratio)]
+    suggest_aspect: Option<f32>,
+
+    /// Crop every image to this X,Y,W,H rectangle (pixels or percent) and save it without opening the UI
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_crop_spec)]
+    apply_to_all: Option<CropSpec>,
+
+    /// Crop every image to its largest centered region with this aspect ratio (e.g. 1:1) without opening the UI
+    #[arg(long, value_name = "RATIO", value_parser = parse_aspect_ratio, conflicts_with = "apply_to_all")]
+    center_crop: Option<f32>,
+
+    /// Limit batch crops (Shift+Enter, --apply-to-all, --center-crop) to this many images
+    #[arg(long, value_name = "N")]
+    apply_count: Option<usize>,
+
+    /// Where Delete moves images: a .imagecropper-trash folder or the system trash
+    #[arg(long, value_enum, default_value_t = TrashMode::Folder)]
+    trash_mode: TrashMode,
+
+    /// Write results into this directory (mirroring the input structure) and leave originals untouched
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Replace source files directly instead of moving them to the originals folder
+    #[arg(long, default_value_t = false)]
+    no_backup: bool,
+
+    /// Create the trash, originals and temp folders below this directory instead of next to the images
+    #[arg(long, value_name = "DIR")]
+    workdir: Option<PathBuf>,
+
+    /// Trash folder (relative paths are resolved against --workdir or the image's directory)
+    #[arg(long, value_name = "DIR")]
+    trash_dir: Option<PathBuf>,
+
+    /// Folder for backed up originals (relative paths as for --trash-dir)
+    #[arg(long, value_name = "DIR")]
+    originals_dir: Option<PathBuf>,
+
+    /// Folder for files being written (relative paths as for --trash-dir)
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Append a JSON line for every crop, resave, skip and delete to this file
+    #[arg(long, value_name = "FILE", default_value = DEFAULT_LOG_FILE)]
+    log: PathBuf,
+
+    /// Don't write the operation log
+    #[arg(long, default_value_t = false)]
+    no_log: bool,
+
+    /// Also record all operations in a SQLite catalog (default: <data dir>/imagecropper/catalog.sqlite)
+    #[arg(long, value_name = "FILE", num_args = 0..=1)]
+    catalog: Option<Option<PathBuf>>,
+
+    /// Configuration file (default: <config dir>/imagecropper/config.toml)
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Number of upcoming images to decode in the background
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_PRELOAD_AHEAD)]
+    preload: usize,
+
+    /// Number of previous images to keep decoded for going back
+    #[arg(long, value_name = "M", default_value_t = DEFAULT_PRELOAD_BEHIND)]
+    preload_behind: usize,
+
+    /// Scale decoded images down to fit into this size (WIDTHxHEIGHT, one size for both, or "none")
+    #[arg(long, value_name = "SIZE", default_value = "3840x2160", value_parser = parse_max_preview)]
+    // Spelled out so clap passes "none" through as a value instead of treating the option as optional
+    max_preview_dimension: std::option::Option<(u32, u32)>,
+
+    /// Memory for preloaded images, e.g. 2G; least recently used images are dropped beyond it
+    #[arg(long, value_name = "SIZE", value_parser = parse_file_size)]
+    cache_mem: Option<u64>,
+
+    /// Don't read or write previews in the on-disk thumbnail cache
+    #[arg(long, default_value_t = false)]
+    no_thumbnail_cache: bool,
+
     /// Show performance diagnostics
     #[arg(long, default_value_t = false)]
     benchmark: bool,
+
+    /// Show how long reading, decoding, resizing and uploading each image took (F3 toggles)
+    #[arg(long, default_value_t = false)]
+    perf_hud: bool,
+
+    /// Stay on the image after a crop instead of moving on, e.g. to crop more variants of it (C toggles)
+    #[arg(long, default_value_t = false)]
+    no_auto_advance: bool,
+
+    /// Ask before Delete moves an image to the trash
+    #[arg(long, default_value_t = false)]
+    confirm_delete: bool,
+
+    /// Delete only marks images; they are moved to the trash together after a review when you quit (X reviews)
+    #[arg(long, default_value_t = false)]
+    mark_delete: bool,
+
+    /// Only show images that weren't cropped, resaved or marked done (D) in an earlier run; implies --track-processed
+    #[arg(long, default_value_t = false)]
+    skip_processed: bool,
+
+    /// List cropped, resaved and finished (D) images in .imagecropper-processed for a later --skip-processed
+    #[arg(long, default_value_t = false)]
+    track_processed: bool,
+
+    /// Start at the image the last session in this directory ended at
+    #[arg(long, default_value_t = false, conflicts_with = "start_at")]
+    resume: bool,
+
+    /// Culling mode: the number keys move the image into the folders of the
+    /// config's [culling] section (default keep/, maybe/, reject/) instead of applying presets
+    #[arg(long, default_value_t = false)]
+    cull: bool,
+
+    /// Rating mode: 0-5 write an XMP star rating, to a sidecar (default) or embedded in JPEGs
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        default_missing_value = "sidecar",
+        conflicts_with = "cull"
+    )]
+    rate: Option<RatingTarget>,
+
+    /// Arrangement of multiple selections saved as one image (M cycles at runtime) [default: pack, or `layout` in the config's [combine]]
+    #[arg(long, value_enum)]
+    layout: Option<CombineLayout>,
+
+    /// Save each crop (or the whole image) as a grid of COLUMNSxROWS separate tiles, e.g. 4x3
+    #[arg(long, value_name = "COLUMNSxROWS", value_parser = parse_tile_grid)]
+    tiles: Option<(u32, u32)>,
+
+    /// Also save a black-and-white mask of the selections (white inside) as <output name>_mask.png
+    #[arg(long, default_value_t = false)]
+    mask: bool,
+
+    /// Annotation mode: Enter writes the selections as YOLO (<image name>.txt) or COCO
+    /// (annotations.json) bounding boxes instead of cropping; 0-9 set the class of the selection
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["cull", "rate", "tiles", "mask"])]
+    annotate: Option<AnnotationFormat>,
+
+    /// Redaction mode: Enter blurs, pixelates or fills the selections and saves the full image instead of cropping
+    #[arg(long, value_enum, value_name = "STYLE", conflicts_with_all = ["tiles", "annotate"])]
+    redact: Option<RedactStyle>,
+
+    /// Color of `--redact fill`: black, white or #rrggbb [default: black, or `color` in the config's [redact]]
+    #[arg(long, value_name = "COLOR", value_parser = parse_fill_color)]
+    redact_color: Option<image::Rgba<u8>>,
+
+    /// Inverse crop: Enter removes the selected bands (rows for wide selections, columns for tall ones)
+    /// and stitches the rest of the image back together
+    #[arg(long, default_value_t = false, conflicts_with_all = ["tiles", "mask", "annotate", "redact"])]
+    remove_bands: bool,
+
+    /// Show a toolbar with buttons for crop, navigation, delete, rotate and quit
+    #[arg(long, default_value_t = false)]
+    toolbar: bool,
+
+    /// Start in a window instead of fullscreen (F11 toggles at runtime)
+    #[arg(long, default_value_t = false)]
+    windowed: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let file_filter = PathFilter::compile(
-        args.filter_syntax,
-        &args.whitelist,
-        &args.blacklist,
-    )?;
-    let mut files = collect_images_with_filter(&args.paths, args.recursive, file_filter.as_ref())?;
-    if files.is_empty() {
+fn clean(dirs: &[PathBuf], older_than: Option<u64>, dry_run: bool) -> Result<()> {
+    let mut total_bytes = 0;
+    let mut purged = (0, 0);
+    for dir in dirs {
+        for usage in find_helper_dirs(dir)? {
+            println!(
+                "{}: {} files, {}",
+                usage.path.display(),
+                usage.files,
+                format_size(usage.bytes)
+            );
+            total_bytes += usage.bytes;
+            if let Some(days) = older_than {
+                let min_age = std::time::Duration::from_secs(days * 24 * 60 * 60);
+                let (files, bytes) = purge_helper_dir(&usage.path, min_age, dry_run)?;
+                purged = (purged.0 + files, purged.1 + bytes);
+            }
+        }
+    }
+    println!("Total: {}", format_size(total_bytes));
+    if let Some(days) = older_than {
+        let verb = if dry_run { "Would delete" } else { "Deleted" };
+        println!(
+            "{verb} {} files ({}) older than {days} days",
+            purged.0,
+            format_size(purged.1)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(feature = "catalog")]
+fn open_catalog(path: Option<&Path>) -> Result<imagecropper::catalog::Catalog> {
+    use imagecropper::catalog::Catalog;
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => Catalog::default_path().ok_or_else(|| anyhow!("No data directory for the catalog, use --catalog FILE"))?,
+    };
+    Catalog::open(&path)
+}
+
+#[cfg(feature = "catalog")]
+fn with_catalog(journal: Journal, path: Option<&Path>) -> Result<Journal> {
+    Ok(journal.with_catalog(open_catalog(path)?))
+}
+
+#[cfg(not(feature = "catalog"))]
+fn with_catalog(_journal: Journal, _path: Option<&Path>) -> Result<Journal> {
+    Err(anyhow!(CATALOG_FEATURE_ERROR))
+}
+
+#[cfg(not(feature = "catalog"))]
+const CATALOG_FEATURE_ERROR: &str =
+    "The catalog needs a build with the `catalog` feature (cargo install imagecropper --features catalog)";
+
+#[cfg(feature = "catalog")]
+fn report(dir: &Path, catalog: Option<&Path>) -> Result<()> {
+    let report = open_catalog(catalog)?.report(dir)?;
+    if report.files == 0 {
+        println!("No images below {} in the catalog", dir.display());
+        return Ok(());
+    }
+    println!("{} images", report.files);
+    for (status, count) in &report.by_status {
+        println!("  {status}: {count}");
+    }
+    if report.saved_files > 0 {
+        let saved = report.original_bytes as i64 - report.new_bytes as i64;
+        println!(
+            "Saved {} images: {} before, {} after ({}{} saved)",
+            report.saved_files,
+            format_size(report.original_bytes),
+            format_size(report.new_bytes),
+            if saved < 0 { "-" } else { "" },
+            format_size(saved.unsigned_abs())
+        );
+    }
+    if let (Some(first), Some(last)) = (&report.first, &report.last) {
+        println!("First operation: {first}");
+        println!("Last operation: {last}");
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "catalog"))]
+fn report(_dir: &Path, _catalog: Option<&Path>) -> Result<()> {
+    Err(anyhow!(CATALOG_FEATURE_ERROR))
+}
+
+fn check_format_available(format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Jxl && !cfg!(feature = "jxl") {
         return Err(anyhow!(
-            "No supported image files found in the provided paths. Supported formats are: {}",
-            imagecropper::fs_utils::SUPPORTED_EXTENSIONS.join(", ")
+            "JPEG XL output needs a build with the `jxl` feature (cargo install imagecropper --features jxl)"
         ));
     }
-    match args.order {
-        SortOrder::Filename => files.sort(),
-        SortOrder::Randomize => files.shuffle(&mut rand::thread_rng()),
-        SortOrder::Modified => files.sort_by_key(|path| {
-            std::fs::metadata(path)
-                .and_then(|m| m.modified())
-                .ok()
-        }),
-        SortOrder::Size => files.sort_by_key(|path| {
-            std::fs::metadata(path)
-                .map(|m| m.len())
-                .unwrap_or(0)
-        }),
-    }
-
-    // If the inverse flag is set and ordering isn't randomized, invert the order
-    if args.inverse && args.order != SortOrder::Randomize {
-        files.reverse();
-    }
-    let dry_run = args.dry_run;
-    let quality = args.quality;
-    let resave = args.resave;
-    let format = args.format;
-    let parallel = args.parallel;
-    let benchmark = args.benchmark;
-    let files_for_app = files.clone();
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn crop(
+    input: &Path,
+    rects: &[CropSpec],
+    output: Option<&Path>,
+    format: Option<OutputFormat>,
+    quality: Option<u8>,
+    no_backup: bool,
+    mask: bool,
+    fsync: bool,
+    log: Option<&Path>,
+    config: Option<&Path>,
+) -> Result<()> {
+    let format = format
+        .or_else(|| {
+            output
+                .and_then(|output| output.extension())
+                .and_then(|extension| OutputFormat::from_extension(&extension.to_string_lossy()))
+        })
+        .unwrap_or(OutputFormat::Avif);
+    check_format_available(format)?;
+    let config = Config::load(config)?;
+    let mut job = CropJob::new(input, rects.to_vec(), format);
+    job.settings = config.encode.settings(
+        format,
+        &FormatConfig {
+            quality,
+            ..Default::default()
+        },
+    );
+    job.combine = config.combine;
+    job.mask = mask;
+    job.destination = output.map(Path::to_path_buf);
+    job.options.work_dirs = config.dirs;
+    job.options.originals = if output.is_some_and(|output| output != input) {
+        OriginalsMode::Keep
+    } else if no_backup {
+        OriginalsMode::Replace
+    } else {
+        OriginalsMode::Backup
+    };
+    job.options.fsync = fsync;
+    if let Some(log) = log {
+        job.options.journal = Journal::open(log)?;
+    }
+
+    let result = job.run()?;
+    match (result.original_bytes, result.new_bytes) {
+        (Some(original), Some(new)) => println!(
+            "Saved {} ({} -> {})",
+            result.output.display(),
+            format_size(original),
+            format_size(new)
+        ),
+        _ => println!("Saved {}", result.output.display()),
+    }
+    if let Some(backup) = &result.backup {
+        println!("Original backed up to {}", backup.display());
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn convert(
+    paths: &[PathBuf],
+    format: OutputFormat,
+    quality: Option<u8>,
+    parallel: usize,
+    output_dir: Option<&Path>,
+    no_backup: bool,
+    dry_run: bool,
+    track_processed: bool,
+    fsync: bool,
+    log: Option<&Path>,
+    config: Option<&Path>,
+) -> Result<()> {
+    check_format_available(format)?;
+    let config = Config::load(config)?;
+    let mut files = collect_images_with_filter(paths, true, None)?;
+    // Frames of videos and pages of PDFs are only cropped in the app
+    files.retain(|path| !is_video(path) && !is_pdf(path));
+    let output = OutputLocation {
+        dir: output_dir.map(Path::to_path_buf),
+        roots: paths.iter().filter(|path| path.is_dir()).cloned().collect(),
+    };
+    let originals = if output.is_separate() {
+        OriginalsMode::Keep
+    } else if no_backup {
+        OriginalsMode::Replace
+    } else {
+        OriginalsMode::Backup
+    };
+    let journal = match log {
+        Some(log) if !dry_run => Journal::open(log)?,
+        _ => Journal::default(),
+    };
+    let failed = convert_all(
+        &files,
+        BatchOptions {
+            encode: config.encode.settings(
+                format,
+                &FormatConfig {
+                    quality,
+                    ..Default::default()
+                },
+            ),
+            parallel,
+            dry_run,
+            saver: SaverOptions {
+                work_dirs: config.dirs,
+                originals,
+                journal,
+                track_processed,
+                fsync,
+            },
+            output,
+        },
+    );
+    if failed > 0 {
+        return Err(anyhow!("{failed} images could not be converted"));
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(Command::Clean {
+        dirs,
+        older_than,
+        dry_run,
+    }) = &args.command
+    {
+        return clean(dirs, *older_than, *dry_run);
+    }
+    if let Some(Command::Report { dir, catalog }) = &args.command {
+        return report(dir, catalog.as_deref());
+    }
+    if let Some(Command::Crop {
+        input,
+        rect,
+        output,
+        format,
+        quality,
+        no_backup,
+        mask,
+        log,
+        config,
+    }) = &args.command
+    {
+        return crop(
+            input,
+            rect,
+            output.as_deref(),
+            *format,
+            *quality,
+            *no_backup,
+            *mask,
+            args.fsync,
+            log.as_deref(),
+            config.as_deref(),
+        );
+    }
+    if let Some(Command::Convert {
+        paths,
+        format,
+        quality,
+        parallel,
+        output_dir,
+        no_backup,
+        dry_run,
+        track_processed,
+        log,
+        config,
+    }) = &args.command
+    {
+        return convert(
+            paths,
+            *format,
+            *quality,
+            *parallel,
+            output_dir.as_deref(),
+            *no_backup,
+            *dry_run,
+            *track_processed,
+            args.fsync,
+            log.as_deref(),
+            config.as_deref(),
+        );
+    }
+    if args.paths.is_empty() && args.files_from.is_none() {
+        return launch(args);
+    }
+    let Some((files, options)) = prepare_session(&args)? else {
+        return Ok(());
+    };
 
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_fullscreen(true),
+        viewport: if args.windowed {
+            egui::ViewportBuilder::default().with_inner_size([1280.0, 800.0])
+        } else {
+            egui::ViewportBuilder::default().with_fullscreen(true)
+        },
         ..Default::default()
     };
 
@@ -131,7 +748,7 @@ fn main() -> Result<()> {
         "ImageCropper",
         native_options,
         Box::new(
-            move |cc| match ImageCropperApp::new(cc, files_for_app.clone(), dry_run, quality, resave, args.report_sizes, format, parallel, benchmark) {
+            move |cc| match ImageCropperApp::new(cc, files.clone(), options.clone()) {
                 Ok(app) => Ok(Box::new(app) as Box<dyn eframe::App>),
                 Err(err) => {
                     eprintln!("{err:#}");
@@ -144,3 +761,250 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Started without paths, e.g. from a desktop launcher: pick a folder and the
+/// main options in a window, then crop in it as usual.
+fn launch(args: Args) -> Result<()> {
+    let config = Config::load(args.config.as_deref())?;
+    let settings = LaunchSettings {
+        folder: None,
+        format: args.format,
+        quality: args
+            .quality
+            .unwrap_or_else(|| config.encode.settings(args.format, &FormatConfig::default()).quality),
+        order: args.order,
+        recursive: args.recursive,
+        resume: false,
+    };
+    let fullscreen = !args.windowed;
+    let start = Box::new(move |settings: &LaunchSettings| {
+        let mut args = args.clone();
+        args.paths = settings.folder.iter().cloned().collect();
+        args.format = settings.format;
+        args.quality = Some(settings.quality);
+        args.order = settings.order;
+        args.recursive = settings.recursive;
+        args.resume = settings.resume;
+        prepare_session(&args)
+    });
+    let launcher = Launcher::new(settings, RecentDirs::load_default(), fullscreen, start);
+    let native_options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([640.0, 480.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "ImageCropper",
+        native_options,
+        Box::new(move |_cc| Ok(Box::new(launcher) as Box<dyn eframe::App>)),
+    )?;
+    Ok(())
+}
+
+/// Images and app options for the paths and options in `args`, or `None` if
+/// `--apply-to-all` or `--center-crop` already cropped them without the UI.
+fn prepare_session(args: &Args) -> Result<Option<(Vec<PathBuf>, AppOptions)>> {
+    check_format_available(args.format)?;
+    let config = Config::load(args.config.as_deref())?;
+    let encode = config.encode.settings(
+        args.format,
+        &FormatConfig {
+            quality: args.quality,
+            compression: match args.format {
+                OutputFormat::Tiff => args.tiff_compression.map(Compression::Tiff),
+                _ => args.png_compression.map(Compression::Png),
+            },
+            effort: args.jxl_effort,
+            ..Default::default()
+        },
+    );
+    let mut work_dirs = config.dirs.clone();
+    for (value, arg) in [
+        (&mut work_dirs.workdir, &args.workdir),
+        (&mut work_dirs.trash, &args.trash_dir),
+        (&mut work_dirs.originals, &args.originals_dir),
+        (&mut work_dirs.temp, &args.temp_dir),
+    ] {
+        if arg.is_some() {
+            value.clone_from(arg);
+        }
+    }
+    let file_filter = PathFilter::compile_with_include(
+        args.filter_syntax,
+        &args.include,
+        &args.whitelist,
+        &args.blacklist,
+    )?;
+    let mut paths = args.paths.clone();
+    if let Some(list) = &args.files_from {
+        paths.extend(if list.as_os_str() == "-" {
+            read_file_list(std::io::stdin().lock())?
+        } else {
+            let file = std::fs::File::open(list)
+                .with_context(|| format!("Failed to open file list {}", list.display()))?;
+            read_file_list(std::io::BufReader::new(file))?
+        });
+    }
+    let files = collect_images_with_filter(&paths, args.recursive, file_filter.as_ref())?;
+    let mut files = pdf::expand_pages(files);
+    let size_filter = SizeFilter {
+        min_pixels: args.min_pixels,
+        min_bytes: args.min_size,
+        max_bytes: args.max_size,
+    };
+    if !size_filter.is_empty() {
+        files.retain(|path| size_filter.matches(path));
+    }
+    if args.skip_processed {
+        retain_unprocessed(&mut files);
+    }
+    if files.is_empty() {
+        return Err(anyhow!(
+            "No supported image files found in the provided paths. Supported formats are: {}",
+            imagecropper::fs_utils::SUPPORTED_EXTENSIONS.join(", ")
+        ));
+    }
+    args.order.sort(&mut files, args.inverse);
+
+    if let Some(name) = &args.start_at {
+        let start = find_start(&files, name)?;
+        files.drain(..start);
+    } else if args.resume {
+        // The image may have been moved away since, then all images are shown
+        if let Some(start) = RecentDirs::load_default().resume_index(&paths, &files) {
+            files.drain(..start);
+        }
+    }
+    if let Some(limit) = args.limit {
+        if limit == 0 {
+            return Err(anyhow!("--limit must be at least 1"));
+        }
+        files.truncate(limit);
+    }
+
+    let output = OutputLocation {
+        dir: args.output_dir.clone(),
+        roots: args.paths.iter().filter(|path| path.is_dir()).cloned().collect(),
+    };
+
+    let originals_mode = if output.is_separate() {
+        OriginalsMode::Keep
+    } else if args.no_backup {
+        OriginalsMode::Replace
+    } else {
+        OriginalsMode::Backup
+    };
+
+    // Nothing happens to the files in a dry run, so there is nothing to log
+    let mut journal = if args.dry_run || args.no_log {
+        Journal::default()
+    } else {
+        Journal::open(&args.log)?
+    };
+    if let Some(catalog) = &args.catalog {
+        if !args.dry_run {
+            journal = with_catalog(journal, catalog.as_deref())?;
+        }
+    }
+
+    let batch_crop = match (args.apply_to_all, args.center_crop) {
+        (Some(spec), _) => Some(BatchCrop::Rect(spec)),
+        (None, Some(aspect)) => Some(BatchCrop::Center(aspect)),
+        (None, None) => None,
+    };
+    if let Some(crop) = batch_crop {
+        files.retain(|path| !is_video(path) && pdf::page_of(path).is_none());
+        if let Some(count) = args.apply_count {
+            files.truncate(count);
+        }
+        let failed = apply_to_all(
+            &files,
+            crop,
+            BatchOptions {
+                encode,
+                parallel: args.parallel,
+                dry_run: args.dry_run,
+                saver: SaverOptions {
+                    work_dirs,
+                    originals: originals_mode,
+                    journal,
+                    track_processed: args.track_processed || args.skip_processed,
+                    fsync: args.fsync,
+                },
+                output,
+            },
+        );
+        if failed > 0 {
+            return Err(anyhow!("{failed} of {} images could not be cropped", files.len()));
+        }
+        return Ok(None);
+    }
+
+    let options = AppOptions {
+        dry_run: args.dry_run,
+        resave: args.resave,
+        report_sizes: args.report_sizes,
+        contact_sheet: args.contact_sheet.clone(),
+        export_archive: args.export_zip.clone(),
+        encode,
+        parallel: args.parallel,
+        max_pending_saves: Some(args.max_pending_saves).filter(|max| *max > 0),
+        fsync: args.fsync,
+        track_processed: args.track_processed || args.skip_processed,
+        benchmark: args.benchmark,
+        snap: args.snap,
+        snap_edges: args.snap_edges,
+        trim_tolerance: args.trim_tolerance,
+        suggest_aspect: args.suggest_aspect,
+        apply_count: args.apply_count,
+        presets: config.presets,
+        arrow_speed: config.arrow_keys,
+        toolbar: args.toolbar,
+        rating_target: args.rate,
+        tiles: args.tiles,
+        export_mask: args.mask,
+        annotate: args.annotate,
+        redact: args.redact,
+        remove_bands: args.remove_bands,
+        redact_color: args.redact_color.unwrap_or(parse_fill_color(&config.redact.color)?),
+        class_names: config.annotations.classes,
+        combine: CombineStyle {
+            layout: args.layout.unwrap_or(config.combine.layout),
+            ..config.combine
+        },
+        cull_folders: if args.cull { config.culling.folders } else { Vec::new() },
+        move_keys: config.move_keys.bindings()?,
+        overlays: config.overlays,
+        selection_style: config.selection,
+        trash_mode: args.trash_mode,
+        work_dirs,
+        originals: originals_mode,
+        output,
+        perf_hud: args.perf_hud,
+        auto_advance: !args.no_auto_advance,
+        confirm_delete: args.confirm_delete,
+        mark_delete: args.mark_delete,
+        preload_ahead: args.preload,
+        preload_behind: args.preload_behind,
+        cache_budget: args.cache_mem,
+        max_preview: args.max_preview_dimension,
+        thumbnail_cache: if args.no_thumbnail_cache {
+            None
+        } else {
+            ThumbnailCache::default_dir().map(ThumbnailCache::new)
+        },
+        journal,
+        recent_dirs: RecentDirs::default_path().map(|file| RecentDirs::load(&file)),
+    };
+    Ok(Some((files, options)))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::Args;
+
+    #[test]
+    fn cli() {
+        Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,149 @@
+//! XMP star ratings.
+//!
+//! Ratings are the `xmp:Rating` property (0-5) that Lightroom, digiKam and
+//! darktable read. They go into an XMP sidecar next to the image or, for JPEG,
+//! into the XMP packet embedded in an APP1 segment.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use img_parts::jpeg::{markers, Jpeg, JpegSegment};
+
+use crate::fs_utils::{find_sidecars, write_atomically, SIDECAR_EXTENSION};
+
+/// Namespace of the `xmp:` properties
+pub const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+
+/// Highest star rating
+pub const MAX_RATING: u8 = 5;
+
+/// Identifier at the start of a JPEG APP1 segment holding XMP
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Where ratings are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum RatingTarget {
+    /// An XMP sidecar: the existing one, or a new `photo.jpg.xmp`
+    #[default]
+    Sidecar,
+    /// The XMP packet inside JPEG files; other formats get a sidecar
+    Embedded,
+}
+
+/// A minimal XMP packet with just `rating`.
+pub fn rating_packet(rating: u8) -> String {
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+         <rdf:Description rdf:about=\"\" xmlns:xmp=\"{XMP_NAMESPACE}\" xmp:Rating=\"{rating}\"/>\n \
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>"
+    )
+}
+
+/// `xmp` with its rating set to `rating`, keeping all other properties.
+///
+/// Handles the attribute (`xmp:Rating="3"`) and element
+/// (`<xmp:Rating>3</xmp:Rating>`) forms. Without an `rdf:Description` the
+/// packet is replaced by [`rating_packet`].
+pub fn set_rating(xmp: &str, rating: u8) -> String {
+    if let Some(start) = xmp.find("xmp:Rating=") {
+        let value_start = start + "xmp:Rating=".len();
+        let quote = xmp[value_start..].chars().next().filter(|c| *c == '"' || *c == '\'');
+        if let Some(quote) = quote {
+            if let Some(len) = xmp[value_start + 1..].find(quote) {
+                let value_end = value_start + 1 + len + 1;
+                return format!("{}{quote}{rating}{quote}{}", &xmp[..value_start], &xmp[value_end..]);
+            }
+        }
+    }
+    if let (Some(start), Some(end)) = (xmp.find("<xmp:Rating>"), xmp.find("</xmp:Rating>")) {
+        if start < end {
+            return format!("{}<xmp:Rating>{rating}{}", &xmp[..start], &xmp[end..]);
+        }
+    }
+    let Some(description) = xmp.find("<rdf:Description") else {
+        return rating_packet(rating);
+    };
+    let insert_at = description + "<rdf:Description".len();
+    let namespace = if xmp.contains("xmlns:xmp=") {
+        String::new()
+    } else {
+        format!(" xmlns:xmp=\"{XMP_NAMESPACE}\"")
+    };
+    format!(
+        "{}{namespace} xmp:Rating=\"{rating}\"{}",
+        &xmp[..insert_at],
+        &xmp[insert_at..]
+    )
+}
+
+/// The sidecar a rating of `image` goes to: an existing one, or `photo.jpg.xmp`.
+pub fn rating_sidecar(image: &Path) -> PathBuf {
+    find_sidecars(image).into_iter().next().unwrap_or_else(|| {
+        let mut name = image.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(SIDECAR_EXTENSION);
+        image.with_file_name(name)
+    })
+}
+
+/// JPEG `data` with `rating` set in its embedded XMP packet, which is added if
+/// missing. `None` if `data` isn't a JPEG.
+pub fn set_jpeg_rating(data: &[u8], rating: u8) -> Option<Vec<u8>> {
+    let mut jpeg = Jpeg::from_bytes(data.to_vec().into()).ok()?;
+    let segments = jpeg.segments_mut();
+    let existing = segments
+        .iter()
+        .position(|segment| segment.marker() == markers::APP1 && segment.contents().starts_with(JPEG_XMP_HEADER));
+    let packet = match existing {
+        Some(index) => {
+            let xmp = String::from_utf8_lossy(&segments[index].contents()[JPEG_XMP_HEADER.len()..]).into_owned();
+            set_rating(&xmp, rating)
+        }
+        None => rating_packet(rating),
+    };
+    let contents = [JPEG_XMP_HEADER, packet.as_bytes()].concat();
+    if contents.len() > u16::MAX as usize - 2 {
+        return None;
+    }
+    let segment = JpegSegment::new_with_contents(markers::APP1, contents.into());
+    match existing {
+        Some(index) => segments[index] = segment,
+        None => {
+            // After JFIF and EXIF, which readers expect first
+            let index = segments
+                .iter()
+                .position(|segment| !matches!(segment.marker(), markers::APP0 | markers::APP1))
+                .unwrap_or(segments.len());
+            segments.insert(index, segment);
+        }
+    }
+    let mut out = Vec::new();
+    jpeg.encoder().write_to(&mut out).ok()?;
+    Some(out)
+}
+
+/// Write `rating` (0-5) for `image` to `target`. Returns the file written.
+pub fn write_rating(image: &Path, rating: u8, target: RatingTarget) -> Result<PathBuf> {
+    if rating > MAX_RATING {
+        return Err(anyhow!("Ratings go from 0 to {MAX_RATING}"));
+    }
+    if target == RatingTarget::Embedded {
+        let data = std::fs::read(image).with_context(|| format!("Unable to read {}", image.display()))?;
+        if let Some(rated) = set_jpeg_rating(&data, rating) {
+            write_atomically(image, rated)?;
+            return Ok(image.to_path_buf());
+        }
+    }
+    let sidecar = rating_sidecar(image);
+    let xmp = match std::fs::read_to_string(&sidecar) {
+        Ok(existing) => set_rating(&existing, rating),
+        Err(_) => rating_packet(rating),
+    };
+    write_atomically(&sidecar, xmp)?;
+    Ok(sidecar)
+}
@@ -0,0 +1,101 @@
+//! Pages of PDF files as images, rendered with the `pdfinfo` and `pdftoppm`
+//! programs of poppler. PDFs are only taken into the queue by builds with the
+//! `pdf` feature, where each page gets a path of its own next to the PDF
+//! (`paper_page-003.png`) that is rendered when it is loaded and isn't written
+//! to disk; crops of it are saved next to the PDF like those of any image.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use image::DynamicImage;
+
+use crate::external::run;
+
+/// Resolution pages are rendered at, enough for figures to stay sharp
+pub const RENDER_DPI: u32 = 200;
+
+/// Marker between the name of the PDF and the page number in page paths
+const PAGE_MARKER: &str = "_page-";
+
+/// Whether `path` is a PDF whose pages can be rendered in this build.
+pub fn is_pdf(path: &Path) -> bool {
+    cfg!(feature = "pdf")
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Number of pages of the PDF at `path`.
+pub fn page_count(path: &Path) -> io::Result<u32> {
+    let output = run(Command::new("pdfinfo").arg(path), "poppler")?;
+    String::from_utf8_lossy(&output)
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:"))
+        .and_then(|pages| pages.trim().parse().ok())
+        .ok_or_else(|| io::Error::other(format!("{} has no page count", path.display())))
+}
+
+/// Path standing for page `page` (counted from 1) of `pdf`: `paper_page-003.png` next to it.
+pub fn page_path(pdf: &Path, page: u32) -> PathBuf {
+    let stem = pdf.file_stem().unwrap_or_default().to_string_lossy();
+    pdf.with_file_name(format!("{stem}{PAGE_MARKER}{page:03}.png"))
+}
+
+/// The PDF and page number a path from [`page_path`] stands for, unless a file
+/// of that name exists.
+pub fn page_of(path: &Path) -> Option<(PathBuf, u32)> {
+    if !cfg!(feature = "pdf") || path.exists() {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?.strip_suffix(".png")?;
+    let (stem, page) = name.rsplit_once(PAGE_MARKER)?;
+    let page = page.parse().ok().filter(|&page| page > 0)?;
+    let pdf = [format!("{stem}.pdf"), format!("{stem}.PDF")]
+        .into_iter()
+        .map(|name| path.with_file_name(name))
+        .find(|pdf| pdf.is_file())?;
+    Some((pdf, page))
+}
+
+/// Replace each PDF in `files` by the paths of its pages. PDFs whose pages
+/// can't be counted are left out with a warning.
+pub fn expand_pages(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut expanded = Vec::with_capacity(files.len());
+    for path in files {
+        if !is_pdf(&path) {
+            expanded.push(path);
+            continue;
+        }
+        match page_count(&path) {
+            Ok(pages) => expanded.extend((1..=pages).map(|page| page_path(&path, page))),
+            Err(err) => eprintln!("Skipping {}: {err}", path.display()),
+        }
+    }
+    expanded
+}
+
+/// Page `page` of `pdf` rendered at [`RENDER_DPI`], encoded as PNG.
+pub fn render_page_png(pdf: &Path, page: u32) -> io::Result<Vec<u8>> {
+    // Without an output name, pdftoppm writes the single page to stdout
+    let page = page.to_string();
+    let mut command = Command::new("pdftoppm");
+    command
+        .args(["-png", "-r"])
+        .arg(RENDER_DPI.to_string())
+        .args(["-f", page.as_str(), "-l", page.as_str()])
+        .arg(pdf);
+    let png = run(&mut command, "poppler")?;
+    if png.is_empty() {
+        return Err(io::Error::other(format!("{} has no page {page}", pdf.display())));
+    }
+    Ok(png)
+}
+
+/// Page `page` of `pdf` rendered at [`RENDER_DPI`].
+pub fn render_page(pdf: &Path, page: u32) -> image::ImageResult<DynamicImage> {
+    image::load_from_memory_with_format(&render_page_png(pdf, page)?, image::ImageFormat::Png)
+}
@@ -0,0 +1,370 @@
+//! Crop, encode and save images without the UI.
+//!
+//! Every save of the app and of the headless modes goes through [`save`]: the
+//! original is backed up (or replaced), the image is encoded into a temp file,
+//! EXIF and ICC data of the source are copied over and XMP sidecars follow the
+//! image. [`CropJob`] wraps loading, cropping and saving a single file for other
+//! programs.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use image::DynamicImage;
+use img_parts::jpeg::Jpeg;
+use img_parts::png::Png;
+use img_parts::webp::WebP;
+use img_parts::{Bytes, ImageEXIF, ImageICC};
+
+use crate::{
+    avif::add_avif_metadata,
+    crop::{crop_file_with, resolve_specs, CropSpec},
+    fs_utils::{move_file, move_file_durable, move_sidecars, unique_destination, OutputLocation, WorkDirs},
+    image_utils::{
+        add_jxl_exif, encode, encode_tiff, mask_path, selection_mask, CombineStyle, CopyMetadata, EncodeSettings,
+        ExtraOutput, OutputFormat, SaveRequest, SaveStatus,
+    },
+    journal::{Event, Journal},
+    processed::mark_processed,
+    selection::Selection,
+};
+
+/// What happens to the source file of a save.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OriginalsMode {
+    /// Move it into the originals folder
+    #[default]
+    Backup,
+    /// Overwrite it (or delete it if the output has another extension)
+    Replace,
+    /// Leave it alone, the output is written elsewhere
+    Keep,
+}
+
+/// How [`save`] treats the files it replaces.
+#[derive(Clone, Debug, Default)]
+pub struct SaverOptions {
+    /// Where originals and temporary files are kept
+    pub work_dirs: WorkDirs,
+    pub originals: OriginalsMode,
+    /// Records the outcome of every save
+    pub journal: Journal,
+    /// Add saved images to the list read by `--skip-processed`
+    pub track_processed: bool,
+    /// Flush every output to disk before it replaces a file (`--fsync`)
+    pub fsync: bool,
+}
+
+/// Crop one image and save the result.
+///
+/// ```no_run
+/// use imagecropper::image_utils::OutputFormat;
+/// use imagecropper::pipeline::CropJob;
+///
+/// let mut job = CropJob::new("photo.jpg", vec![[100, 200, 800, 600]], OutputFormat::Avif);
+/// job.settings.quality = 60;
+/// let result = job.run()?;
+/// println!("Saved {}", result.output.display());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct CropJob {
+    pub input: PathBuf,
+    /// Rectangles in pixels or percent of the input, clamped to the image.
+    /// Several rectangles are combined into one image, none keeps the whole image.
+    pub rects: Vec<CropSpec>,
+    /// Arrangement and spacing of several rectangles in the output
+    pub combine: CombineStyle,
+    pub settings: EncodeSettings,
+    pub output: OutputLocation,
+    /// Write to exactly this file instead of the path given by `output`
+    pub destination: Option<PathBuf>,
+    /// Also write a black-and-white mask of the rectangles on the input as
+    /// `<output name>_mask.png`
+    pub mask: bool,
+    /// Backups, work folders and the journal, as for the app
+    pub options: SaverOptions,
+}
+
+/// What a [`CropJob`] wrote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CropResult {
+    pub output: PathBuf,
+    /// Where the original was moved, with [`OriginalsMode::Backup`]
+    pub backup: Option<PathBuf>,
+    pub original_bytes: Option<u64>,
+    pub new_bytes: Option<u64>,
+}
+
+impl CropJob {
+    /// Save `input` cropped to `rects` as `format` next to it with the default
+    /// settings, backing up the original.
+    pub fn new(
+        input: impl Into<PathBuf>,
+        rects: impl IntoIterator<Item = impl Into<CropSpec>>,
+        format: OutputFormat,
+    ) -> Self {
+        Self {
+            input: input.into(),
+            rects: rects.into_iter().map(Into::into).collect(),
+            combine: CombineStyle::default(),
+            settings: EncodeSettings::new(format),
+            output: OutputLocation::default(),
+            destination: None,
+            mask: false,
+            options: SaverOptions::default(),
+        }
+    }
+
+    /// The rectangles in pixels of the input.
+    pub fn selections(&self) -> Result<Vec<Selection>> {
+        resolve_specs(&self.input, &self.rects)
+    }
+
+    /// Path the result will be written to.
+    pub fn output_path(&self) -> PathBuf {
+        match &self.destination {
+            Some(destination) => destination.clone(),
+            None => self.output.output_path(&self.input, self.settings.format.extension()),
+        }
+    }
+
+    /// Load, crop and save the image in the current thread.
+    pub fn run(&self) -> Result<CropResult> {
+        let cropped = crop_file_with(&self.input, &self.selections()?, None, &self.combine)?;
+        let output = self.output_path();
+        self.options.journal.record(&self.input, Event::crop(&cropped.selections, &output));
+        let mut extra_outputs = Vec::new();
+        if self.mask {
+            let (width, height) = image::image_dimensions(&self.input)?;
+            extra_outputs.push(ExtraOutput {
+                path: mask_path(&output),
+                image: selection_mask(width, height, &cropped.selections),
+                settings: EncodeSettings::mask(),
+            });
+        }
+        let request = SaveRequest {
+            image: cropped.image,
+            path: output,
+            original_path: cropped.path,
+            settings: self.settings,
+            extra_outputs,
+        };
+        let status = save(&request, &self.options);
+        status.result?;
+        Ok(CropResult {
+            output: status.path,
+            backup: status.backup_path,
+            original_bytes: status.original_size,
+            new_bytes: status.new_size,
+        })
+    }
+}
+
+/// Write `req` as described in the [module docs](self) and record the outcome in
+/// the journal of `options`. If writing fails, a backed up original is moved back.
+pub fn save(req: &SaveRequest, options: &SaverOptions) -> SaveStatus {
+    let mut original_size: Option<u64> = None;
+    let mut new_size: Option<u64> = None;
+    let mut backup_path: Option<PathBuf> = None;
+
+    let result = (|| -> Result<()> {
+        // capture original size if possible before backup moves the file
+        if let Ok(meta) = std::fs::metadata(&req.original_path) {
+            original_size = Some(meta.len());
+        }
+
+        let work_dirs = &options.work_dirs;
+        // Without a backup the original stays in place until the new file is written.
+        // Pages of PDFs and pasted images have no file of their own to back up.
+        let backed_up_path = if options.originals == OriginalsMode::Backup && req.original_path.exists() {
+            let backed_up_path = work_dirs.backup_original(&req.original_path)?;
+            backup_path = Some(backed_up_path.clone());
+            backed_up_path
+        } else {
+            req.original_path.clone()
+        };
+        write_output(&req.image, &req.settings, &req.path, &backed_up_path, work_dirs, options.fsync)?;
+        for extra in &req.extra_outputs {
+            write_output(&extra.image, &extra.settings, &extra.path, &backed_up_path, work_dirs, options.fsync)?;
+        }
+
+        // The output replaced the original in place unless the extension changed
+        if options.originals == OriginalsMode::Replace
+            && req.original_path != req.path
+            && req.original_path.exists()
+        {
+            std::fs::remove_file(&req.original_path)?;
+        }
+
+        // Sidecars follow the image unless the source stays where it is
+        let copy_sidecars = options.originals == OriginalsMode::Keep;
+        if let Err(err) = move_sidecars(&req.original_path, &req.path, copy_sidecars) {
+            eprintln!("Failed to move sidecar: {err:#}");
+        }
+
+        // capture new file size if possible, all outputs together
+        let outputs = std::iter::once(&req.path).chain(req.extra_outputs.iter().map(|extra| &extra.path));
+        new_size = outputs
+            .map(|path| std::fs::metadata(path).map(|meta| meta.len()).ok())
+            .sum();
+        Ok(())
+    })();
+    // Put the original back, so the save can be retried and nothing goes missing
+    if result.is_err() && !req.original_path.exists() {
+        if let Some(backup) = backup_path.take() {
+            if let Err(err) = move_file(&backup, &req.original_path) {
+                eprintln!("Failed to restore {}: {err:#}", req.original_path.display());
+                backup_path = Some(backup);
+            }
+        }
+    }
+    let event = match &result {
+        Ok(()) => Event::saved(&req.path, (original_size, new_size), backup_path.clone()),
+        Err(err) => Event::SaveFailed {
+            error: format!("{err:#}"),
+        },
+    };
+    options.journal.record(&req.original_path, event);
+    if result.is_ok() && options.track_processed {
+        let mut paths = vec![&req.original_path];
+        if req.path != req.original_path {
+            paths.push(&req.path);
+        }
+        paths.extend(req.extra_outputs.iter().map(|extra| &extra.path));
+        for path in paths {
+            if let Err(err) = mark_processed(path) {
+                eprintln!("{err:#}");
+            }
+        }
+    }
+    SaveStatus {
+        path: req.path.clone(),
+        result,
+        original_size,
+        new_size,
+        backup_path,
+        extra_paths: req.extra_outputs.iter().map(|extra| extra.path.clone()).collect(),
+    }
+}
+
+/// EXIF data and ICC profile of the JPEG, PNG or WebP file at `source`, as far
+/// as `metadata` allows.
+fn source_metadata(source: &Path, metadata: CopyMetadata) -> Result<(Option<Bytes>, Option<Bytes>)> {
+    if metadata == CopyMetadata::Nothing {
+        return Ok((None, None));
+    }
+    let input_data = Bytes::from(std::fs::read(source)?);
+    let (exif, icc) = if let Ok(input_jpeg) = Jpeg::from_bytes(input_data.clone()) {
+        (input_jpeg.exif(), input_jpeg.icc_profile())
+    } else if let Ok(input_png) = Png::from_bytes(input_data.clone()) {
+        (input_png.exif(), input_png.icc_profile())
+    } else if let Ok(input_webp) = WebP::from_bytes(input_data) {
+        (input_webp.exif(), input_webp.icc_profile())
+    } else {
+        (None, None)
+    };
+    Ok((exif.filter(|_| metadata == CopyMetadata::All), icc))
+}
+
+/// Encode `image` into `path` through a temp file and copy the EXIF data and
+/// ICC profile of `metadata_source` into it, as far as `settings.metadata` allows.
+/// With `fsync` the file is flushed to disk before it replaces `path`.
+fn write_output(
+    image: &DynamicImage,
+    settings: &EncodeSettings,
+    path: &Path,
+    metadata_source: &Path,
+    work_dirs: &WorkDirs,
+    fsync: bool,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Save to temp file first
+    let temp_dir = work_dirs.temp_dir(path)?;
+    let file_name = path.file_name().ok_or_else(|| anyhow!("No filename"))?;
+    // The temp folder may be shared by several image directories
+    let temp_path = unique_destination(&temp_dir, file_name);
+
+    // Read EXIF/ICC of the original first, so formats whose metadata can't be
+    // edited afterwards get it from the encoder
+    let (exif, icc) = match source_metadata(metadata_source, settings.metadata) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("Failed to copy metadata: {}", e);
+            (None, None)
+        }
+    };
+    let encoded = match settings.format {
+        // img-parts can't edit TIFF, so the profile is written by the encoder. EXIF
+        // would need its offsets rewritten and is not copied.
+        OutputFormat::Tiff => encode_tiff(image, settings.tiff_compression, icc.as_deref())?,
+        _ => encode(image, settings)?,
+    };
+
+    // Inject the metadata into the encoded file. If injection fails, the file is
+    // written as encoded.
+    let copy_metadata = || -> Result<Option<Vec<u8>>> {
+        if exif.is_none() && icc.is_none() {
+            // No metadata to copy
+            return Ok(None);
+        }
+        let output_bytes = match settings.format {
+            OutputFormat::Jpg => {
+                if let Ok(mut out_jpeg) = Jpeg::from_bytes(encoded.clone().into()) {
+                    if let Some(exif) = exif.clone() { out_jpeg.set_exif(Some(exif)); }
+                    if let Some(icc) = icc.clone() { out_jpeg.set_icc_profile(Some(icc)); }
+                    let mut out = Vec::new();
+                    out_jpeg.encoder().write_to(&mut out)?;
+                    Some(out)
+                } else { None }
+            }
+            OutputFormat::Png => {
+                if let Ok(mut out_png) = Png::from_bytes(encoded.clone().into()) {
+                    if let Some(exif) = exif.clone() { out_png.set_exif(Some(exif)); }
+                    if let Some(icc) = icc.clone() { out_png.set_icc_profile(Some(icc)); }
+                    let mut out = Vec::new();
+                    out_png.encoder().write_to(&mut out)?;
+                    Some(out)
+                } else { None }
+            }
+            OutputFormat::Webp => {
+                if let Ok(mut out_webp) = WebP::from_bytes(encoded.clone().into()) {
+                    if let Some(exif) = exif.clone() { out_webp.set_exif(Some(exif)); }
+                    if let Some(icc) = icc.clone() { out_webp.set_icc_profile(Some(icc)); }
+                    let mut out = Vec::new();
+                    out_webp.encoder().write_to(&mut out)?;
+                    Some(out)
+                } else { None }
+            }
+            OutputFormat::Avif => {
+                add_avif_metadata(&encoded, exif.as_deref(), icc.as_deref())
+            }
+            OutputFormat::Jxl => {
+                // JPEG XL has no ICC box, the profile is part of the codestream
+                exif.as_deref().and_then(|exif| add_jxl_exif(&encoded, exif))
+            }
+            // Written by the encoder
+            OutputFormat::Tiff => None,
+        };
+
+        Ok(output_bytes)
+    };
+
+    let data = match copy_metadata() {
+        Ok(Some(bytes)) => bytes,
+        Ok(None) => encoded,
+        Err(e) => {
+            eprintln!("Failed to copy metadata: {}", e);
+            encoded
+        }
+    };
+    std::fs::write(&temp_path, data)?;
+    // The temp file replaces `path` in one step, so it is never seen half-written
+    if fsync {
+        move_file_durable(&temp_path, path)
+    } else {
+        move_file(&temp_path, path)
+    }
+}
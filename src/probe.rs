@@ -0,0 +1,166 @@
+use std::{io::Read, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Width and height of the image at `path`, read from the file header without decoding pixels.
+pub fn dimensions(path: &Path) -> Result<(u32, u32)> {
+    image::ImageReader::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?
+        .with_guessed_format()?
+        .into_dimensions()
+        .with_context(|| format!("Failed to read dimensions of {}", path.display()))
+}
+
+/// Bytes read from the start of a JPEG to look for an embedded EXIF thumbnail
+const EXIF_SCAN_BYTES: u64 = 128 * 1024;
+
+/// The JPEG thumbnail embedded in the EXIF data of a JPEG file, if any.
+pub fn exif_thumbnail_bytes(jpeg: &[u8]) -> Option<&[u8]> {
+    if jpeg.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut pos = 2;
+    loop {
+        let &[0xFF, marker, len_hi, len_lo] = jpeg.get(pos..pos + 4)? else {
+            return None;
+        };
+        // Start of scan or end of image: no more metadata segments
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let len = u16::from_be_bytes([len_hi, len_lo]) as usize;
+        let segment = jpeg.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_thumbnail(tiff);
+            }
+        }
+        pos += 2 + len;
+    }
+}
+
+/// Locate the thumbnail referenced by IFD1 of EXIF TIFF data.
+fn tiff_thumbnail(tiff: &[u8]) -> Option<&[u8]> {
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| {
+        let bytes = [*tiff.get(pos)?, *tiff.get(pos + 1)?];
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) } as usize)
+    };
+    let u32_at = |pos: usize| {
+        let bytes: [u8; 4] = tiff.get(pos..pos + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) } as usize)
+    };
+
+    let ifd0 = u32_at(4)?;
+    let ifd1 = u32_at(ifd0 + 2 + u16_at(ifd0)? * 12)?;
+    if ifd1 == 0 {
+        return None;
+    }
+    let (mut offset, mut length) = (None, None);
+    for entry in (0..u16_at(ifd1)?).map(|i| ifd1 + 2 + i * 12) {
+        match u16_at(entry)? {
+            0x0201 => offset = u32_at(entry + 8),
+            0x0202 => length = u32_at(entry + 8),
+            _ => {}
+        }
+    }
+    let (offset, length) = (offset?, length?);
+    tiff.get(offset..offset + length)
+        .filter(|thumbnail| thumbnail.starts_with(&[0xFF, 0xD8]))
+}
+
+/// Quickly decodable low-resolution version of the image at `path`, read from
+/// the first few kilobytes of the file. Currently the EXIF thumbnail of JPEGs.
+pub fn quick_preview(path: &Path) -> Option<image::DynamicImage> {
+    let mut head = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(EXIF_SCAN_BYTES)
+        .read_to_end(&mut head)
+        .ok()?;
+    image::load_from_memory_with_format(exif_thumbnail_bytes(&head)?, image::ImageFormat::Jpeg).ok()
+}
+
+/// Split `value` into a number and a case-insensitive unit suffix.
+fn split_unit(value: &str) -> Result<(f64, String)> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid number in {value:?}"))?;
+    Ok((number, unit.trim().to_ascii_lowercase()))
+}
+
+/// Parse a file size such as `2048`, `500K`, `1.5MB` or `2G` (binary units, as shown by `format_size`).
+pub fn parse_file_size(value: &str) -> Result<u64> {
+    let (number, unit) = split_unit(value)?;
+    let factor: u64 = match unit.as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return Err(anyhow!("Unknown size unit in {value:?} (use K, M or G)")),
+    };
+    Ok((number * factor as f64).round() as u64)
+}
+
+/// Parse a pixel count such as `250000`, `0.5M`, `12MP` or `1920x1080`.
+pub fn parse_pixel_count(value: &str) -> Result<u64> {
+    if let Some((w, h)) = value.split_once(['x', 'X']) {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|_| anyhow!("Invalid resolution {value:?} (expected WIDTHxHEIGHT)"))
+        };
+        return Ok(parse(w)? * parse(h)?);
+    }
+    let (number, unit) = split_unit(value)?;
+    let factor = match unit.as_str() {
+        "" => 1.0,
+        "k" => 1e3,
+        "m" | "mp" => 1e6,
+        _ => return Err(anyhow!("Unknown pixel unit in {value:?} (use K, M or MP)")),
+    };
+    Ok((number * factor).round() as u64)
+}
+
+/// Thresholds of `--min-pixels`, `--min-size` and `--max-size`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeFilter {
+    pub min_pixels: Option<u64>,
+    pub min_bytes: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl SizeFilter {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `path` is within all thresholds.
+    ///
+    /// Images whose header can't be read are kept so the viewer can report the error.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.min_bytes.is_some() || self.max_bytes.is_some() {
+            let Ok(bytes) = std::fs::metadata(path).map(|meta| meta.len()) else {
+                return true;
+            };
+            if self.min_bytes.is_some_and(|min| bytes < min)
+                || self.max_bytes.is_some_and(|max| bytes > max)
+            {
+                return false;
+            }
+        }
+        let Some(min_pixels) = self.min_pixels else {
+            return true;
+        };
+        dimensions(path).map_or(true, |(w, h)| w as u64 * h as u64 >= min_pixels)
+    }
+}
@@ -0,0 +1,50 @@
+//! Record of images that were already handled, for `--skip-processed`.
+//!
+//! Each image directory gets a small file listing the names of its cropped,
+//! resaved or explicitly finished images, so the record moves along with the
+//! folder.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Name of the list of processed images in each directory, one file name per line
+pub const PROCESSED_FILE: &str = ".imagecropper-processed";
+
+/// Add `path` to the list of its directory.
+pub fn mark_processed(path: &Path) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    let list = path.with_file_name(PROCESSED_FILE);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&list)
+        .with_context(|| format!("Unable to open {}", list.display()))?;
+    writeln!(file, "{}", name.to_string_lossy()).with_context(|| format!("Unable to write {}", list.display()))
+}
+
+/// Names of the processed images in `dir`.
+pub fn processed_names(dir: &Path) -> HashSet<String> {
+    std::fs::read_to_string(dir.join(PROCESSED_FILE))
+        .map(|text| text.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Drop the processed images from `files`.
+pub fn retain_unprocessed(files: &mut Vec<PathBuf>) {
+    let mut lists: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+    files.retain(|path| {
+        let dir = path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+        let names = lists.entry(dir).or_insert_with_key(|dir| processed_names(dir));
+        !path
+            .file_name()
+            .is_some_and(|name| names.contains(name.to_string_lossy().as_ref()))
+    });
+}
@@ -0,0 +1,125 @@
+//! Directories processed in earlier sessions, newest first, together with the
+//! image each session ended at, so it can be resumed there (`--resume` or the
+//! startup screen).
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fs_utils::{find_start, write_atomically};
+
+/// File name of the list in the config directory
+pub const RECENT_FILE_NAME: &str = "recent.json";
+
+/// How many directories are remembered
+pub const MAX_RECENT_DIRS: usize = 10;
+
+/// A directory processed in an earlier session.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecentDir {
+    pub dir: PathBuf,
+    /// Image shown when the session ended; `None` if all images were processed
+    pub position: Option<PathBuf>,
+    /// When the session ended, in RFC 3339 format
+    pub time: String,
+}
+
+/// The remembered directories and the file they are stored in.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecentDirs {
+    file: Option<PathBuf>,
+    pub entries: Vec<RecentDir>,
+}
+
+/// `path` as absolute path without symlinks, so the same directory is found
+/// however it was given.
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl RecentDirs {
+    /// `<config dir>/imagecropper/recent.json`, e.g. `~/.config/imagecropper/recent.json` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("imagecropper").join(RECENT_FILE_NAME))
+    }
+
+    /// The list stored in `file`. A missing or unreadable list is empty, as
+    /// losing it only loses a convenience.
+    pub fn load(file: &Path) -> Self {
+        let entries = fs::read_to_string(file)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            file: Some(file.to_path_buf()),
+            entries,
+        }
+    }
+
+    /// The list in the default location, if there is a config directory.
+    pub fn load_default() -> Self {
+        Self::default_path().map(|file| Self::load(&file)).unwrap_or_default()
+    }
+
+    /// The entry of `dir`, if it was processed before.
+    pub fn get(&self, dir: &Path) -> Option<&RecentDir> {
+        let dir = normalize(dir);
+        self.entries.iter().find(|entry| entry.dir == dir)
+    }
+
+    /// Index in `files` of the image the last session in one of `dirs` ended at.
+    /// The files are normalized like the remembered position, so relative input
+    /// paths match as well.
+    pub fn resume_index(&self, dirs: &[PathBuf], files: &[PathBuf]) -> Option<usize> {
+        let position = dirs.iter().find_map(|dir| self.get(dir)?.position.clone())?;
+        let files: Vec<PathBuf> = files.iter().map(|path| normalize(path)).collect();
+        find_start(&files, &position).ok()
+    }
+
+    /// Move `dir` to the front with the image the session ended at, dropping
+    /// the oldest directories beyond [`MAX_RECENT_DIRS`].
+    pub fn remember(&mut self, dir: &Path, position: Option<&Path>) {
+        let dir = normalize(dir);
+        self.entries.retain(|entry| entry.dir != dir);
+        self.entries.insert(
+            0,
+            RecentDir {
+                dir,
+                position: position.map(normalize),
+                time: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            },
+        );
+        self.entries.truncate(MAX_RECENT_DIRS);
+    }
+
+    /// Write the list back to the file it was loaded from, merged with what
+    /// other sessions saved there in the meantime: the newer entry of a
+    /// directory wins.
+    pub fn save(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut entries = self.entries.clone();
+        for theirs in Self::load(file).entries {
+            match entries.iter_mut().find(|entry| entry.dir == theirs.dir) {
+                Some(ours) if theirs.time > ours.time => *ours = theirs,
+                Some(_) => {}
+                None => entries.push(theirs),
+            }
+        }
+        // RFC 3339 times in UTC sort like strings; ties keep this session's order
+        entries.sort_by(|a, b| b.time.cmp(&a.time));
+        entries.truncate(MAX_RECENT_DIRS);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Unable to create {}", parent.display()))?;
+        }
+        write_atomically(file, serde_json::to_string_pretty(&entries)?)
+    }
+}
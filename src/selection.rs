@@ -1,15 +1,118 @@
+use anyhow::{anyhow, Result};
 use eframe::egui::{self, Color32, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::image_utils::{EncodeSettings, OutputFormat};
 
 pub const HANDLE_THICKNESS: f32 = 10.0;
 pub const MIN_HANDLE_LENGTH: f32 = 20.0;
 pub const MAX_HANDLE_LENGTH: f32 = 100.0;
 
+/// Outline of a selection inside its rectangle. Crops are cut to the rectangle
+/// and the area outside the shape is made transparent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SelectionShape {
+    #[default]
+    Rectangle,
+    /// The ellipse filling the rectangle, e.g. for round avatars
+    Ellipse,
+    /// A polygon drawn vertex by vertex or freehand. Vertices are fractions of the
+    /// rectangle, `(0, 0)` its top left and `(1, 1)` its bottom right corner, so
+    /// the polygon follows when the selection is moved or resized.
+    Polygon(Vec<egui::Pos2>),
+}
+
+impl SelectionShape {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Rectangle => "rectangle",
+            Self::Ellipse => "ellipse",
+            Self::Polygon(_) => "polygon",
+        }
+    }
+}
+
+/// Output of a selection that is written to a file of its own instead of being
+/// combined with the other selections, set in the selection editor (**N**).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelectionOutput {
+    /// Appended to the output file name, e.g. `photo_logo.png`; the selection's
+    /// number if empty
+    pub label: String,
+    /// Format of this crop; the image's output format if unset
+    pub format: Option<OutputFormat>,
+    /// Quality of this crop; the image's quality if unset
+    pub quality: Option<u8>,
+}
+
+impl SelectionOutput {
+    /// `base` (the image's settings) with this output's format and quality.
+    pub fn settings(&self, base: EncodeSettings) -> EncodeSettings {
+        EncodeSettings {
+            format: self.format.unwrap_or(base.format),
+            quality: self.quality.unwrap_or(base.quality),
+            ..base
+        }
+    }
+
+    /// Name part of the output file: the label without characters that don't
+    /// belong in file names, or the 1-based `number` of the selection.
+    pub fn file_label(&self, number: usize) -> String {
+        let label: String = self
+            .label
+            .trim()
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        if label.is_empty() {
+            number.to_string()
+        } else {
+            label
+        }
+    }
+}
+
+/// Whether `point` lies inside the polygon `vertices` (even-odd rule).
+fn polygon_contains(vertices: &[egui::Pos2], point: egui::Pos2) -> bool {
+    let Some(&last) = vertices.last() else { return false };
+    let (mut inside, mut previous) = (false, last);
+    for &vertex in vertices {
+        if (vertex.y > point.y) != (previous.y > point.y)
+            && point.x < (previous.x - vertex.x) * (point.y - vertex.y) / (previous.y - vertex.y) + vertex.x
+        {
+            inside = !inside;
+        }
+        previous = vertex;
+    }
+    inside
+}
+
+/// Points on the outline of an ellipse drawn and hit-tested as a polygon
+const ELLIPSE_SEGMENTS: usize = 64;
+
 #[derive(Clone)]
 pub struct Selection {
     pub rect: Rect,
+    /// Object class for annotation export, set with the number keys in `--annotate` mode
+    pub class: u32,
+    /// Clockwise rotation in degrees of `rect` around its center, within ±180°
+    pub angle: f32,
+    pub shape: SelectionShape,
+    /// Own file, format and quality for this crop, if set
+    pub output: Option<SelectionOutput>,
 }
 
 impl Selection {
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            class: 0,
+            angle: 0.0,
+            shape: SelectionShape::Rectangle,
+            output: None,
+        }
+    }
+
     pub fn from_points(a: egui::Pos2, b: egui::Pos2, bounds: Vec2) -> Self {
         let min = egui::pos2(
             a.x.min(b.x).clamp(0.0, bounds.x),
@@ -19,18 +122,198 @@ impl Selection {
             a.x.max(b.x).clamp(0.0, bounds.x),
             a.y.max(b.y).clamp(0.0, bounds.y),
         );
-        let mut selection = Self {
-            rect: Rect::from_min_max(min, max),
-        };
+        let mut selection = Self::new(Rect::from_min_max(min, max));
         selection.clamp_within(bounds);
         selection
     }
 
+    /// A selection centered on `center` with `corner` as one of its corners, or a
+    /// square reaching as far as the farther axis if `square`. It shrinks
+    /// symmetrically at the image border so it stays centered.
+    pub fn from_center(center: egui::Pos2, corner: egui::Pos2, square: bool, bounds: Vec2) -> Self {
+        let center = egui::pos2(center.x.clamp(0.0, bounds.x), center.y.clamp(0.0, bounds.y));
+        let limit = egui::vec2(center.x.min(bounds.x - center.x), center.y.min(bounds.y - center.y));
+        let half = (corner - center).abs();
+        let half = if square {
+            Vec2::splat(half.max_elem().min(limit.min_elem()))
+        } else {
+            half.min(limit)
+        };
+        Self::new(Rect::from_center_size(center, half * 2.0))
+    }
+
+    /// A polygon selection through `points` (in image pixels, clamped to `bounds`),
+    /// `None` for fewer than three points or an area thinner than a pixel.
+    pub fn polygon(points: &[egui::Pos2], bounds: Vec2) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let points: Vec<egui::Pos2> = points
+            .iter()
+            .map(|point| egui::pos2(point.x.clamp(0.0, bounds.x), point.y.clamp(0.0, bounds.y)))
+            .collect();
+        let rect = Rect::from_points(&points);
+        if rect.width() < 1.0 || rect.height() < 1.0 {
+            return None;
+        }
+        let vertices = points
+            .iter()
+            .map(|point| egui::pos2((point.x - rect.min.x) / rect.width(), (point.y - rect.min.y) / rect.height()))
+            .collect();
+        Some(Self {
+            shape: SelectionShape::Polygon(vertices),
+            ..Self::new(rect)
+        })
+    }
+
+    /// Move the selection by `delta`, stopping at the image border so the
+    /// selection keeps its size.
     pub fn translate(&mut self, delta: Vec2, bounds: Vec2) {
-        self.rect = self.rect.translate(delta);
+        let dx = delta.x.clamp(-self.rect.min.x, (bounds.x - self.rect.max.x).max(-self.rect.min.x));
+        let dy = delta.y.clamp(-self.rect.min.y, (bounds.y - self.rect.max.y).max(-self.rect.min.y));
+        self.rect = self.rect.translate(egui::vec2(dx, dy));
         self.clamp_within(bounds);
     }
 
+    /// Map the selection onto the image rotated by 90° (`clockwise`) or -90°.
+    ///
+    /// `bounds` is the size of the image *before* rotation.
+    pub fn rotated_90(&self, clockwise: bool, bounds: Vec2) -> Self {
+        let Rect { min, max } = self.rect;
+        let rect = if clockwise {
+            Rect::from_min_max(
+                egui::pos2(bounds.y - max.y, min.x),
+                egui::pos2(bounds.y - min.y, max.x),
+            )
+        } else {
+            Rect::from_min_max(
+                egui::pos2(min.y, bounds.x - max.x),
+                egui::pos2(max.y, bounds.x - min.x),
+            )
+        };
+        let mut selection = Self { rect, ..self.clone() };
+        if let SelectionShape::Polygon(vertices) = &mut selection.shape {
+            for vertex in vertices {
+                *vertex = if clockwise {
+                    egui::pos2(1.0 - vertex.y, vertex.x)
+                } else {
+                    egui::pos2(vertex.y, 1.0 - vertex.x)
+                };
+            }
+        }
+        selection.clamp_within(egui::vec2(bounds.y, bounds.x));
+        selection
+    }
+
+    /// Map the selection from an image of size `from` onto one of size `to`,
+    /// keeping its position and size relative to the image.
+    pub fn scaled(&self, from: Vec2, to: Vec2) -> Self {
+        let scale = egui::vec2(to.x / from.x.max(1.0), to.y / from.y.max(1.0));
+        let rect = Rect::from_min_max(
+            egui::pos2(self.rect.min.x * scale.x, self.rect.min.y * scale.y),
+            egui::pos2(self.rect.max.x * scale.x, self.rect.max.y * scale.y),
+        );
+        let mut selection = Self { rect, ..self.clone() };
+        selection.clamp_within(to);
+        selection
+    }
+
+    /// Move every edge outwards by `amount` pixels, or inwards if negative.
+    /// Shrinking stops once the shorter side is a pixel long.
+    pub fn grow(&mut self, amount: f32, bounds: Vec2) {
+        let amount = amount.max(-((self.rect.size().min_elem() - 1.0) / 2.0).max(0.0));
+        self.rect = self.rect.expand(amount);
+        self.clamp_within(bounds);
+    }
+
+    /// Stretch the selection to the full image width and/or height, keeping the
+    /// other edges. A stretched selection is no longer rotated.
+    pub fn stretch(&mut self, width: bool, height: bool, bounds: Vec2) {
+        if width {
+            self.rect.min.x = 0.0;
+            self.rect.max.x = bounds.x;
+        }
+        if height {
+            self.rect.min.y = 0.0;
+            self.rect.max.y = bounds.y;
+        }
+        self.angle = 0.0;
+        self.clamp_within(bounds);
+    }
+
+    /// Rotate the selection clockwise around its center by `degrees`.
+    pub fn rotate_by(&mut self, degrees: f32) {
+        self.angle = (self.angle + degrees + 180.0).rem_euclid(360.0) - 180.0;
+    }
+
+    /// Corners of the rotated selection, clockwise from the top left one.
+    pub fn corners(&self) -> [egui::Pos2; 4] {
+        let center = self.rect.center();
+        let rotation = egui::emath::Rot2::from_angle(self.angle.to_radians());
+        [self.rect.left_top(), self.rect.right_top(), self.rect.right_bottom(), self.rect.left_bottom()]
+            .map(|corner| center + rotation * (corner - center))
+    }
+
+    /// Outline of the rotated shape: the corners of a rectangle, points along an
+    /// ellipse or the vertices of a polygon.
+    pub fn outline(&self) -> Vec<egui::Pos2> {
+        match &self.shape {
+            SelectionShape::Rectangle => self.corners().to_vec(),
+            SelectionShape::Polygon(vertices) => {
+                let center = self.rect.center();
+                let rotation = egui::emath::Rot2::from_angle(self.angle.to_radians());
+                vertices
+                    .iter()
+                    .map(|vertex| {
+                        let point = self.rect.min + vertex.to_vec2() * self.rect.size();
+                        center + rotation * (point - center)
+                    })
+                    .collect()
+            }
+            SelectionShape::Ellipse => {
+                let (center, radius) = (self.rect.center(), self.rect.size() / 2.0);
+                let rotation = egui::emath::Rot2::from_angle(self.angle.to_radians());
+                (0..ELLIPSE_SEGMENTS)
+                    .map(|i| {
+                        let t = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                        center + rotation * egui::vec2(radius.x * t.cos(), radius.y * t.sin())
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Whether `pos` lies inside the rotated shape.
+    pub fn contains(&self, pos: egui::Pos2) -> bool {
+        let center = self.rect.center();
+        let rotation = egui::emath::Rot2::from_angle(-self.angle.to_radians());
+        self.shape_contains(center + rotation * (pos - center))
+    }
+
+    /// Whether `pos`, given relative to the unrotated `rect`, lies inside the shape.
+    pub fn shape_contains(&self, pos: egui::Pos2) -> bool {
+        if !self.rect.contains(pos) {
+            return false;
+        }
+        match &self.shape {
+            SelectionShape::Rectangle => true,
+            SelectionShape::Ellipse => {
+                let offset = pos - self.rect.center();
+                let (rx, ry) = (self.rect.width() / 2.0, self.rect.height() / 2.0);
+                (offset.x / rx).powi(2) + (offset.y / ry).powi(2) <= 1.0
+            }
+            SelectionShape::Polygon(vertices) => {
+                let relative = (pos - self.rect.min) / self.rect.size();
+                polygon_contains(vertices, relative.to_pos2())
+            }
+        }
+    }
+
+    /// Smallest axis-aligned rectangle around the rotated selection.
+    pub fn bounding_box(&self) -> Rect {
+        Rect::from_points(&self.corners())
+    }
+
     pub fn to_u32_bounds(&self) -> Option<(u32, u32, u32, u32)> {
         let width = self.rect.width();
         let height = self.rect.height();
@@ -79,7 +362,39 @@ impl Selection {
         self
     }
 
-    fn clamp_within(&mut self, bounds: Vec2) {
+    /// Round all edges to the nearest multiple of `grid` pixels.
+    ///
+    /// Edges lying on (or rounding past) the image border stay on the border,
+    /// so a selection can still cover the full image even if its size is not a
+    /// multiple of the grid.
+    pub fn snap_to_grid(&mut self, grid: u32, bounds: Vec2) {
+        if grid <= 1 {
+            return;
+        }
+        let grid = grid as f32;
+        let snap = |value: f32, limit: f32| {
+            if value >= limit {
+                limit
+            } else {
+                ((value / grid).round() * grid).min(limit)
+            }
+        };
+        let mut min = egui::pos2(snap(self.rect.min.x, bounds.x), snap(self.rect.min.y, bounds.y));
+        let mut max = egui::pos2(snap(self.rect.max.x, bounds.x), snap(self.rect.max.y, bounds.y));
+        if max.x <= min.x {
+            max.x = (min.x + grid).min(bounds.x);
+            min.x = (max.x - grid).max(0.0);
+        }
+        if max.y <= min.y {
+            max.y = (min.y + grid).min(bounds.y);
+            min.y = (max.y - grid).max(0.0);
+        }
+        self.rect = Rect::from_min_max(min, max);
+        self.clamp_within(bounds);
+    }
+
+    /// Keep the selection inside the image and at least one pixel in size.
+    pub fn clamp_within(&mut self, bounds: Vec2) {
         let mut min = self.rect.min;
         let mut max = self.rect.max;
         min.x = min.x.clamp(0.0, bounds.x);
@@ -98,6 +413,84 @@ impl Selection {
     }
 }
 
+/// Ways [`arrange`] lines up several selections, e.g. the photos of a contact sheet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrangement {
+    /// Give every selection the size of the active one, keeping its top left corner
+    SameSize,
+    /// Move every selection to the top edge of the active one
+    AlignTops,
+    /// Move every selection to the left edge of the active one
+    AlignLefts,
+    /// Space the selections evenly between the leftmost and the rightmost one
+    DistributeHorizontally,
+    /// Space the selections evenly between the topmost and the bottommost one
+    DistributeVertically,
+}
+
+impl Arrangement {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::SameSize => "made the same size",
+            Self::AlignTops => "aligned at the top",
+            Self::AlignLefts => "aligned at the left",
+            Self::DistributeHorizontally => "distributed horizontally",
+            Self::DistributeVertically => "distributed vertically",
+        }
+    }
+}
+
+/// Line up `selections` as described by `arrangement`, taking size and edges
+/// from the one at `active`. Selections stay inside `bounds`.
+pub fn arrange(selections: &mut [Selection], active: usize, arrangement: Arrangement, bounds: Vec2) {
+    let Some(reference) = selections.get(active).map(|selection| selection.rect) else {
+        return;
+    };
+    match arrangement {
+        Arrangement::SameSize => {
+            let size = reference.size();
+            for selection in selections.iter_mut() {
+                let min = selection.rect.min.min((bounds - size).to_pos2()).max(egui::Pos2::ZERO);
+                selection.rect = Rect::from_min_size(min, size);
+                selection.clamp_within(bounds);
+            }
+        }
+        Arrangement::AlignTops => {
+            for selection in selections.iter_mut() {
+                selection.translate(egui::vec2(0.0, reference.min.y - selection.rect.min.y), bounds);
+            }
+        }
+        Arrangement::AlignLefts => {
+            for selection in selections.iter_mut() {
+                selection.translate(egui::vec2(reference.min.x - selection.rect.min.x, 0.0), bounds);
+            }
+        }
+        Arrangement::DistributeHorizontally => distribute(selections, 0, bounds),
+        Arrangement::DistributeVertically => distribute(selections, 1, bounds),
+    }
+}
+
+/// Keep the outermost selections along `axis` (0: x, 1: y) in place and move
+/// the ones between so the gaps between neighbors are all the same.
+fn distribute(selections: &mut [Selection], axis: usize, bounds: Vec2) {
+    if selections.len() < 3 {
+        return;
+    }
+    let mut order: Vec<usize> = (0..selections.len()).collect();
+    order.sort_by(|&a, &b| selections[a].rect.min[axis].total_cmp(&selections[b].rect.min[axis]));
+    let (first, last) = (selections[order[0]].rect, selections[order[order.len() - 1]].rect);
+    let lengths: f32 = order.iter().map(|&index| selections[index].rect.size()[axis]).sum();
+    let gap = (last.max[axis] - first.min[axis] - lengths) / (order.len() - 1) as f32;
+    let mut start = first.max[axis] + gap;
+    for &index in &order[1..order.len() - 1] {
+        let selection = &mut selections[index];
+        let mut delta = Vec2::ZERO;
+        delta[axis] = start - selection.rect.min[axis];
+        selection.translate(delta, bounds);
+        start += selection.rect.size()[axis] + gap;
+    }
+}
+
 #[derive(Clone)]
 pub struct HandleDrag {
     pub handle: SelectionHandle,
@@ -106,6 +499,14 @@ pub struct HandleDrag {
     pub selection_index: usize,
 }
 
+/// State of a selection being moved by dragging its body.
+#[derive(Clone)]
+pub struct MoveDrag {
+    pub original: Selection,
+    pub start_pos: egui::Pos2,
+    pub selection_index: usize,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum SelectionHandle {
     Top,
@@ -191,3 +592,95 @@ pub fn selection_color(index: usize) -> Color32 {
     Color32::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
 }
 
+/// The Okabe-Ito palette without black, distinguishable with the common color vision deficiencies
+pub const COLORBLIND_PALETTE: [Color32; 7] = [
+    Color32::from_rgb(230, 159, 0),
+    Color32::from_rgb(86, 180, 233),
+    Color32::from_rgb(0, 158, 115),
+    Color32::from_rgb(240, 228, 66),
+    Color32::from_rgb(0, 114, 178),
+    Color32::from_rgb(213, 94, 0),
+    Color32::from_rgb(204, 121, 167),
+];
+
+/// Color sequence of the selections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Hues spaced by the golden ratio, see [`selection_color`]
+    #[default]
+    Golden,
+    /// [`COLORBLIND_PALETTE`]
+    Colorblind,
+}
+
+/// Look of the selection rectangles, the `[selection]` table of the config.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SelectionStyle {
+    /// Outline width in screen pixels
+    pub stroke_width: f32,
+    /// Outline width of the active selection while there are several
+    pub active_stroke_width: f32,
+    /// Opacity of the white fill, 0 (none) to 1
+    pub fill_opacity: f32,
+    pub palette: Palette,
+    /// `#rrggbb` colors used instead of `palette`, repeated for further selections
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<String>,
+}
+
+impl Default for SelectionStyle {
+    fn default() -> Self {
+        Self {
+            stroke_width: 2.0,
+            active_stroke_width: 3.0,
+            fill_opacity: 0.1,
+            palette: Palette::default(),
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl SelectionStyle {
+    /// Color of the selection at `index`.
+    pub fn color(&self, index: usize) -> Color32 {
+        if !self.colors.is_empty() {
+            if let Ok(color) = parse_hex_color(&self.colors[index % self.colors.len()]) {
+                return color;
+            }
+        }
+        match self.palette {
+            Palette::Golden => selection_color(index),
+            Palette::Colorblind => COLORBLIND_PALETTE[index % COLORBLIND_PALETTE.len()],
+        }
+    }
+
+    pub fn fill(&self) -> Color32 {
+        Color32::from_white_alpha((self.fill_opacity.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(self.stroke_width > 0.0 && self.active_stroke_width > 0.0) {
+            return Err(anyhow!("[selection] stroke widths must be positive"));
+        }
+        if !(0.0..=1.0).contains(&self.fill_opacity) {
+            return Err(anyhow!("[selection] fill_opacity must be between 0 and 1"));
+        }
+        for color in &self.colors {
+            parse_hex_color(color)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `#rrggbb` color; the `#` is optional.
+pub fn parse_hex_color(text: &str) -> Result<Color32> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok(Color32::from_rgb(r, g, b)),
+        _ => Err(anyhow!("Invalid color \"{text}\", expected #rrggbb")),
+    }
+}
+
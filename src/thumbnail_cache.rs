@@ -0,0 +1,76 @@
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Result};
+use image::DynamicImage;
+
+/// Longest side of cached previews in pixels
+pub const PREVIEW_SIZE: u32 = 512;
+
+/// Downscaled previews of images seen before, stored on disk so revisiting a
+/// directory shows them instantly.
+///
+/// Entries are keyed by the image's path, modification time and size, so an
+/// edited image gets a new entry instead of a stale preview.
+#[derive(Clone, Debug)]
+pub struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `$XDG_CACHE_HOME/imagecropper` (`~/.cache/imagecropper`) on Linux, the platform cache directory elsewhere.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("imagecropper"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Cache file for the current version of `path`.
+    pub fn entry_path(&self, path: &Path) -> Option<PathBuf> {
+        let meta = std::fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let key = format!("{}\0{modified}\0{}", path.to_string_lossy(), meta.len());
+        Some(self.dir.join(format!("{:016x}.jpg", fnv1a(key.as_bytes()))))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<DynamicImage> {
+        image::open(self.entry_path(path)?).ok()
+    }
+
+    /// Store a preview of `image`, the decoded content of `path`, unless one exists.
+    pub fn put(&self, path: &Path, image: &DynamicImage) -> Result<()> {
+        let entry = self
+            .entry_path(path)
+            .ok_or_else(|| anyhow!("Can't read modification time of {}", path.display()))?;
+        if entry.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let preview = if image.width().max(image.height()) > PREVIEW_SIZE {
+            image.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE).to_rgb8()
+        } else {
+            image.to_rgb8()
+        };
+        // Write under a temporary name so a concurrent reader never sees a partial file
+        let temp = entry.with_extension(format!("{:08x}.tmp", rand::random::<u32>()));
+        preview.save_with_format(&temp, image::ImageFormat::Jpeg)?;
+        std::fs::rename(&temp, &entry)?;
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a, a hash that stays the same across Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
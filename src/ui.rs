@@ -1,8 +1,91 @@
-use eframe::egui::{self, Pos2, Rect, Vec2};
+use std::path::PathBuf;
 
-use crate::selection::Selection;
+use eframe::egui::{self, Color32, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
 
-pub const ARROW_MOVE_STEP: f32 = 2.0;
+use crate::{
+    image_utils::Histogram,
+    selection::{Arrangement, Selection},
+};
+
+/// Longest frame time used for arrow movement, so a stalled frame doesn't jump
+pub const MAX_ARROW_FRAME_TIME: f32 = 0.1;
+
+/// Speed of arrow-key selection movement in image pixels per second.
+///
+/// Movement starts at `speed` and accelerates while a key is held, up to `max_speed`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArrowSpeed {
+    pub speed: f32,
+    /// Increase of the speed per second of holding, in pixels per second²
+    pub acceleration: f32,
+    pub max_speed: f32,
+}
+
+impl Default for ArrowSpeed {
+    fn default() -> Self {
+        Self {
+            speed: 120.0,
+            acceleration: 600.0,
+            max_speed: 1500.0,
+        }
+    }
+}
+
+impl ArrowSpeed {
+    /// Distance travelled during a frame of `dt` seconds after holding a key for `held` seconds.
+    pub fn distance(&self, held: f32, dt: f32) -> f32 {
+        let dt = dt.clamp(0.0, MAX_ARROW_FRAME_TIME);
+        let speed = (self.speed + self.acceleration * (held + dt)).min(self.max_speed.max(self.speed));
+        speed * dt
+    }
+}
+
+/// Text overlays drawn over the image. Tab hides all of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overlays {
+    /// Status line in the bottom left
+    pub status: bool,
+    /// "Image X of Y" in the top left
+    pub counter: bool,
+    /// Shortcut hint in the bottom right
+    pub hints: bool,
+    /// Names of the images being saved
+    pub saving: bool,
+    /// Near-duplicate warning
+    pub duplicate: bool,
+    /// Image coordinates and color of the pixel under the cursor
+    pub cursor: bool,
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Self {
+            status: true,
+            counter: true,
+            hints: true,
+            saving: true,
+            duplicate: true,
+            cursor: false,
+        }
+    }
+}
+
+impl Overlays {
+    /// All overlays turned off.
+    pub fn none() -> Self {
+        Self {
+            status: false,
+            counter: false,
+            hints: false,
+            saving: false,
+            duplicate: false,
+            cursor: false,
+        }
+    }
+}
 
 pub struct ImageMetrics {
     pub image_rect: Rect,
@@ -22,6 +105,18 @@ impl ImageMetrics {
         }
     }
 
+    /// Like [`ImageMetrics::new`], magnified by `zoom` (1 fits the image into
+    /// `canvas`) and moved by `pan` screen pixels from the center.
+    pub fn zoomed(canvas: Rect, image_size: Vec2, zoom: f32, pan: Vec2) -> Self {
+        let fit = Self::new(canvas, image_size);
+        let scale = fit.scale * zoom;
+        Self {
+            image_rect: Rect::from_center_size(canvas.center() + pan, fit.image_rect.size() * zoom),
+            image_size,
+            scale,
+        }
+    }
+
     pub fn screen_to_image(&self, pos: Pos2) -> Pos2 {
         let rel = pos - self.image_rect.min;
         egui::pos2(
@@ -30,6 +125,10 @@ impl ImageMetrics {
         )
     }
 
+    pub fn image_to_screen(&self, pos: Pos2) -> Pos2 {
+        self.image_rect.min + pos.to_vec2() * self.scale
+    }
+
     pub fn selection_rect(&self, selection: &Selection) -> Rect {
         let min = egui::pos2(
             self.image_rect.min.x + selection.rect.min.x * self.scale,
@@ -51,18 +150,274 @@ pub fn fit_within(image_size: Vec2, available: Vec2) -> (Vec2, f32) {
     (safe_size * scale, scale)
 }
 
+/// Draw RGB and luminance curves of `histogram` into `rect` on a dark background.
+pub fn draw_histogram(painter: &egui::Painter, rect: Rect, histogram: &Histogram) {
+    painter.rect_filled(rect, 4.0, Color32::from_black_alpha(178));
+    let max = histogram.max_count().max(1) as f32;
+    let inner = rect.shrink(4.0);
+    let curve = |counts: &[u32; 256]| -> Vec<Pos2> {
+        counts
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| {
+                egui::pos2(
+                    inner.min.x + inner.width() * i as f32 / 255.0,
+                    inner.max.y - inner.height() * (count as f32 / max),
+                )
+            })
+            .collect()
+    };
+    let channels = [
+        (&histogram.red, Color32::from_rgba_unmultiplied(255, 60, 60, 200)),
+        (&histogram.green, Color32::from_rgba_unmultiplied(60, 255, 60, 200)),
+        (&histogram.blue, Color32::from_rgba_unmultiplied(80, 120, 255, 200)),
+        (&histogram.luma, Color32::from_gray(230)),
+    ];
+    for (counts, color) in channels {
+        painter.add(egui::Shape::line(curve(counts), egui::Stroke::new(1.0, color)));
+    }
+}
+
+/// Tilt in degrees of the line from `a` to `b` relative to the nearest axis,
+/// in the range (-45, 45]. Positive values mean the line is rotated clockwise.
+///
+/// Returns `None` for lines too short to give a meaningful angle.
+pub fn horizon_angle(a: Pos2, b: Pos2) -> Option<f32> {
+    let delta = b - a;
+    if delta.length() < 2.0 {
+        return None;
+    }
+    let mut angle = delta.y.atan2(delta.x).to_degrees();
+    while angle > 45.0 {
+        angle -= 90.0;
+    }
+    while angle <= -45.0 {
+        angle += 90.0;
+    }
+    Some(angle)
+}
+
+/// Size, aspect ratio and megapixels of a selection of `size` pixels, shown
+/// next to it while dragging, e.g. `1200×800 px · 3:2 · 0.96 MP`.
+pub fn selection_readout(size: Vec2) -> String {
+    let (width, height) = (size.x.round() as u32, size.y.round() as u32);
+    let gcd = |mut a: u32, mut b: u32| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let divisor = gcd(width, height).max(1);
+    // Ratios with small terms read better than decimals
+    let ratio = if width / divisor <= 32 && height / divisor <= 32 {
+        format!("{}:{}", width / divisor, height / divisor)
+    } else {
+        format!("{:.2}:1", width as f32 / height.max(1) as f32)
+    };
+    let megapixels = width as f64 * height as f64 / 1e6;
+    format!("{width}×{height} px · {ratio} · {megapixels:.2} MP")
+}
+
+/// Position and color of the pixel at `(x, y)`, e.g. `x 12  y 34  RGB 255 128 0  #ff8000`.
+pub fn pixel_readout(x: u32, y: u32, color: [u8; 3]) -> String {
+    let [r, g, b] = color;
+    format!("x {x}  y {y}  RGB {r} {g} {b}  #{r:02x}{g:02x}{b:02x}")
+}
+
+/// Composition guides drawn inside every selection rectangle.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GuideMode {
+    #[default]
+    None,
+    RuleOfThirds,
+    GoldenRatio,
+    CenterCross,
+}
+
+impl GuideMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::RuleOfThirds,
+            Self::RuleOfThirds => Self::GoldenRatio,
+            Self::GoldenRatio => Self::CenterCross,
+            Self::CenterCross => Self::None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::None => "off",
+            Self::RuleOfThirds => "rule of thirds",
+            Self::GoldenRatio => "golden ratio",
+            Self::CenterCross => "center cross",
+        }
+    }
+
+    /// Relative positions (0..1) of the guide lines along each axis.
+    pub fn fractions(self) -> &'static [f32] {
+        match self {
+            Self::None => &[],
+            Self::RuleOfThirds => &[1.0 / 3.0, 2.0 / 3.0],
+            Self::GoldenRatio => &[0.381_966, 0.618_034],
+            Self::CenterCross => &[0.5],
+        }
+    }
+}
+
+/// Keys and mouse actions with their effect, in the order listed in the help window
+pub const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("Drag", "Draw a selection, or move the one under the cursor"),
+    ("Ctrl + Drag", "Draw an additional selection"),
+    ("Alt + Drag", "Draw from the center (Shift: a square)"),
+    ("Arrow keys", "Move all selections"),
+    ("Enter", "Crop the selections, save and go to the next image"),
+    ("Shift + Enter", "Apply the selections to the remaining images"),
+    ("Space", "Next image"),
+    ("C", "Toggle moving on to the next image after a crop"),
+    ("Backspace", "Previous image"),
+    ("Page Down / Page Up", "Jump 10 images forward / back"),
+    ("Home / End", "Go to the first / last image"),
+    ("D", "Mark as done for --skip-processed and go to the next image"),
+    ("Ctrl + D", "Duplicate the active selection"),
+    ("Delete", "Move the image to the trash (--mark-delete: mark it)"),
+    ("X", "Review the images marked for deletion"),
+    ("J", "Jump to the image this one duplicates"),
+    ("Shift + Delete", "Move that earlier duplicate (or the image side by side) to the trash"),
+    ("B", "Compare side by side with the duplicate or the previous image"),
+    ("R / Shift + R", "Rotate clockwise / counter-clockwise"),
+    ("W / Shift + W", "Stretch the active selection to the full width / height"),
+    ("Ctrl + A", "Select the full image"),
+    ("Ctrl + V", "Paste an image from the clipboard as the next image"),
+    ("Ctrl + C", "Copy the crop to the clipboard (Shift: the whole image)"),
+    ("[ / ]", "Rotate the active selection by 1° (Shift: 0.1°)"),
+    ("E", "Cycle rectangle, ellipse and polygon selections"),
+    ("T", "Straighten: drag along a line that should be level"),
+    ("A", "Suggest a selection trimming uniform borders"),
+    ("S", "Suggest a crop around the most detailed area"),
+    ("L", "Repeat the last crop"),
+    ("1-9", "Apply a selection preset, or sort into a folder with --cull"),
+    ("0-5", "Set the star rating with --rate"),
+    ("0-9", "Set the class of the selection with --annotate"),
+    ("K", "List the presets"),
+    ("F", "Toggle the filmstrip"),
+    ("H", "Toggle the histogram"),
+    ("I", "Toggle the cursor position and color readout"),
+    ("N", "Toggle the selection number editor"),
+    ("G", "Cycle composition guides"),
+    ("M", "Cycle the layout of multiple selections in one image"),
+    ("+ / -", "Raise / lower the quality of this image"),
+    ("Alt + + / -", "Grow / shrink the active selection on every side"),
+    ("Alt + S", "Give all selections the size of the active one"),
+    ("Alt + T / Alt + L", "Align all selections with the top / left of the active one"),
+    ("Alt + H / Alt + V", "Space the selections evenly across / down"),
+    ("P (hold)", "Preview the crop and its file size"),
+    ("O (hold)", "Show the original"),
+    ("Tab", "Hide or show the status, counter and hints"),
+    ("F11", "Toggle fullscreen"),
+    ("F3", "Toggle the load timings overlay"),
+    ("F5", "Retry failed saves"),
+    ("F1 / ?", "Show or hide this help"),
+    ("Esc", "Close this help, clear the selections or quit"),
+];
+
+/// Window listing `KEY_BINDINGS` and the keys of the config's `[move_keys]`,
+/// scrolling if it is taller than most of `rect`. Clears `open` when closed.
+pub fn show_help(ctx: &egui::Context, rect: Rect, open: &mut bool, move_keys: &[(egui::Key, PathBuf)]) {
+    let key_color = Color32::from_rgb(255, 210, 80);
+    let row = |ui: &mut egui::Ui, key: &str, action: &str| {
+        ui.label(egui::RichText::new(key).monospace().color(key_color));
+        ui.label(action);
+        ui.end_row();
+    };
+    egui::Window::new("Keyboard shortcuts")
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(rect.height() * 0.75).show(ui, |ui| {
+                egui::Grid::new("help").num_columns(2).striped(true).show(ui, |ui| {
+                    for &(key, action) in KEY_BINDINGS {
+                        row(ui, key, action);
+                    }
+                    for (key, folder) in move_keys {
+                        row(ui, key.name(), &format!("Move the image into {}", folder.display()));
+                    }
+                });
+            });
+        });
+}
+
+#[derive(Default)]
 pub struct KeyboardState {
     pub next_image: bool,
     pub prev_image: bool,
+    /// Images to jump forward (Page Down) or back (Page Up)
+    pub jump_by: isize,
+    pub first_image: bool,
+    pub last_image: bool,
     pub save_selection: bool,
+    pub batch_crop: bool,
     pub delete: bool,
+    pub mark_done: bool,
+    pub delete_duplicate: bool,
+    pub jump_to_duplicate: bool,
     pub escape: bool,
     pub move_up: bool,
     pub move_down: bool,
     pub move_left: bool,
     pub move_right: bool,
     pub preview: bool,
+    pub compare: bool,
+    pub quality_up: bool,
+    pub quality_down: bool,
     pub rotate_cw: bool,
     pub rotate_ccw: bool,
+    /// Degrees to rotate the active selection by with `[` and `]`
+    pub rotate_selection: f32,
+    /// Pixels to grow the active selection by on every side with Alt + `+` / `-`
+    pub grow_selection: f32,
+    /// Stretch the active selection to the full image width (W, Ctrl + A)
+    pub stretch_width: bool,
+    /// Stretch the active selection to the full image height (Shift + W, Ctrl + A)
+    pub stretch_height: bool,
+    /// Line up multiple selections after the active one (Alt + S/T/L/H/V)
+    pub arrange: Option<Arrangement>,
+    /// Copy the active selection (Ctrl + D)
+    pub duplicate_selection: bool,
+    pub cycle_guides: bool,
+    pub toggle_shape: bool,
+    pub cycle_layout: bool,
+    pub straighten: bool,
+    pub auto_trim: bool,
+    pub suggest_crop: bool,
+    pub toggle_filmstrip: bool,
+    pub toggle_histogram: bool,
+    pub toggle_cursor_readout: bool,
+    pub toggle_selection_editor: bool,
+    pub repeat_last_crop: bool,
+    pub toggle_presets: bool,
+    pub toggle_help: bool,
+    pub toggle_overlays: bool,
+    pub toggle_fullscreen: bool,
+    pub toggle_perf_hud: bool,
+    pub toggle_auto_advance: bool,
+    /// Open the review of the images marked with `--mark-delete`
+    pub review_marked: bool,
+    /// Show the previous image or the earlier near-duplicate next to the current one
+    pub toggle_side_by_side: bool,
+    pub retry_failed_saves: bool,
+    pub paste: bool,
+    /// Ctrl+C: copy the crop of the selections to the clipboard
+    pub copy_crop: bool,
+    /// Ctrl+Shift+C: copy the whole image to the clipboard
+    pub copy_image: bool,
+    /// Only set by the toolbar's Quit button
+    pub quit: bool,
+    /// Index of the `[move_keys]` binding whose key was pressed
+    pub move_to: Option<usize>,
+    /// Index of the preset whose number key was pressed
+    pub preset: Option<usize>,
+    pub zero: bool,
 }
 
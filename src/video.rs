@@ -0,0 +1,91 @@
+//! Frames of video files as images, grabbed with the `ffmpeg` and `ffprobe`
+//! programs. Videos are only taken into the queue by builds with the `video`
+//! feature; grabbed frames are written as PNG files next to the video and
+//! cropped like any other image.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use image::DynamicImage;
+
+use crate::external::run;
+
+/// Extensions of the videos taken into the queue with the `video` feature
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "mov", "webm", "avi", "m4v"];
+
+/// Whether `path` is a video whose frames can be grabbed in this build.
+pub fn is_video(path: &Path) -> bool {
+    cfg!(feature = "video")
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Length of the video at `path` in seconds.
+pub fn duration(path: &Path) -> io::Result<f64> {
+    let mut command = Command::new("ffprobe");
+    command
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path);
+    let output = run(&mut command, "ffmpeg")?;
+    String::from_utf8_lossy(&output)
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::other(format!("{} has no duration", path.display())))
+}
+
+/// The frame shown `seconds` into the video at `path`, encoded as PNG.
+pub fn frame_png(path: &Path, seconds: f64) -> io::Result<Vec<u8>> {
+    // Seeking before the input is fast and still exact with current ffmpeg versions
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-ss"])
+        .arg(format!("{:.3}", seconds.max(0.0)))
+        .arg("-i")
+        .arg(path)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"]);
+    let png = run(&mut command, "ffmpeg")?;
+    if png.is_empty() {
+        return Err(io::Error::other(format!("{} has no frame at {seconds:.3}s", path.display())));
+    }
+    Ok(png)
+}
+
+/// The frame shown `seconds` into the video at `path`.
+pub fn grab_frame(path: &Path, seconds: f64) -> image::ImageResult<DynamicImage> {
+    image::load_from_memory_with_format(&frame_png(path, seconds)?, image::ImageFormat::Png)
+}
+
+/// `seconds` as hours, minutes, seconds and milliseconds joined by `separator`,
+/// e.g. `00:01:23.456`.
+pub fn timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}{separator}{:02}{separator}{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Path of the frame grabbed `seconds` into `video`: `clip_00-01-23.456.png` next to it.
+pub fn frame_path(video: &Path, seconds: f64) -> PathBuf {
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    video.with_file_name(format!("{stem}_{}.png", timestamp(seconds, '-')))
+}
+
+/// Whether `path` is named like a frame grabbed from `video` by [`frame_path`].
+pub fn is_frame_of(video: &Path, path: &Path) -> bool {
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    path.parent() == video.parent()
+        && path.extension().is_some_and(|ext| ext == "png")
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(&format!("{stem}_")))
+}
@@ -0,0 +1,155 @@
+use eframe::egui;
+use imagecropper::annotations::{
+    class_name, parse_yolo, read_selections, write_annotations, yolo_clash, yolo_lines, AnnotationFormat, CocoDataset,
+    YOLO_CLASSES_FILE_NAME,
+};
+use imagecropper::selection::Selection;
+use std::fs;
+
+mod common;
+use common::{solid_image, with_temp_workdir};
+
+fn selection(min: (f32, f32), size: (f32, f32), class: u32) -> Selection {
+    Selection {
+        class,
+        ..Selection::new(egui::Rect::from_min_size(egui::pos2(min.0, min.1), egui::vec2(size.0, size.1)))
+    }
+}
+
+#[test]
+fn yolo_lines_are_normalized_centers_and_sizes() {
+    let lines = yolo_lines(
+        egui::vec2(200.0, 100.0),
+        &[selection((0.0, 0.0), (100.0, 50.0), 0), selection((150.0, 50.0), (50.0, 50.0), 3)],
+    );
+    assert_eq!(
+        lines,
+        "0 0.250000 0.250000 0.500000 0.500000\n3 0.875000 0.750000 0.250000 0.500000\n"
+    );
+}
+
+#[test]
+fn rotated_selections_are_exported_as_their_bounding_box() {
+    let mut upright = selection((80.0, 40.0), (40.0, 20.0), 0);
+    upright.angle = 90.0;
+    assert_eq!(
+        yolo_lines(egui::vec2(200.0, 100.0), &[upright]),
+        "0 0.500000 0.500000 0.100000 0.400000\n"
+    );
+}
+
+#[test]
+fn class_name_falls_back_to_number() {
+    let names = vec!["person".to_string()];
+    assert_eq!(class_name(0, &names), "person");
+    assert_eq!(class_name(4, &names), "4");
+}
+
+#[test]
+fn yolo_annotations_scale_preview_selections_to_the_file() {
+    with_temp_workdir(|cwd| {
+        let path = cwd.join("photo.png");
+        solid_image(400, 200, [0, 0, 0, 255]).save(&path).unwrap();
+
+        // Drawn on a half-size preview
+        let selections = [selection((50.0, 25.0), (100.0, 50.0), 1)];
+        let names = vec!["person".to_string(), "car".to_string()];
+        let output =
+            write_annotations(AnnotationFormat::Yolo, &path, egui::vec2(200.0, 100.0), &selections, &names).unwrap();
+
+        assert_eq!(output, cwd.join("photo.txt"));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "1 0.500000 0.500000 0.500000 0.500000\n");
+        assert_eq!(
+            fs::read_to_string(cwd.join(YOLO_CLASSES_FILE_NAME)).unwrap(),
+            "person\ncar\n"
+        );
+    });
+}
+
+#[test]
+fn yolo_annotations_refuse_images_sharing_a_name() {
+    with_temp_workdir(|cwd| {
+        let (jpg, png) = (cwd.join("photo.jpg"), cwd.join("photo.png"));
+        solid_image(40, 20, [0, 0, 0, 255]).save(&png).unwrap();
+        assert_eq!(yolo_clash(&png), None);
+        fs::write(&jpg, b"").unwrap();
+        assert_eq!(yolo_clash(&png), Some(jpg));
+
+        let selections = [selection((5.0, 5.0), (10.0, 10.0), 0)];
+        let result = write_annotations(AnnotationFormat::Yolo, &png, egui::vec2(40.0, 20.0), &selections, &[]);
+        assert!(result.is_err());
+        assert!(!cwd.join("photo.txt").exists());
+    });
+}
+
+#[test]
+fn coco_annotations_replace_entries_of_reannotated_images() {
+    with_temp_workdir(|cwd| {
+        let (a, b) = (cwd.join("a.png"), cwd.join("b.png"));
+        solid_image(100, 80, [0, 0, 0, 255]).save(&a).unwrap();
+        solid_image(100, 80, [0, 0, 0, 255]).save(&b).unwrap();
+        let size = egui::vec2(100.0, 80.0);
+        let names = vec!["person".to_string()];
+
+        write_annotations(AnnotationFormat::Coco, &a, size, &[selection((10.0, 10.0), (20.0, 30.0), 0)], &names)
+            .unwrap();
+        write_annotations(AnnotationFormat::Coco, &b, size, &[selection((0.0, 0.0), (5.0, 5.0), 2)], &names).unwrap();
+        let output = write_annotations(
+            AnnotationFormat::Coco,
+            &a,
+            size,
+            &[selection((1.0, 2.0), (3.0, 4.0), 0), selection((5.0, 5.0), (10.0, 10.0), 0)],
+            &names,
+        )
+        .unwrap();
+
+        let dataset: CocoDataset = serde_json::from_str(&fs::read_to_string(output).unwrap()).unwrap();
+        assert_eq!(dataset.images.len(), 2);
+        assert_eq!((dataset.images[0].file_name.as_str(), dataset.images[0].width), ("a.png", 100));
+        let boxes: Vec<_> = dataset
+            .annotations
+            .iter()
+            .filter(|annotation| annotation.image_id == dataset.images[0].id)
+            .map(|annotation| annotation.bbox)
+            .collect();
+        assert_eq!(boxes, vec![[1.0, 2.0, 3.0, 4.0], [5.0, 5.0, 10.0, 10.0]]);
+        let categories: Vec<_> = dataset.categories.iter().map(|c| (c.id, c.name.as_str())).collect();
+        assert_eq!(categories, vec![(0, "person"), (2, "2")]);
+    });
+}
+
+#[test]
+fn yolo_lines_parse_back_into_selections() {
+    let size = egui::vec2(200.0, 100.0);
+    let parsed = parse_yolo(&yolo_lines(size, &[selection((150.0, 50.0), (50.0, 50.0), 3)]), size).unwrap();
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].class, 3);
+    assert_eq!(parsed[0].to_u32_bounds(), Some((150, 50, 50, 50)));
+    assert!(parse_yolo("0 0.5 0.5\n", size).is_err());
+}
+
+#[test]
+fn read_selections_prefers_annotations_over_crops_sidecar() {
+    with_temp_workdir(|cwd| {
+        let path = cwd.join("photo.png");
+        solid_image(400, 200, [0, 0, 0, 255]).save(&path).unwrap();
+        let preview = egui::vec2(200.0, 100.0);
+        assert!(read_selections(None, &path, preview).unwrap().is_none());
+
+        fs::write(cwd.join("photo.crops.json"), "[[100, 50, 200, 100]]").unwrap();
+        let (file, selections) = read_selections(Some(AnnotationFormat::Coco), &path, preview).unwrap().unwrap();
+        assert_eq!(file, cwd.join("photo.crops.json"));
+        assert_eq!(selections[0].to_u32_bounds(), Some((50, 25, 100, 50)));
+
+        fs::write(cwd.join("photo.crops.json"), r#"["50%,0,25%,50%"]"#).unwrap();
+        let (_, selections) = read_selections(None, &path, preview).unwrap().unwrap();
+        assert_eq!(selections[0].to_u32_bounds(), Some((100, 0, 50, 50)));
+
+        write_annotations(AnnotationFormat::Coco, &path, preview, &[selection((0.0, 0.0), (20.0, 10.0), 1)], &[])
+            .unwrap();
+        let (file, selections) = read_selections(Some(AnnotationFormat::Coco), &path, preview).unwrap().unwrap();
+        assert_eq!(file, cwd.join("annotations.json"));
+        assert_eq!(selections[0].class, 1);
+        assert_eq!(selections[0].to_u32_bounds(), Some((0, 0, 20, 10)));
+    });
+}
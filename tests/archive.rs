@@ -0,0 +1,94 @@
+use imagecropper::archive::*;
+use imagecropper::fs_utils::{WorkDirs, ORIGINALS_DIR, TRASH_DIR};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+#[test]
+fn archive_format_follows_the_extension() {
+    assert_eq!(ArchiveFormat::of(Path::new("results.zip")).unwrap(), ArchiveFormat::Zip);
+    assert_eq!(ArchiveFormat::of(Path::new("results.TAR")).unwrap(), ArchiveFormat::Tar);
+    assert!(parse_archive_path("results.rar").is_err());
+    assert!(parse_archive_path("results").is_err());
+}
+
+#[test]
+fn archive_names_are_relative_to_the_innermost_root() {
+    let roots = [PathBuf::from("photos"), PathBuf::from("photos/2024")];
+    assert_eq!(
+        archive_name(Path::new("photos/2024/trip/a.avif"), &roots),
+        "trip/a.avif"
+    );
+    assert_eq!(archive_name(Path::new("photos/b.avif"), &roots), "b.avif");
+    assert_eq!(archive_name(Path::new("/elsewhere/c.avif"), &roots), "c.avif");
+}
+
+#[test]
+fn helper_folders_are_left_out() {
+    let dirs = WorkDirs {
+        trash: Some(PathBuf::from("bin")),
+        ..WorkDirs::default()
+    };
+    assert!(in_helper_dir(
+        &Path::new("photos").join(ORIGINALS_DIR).join("a.jpg"),
+        &dirs
+    ));
+    assert!(in_helper_dir(&Path::new("photos").join(TRASH_DIR).join("a.jpg"), &dirs));
+    assert!(in_helper_dir(Path::new("photos/bin/a.jpg"), &dirs));
+    assert!(!in_helper_dir(Path::new("photos/a.avif"), &dirs));
+    assert!(!in_helper_dir(Path::new("photos/bin.avif"), &dirs));
+}
+
+#[test]
+fn written_files_are_collected_in_zip_and_tar_archives() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().join("photos");
+    fs::create_dir_all(root.join("trip")).unwrap();
+    fs::create_dir_all(root.join(ORIGINALS_DIR)).unwrap();
+    let files = [
+        root.join("a.avif"),
+        root.join("trip/b.avif"),
+        root.join("trip/b_mask.png"),
+        root.join(ORIGINALS_DIR).join("a.jpg"),
+    ];
+    for file in &files {
+        fs::write(file, file.to_string_lossy().as_bytes()).unwrap();
+    }
+    let mut written = files.to_vec();
+    // Duplicates and files deleted since are skipped
+    written.push(root.join("a.avif"));
+    written.push(root.join("gone.avif"));
+    let roots = [root.clone()];
+    let expected = ["a.avif", "trip/b.avif", "trip/b_mask.png"];
+
+    let zip_path = tmp.path().join("results.zip");
+    assert_eq!(
+        write_archive(&zip_path, &written, &roots, &WorkDirs::default()).unwrap(),
+        3
+    );
+    let mut zip = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+    let mut names: Vec<_> = zip.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, expected);
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut zip.by_name("trip/b.avif").unwrap(), &mut content).unwrap();
+    assert_eq!(content, root.join("trip/b.avif").to_string_lossy());
+
+    let tar_path = tmp.path().join("results.tar");
+    assert_eq!(
+        write_archive(&tar_path, &written, &roots, &WorkDirs::default()).unwrap(),
+        3
+    );
+    let mut tar = tar::Archive::new(File::open(&tar_path).unwrap());
+    let names: Vec<_> = tar
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, expected);
+
+    // Nothing written, no archive
+    let empty = tmp.path().join("empty.zip");
+    assert!(write_archive(&empty, &[], &roots, &WorkDirs::default()).is_err());
+    assert!(!empty.exists());
+}
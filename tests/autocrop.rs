@@ -0,0 +1,43 @@
+use imagecropper::autocrop::*;
+
+mod common;
+use common::solid_image;
+
+#[test]
+fn parse_aspect_ratio_accepts_ratios_and_decimals() {
+    assert_eq!(parse_aspect_ratio("4:5").unwrap(), 0.8);
+    assert_eq!(parse_aspect_ratio("16/8").unwrap(), 2.0);
+    assert_eq!(parse_aspect_ratio(" 1.5 ").unwrap(), 1.5);
+    assert!(parse_aspect_ratio("1:0").is_err());
+    assert!(parse_aspect_ratio("wide").is_err());
+    assert!(parse_aspect_ratio("-2").is_err());
+}
+
+#[test]
+fn suggest_crops_centers_on_detailed_region() {
+    // Flat gray image with a checkerboard patch in the right half
+    let mut image = solid_image(400, 200, [128, 128, 128, 255]).to_rgba8();
+    for y in 60..140 {
+        for x in 280..360 {
+            let value = if (x / 4 + y / 4) % 2 == 0 { 0 } else { 255 };
+            image.put_pixel(x, y, image::Rgba([value, value, value, 255]));
+        }
+    }
+    let image = image::DynamicImage::ImageRgba8(image);
+
+    let crops = suggest_crops(&image, 1.0, 3);
+    assert!(!crops.is_empty() && crops.len() <= 3);
+    let (x, y, w, h) = crops[0];
+    assert_eq!(w, h);
+    assert!(x + w <= 400 && y + h <= 200);
+    // The best crop contains the detailed patch
+    assert!(x <= 280 && x + w >= 360);
+}
+
+#[test]
+fn center_crop_takes_largest_centered_region() {
+    assert_eq!(center_crop(400, 200, 1.0), (100, 0, 200, 200));
+    assert_eq!(center_crop(200, 400, 1.0), (0, 100, 200, 200));
+    assert_eq!(center_crop(300, 200, 1.5), (0, 0, 300, 200));
+    assert_eq!(center_crop(1000, 1000, 4.0 / 3.0), (0, 125, 1000, 750));
+}
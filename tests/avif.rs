@@ -0,0 +1,36 @@
+use imagecropper::avif::add_avif_metadata;
+use imagecropper::image_utils::{encode, EncodeSettings, OutputFormat};
+
+mod common;
+use common::solid_image;
+
+fn count(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack.windows(needle.len()).filter(|w| *w == needle).count()
+}
+
+#[test]
+fn add_avif_metadata_keeps_image_decodable() {
+    let image = solid_image(16, 8, [200, 100, 50, 255]);
+    let avif = encode(&image, &EncodeSettings::new(OutputFormat::Avif)).unwrap();
+    let exif: &[u8] = b"II*\0\x08\0\0\0exif-payload";
+    let icc: &[u8] = b"fake-icc-profile";
+
+    let out = add_avif_metadata(&avif, Some(exif), Some(icc)).unwrap();
+    assert!(out.ends_with(exif));
+    assert_eq!(count(&out, b"Exif\0"), 1);
+    assert_eq!(count(&out, b"cdsc"), 1);
+    assert_eq!(count(&out, b"colrprof"), 1);
+
+    let decoded = image::load_from_memory_with_format(&out, image::ImageFormat::Avif).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (16, 8));
+
+    // An existing profile is kept
+    let again = add_avif_metadata(&out, None, Some(&b"other-profile"[..])).unwrap();
+    assert_eq!(again, out);
+}
+
+#[test]
+fn add_avif_metadata_rejects_other_formats() {
+    assert!(add_avif_metadata(b"\xFF\xD8\xFF\xE0 not an avif", Some(&b"II*\0"[..]), None).is_none());
+    assert!(add_avif_metadata(&[], Some(&b"II*\0"[..]), None).is_none());
+}
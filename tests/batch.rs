@@ -0,0 +1,42 @@
+use imagecropper::app::batch::{convert_all, is_in_format, BatchOptions};
+use imagecropper::fs_utils::OutputLocation;
+use imagecropper::image_utils::{EncodeSettings, OutputFormat};
+use imagecropper::pipeline::SaverOptions;
+use std::path::Path;
+
+mod common;
+use common::{solid_image, with_temp_workdir, write_image};
+
+#[test]
+fn is_in_format_compares_extensions_case_insensitively() {
+    assert!(is_in_format(Path::new("a/photo.JPEG"), OutputFormat::Jpg));
+    assert!(is_in_format(Path::new("photo.tif"), OutputFormat::Tiff));
+    assert!(!is_in_format(Path::new("photo.png"), OutputFormat::Avif));
+    assert!(!is_in_format(Path::new("photo"), OutputFormat::Avif));
+}
+
+#[test]
+fn convert_all_reencodes_images_in_other_formats() {
+    with_temp_workdir(|cwd| {
+        let png = cwd.join("a.png");
+        let jpg = cwd.join("b.jpg");
+        write_image(&png, &solid_image(8, 8, [1, 2, 3, 255]));
+        // Already in the output format, so never decoded
+        std::fs::write(&jpg, b"not an image").unwrap();
+
+        let failed = convert_all(
+            &[png.clone(), jpg.clone()],
+            BatchOptions {
+                encode: EncodeSettings::new(OutputFormat::Jpg),
+                parallel: 2,
+                dry_run: false,
+                saver: SaverOptions::default(),
+                output: OutputLocation::default(),
+            },
+        );
+        assert_eq!(failed, 0);
+        assert!(!png.exists());
+        assert!(cwd.join("a.jpg").exists());
+        assert_eq!(std::fs::read(&jpg).unwrap(), b"not an image");
+    });
+}
@@ -1,12 +1,10 @@
 use imagecropper::app::canvas::Canvas;
 use imagecropper::selection::Selection;
-use imagecropper::ui::{ImageMetrics, KeyboardState, ARROW_MOVE_STEP};
+use imagecropper::ui::{ArrowSpeed, ImageMetrics, KeyboardState};
 use eframe::egui;
 
 fn selection_from_coords(min: (f32, f32), max: (f32, f32)) -> Selection {
-    Selection {
-        rect: egui::Rect::from_min_max(egui::pos2(min.0, min.1), egui::pos2(max.0, max.1)),
-    }
+    Selection::new(egui::Rect::from_min_max(egui::pos2(min.0, min.1), egui::pos2(max.0, max.1)))
 }
 
 #[test]
@@ -14,23 +12,43 @@ fn handle_arrow_movement_translates_selection() {
     let mut canvas = Canvas::new();
     canvas.selections.push(selection_from_coords((10.0, 10.0), (20.0, 20.0)));
     let keys = KeyboardState {
-        next_image: false,
-        prev_image: false,
-        save_selection: false,
-        delete: false,
-        escape: false,
-        move_up: false,
-        move_down: false,
-        move_left: false,
         move_right: true,
-        preview: false,
-        rotate_cw: false,
-        rotate_ccw: false,
+        ..Default::default()
     };
-    canvas.handle_arrow_movement(&keys, egui::vec2(100.0, 100.0));
+    canvas.handle_arrow_movement(&keys, egui::vec2(100.0, 100.0), 0.02);
+    let step = ArrowSpeed::default().distance(0.0, 0.02);
     let selection = &canvas.selections[0];
-    assert_eq!(selection.rect.min.x, 10.0 + ARROW_MOVE_STEP);
-    assert_eq!(selection.rect.max.x, 20.0 + ARROW_MOVE_STEP);
+    assert_eq!(selection.rect.min.x, 10.0 + step);
+    assert_eq!(selection.rect.max.x, 20.0 + step);
+}
+
+#[test]
+fn arrow_movement_is_time_based_and_accelerates() {
+    let keys = KeyboardState {
+        move_right: true,
+        ..Default::default()
+    };
+    let bounds = egui::vec2(10_000.0, 100.0);
+
+    // Same held time at different frame rates covers the same distance
+    let mut fast = Canvas::new();
+    fast.selections.push(selection_from_coords((0.0, 0.0), (10.0, 10.0)));
+    for _ in 0..4 {
+        fast.handle_arrow_movement(&keys, bounds, 0.01);
+    }
+    let mut slow = Canvas::new();
+    slow.selections.push(selection_from_coords((0.0, 0.0), (10.0, 10.0)));
+    for _ in 0..2 {
+        slow.handle_arrow_movement(&keys, bounds, 0.02);
+    }
+    let fast_x = fast.selections[0].rect.min.x;
+    let slow_x = slow.selections[0].rect.min.x;
+    assert!((fast_x - slow_x).abs() < 0.5, "{fast_x} vs {slow_x}");
+
+    // Frames later in a long press move further than the first one
+    let speed = ArrowSpeed::default();
+    assert!(speed.distance(1.0, 0.02) > speed.distance(0.0, 0.02));
+    assert!(speed.distance(100.0, 0.02) <= speed.max_speed * 0.02 + f32::EPSILON);
 }
 
 #[test]
@@ -45,3 +63,77 @@ fn begin_selection_outside_image_clamps_to_image_border() {
     assert_eq!(selection.rect.min.x, 0.0);
     assert_eq!(selection.rect.max.x, 1.0);
 }
+
+#[test]
+fn dragging_inside_selection_moves_it_without_resizing() {
+    let mut canvas = Canvas::new();
+    canvas.selections.push(selection_from_coords((10.0, 10.0), (40.0, 30.0)));
+    let canvas_rect = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(100.0, 100.0));
+    let image_size = egui::vec2(100.0, 100.0);
+    let metrics = ImageMetrics::new(canvas_rect, image_size);
+
+    assert!(!canvas.begin_move(egui::pos2(80.0, 80.0), &metrics));
+    assert!(canvas.begin_move(egui::pos2(25.0, 20.0), &metrics));
+    canvas.update_move(egui::pos2(35.0, 25.0), &metrics, image_size);
+    assert_eq!(canvas.selections[0].rect.min, egui::pos2(20.0, 15.0));
+
+    // Moving past the border stops at the edge and keeps the size
+    canvas.update_move(egui::pos2(200.0, 20.0), &metrics, image_size);
+    let rect = canvas.selections[0].rect;
+    assert_eq!(rect.max.x, 100.0);
+    assert_eq!(rect.width(), 30.0);
+    assert_eq!(rect.height(), 20.0);
+}
+
+#[test]
+fn active_index_follows_last_interaction() {
+    let mut canvas = Canvas::new();
+    assert_eq!(canvas.active_index(), None);
+    canvas.selections.push(selection_from_coords((0.0, 0.0), (40.0, 40.0)));
+    canvas.selections.push(selection_from_coords((50.0, 50.0), (90.0, 90.0)));
+    assert_eq!(canvas.active_index(), Some(1));
+
+    let canvas_rect = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(100.0, 100.0));
+    let metrics = ImageMetrics::new(canvas_rect, egui::vec2(100.0, 100.0));
+    assert!(canvas.begin_move(egui::pos2(20.0, 20.0), &metrics));
+    assert_eq!(canvas.active_index(), Some(0));
+
+    canvas.selections.truncate(0);
+    assert_eq!(canvas.active_index(), None);
+}
+
+#[test]
+fn pinch_zooms_around_the_touch_center() {
+    let mut canvas = Canvas::new();
+    let rect = egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(200.0, 100.0));
+    let image_size = egui::vec2(400.0, 200.0);
+    let center = egui::pos2(50.0, 25.0);
+    let under_center = canvas.metrics(rect, image_size).screen_to_image(center);
+
+    canvas.pinch(rect, image_size, 2.0, center, egui::Vec2::ZERO);
+    assert_eq!(canvas.zoom, 2.0);
+    let metrics = canvas.metrics(rect, image_size);
+    assert_eq!(metrics.scale, 1.0);
+    assert!((metrics.screen_to_image(center) - under_center).length() < 1e-3);
+
+    // Zooming out past the fitted view resets it
+    canvas.pinch(rect, image_size, 0.25, center, egui::vec2(10.0, 0.0));
+    assert_eq!((canvas.zoom, canvas.pan), (1.0, egui::Vec2::ZERO));
+}
+
+#[test]
+fn duplicate_active_offsets_the_copy_and_makes_it_active() {
+    let bounds = egui::vec2(100.0, 100.0);
+    let mut canvas = Canvas::new();
+    assert!(canvas.duplicate_active(bounds).is_none());
+
+    canvas.selections.push(selection_from_coords((10.0, 10.0), (30.0, 20.0)));
+    assert_eq!(canvas.duplicate_active(bounds), Some(egui::vec2(20.0, 10.0)));
+    assert_eq!(canvas.active_index(), Some(1));
+    assert_eq!(canvas.selections[1].rect, egui::Rect::from_min_max(egui::pos2(30.0, 30.0), egui::pos2(50.0, 40.0)));
+
+    // At the bottom right corner the copy goes back up and left instead
+    canvas.selections = vec![selection_from_coords((80.0, 90.0), (100.0, 100.0))];
+    canvas.duplicate_active(bounds);
+    assert_eq!(canvas.selections[1].rect.min, egui::pos2(60.0, 70.0));
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "catalog")]
+
+use imagecropper::catalog::Catalog;
+use imagecropper::journal::{Entry, Event};
+use std::path::PathBuf;
+
+fn entry(time: &str, path: &str, event: Event) -> Entry {
+    Entry {
+        time: time.to_string(),
+        path: PathBuf::from(path),
+        event,
+    }
+}
+
+#[test]
+fn report_counts_latest_status_and_sizes_below_dir() {
+    let catalog = Catalog::open_in_memory().unwrap();
+    let crop = Event::Crop {
+        rects: vec![[0, 0, 10, 10]],
+        output: PathBuf::from("/photos/a.avif"),
+    };
+    catalog.record(&entry("2024-01-01T10:00:00Z", "/photos/a.jpg", crop)).unwrap();
+    let saved = Event::Saved {
+        output: PathBuf::from("/photos/a.avif"),
+        original_bytes: Some(1000),
+        new_bytes: Some(400),
+        saved_bytes: Some(600),
+        backup: None,
+    };
+    catalog.record(&entry("2024-01-01T10:00:05Z", "/photos/a.jpg", saved)).unwrap();
+    catalog.record(&entry("2024-01-02T09:00:00Z", "/photos/sub/b.jpg", Event::Skip)).unwrap();
+    catalog.record(&entry("2024-01-03T09:00:00Z", "/photos/sub/b.jpg", Event::Done)).unwrap();
+    catalog.record(&entry("2024-01-04T09:00:00Z", "/other/c.jpg", Event::Skip)).unwrap();
+
+    let report = catalog.report(std::path::Path::new("/photos")).unwrap();
+    assert_eq!(report.files, 2);
+    assert_eq!(report.by_status.get("cropped"), Some(&1));
+    assert_eq!(report.by_status.get("done"), Some(&1));
+    assert_eq!(report.by_status.get("skipped"), None);
+    assert_eq!((report.saved_files, report.original_bytes, report.new_bytes), (1, 1000, 400));
+    assert_eq!(report.first.as_deref(), Some("2024-01-01T10:00:00Z"));
+    assert_eq!(report.last.as_deref(), Some("2024-01-03T09:00:00Z"));
+}
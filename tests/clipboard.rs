@@ -0,0 +1,15 @@
+use imagecropper::clipboard::pasted_path;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn pasted_images_are_named_after_the_time() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_714_566_600);
+    let dir = Path::new("shots");
+    assert_eq!(pasted_path(dir, time, |_| false), dir.join("pasted-2024-05-01T12-30-00.png"));
+
+    let first = dir.join("pasted-2024-05-01T12-30-00.png");
+    let second = dir.join("pasted-2024-05-01T12-30-00-2.png");
+    let taken = |path: &Path| path == first || path == second;
+    assert_eq!(pasted_path(dir, time, taken), dir.join("pasted-2024-05-01T12-30-00-3.png"));
+}
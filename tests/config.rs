@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+use imagecropper::config::{Config, FormatConfig, Preset};
+use imagecropper::image_utils::{CombineLayout, EncodeSettings, OutputFormat, TiffCompression};
+
+#[test]
+fn parse_reads_presets_and_defaults_missing_keys() {
+    let config = Config::parse(
+        r#"
+        [[presets]]
+        name = "Instagram 4:5"
+        ratio = "4:5"
+
+        [[presets]]
+        name = "Thumb"
+        width = 1280
+        height = 720
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.presets,
+        vec![Preset::ratio("Instagram 4:5", "4:5"), Preset::size("Thumb", 1280, 720)]
+    );
+
+    assert_eq!(Config::parse("").unwrap(), Config::default());
+
+    let config = Config::parse("[overlays]\nhints = false\n").unwrap();
+    assert!(!config.overlays.hints && config.overlays.status);
+    assert!(Config::parse("[overlays]\nhint = false\n").is_err());
+
+    assert_eq!(config.culling.folders.len(), 3);
+    assert!(Config::parse("[culling]\nfolders = []\n").is_err());
+
+    let config = Config::parse("[combine]\nlayout = \"grid\"\n").unwrap();
+    assert_eq!(config.combine.layout, CombineLayout::Grid);
+    assert!(Config::parse("[combine]\nlayout = \"spiral\"\n").is_err());
+    let config = Config::parse("[combine]\ngap = 8\nbackground = \"white\"\n").unwrap();
+    assert_eq!((config.combine.gap, config.combine.background.as_str()), (8, "white"));
+    assert!(Config::parse("[combine]\nseparator_color = \"#12345\"\n").is_err());
+    assert_eq!(config.redact.color, "black");
+    assert!(Config::parse("[redact]\ncolor = \"#ffcc00\"\n").is_ok());
+    assert!(Config::parse("[redact]\ncolor = \"pink\"\n").is_err());
+
+    let config = Config::parse("[arrow_keys]\nspeed = 50.0\n").unwrap();
+    assert_eq!(config.arrow_keys.speed, 50.0);
+    assert_eq!(config.arrow_keys.max_speed, Config::default().arrow_keys.max_speed);
+}
+
+#[test]
+fn parse_rejects_incomplete_presets() {
+    assert!(Config::parse("[[presets]]\nname = \"Broken\"\nwidth = 100\n").is_err());
+    assert!(Config::parse("[[presets]]\nname = \"Broken\"\nratio = \"4:0\"\n").is_err());
+}
+
+#[test]
+fn preset_selection_is_centered() {
+    let image = egui::vec2(1000.0, 500.0);
+
+    let square = Preset::ratio("Square", "1:1").selection(image).unwrap();
+    assert_eq!(square.rect, egui::Rect::from_min_size(egui::pos2(250.0, 0.0), egui::vec2(500.0, 500.0)));
+
+    let small = Preset::size("Small", 200, 100).selection(image).unwrap();
+    assert_eq!(small.rect, egui::Rect::from_min_size(egui::pos2(400.0, 200.0), egui::vec2(200.0, 100.0)));
+
+    // Too large: scaled down to fit, keeping 2:1
+    let large = Preset::size("Large", 2000, 1000).selection(image).unwrap();
+    assert_eq!(large.rect, egui::Rect::from_min_size(egui::pos2(0.0, 0.0), egui::vec2(1000.0, 500.0)));
+}
+
+#[test]
+fn encode_settings_per_format() {
+    let config = Config::parse(
+        "[encode.avif]\nquality = 55\nspeed = 6\n\n[encode.jpg]\nquality = 82\nprogressive = true\n",
+    )
+    .unwrap();
+
+    let avif = config.encode.settings(OutputFormat::Avif, &FormatConfig::default());
+    assert_eq!((avif.quality, avif.avif_speed), (55, 6));
+    let jpg = config.encode.settings(OutputFormat::Jpg, &FormatConfig::default());
+    assert_eq!((jpg.quality, jpg.progressive), (82, true));
+    assert_eq!(
+        config.encode.settings(OutputFormat::Png, &FormatConfig::default()),
+        EncodeSettings::new(OutputFormat::Png)
+    );
+
+    // Command-line values win over the config
+    let overrides = FormatConfig {
+        quality: Some(40),
+        ..Default::default()
+    };
+    assert_eq!(config.encode.settings(OutputFormat::Jpg, &overrides).quality, 40);
+}
+
+#[test]
+fn encode_settings_reject_misplaced_or_out_of_range_keys() {
+    assert!(Config::parse("[encode.png]\nquality = 80\n").is_err());
+    assert!(Config::parse("[encode.avif]\nspeed = 11\n").is_err());
+    assert!(Config::parse("[encode.jxl]\neffort = 0\n").is_err());
+    assert!(Config::parse("[encode.jpg]\nqualty = 80\n").is_err());
+    assert!(Config::parse("[encode.png]\ncompression = \"best\"\n").is_ok());
+    assert!(Config::parse("[encode.png]\ncompression = \"lzw\"\n").is_err());
+    assert!(Config::parse("[encode.jpg]\ncompression = \"lzw\"\n").is_err());
+
+    let config = Config::parse("[encode.tiff]\ncompression = \"deflate\"\n").unwrap();
+    let tiff = config.encode.settings(OutputFormat::Tiff, &FormatConfig::default());
+    assert_eq!(tiff.tiff_compression, TiffCompression::Deflate);
+}
+
+#[test]
+fn move_keys_bind_free_function_keys_to_folders() {
+    let config = Config::parse("[move_keys]\nF6 = \"sorted/landscapes\"\nF7 = \"/tmp/portraits\"\n").unwrap();
+    assert_eq!(
+        config.move_keys.bindings().unwrap(),
+        vec![
+            (egui::Key::F6, PathBuf::from("sorted/landscapes")),
+            (egui::Key::F7, PathBuf::from("/tmp/portraits")),
+        ]
+    );
+    assert!(Config::default().move_keys.bindings().unwrap().is_empty());
+
+    // F5 retries failed saves, letters are taken by other shortcuts
+    assert!(Config::parse("[move_keys]\nF5 = \"sorted\"\n").is_err());
+    assert!(Config::parse("[move_keys]\nQ = \"sorted\"\n").is_err());
+    assert!(Config::parse("[move_keys]\nF99 = \"sorted\"\n").is_err());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::solid_image;
+use image::GenericImageView;
+use imagecropper::contact_sheet::{contact_sheet, labeled_thumbnail, THUMBNAIL_SIZE};
+use tempfile::tempdir;
+
+#[test]
+fn thumbnails_are_captioned_with_the_file_name() {
+    let image = solid_image(1600, 800, [0, 128, 255, 255]);
+    let labeled = labeled_thumbnail(&image, "landscape.avif");
+    assert_eq!(labeled.width(), THUMBNAIL_SIZE);
+    assert!(labeled.height() > THUMBNAIL_SIZE / 2);
+    assert_eq!(labeled.get_pixel(10, 10).0, [0, 128, 255, 255]);
+
+    // The caption strip is white with dark text
+    let caption = (THUMBNAIL_SIZE / 2..labeled.height()).flat_map(|y| (0..labeled.width()).map(move |x| (x, y)));
+    let pixels: Vec<_> = caption.map(|(x, y)| labeled.get_pixel(x, y).0).collect();
+    assert!(pixels.contains(&[255, 255, 255, 255]));
+    assert!(pixels.iter().any(|pixel| pixel[0] < 64));
+
+    // Narrow images still leave room for the name
+    let narrow = labeled_thumbnail(&solid_image(10, 400, [0, 0, 0, 255]), "strip.png");
+    assert_eq!(narrow.width(), THUMBNAIL_SIZE / 2);
+}
+
+#[test]
+fn contact_sheet_skips_unreadable_files() {
+    let tmp = tempdir().unwrap();
+    let first = tmp.path().join("a.png");
+    let second = tmp.path().join("b.png");
+    solid_image(400, 300, [255, 0, 0, 255]).save(&first).unwrap();
+    solid_image(300, 400, [0, 255, 0, 255]).save(&second).unwrap();
+    let broken = tmp.path().join("broken.png");
+    std::fs::write(&broken, b"not an image").unwrap();
+
+    let sheet = contact_sheet(&[first.clone(), second, broken.clone()]).unwrap();
+    let single = contact_sheet(&[first]).unwrap();
+    assert!(sheet.width() > single.width());
+    assert_eq!(sheet.get_pixel(0, 0).0, [255, 255, 255, 255]);
+
+    assert!(contact_sheet(&[broken]).is_err());
+    assert!(contact_sheet(&[]).is_err());
+}
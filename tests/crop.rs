@@ -0,0 +1,46 @@
+use eframe::egui;
+use imagecropper::crop::{crop_file, parse_crop_spec, CropSpec, Length};
+use imagecropper::selection::Selection;
+
+mod common;
+use common::{solid_image, with_temp_workdir};
+
+#[test]
+fn crop_file_scales_selection_to_full_resolution() {
+    with_temp_workdir(|cwd| {
+        let path = cwd.join("large.png");
+        solid_image(400, 200, [10, 20, 30, 255]).save(&path).unwrap();
+
+        // Drawn on a half-size preview of the image
+        let selection = Selection::new(egui::Rect::from_min_size(egui::pos2(10.0, 10.0), egui::vec2(50.0, 40.0)));
+        let cropped = crop_file(&path, std::slice::from_ref(&selection), Some(egui::vec2(200.0, 100.0))).unwrap();
+        assert_eq!((cropped.image.width(), cropped.image.height()), (100, 80));
+
+        // Without a reference size the selection is taken as pixels
+        let cropped = crop_file(&path, &[selection], None).unwrap();
+        assert_eq!((cropped.image.width(), cropped.image.height()), (50, 40));
+    });
+}
+
+#[test]
+fn crop_spec_resolves_percentages_per_image() {
+    let spec = parse_crop_spec("10%, 5 ,80%,50.5%").unwrap();
+    assert_eq!(spec.x, Length::Percent(10.0));
+    assert_eq!(spec.y, Length::Pixels(5));
+    assert!(spec.is_relative());
+    assert_eq!(spec.resolve(1000, 200), [100, 5, 800, 101]);
+    assert_eq!(spec.resolve(500, 100), [50, 5, 400, 51]);
+    assert_eq!(spec.to_string(), "10%,5,80%,50.5%");
+
+    assert!(!CropSpec::from([1, 2, 3, 4]).is_relative());
+    assert!(parse_crop_spec("0,0,0%,10").is_err());
+    assert!(parse_crop_spec("0,0,150%,10").is_err());
+    assert!(parse_crop_spec("0,0,10").is_err());
+
+    let json = serde_json::to_string(&spec).unwrap();
+    assert_eq!(json, "\"10%,5,80%,50.5%\"");
+    assert_eq!(serde_json::from_str::<CropSpec>(&json).unwrap(), spec);
+    let specs: Vec<CropSpec> = serde_json::from_str(r#"["10%,0,50%,50%", [10, 20.4, 30, 40]]"#).unwrap();
+    assert_eq!(specs[1], CropSpec::from([10, 20, 30, 40]));
+    assert!(serde_json::from_str::<CropSpec>("[10, 20, 0, 40]").is_err());
+}
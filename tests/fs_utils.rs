@@ -1,7 +1,7 @@
 use imagecropper::fs_utils::*;
 use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 
 mod common;
@@ -15,7 +15,7 @@ fn collect_images_includes_supported_extensions() {
     for name in supported {
         fs::write(root.join(name), []).unwrap();
     }
-    let unsupported = ["doc.txt", "movie.mp4", "README"]; // should be ignored
+    let unsupported = ["doc.txt", "movie.mp4", "README"]; // should be ignored (videos without the feature)
     for name in unsupported {
         fs::write(root.join(name), []).unwrap();
     }
@@ -24,6 +24,10 @@ fn collect_images_includes_supported_extensions() {
     files.sort();
 
     let mut expected: Vec<_> = supported.iter().map(|n| root.join(n)).collect();
+    // Videos are taken into the queue by builds with the video feature
+    if cfg!(feature = "video") {
+        expected.push(root.join("movie.mp4"));
+    }
     expected.sort();
     assert_eq!(files, expected);
 }
@@ -47,6 +51,30 @@ fn collect_images_respects_recursive_flag() {
     assert_eq!(rec, vec![root.join("subdir/image.png")]);
 }
 
+#[test]
+fn collect_images_skips_helper_folders() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("album").join(ORIGINALS_DIR)).unwrap();
+    fs::create_dir(root.join(TRASH_DIR)).unwrap();
+    fs::write(root.join("album/image.png"), []).unwrap();
+    fs::write(root.join("album").join(ORIGINALS_DIR).join("image.png"), []).unwrap();
+    fs::write(root.join(TRASH_DIR).join("deleted.png"), []).unwrap();
+
+    let files = collect_images(&[root.to_path_buf()], true).unwrap();
+    assert_eq!(files, vec![root.join("album/image.png")]);
+}
+
+#[test]
+fn write_atomically_replaces_the_file_without_leftovers() {
+    let tmp = tempdir().unwrap();
+    let file = tmp.path().join("photo.xmp");
+    fs::write(&file, "old").unwrap();
+    write_atomically(&file, "new").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+    assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+}
+
 #[test]
 fn collect_images_errors_for_missing_directory() {
     let missing = Path::new("/does/not/exist");
@@ -86,6 +114,48 @@ fn move_with_unique_name_avoids_overwrites() {
         .any(|p| p.file_name().unwrap().to_string_lossy().starts_with("image-")));
 }
 
+#[test]
+fn move_file_durable_replaces_destination_without_leftovers() {
+    let tmp = tempdir().unwrap();
+    let source = tmp.path().join("tmp").join("image.png");
+    fs::create_dir(source.parent().unwrap()).unwrap();
+    fs::write(&source, b"new").unwrap();
+    let destination = tmp.path().join("image.png");
+    fs::write(&destination, b"old").unwrap();
+
+    move_file_durable(&source, &destination).unwrap();
+
+    assert_eq!(fs::read(&destination).unwrap(), b"new");
+    assert!(!source.exists());
+    let names: Vec<_> = fs::read_dir(tmp.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2, "no partial copy is left behind: {names:?}");
+    assert!(move_file_durable(&source, &destination).is_err());
+}
+
+#[test]
+fn sort_into_folder_moves_image_and_sidecar_below_its_directory() {
+    let tmp = tempdir().unwrap();
+    let image = tmp.path().join("shot.jpg");
+    fs::write(&image, b"jpeg").unwrap();
+    fs::write(tmp.path().join("shot.jpg.xmp"), b"xmp").unwrap();
+
+    let moved = sort_into_folder(&image, Path::new("keep")).unwrap();
+    assert_eq!(moved, tmp.path().join("keep").join("shot.jpg"));
+    assert!(!image.exists());
+    assert!(tmp.path().join("keep").join("shot.jpg.xmp").exists());
+
+    // The sidecar of a RAW+JPEG pair is named after the shared stem
+    let absolute = tmp.path().join("elsewhere");
+    fs::write(&image, b"jpeg").unwrap();
+    fs::write(tmp.path().join("shot.xmp"), b"xmp").unwrap();
+    assert_eq!(sort_into_folder(&image, &absolute).unwrap(), absolute.join("shot.jpg"));
+    assert!(absolute.join("shot.xmp").exists());
+    assert!(!tmp.path().join("shot.xmp").exists());
+}
+
 #[test]
 fn split_name_handles_extensions_and_plain_names() {
     let (stem, ext) = split_name(OsStr::new("photo.avif"));
@@ -120,6 +190,91 @@ fn backup_original_moves_file_to_originals_dir() {
     });
 }
 
+#[test]
+fn trash_file_folder_mode_moves_into_trash_dir() {
+    with_temp_workdir(|cwd| {
+        let source = cwd.join("bad.png");
+        fs::write(&source, b"data").unwrap();
+        trash_file(&source, TrashMode::Folder, &WorkDirs::default()).unwrap();
+        assert!(!source.exists());
+        assert!(cwd.join(TRASH_DIR).join("bad.png").exists());
+    });
+}
+
+#[test]
+fn sidecars_are_found_renamed_and_trashed_with_their_image() {
+    with_temp_workdir(|cwd| {
+        let image = cwd.join("raw.jpg");
+        fs::write(&image, b"data").unwrap();
+        fs::write(cwd.join("raw.jpg.xmp"), b"darktable").unwrap();
+        fs::write(cwd.join("raw.xmp"), b"lightroom").unwrap();
+        fs::write(cwd.join("other.xmp"), b"unrelated").unwrap();
+
+        let sidecars = find_sidecars(&image);
+        assert_eq!(sidecars, vec![cwd.join("raw.jpg.xmp"), cwd.join("raw.xmp")]);
+        let output = cwd.join("raw.avif");
+        assert_eq!(sidecar_destination(&sidecars[0], &image, &output), cwd.join("raw.avif.xmp"));
+        assert_eq!(sidecar_destination(&sidecars[1], &image, &output), cwd.join("raw.xmp"));
+
+        trash_file(&image, TrashMode::Folder, &WorkDirs::default()).unwrap();
+        assert!(cwd.join(TRASH_DIR).join("raw.jpg.xmp").exists());
+        assert!(cwd.join(TRASH_DIR).join("raw.xmp").exists());
+        assert!(!cwd.join("raw.jpg.xmp").exists());
+        assert!(cwd.join("other.xmp").exists());
+    });
+}
+
+#[test]
+fn work_dirs_resolve_overrides_against_workdir_or_image_dir() {
+    let images = tempdir().unwrap();
+    let scratch = tempdir().unwrap();
+    let image = images.path().join("photo.png");
+
+    let default = WorkDirs::default();
+    assert_eq!(default.trash_dir(&image).unwrap(), images.path().join(TRASH_DIR));
+
+    let relative = WorkDirs {
+        originals: Some("backup".into()),
+        ..Default::default()
+    };
+    assert_eq!(relative.originals_dir(&image).unwrap(), images.path().join("backup"));
+
+    let shared = WorkDirs {
+        workdir: Some(scratch.path().to_path_buf()),
+        temp: Some(scratch.path().join("tmp-abs")),
+        ..Default::default()
+    };
+    assert_eq!(shared.trash_dir(&image).unwrap(), scratch.path().join(TRASH_DIR));
+    assert_eq!(shared.temp_dir(&image).unwrap(), scratch.path().join("tmp-abs"));
+    assert!(scratch.path().join(TRASH_DIR).is_dir());
+}
+
+#[test]
+fn find_helper_dirs_reports_sizes_and_purge_respects_age() {
+    let tmp = tempdir().unwrap();
+    let nested = tmp.path().join("album");
+    fs::create_dir_all(nested.join(TRASH_DIR)).unwrap();
+    fs::create_dir_all(tmp.path().join(ORIGINALS_DIR)).unwrap();
+    fs::write(nested.join(TRASH_DIR).join("a.png"), [0u8; 10]).unwrap();
+    fs::write(nested.join(TRASH_DIR).join("b.png"), [0u8; 5]).unwrap();
+    fs::write(tmp.path().join(ORIGINALS_DIR).join("c.jpg"), [0u8; 7]).unwrap();
+    fs::write(nested.join("keep.png"), [0u8; 3]).unwrap();
+
+    let found = find_helper_dirs(tmp.path()).unwrap();
+    assert_eq!(found.len(), 2);
+    let trash = found.iter().find(|usage| usage.path.ends_with(TRASH_DIR)).unwrap();
+    assert_eq!((trash.files, trash.bytes), (2, 15));
+
+    // Fresh files are younger than a day
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    assert_eq!(purge_helper_dir(&trash.path, day, false).unwrap(), (0, 0));
+    assert_eq!(purge_helper_dir(&trash.path, std::time::Duration::ZERO, true).unwrap(), (2, 15));
+    assert!(nested.join(TRASH_DIR).join("a.png").exists());
+    assert_eq!(purge_helper_dir(&trash.path, std::time::Duration::ZERO, false).unwrap(), (2, 15));
+    assert!(!nested.join(TRASH_DIR).join("a.png").exists());
+    assert!(nested.join("keep.png").exists());
+}
+
 #[test]
 fn collect_images_handles_multiple_paths_and_mixed_inputs() {
     let tmp = tempdir().unwrap();
@@ -163,6 +318,31 @@ fn collect_images_handles_multiple_paths_and_mixed_inputs() {
     assert_eq!(files, vec![dir1.join("img1.png"), img3.clone()]);
 }
 
+#[test]
+fn collect_images_merges_overlapping_paths_into_one_queue() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("a.png"), []).unwrap();
+    fs::write(root.join("b.jpg"), []).unwrap();
+
+    let files = collect_images(
+        &[root.join("b.jpg"), root.to_path_buf(), root.join("b.jpg")],
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0], root.join("b.jpg"));
+    assert!(files.contains(&root.join("a.png")));
+}
+
+#[test]
+fn read_file_list_skips_blank_lines() {
+    let input = "a.jpg\r\n\n  \ndir/b c.png\n";
+    let paths = read_file_list(input.as_bytes()).unwrap();
+    assert_eq!(paths, vec![PathBuf::from("a.jpg"), PathBuf::from("dir/b c.png")]);
+}
+
 #[test]
 fn collect_images_with_glob_filters_applies_blacklist_and_whitelist_precedence() {
     let tmp = tempdir().unwrap();
@@ -201,6 +381,25 @@ fn collect_images_with_regex_blacklist_excludes_matching_paths() {
     assert_eq!(files, vec![root.join("nested/keep.jpg")]);
 }
 
+#[test]
+fn collect_images_with_include_restricts_to_matching_paths() {
+    let tmp = tempdir().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("photo.jpg"), []).unwrap();
+    fs::write(root.join("photo_thumb.jpg"), []).unwrap();
+    fs::write(root.join("scan.png"), []).unwrap();
+
+    let include = vec!["*.jpg".to_string()];
+    let exclude = vec!["*_thumb.*".to_string()];
+    let filter = PathFilter::compile_with_include(FilterSyntax::Glob, &include, &[], &exclude)
+        .unwrap()
+        .unwrap();
+
+    let files = collect_images_with_filter(&[root.to_path_buf()], false, Some(&filter)).unwrap();
+
+    assert_eq!(files, vec![root.join("photo.jpg")]);
+}
+
 #[test]
 fn format_savings_summary_reports_savings_and_growth() {
     assert_eq!(
@@ -219,6 +418,19 @@ fn format_deletion_summary_reports_deleted_bytes() {
     assert_eq!(format_deletion_summary(2048), "Total deleted file size: 2.0 KB");
 }
 
+#[test]
+fn find_start_matches_path_name_or_stem() {
+    let files: Vec<PathBuf> = ["dir/a.jpg", "dir/b.avif", "other/c.png"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    assert_eq!(find_start(&files, Path::new("other/c.png")).unwrap(), 2);
+    assert_eq!(find_start(&files, Path::new("a.jpg")).unwrap(), 0);
+    // b.jpg was converted to AVIF in an earlier session
+    assert_eq!(find_start(&files, Path::new("b")).unwrap(), 1);
+    assert!(find_start(&files, Path::new("d.jpg")).is_err());
+}
+
 #[test]
 fn format_overall_summary_combines_both() {
     let s = format_overall_summary(4096, 1024, 512);
@@ -227,3 +439,77 @@ fn format_overall_summary_combines_both() {
     let none = format_overall_summary(0, 0, 0);
     assert_eq!(none, "No operations performed");
 }
+
+#[test]
+fn session_summary_lists_counts_sizes_and_time() {
+    assert_eq!(format_duration(std::time::Duration::from_secs(42)), "42s");
+    assert_eq!(format_duration(std::time::Duration::from_secs(200)), "3m 20s");
+    assert_eq!(format_duration(std::time::Duration::from_secs(3725)), "1h 02m 05s");
+
+    let stats = SessionStats {
+        viewed: 12,
+        cropped: 3,
+        resaved: 2,
+        deleted: 1,
+        original_bytes: 4096,
+        new_bytes: 1024,
+        deleted_bytes: 2048,
+        elapsed: std::time::Duration::from_secs(200),
+    };
+    assert_eq!(
+        stats.summary_lines(),
+        [
+            "Viewed 12 images in 3m 20s: 3 cropped, 2 resaved, 1 deleted",
+            "Total conversion savings: 3.0 KB (4.0 KB -> 1.0 KB)",
+            "Total deleted file size: 2.0 KB",
+        ]
+    );
+}
+
+#[test]
+fn output_location_mirrors_input_structure() {
+    let next_to_source = OutputLocation::default();
+    assert_eq!(
+        next_to_source.output_path(Path::new("photos/a/b.jpg"), "avif"),
+        Path::new("photos/a/b.avif")
+    );
+
+    let separate = OutputLocation {
+        dir: Some("crops".into()),
+        roots: vec!["photos".into(), "photos/a".into()],
+    };
+    assert_eq!(
+        separate.output_path(Path::new("photos/a/b.jpg"), "png"),
+        Path::new("crops/b.png")
+    );
+    assert_eq!(
+        separate.output_path(Path::new("photos/x/y/c.jpg"), "png"),
+        Path::new("crops/x/y/c.png")
+    );
+    // Files given directly keep only their name
+    assert_eq!(
+        separate.output_path(Path::new("/elsewhere/d.jpg"), "jpg"),
+        Path::new("crops/d.jpg")
+    );
+}
+
+#[test]
+fn sort_order_sorts_and_inverts() {
+    let tmp = tempdir().unwrap();
+    let small = tmp.path().join("b.png");
+    let large = tmp.path().join("a.png");
+    fs::write(&small, [0; 10]).unwrap();
+    fs::write(&large, [0; 100]).unwrap();
+
+    let mut files = vec![small.clone(), large.clone()];
+    SortOrder::Filename.sort(&mut files, false);
+    assert_eq!(files, [large.clone(), small.clone()]);
+    SortOrder::Size.sort(&mut files, false);
+    assert_eq!(files, [small.clone(), large.clone()]);
+    SortOrder::Size.sort(&mut files, true);
+    assert_eq!(files, [large.clone(), small.clone()]);
+
+    SortOrder::Randomize.sort(&mut files, true);
+    files.sort();
+    assert_eq!(files, [large, small]);
+}
@@ -11,6 +11,84 @@ fn output_format_extensions_match_expectations() {
     assert_eq!(OutputFormat::Png.extension(), "png");
     assert_eq!(OutputFormat::Webp.extension(), "webp");
     assert_eq!(OutputFormat::Avif.extension(), "avif");
+    assert_eq!(OutputFormat::Jxl.extension(), "jxl");
+    assert_eq!(OutputFormat::Tiff.extension(), "tiff");
+}
+
+#[test]
+fn png_compression_levels_roundtrip() {
+    let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, y| {
+        image::Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+    }));
+    let fast = encode_png(&img, PngCompression::Fast).unwrap();
+    let best = encode_png(&img, PngCompression::Best).unwrap();
+    assert!(best.len() <= fast.len());
+    for data in [fast, best] {
+        let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Png).unwrap();
+        assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+    }
+}
+
+#[test]
+fn tiff_compressions_roundtrip() {
+    let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, y| {
+        image::Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+    }));
+    let sizes: Vec<usize> = [TiffCompression::None, TiffCompression::Lzw, TiffCompression::Deflate]
+        .into_iter()
+        .map(|compression| {
+            let data = encode_tiff(&img, compression, None).unwrap();
+            let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Tiff).unwrap();
+            assert_eq!(decoded.to_rgba8(), img.to_rgba8());
+            data.len()
+        })
+        .collect();
+    assert!(sizes[1] < sizes[0] && sizes[2] < sizes[0]);
+
+    let rgb16 = image::DynamicImage::ImageRgb16(image::ImageBuffer::from_fn(8, 4, |x, _| {
+        image::Rgb([x as u16 * 257 + 1, 1000, 65535])
+    }));
+    let settings = EncodeSettings {
+        tiff_compression: TiffCompression::Lzw,
+        ..EncodeSettings::new(OutputFormat::Tiff)
+    };
+    let data = encode(&rgb16, &settings).unwrap();
+    let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Tiff).unwrap();
+    assert_eq!(decoded.color(), image::ColorType::Rgb16);
+    assert_eq!(decoded.to_rgb16(), rgb16.to_rgb16());
+}
+
+#[test]
+fn jxl_distance_follows_libjxl_quality_mapping() {
+    assert_eq!(jxl_distance(100), 0.0);
+    assert!((jxl_distance(90) - 1.0).abs() < 1e-5);
+    assert!((jxl_distance(30) - 6.4).abs() < 1e-5);
+    assert_eq!(jxl_distance(0), 25.0);
+    assert!(jxl_distance(70) < jxl_distance(50));
+}
+
+#[test]
+fn add_jxl_exif_wraps_codestream_in_container() {
+    let codestream = [0xFF, 0x0A, 1, 2, 3];
+    let out = add_jxl_exif(&codestream, b"II*\0exif").unwrap();
+    assert_eq!(&out[4..8], b"JXL ");
+    assert_eq!(&out[16..20], b"ftyp");
+    // Exif box: size, type, 4-byte offset, TIFF data
+    assert_eq!(&out[32..36], &[0, 0, 0, 20]);
+    assert_eq!(&out[36..40], b"Exif");
+    assert_eq!(&out[40..44], &[0, 0, 0, 0]);
+    assert_eq!(&out[44..52], b"II*\0exif");
+    assert_eq!(&out[56..60], b"jxlc");
+    assert_eq!(&out[60..], &codestream);
+
+    // Already a container: the box goes after ftyp, the rest is kept
+    let again = add_jxl_exif(&out, b"MM").unwrap();
+    assert_eq!(&again[36..40], b"Exif");
+    assert_eq!(&again[46..50], &[0, 0, 0, 20]);
+    assert_eq!(&again[50..54], b"Exif");
+    assert_eq!(again.len(), out.len() + 14);
+
+    assert!(add_jxl_exif(&[0xFF, 0xD8], b"II").is_none());
 }
 
 #[test]
@@ -58,12 +136,461 @@ fn build_output_image_returns_entire_image_for_empty_selection_list() {
 #[test]
 fn build_output_image_crops_selected_region() {
     let image = solid_image(5, 4, [0, 0, 0, 255]);
-    let selection = Selection {
-        rect: Rect::from_min_max(eframe::egui::pos2(1.0, 1.0), eframe::egui::pos2(4.0, 3.0)),
-    };
+    let selection = Selection::new(Rect::from_min_max(eframe::egui::pos2(1.0, 1.0), eframe::egui::pos2(4.0, 3.0)));
 
     let output = build_output_image(&image, &[selection]).unwrap();
 
     assert_eq!(output.width(), 3);
     assert_eq!(output.height(), 2);
 }
+
+#[test]
+fn inscribed_size_is_identity_without_rotation_and_shrinks_when_rotated() {
+    assert_eq!(inscribed_size(200.0, 100.0, 0.0), (200.0, 100.0));
+    let (w, h) = inscribed_size(100.0, 100.0, 45.0);
+    assert!((w - 70.71).abs() < 0.1);
+    assert!((h - 70.71).abs() < 0.1);
+    let (w, h) = inscribed_size(400.0, 300.0, 5.0);
+    assert!(w < 400.0 && h < 300.0);
+    assert!(w > 350.0 && h > 250.0);
+}
+
+#[test]
+fn straighten_leaves_no_empty_corners() {
+    let image = solid_image(120, 80, [200, 100, 50, 255]);
+    let straightened = straighten(&image, 7.5).to_rgba8();
+    assert!(straightened.width() < 120);
+    assert!(straightened.height() < 80);
+    assert!(straightened
+        .chunks_exact(4)
+        .all(|chunk| chunk == [200, 100, 50, 255]));
+}
+
+#[test]
+fn detect_content_bounds_trims_uniform_margins() {
+    let mut image = solid_image(20, 10, [255, 255, 255, 255]).to_rgba8();
+    for y in 2..7 {
+        for x in 3..15 {
+            image.put_pixel(x, y, image::Rgba([40, 80, 120, 255]));
+        }
+    }
+    // Slight scanner noise in the margin stays within tolerance
+    image.put_pixel(0, 9, image::Rgba([250, 252, 255, 255]));
+    let image = image::DynamicImage::ImageRgba8(image);
+
+    assert_eq!(detect_content_bounds(&image, 8), Some((3, 2, 12, 5)));
+    assert_eq!(detect_content_bounds(&solid_image(5, 5, [0, 0, 0, 255]), 8), None);
+}
+
+#[test]
+fn histogram_counts_whole_image_and_regions() {
+    let mut image = solid_image(10, 10, [255, 0, 0, 255]).to_rgba8();
+    for y in 0..5 {
+        for x in 0..10 {
+            image.put_pixel(x, y, image::Rgba([0, 0, 255, 255]));
+        }
+    }
+    let image = image::DynamicImage::ImageRgba8(image);
+
+    let full = Histogram::from_image(&image, None);
+    assert_eq!(full.red[255], 50);
+    assert_eq!(full.blue[255], 50);
+    assert_eq!(full.green[0], 100);
+    assert_eq!(full.max_count(), 100);
+
+    let bottom = Histogram::from_image(&image, Some((0, 5, 10, 5)));
+    assert_eq!(bottom.red[255], 50);
+    assert_eq!(bottom.blue[255], 0);
+}
+
+#[test]
+fn dhash_matches_resized_copies_but_not_different_images() {
+    let mut gradient = image::RgbaImage::new(64, 48);
+    for (x, y, pixel) in gradient.enumerate_pixels_mut() {
+        *pixel = image::Rgba([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8, 255]);
+    }
+    let original = image::DynamicImage::ImageRgba8(gradient);
+    let resized = original.resize_exact(128, 96, image::imageops::FilterType::Triangle);
+    let mirrored = original.fliph();
+
+    let hash = dhash(&original);
+    assert!(hash_distance(hash, dhash(&resized)) <= DUPLICATE_HASH_DISTANCE);
+    assert!(hash_distance(hash, dhash(&mirrored)) > DUPLICATE_HASH_DISTANCE);
+    assert_eq!(hash_distance(0b1011, 0b0001), 2);
+}
+
+#[test]
+fn progressive_jpeg_decodes() {
+    let img = solid_image(20, 10, [200, 100, 50, 255]);
+    let settings = EncodeSettings {
+        progressive: true,
+        ..EncodeSettings::new(OutputFormat::Jpg)
+    };
+    let data = encode(&img, &settings).unwrap();
+    let decoded = image::load_from_memory_with_format(&data, image::ImageFormat::Jpeg).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (20, 10));
+}
+
+#[test]
+fn quality_step_stays_in_range() {
+    let settings = EncodeSettings::new(OutputFormat::Avif);
+    assert_eq!(settings.with_quality_step(5).quality, DEFAULT_QUALITY + 5);
+    assert_eq!(settings.with_quality_step(-200).quality, 1);
+    assert_eq!(settings.with_quality_step(200).quality, 100);
+    assert!(OutputFormat::Jxl.uses_quality());
+    assert!(!OutputFormat::Png.uses_quality());
+}
+
+#[test]
+fn sixteen_bit_images_keep_their_depth_in_png() {
+    let image = image::DynamicImage::ImageRgb16(image::ImageBuffer::from_fn(8, 4, |x, _| {
+        image::Rgb([x as u16 * 257 + 1, 1000, 65535])
+    }));
+    assert!(is_high_bit_depth(&image));
+
+    let png = encode(&image, &EncodeSettings::new(OutputFormat::Png)).unwrap();
+    let decoded = image::load_from_memory_with_format(&png, image::ImageFormat::Png).unwrap();
+    assert_eq!(decoded.color(), image::ColorType::Rgb16);
+    assert_eq!(decoded.to_rgb16(), image.to_rgb16());
+
+    // Formats without 16-bit support get 8 bits instead of failing
+    let jpeg = encode(&image, &EncodeSettings::new(OutputFormat::Jpg)).unwrap();
+    let decoded = image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg).unwrap();
+    assert_eq!(decoded.color(), image::ColorType::Rgb8);
+
+    let crops = vec![image.crop_imm(0, 0, 4, 4), image.crop_imm(4, 0, 4, 4)];
+    assert!(is_high_bit_depth(&combine_crops(crops)));
+    assert!(is_high_bit_depth(&straighten(&image, 2.0)));
+    assert!(!is_high_bit_depth(&solid_image(2, 2, [0, 0, 0, 255])));
+}
+
+#[test]
+fn split_into_tiles_covers_image_row_by_row() {
+    let tiles = split_into_tiles(&solid_image(10, 7, [0, 0, 0, 255]), 3, 2);
+    let sizes: Vec<_> = tiles.iter().map(|tile| (tile.width(), tile.height())).collect();
+    assert_eq!(sizes, [(3, 3), (3, 3), (4, 3), (3, 4), (3, 4), (4, 4)]);
+    // No empty tiles for grids finer than the image
+    assert_eq!(split_into_tiles(&solid_image(2, 2, [0, 0, 0, 255]), 5, 5).len(), 4);
+}
+
+#[test]
+fn tile_grid_and_paths() {
+    assert_eq!(parse_tile_grid("4x3").unwrap(), (4, 3));
+    assert_eq!(parse_tile_grid("2").unwrap(), (2, 2));
+    assert!(parse_tile_grid("0x3").is_err());
+    assert!(parse_tile_grid("ax3").is_err());
+
+    let path = std::path::Path::new("dir/photo.avif");
+    assert_eq!(tile_path(path, None, 1, 2), std::path::Path::new("dir/photo_r1_c2.avif"));
+    assert_eq!(tile_path(path, Some(0), 0, 0), std::path::Path::new("dir/photo_1_r0_c0.avif"));
+}
+
+#[test]
+fn selection_mask_is_white_inside_selections() {
+    let selection = Selection::new(Rect::from_min_max(eframe::egui::pos2(1.0, 1.0), eframe::egui::pos2(4.0, 3.0)));
+    let mask = selection_mask(6, 4, &[selection]).to_luma8();
+    assert_eq!(mask.dimensions(), (6, 4));
+    assert_eq!(mask.get_pixel(1, 1).0, [255]);
+    assert_eq!(mask.get_pixel(3, 2).0, [255]);
+    assert_eq!(mask.get_pixel(4, 2).0, [0]);
+    assert_eq!(mask.get_pixel(0, 0).0, [0]);
+    assert_eq!(mask.pixels().filter(|pixel| pixel.0 == [255]).count(), 6);
+    assert!(selection_mask(2, 2, &[]).to_luma8().pixels().all(|pixel| pixel.0 == [255]));
+
+    let path = std::path::Path::new("dir/photo.avif");
+    assert_eq!(mask_path(path), std::path::Path::new("dir/photo_mask.png"));
+}
+
+#[test]
+fn selection_request_numbers_outputs_that_would_share_a_file() {
+    use eframe::egui::{pos2, vec2};
+    use imagecropper::selection::SelectionOutput;
+    use std::path::Path;
+
+    let labeled = |label: &str, format: OutputFormat, x: f32| {
+        let mut selection = Selection::new(Rect::from_min_size(pos2(x, 0.0), vec2(2.0, 3.0)));
+        selection.output = Some(SelectionOutput {
+            label: label.into(),
+            format: Some(format),
+            quality: None,
+        });
+        selection
+    };
+    let selections = [
+        Selection::new(Rect::from_min_size(pos2(0.0, 0.0), vec2(4.0, 4.0))),
+        labeled("logo", OutputFormat::Png, 1.0),
+        labeled("logo", OutputFormat::Png, 2.0),
+        labeled("logo", OutputFormat::Avif, 3.0),
+        labeled("mask", OutputFormat::Png, 4.0),
+    ];
+    let image = solid_image(10, 4, [1, 2, 3, 255]);
+    let style = CombineStyle::default();
+    let settings = EncodeSettings::new(OutputFormat::Avif);
+    let (source, output) = (Path::new("dir/photo.jpg"), Path::new("dir/photo.avif"));
+
+    let request = selection_request(&image, &selections, &style, settings, source, output).unwrap();
+    assert_eq!(request.path, output);
+    assert_eq!(request.original_path, source);
+    assert_eq!((request.image.width(), request.image.height()), (4, 4));
+    let paths: Vec<_> = request.extra_outputs.iter().map(|extra| extra.path.as_path()).collect();
+    assert_eq!(
+        paths,
+        [
+            Path::new("dir/photo_logo.png"),
+            Path::new("dir/photo_logo_2.png"),
+            Path::new("dir/photo_logo.avif"),
+            Path::new("dir/photo_mask_2.png"),
+        ]
+    );
+    assert!(request.extra_outputs.iter().all(|extra| extra.image.width() == 2));
+
+    // Without other selections, the first labeled one is the main output
+    let request = selection_request(&image, &selections[1..3], &style, settings, source, output).unwrap();
+    assert_eq!(request.path, Path::new("dir/photo_logo.png"));
+    assert_eq!(request.settings.format, OutputFormat::Png);
+    assert_eq!(request.extra_outputs[0].path, Path::new("dir/photo_logo_2.png"));
+    assert!(selection_request(&image, &[], &style, settings, source, output).is_none());
+}
+
+#[test]
+fn combine_layouts_arrange_crops() {
+    let sizes = [(10, 4), (6, 8), (4, 2)];
+    assert_eq!(
+        CombineLayout::Horizontal.arrange(&sizes),
+        (vec![(0, 0), (10, 0), (16, 0)], (20, 8))
+    );
+    assert_eq!(
+        CombineLayout::Vertical.arrange(&sizes),
+        (vec![(0, 0), (0, 4), (0, 12)], (10, 14))
+    );
+    // Two columns: widths 10 and 6, rows 8 and 2 high
+    assert_eq!(
+        CombineLayout::Grid.arrange(&sizes),
+        (vec![(0, 0), (10, 0), (0, 8)], (16, 10))
+    );
+    let (positions, (width, height)) = CombineLayout::Pack.arrange(&sizes);
+    for (&(x, y), &(w, h)) in positions.iter().zip(&sizes) {
+        assert!(x + w <= width && y + h <= height);
+    }
+    assert_eq!(CombineLayout::Pack.next(), CombineLayout::Horizontal);
+
+    let red = solid_image(2, 3, [255, 0, 0, 255]);
+    let blue = solid_image(4, 1, [0, 0, 255, 255]);
+    let style = CombineStyle {
+        layout: CombineLayout::Vertical,
+        ..Default::default()
+    };
+    let combined = combine_crops_with(vec![red, blue], &style).to_rgba8();
+    assert_eq!(combined.dimensions(), (4, 4));
+    assert_eq!(combined.get_pixel(0, 3).0, [0, 0, 255, 255]);
+}
+
+#[test]
+fn combine_style_adds_gaps_background_and_separators() {
+    let red = solid_image(2, 2, [255, 0, 0, 255]);
+    let blue = solid_image(2, 2, [0, 0, 255, 255]);
+    let mut style = CombineStyle {
+        layout: CombineLayout::Horizontal,
+        gap: 2,
+        padding: 1,
+        background: "white".to_string(),
+        ..Default::default()
+    };
+    let combined = combine_crops_with(vec![red.clone(), blue.clone()], &style).to_rgba8();
+    assert_eq!(combined.dimensions(), (8, 4));
+    assert_eq!(combined.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    assert_eq!(combined.get_pixel(1, 1).0, [255, 0, 0, 255]);
+    assert_eq!(combined.get_pixel(3, 2).0, [255, 255, 255, 255]);
+    assert_eq!(combined.get_pixel(6, 2).0, [0, 0, 255, 255]);
+
+    // A separator as wide as the gap fills it, the edges stay free without padding
+    style.padding = 0;
+    style.separator_width = 2;
+    style.separator_color = "#00ff00".to_string();
+    let combined = combine_crops_with(vec![red, blue], &style).to_rgba8();
+    assert_eq!(combined.dimensions(), (6, 2));
+    assert_eq!(combined.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(combined.get_pixel(2, 0).0, [0, 255, 0, 255]);
+    assert_eq!(combined.get_pixel(3, 1).0, [0, 255, 0, 255]);
+    assert_eq!(combined.get_pixel(5, 1).0, [0, 0, 255, 255]);
+
+    assert_eq!(parse_fill_color("transparent").unwrap().0, [0, 0, 0, 0]);
+    assert_eq!(parse_fill_color("#102030").unwrap().0, [16, 32, 48, 255]);
+    assert!(parse_fill_color("beige").is_err());
+}
+
+#[test]
+fn pack_layout_wastes_little_canvas() {
+    let sizes = [
+        (154, 46),
+        (158, 123),
+        (114, 176),
+        (189, 130),
+        (186, 145),
+        (124, 210),
+        (102, 134),
+        (208, 129),
+        (30, 119),
+        (189, 164),
+        (69, 128),
+        (154, 115),
+    ];
+    let (positions, (width, height)) = CombineLayout::Pack.arrange(&sizes);
+    let rects: Vec<_> = positions.iter().zip(&sizes).map(|(&(x, y), &(w, h))| (x, y, w, h)).collect();
+    for (i, &(x, y, w, h)) in rects.iter().enumerate() {
+        assert!(x + w <= width && y + h <= height);
+        for &(ox, oy, ow, oh) in &rects[i + 1..] {
+            assert!(x + w <= ox || ox + ow <= x || y + h <= oy || oy + oh <= y, "crops overlap");
+        }
+    }
+    let area: u32 = sizes.iter().map(|(w, h)| w * h).sum();
+    assert!(width * height <= area * 13 / 10, "{width}x{height} canvas for {area} px of crops");
+}
+
+#[test]
+fn redact_hides_only_the_selections() {
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(40, 20, |x, y| {
+        let value = if (x + y) % 2 == 0 { 0 } else { 255 };
+        image::Rgba([value, value, value, 255])
+    }));
+    let selection = Selection::new(Rect::from_min_max(eframe::egui::pos2(0.0, 0.0), eframe::egui::pos2(20.0, 20.0)));
+
+    let black = image::Rgba([0, 0, 0, 255]);
+    for style in [RedactStyle::Blur, RedactStyle::Pixelate] {
+        let output = redact(&image, std::slice::from_ref(&selection), style, black).to_rgba8();
+        assert_eq!(output.dimensions(), (40, 20));
+        // The checkerboard inside averages out to gray
+        let inside = output.get_pixel(10, 10).0[0];
+        assert!((100..=160).contains(&inside), "{style:?}: {inside}");
+        for (x, y) in [(20, 0), (30, 5), (39, 19)] {
+            assert_eq!(output.get_pixel(x, y), image.to_rgba8().get_pixel(x, y));
+        }
+    }
+
+    let filled = redact(&image, &[selection], RedactStyle::Fill, parse_fill_color("#ff0000").unwrap()).to_rgba8();
+    assert!((0..20).all(|x| filled.get_pixel(x, 19).0 == [255, 0, 0, 255]));
+    assert_eq!(filled.get_pixel(20, 19), image.to_rgba8().get_pixel(20, 19));
+
+    let solid = solid_image(8, 8, [10, 20, 30, 255]);
+    assert_eq!(redact(&solid, &[], RedactStyle::Blur, black).to_rgba8(), solid.to_rgba8());
+    assert_eq!(pixelate_region(&solid).to_rgba8(), solid.to_rgba8());
+}
+
+#[test]
+fn redact_follows_rotated_selections() {
+    let image = solid_image(40, 40, [0, 0, 0, 255]);
+    let mut selection = Selection::new(Rect::from_center_size(
+        eframe::egui::pos2(20.0, 20.0),
+        eframe::egui::vec2(20.0, 20.0),
+    ));
+    selection.angle = 45.0;
+
+    let red = image::Rgba([255, 0, 0, 255]);
+    let output = redact(&image, &[selection], RedactStyle::Fill, red).to_rgba8();
+    assert_eq!(*output.get_pixel(20, 20), red);
+    // Inside the diamond but outside the unrotated square, and the other way round
+    assert_eq!(*output.get_pixel(20, 6), red);
+    assert_eq!(output.get_pixel(11, 11).0, [0, 0, 0, 255]);
+}
+
+#[test]
+fn remove_bands_stitches_the_rest_together() {
+    // Every pixel encodes its own position
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 8, |x, y| {
+        image::Rgba([x as u8, y as u8, 0, 255])
+    }));
+    let band = |min: (f32, f32), max: (f32, f32)| {
+        Selection::new(Rect::from_min_max(eframe::egui::pos2(min.0, min.1), eframe::egui::pos2(max.0, max.1)))
+    };
+    // A wide band removes rows 2 and 3, a tall one columns 5 to 7
+    let selections = [band((0.0, 2.0), (10.0, 4.0)), band((5.0, 0.0), (8.0, 8.0))];
+    assert!(is_horizontal_band(&selections[0], 10, 8));
+    assert!(!is_horizontal_band(&selections[1], 10, 8));
+
+    let output = remove_bands(&image, &selections).unwrap().to_rgba8();
+    assert_eq!(output.dimensions(), (7, 6));
+    assert_eq!(output.get_pixel(0, 0).0, [0, 0, 0, 255]);
+    assert_eq!(output.get_pixel(4, 1).0, [4, 1, 0, 255]);
+    assert_eq!(output.get_pixel(5, 2).0, [8, 4, 0, 255]);
+    assert_eq!(output.get_pixel(6, 5).0, [9, 7, 0, 255]);
+
+    assert!(remove_bands(&image, &[band((0.0, 0.0), (10.0, 8.0))]).is_none());
+    assert_eq!(remove_bands(&image, &[]).unwrap().to_rgba8(), image.to_rgba8());
+}
+
+#[test]
+fn rotated_selections_are_cut_out_upright() {
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(10, 10, |x, y| {
+        image::Rgba([(x * 20) as u8, (y * 20) as u8, 0, 255])
+    }));
+    // 6x2 around the center, rotated into a vertical strip
+    let mut selection = Selection::new(Rect::from_min_max(eframe::egui::pos2(2.0, 4.0), eframe::egui::pos2(8.0, 6.0)));
+    selection.rotate_by(90.0);
+
+    let output = build_output_image(&image, std::slice::from_ref(&selection)).unwrap().to_rgba8();
+    assert_eq!(output.dimensions(), (6, 2));
+    assert_eq!(output.get_pixel(0, 0).0, [100, 40, 0, 255]);
+    assert_eq!(output.get_pixel(5, 1).0, [80, 140, 0, 255]);
+
+    let mask = selection_mask(10, 10, &[selection]).to_luma8();
+    assert_eq!(mask.pixels().filter(|pixel| pixel.0 == [255]).count(), 12);
+    assert_eq!(mask.get_pixel(4, 2).0, [255]);
+    assert_eq!(mask.get_pixel(2, 4).0, [0]);
+}
+
+#[test]
+fn ellipse_crops_are_transparent_outside_the_ellipse() {
+    let image = solid_image(30, 20, [10, 20, 30, 255]);
+    let selection = Selection {
+        shape: imagecropper::selection::SelectionShape::Ellipse,
+        ..Selection::new(Rect::from_min_max(eframe::egui::pos2(5.0, 5.0), eframe::egui::pos2(25.0, 15.0)))
+    };
+
+    let output = build_output_image(&image, std::slice::from_ref(&selection)).unwrap().to_rgba8();
+    assert_eq!(output.dimensions(), (20, 10));
+    assert_eq!(output.get_pixel(10, 5).0, [10, 20, 30, 255]);
+    assert_eq!(output.get_pixel(0, 0).0, OUTSIDE_SHAPE_COLOR.0);
+    assert_eq!(output.get_pixel(19, 9).0[3], 0);
+
+    let mask = selection_mask(30, 20, &[selection]).to_luma8();
+    assert_eq!(mask.get_pixel(15, 10).0, [255]);
+    assert_eq!(mask.get_pixel(5, 5).0, [0]);
+}
+
+#[test]
+fn polygon_crops_are_transparent_outside_the_polygon() {
+    let image = solid_image(30, 20, [10, 20, 30, 255]);
+    let triangle = [(0.0, 0.0), (20.0, 0.0), (0.0, 20.0)].map(|(x, y)| eframe::egui::pos2(x, y));
+    let selection = Selection::polygon(&triangle, eframe::egui::vec2(30.0, 20.0)).unwrap();
+
+    let output = build_output_image(&image, std::slice::from_ref(&selection)).unwrap().to_rgba8();
+    assert_eq!(output.dimensions(), (20, 20));
+    assert_eq!(output.get_pixel(2, 2).0, [10, 20, 30, 255]);
+    assert_eq!(output.get_pixel(18, 18).0[3], 0);
+
+    let mask = selection_mask(30, 20, &[selection]).to_luma8();
+    assert_eq!(mask.get_pixel(2, 2).0, [255]);
+    assert_eq!(mask.get_pixel(18, 18).0, [0]);
+}
+
+#[test]
+fn edge_map_snaps_selection_edges_to_nearby_image_edges() {
+    // Black left half, white right half: a vertical edge at x = 20
+    let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(40, 20, |x, _| {
+        if x < 20 {
+            image::Rgba([0, 0, 0, 255])
+        } else {
+            image::Rgba([255, 255, 255, 255])
+        }
+    }));
+    let edges = EdgeMap::new(&image);
+
+    let mut selection = Selection::new(Rect::from_min_max(eframe::egui::pos2(3.0, 2.0), eframe::egui::pos2(16.0, 15.0)));
+    edges.snap(&mut selection, 6);
+    assert_eq!(selection.rect, Rect::from_min_max(eframe::egui::pos2(3.0, 2.0), eframe::egui::pos2(20.0, 15.0)));
+
+    // Out of reach
+    let mut far = Selection::new(Rect::from_min_max(eframe::egui::pos2(3.0, 2.0), eframe::egui::pos2(12.0, 15.0)));
+    edges.snap(&mut far, 6);
+    assert_eq!(far.rect.max.x, 12.0);
+}
@@ -0,0 +1,98 @@
+use eframe::egui;
+use imagecropper::app::saver::Saver;
+use imagecropper::image_utils::{EncodeSettings, OutputFormat, SaveRequest};
+use imagecropper::journal::{read_log, Event, Journal};
+use imagecropper::pipeline::SaverOptions;
+use imagecropper::selection::Selection;
+use std::{
+    fs, thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+use common::{solid_image, with_temp_workdir};
+
+#[test]
+fn journal_appends_entries_across_sessions() {
+    with_temp_workdir(|cwd| {
+        let log = cwd.join("log.jsonl");
+        let image = cwd.join("a.jpg");
+        let selection = Selection::new(egui::Rect::from_min_size(egui::pos2(10.2, 20.0), egui::vec2(30.0, 40.7)));
+
+        let journal = Journal::open(&log).unwrap();
+        journal.record(&image, Event::crop(&[selection], &cwd.join("a.avif")));
+        drop(journal);
+        Journal::open(&log).unwrap().record(&image, Event::Skip);
+        Journal::default().record(&image, Event::Skip);
+
+        let entries = read_log(&log).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, image);
+        assert_eq!(
+            entries[0].event,
+            Event::Crop {
+                rects: vec![[10, 20, 30, 41]],
+                output: cwd.join("a.avif"),
+            }
+        );
+        assert_eq!(entries[1].event, Event::Skip);
+        assert!(entries[1].time.ends_with('Z'));
+
+        let line = fs::read_to_string(&log).unwrap();
+        assert!(line.starts_with(r#"{"time":"#));
+        assert!(line.contains(r#""action":"crop""#));
+    });
+}
+
+#[test]
+fn saver_records_byte_savings() {
+    with_temp_workdir(|cwd| {
+        let log = cwd.join("log.jsonl");
+        let mut saver = Saver::with_options(
+            1,
+            SaverOptions {
+                journal: Journal::open(&log).unwrap(),
+                ..Default::default()
+            },
+        );
+        let source = cwd.join("source.png");
+        solid_image(4, 4, [1, 2, 3, 255]).save(&source).unwrap();
+        let original_bytes = fs::metadata(&source).unwrap().len();
+        let target = cwd.join("source.jpg");
+        saver
+            .queue_save(SaveRequest {
+                image: solid_image(2, 2, [1, 2, 3, 255]),
+                path: target.clone(),
+                original_path: source.clone(),
+                settings: EncodeSettings::new(OutputFormat::Jpg),
+                extra_outputs: Vec::new(),
+            })
+            .unwrap();
+
+        let start = Instant::now();
+        while !saver.pending_saves.is_empty() {
+            saver.check_completions();
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for save");
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let entries = read_log(&log).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, source);
+        let new_bytes = fs::metadata(&target).unwrap().len();
+        match &entries[0].event {
+            Event::Saved {
+                output,
+                original_bytes: Some(original),
+                new_bytes: Some(new),
+                saved_bytes: Some(saved),
+                backup: Some(_),
+            } => {
+                assert_eq!(output, &target);
+                assert_eq!((*original, *new), (original_bytes, new_bytes));
+                assert_eq!(*saved, original_bytes as i64 - new_bytes as i64);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    });
+}
@@ -1,6 +1,6 @@
-use imagecropper::app::loader::Loader;
+use imagecropper::app::loader::{parse_max_preview, Loader, LoaderOptions};
 use imagecropper::image_utils::PreloadedImage;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{thread, time::Duration};
 use tempfile::tempdir;
 
@@ -37,6 +37,7 @@ fn history_keeps_only_ten_entries() {
         loader.push_history(PreloadedImage {
             path: PathBuf::from(format!("{idx}.png")),
             image,
+            hash: None,
             color_image: Some(color_image),
             texture: None,
             load_duration: Duration::default(),
@@ -50,3 +51,203 @@ fn history_keeps_only_ten_entries() {
     assert_eq!(loader.history.front().unwrap().path, PathBuf::from("2.png"));
     assert_eq!(loader.history.back().unwrap().path, PathBuf::from("11.png"));
 }
+
+#[test]
+fn loader_generates_downscaled_thumbnails() {
+    let tmp = tempdir().unwrap();
+    let img_path = tmp.path().join("large.png");
+    write_image(&img_path, &solid_image(640, 320, [10, 20, 30, 255]));
+
+    let mut loader = Loader::new();
+    loader.load_thumbnail(img_path.clone());
+    loader.load_thumbnail(img_path.clone());
+
+    for _ in 0..40 {
+        loader.update();
+        if !loader.thumbnails_ready.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert_eq!(loader.thumbnails_ready.len(), 1);
+    let (path, thumbnail) = &loader.thumbnails_ready[0];
+    assert_eq!(path, &img_path);
+    assert_eq!(thumbnail.size, [160, 80]);
+}
+
+#[test]
+fn preload_window_skips_cached_images() {
+    let tmp = tempdir().unwrap();
+    let paths: Vec<PathBuf> = (0..3).map(|i| tmp.path().join(format!("{i}.png"))).collect();
+    for path in &paths {
+        write_image(path, &solid_image(4, 4, [10, 20, 30, 255]));
+    }
+
+    let mut loader = Loader::new();
+    loader.set_preload_window(&paths[..2]);
+    for _ in 0..40 {
+        loader.update();
+        if loader.cache.len() == 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert!(loader.cache.contains_key(&paths[0]) && loader.cache.contains_key(&paths[1]));
+    assert!(loader.pending.is_empty());
+
+    // Moving the window on only requests what isn't decoded yet
+    loader.set_preload_window(&paths[1..]);
+    assert_eq!(loader.pending.iter().collect::<Vec<_>>(), vec![&paths[2]]);
+    for _ in 0..40 {
+        loader.update();
+        if loader.pending.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert!(loader.cache.contains_key(&paths[2]));
+}
+
+fn cached_image(path: &str, width: u32) -> PreloadedImage {
+    PreloadedImage {
+        path: PathBuf::from(path),
+        image: solid_image(width, 1, [0, 0, 0, 255]),
+        hash: None,
+        color_image: None,
+        texture: None,
+        load_duration: Duration::default(),
+        read_duration: Duration::default(),
+        decode_duration: Duration::default(),
+        resize_duration: Duration::default(),
+        texture_gen_duration: Duration::default(),
+    }
+}
+
+#[test]
+fn cache_evicts_least_recently_used_beyond_budget() {
+    let mut loader = Loader::new();
+    // 100 RGBA pixels each
+    loader.cache_budget = Some(1000);
+    loader.insert_cache(cached_image("a.png", 100));
+    loader.insert_cache(cached_image("b.png", 100));
+    assert_eq!(loader.cache_bytes(), 800);
+
+    // Using a makes b the least recently used one
+    loader.set_preload_window(&[PathBuf::from("a.png")]);
+    loader.insert_cache(cached_image("c.png", 100));
+    assert!(loader.cache.contains_key(&PathBuf::from("a.png")));
+    assert!(!loader.cache.contains_key(&PathBuf::from("b.png")));
+    assert!(loader.cache.contains_key(&PathBuf::from("c.png")));
+    assert_eq!(loader.cache_bytes(), 800);
+
+    // An image larger than the budget is still kept on its own
+    loader.insert_cache(cached_image("huge.png", 500));
+    assert_eq!(loader.cache.len(), 1);
+    assert!(loader.get_from_cache(Path::new("huge.png")).is_some());
+    assert_eq!(loader.cache_bytes(), 0);
+}
+
+#[test]
+fn preload_window_stops_requesting_when_budget_is_used() {
+    let mut loader = Loader::new();
+    loader.cache_budget = Some(400);
+    loader.insert_cache(cached_image("a.png", 100));
+    // Requests a missing image again, but nothing after the budget is reached
+    loader.set_preload_window(&[
+        PathBuf::from("missing.png"),
+        PathBuf::from("a.png"),
+        PathBuf::from("b.png"),
+    ]);
+    assert_eq!(loader.pending.len(), 1);
+    assert!(loader.pending.contains(&PathBuf::from("missing.png")));
+}
+
+#[test]
+fn loader_scales_images_down_to_max_preview() {
+    assert_eq!(parse_max_preview("3840x2160").unwrap(), Some((3840, 2160)));
+    assert_eq!(parse_max_preview("5120").unwrap(), Some((5120, 5120)));
+    assert_eq!(parse_max_preview("None").unwrap(), None);
+    assert!(parse_max_preview("0x100").is_err());
+    assert!(parse_max_preview("big").is_err());
+
+    let tmp = tempdir().unwrap();
+    let img_path = tmp.path().join("wide.png");
+    write_image(&img_path, &solid_image(64, 32, [10, 20, 30, 255]));
+
+    for (max_preview, expected) in [(Some((32, 32)), (32, 16)), (None, (64, 32))] {
+        let mut loader = Loader::with_options(LoaderOptions {
+            max_preview,
+            ..Default::default()
+        });
+        loader.load_image(img_path.clone());
+        for _ in 0..40 {
+            loader.update();
+            if loader.cache.contains_key(&img_path) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        let image = loader.get_from_cache(&img_path).unwrap().image;
+        assert_eq!((image.width(), image.height()), expected);
+
+        // Images compared with the current one are scaled the same way
+        loader.load_comparison(img_path.clone());
+        for _ in 0..40 {
+            loader.update();
+            if !loader.comparisons_ready.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        let (path, compared) = loader.comparisons_ready.pop().unwrap();
+        assert_eq!(path, img_path);
+        assert_eq!(compared.unwrap().size, [expected.0 as usize, expected.1 as usize]);
+        assert!(loader.comparisons_requested.is_empty());
+    }
+}
+
+#[test]
+fn load_stats_average_the_most_recent_images() {
+    use imagecropper::app::loader::{LoadStats, LoadTimings, LOAD_STATS_WINDOW};
+    let mut stats = LoadStats::default();
+    assert!(stats.average().is_none());
+    for ms in 0..LOAD_STATS_WINDOW as u64 + 2 {
+        stats.record(LoadTimings {
+            read: Duration::from_millis(ms),
+            total: Duration::from_millis(2 * ms),
+            ..Default::default()
+        });
+    }
+    // The first two images dropped out of the window
+    let average = stats.average().unwrap();
+    // Mean of 2..=WINDOW + 1 ms
+    assert_eq!(average.read, Duration::from_micros((LOAD_STATS_WINDOW as u64 + 3) * 500));
+    assert_eq!(stats.last().unwrap().total, Duration::from_millis(2 * (LOAD_STATS_WINDOW as u64 + 1)));
+    assert_eq!(stats.lines().len(), 3);
+}
+
+#[test]
+fn undecodable_images_are_reported_and_not_requested_again() {
+    let tmp = tempdir().unwrap();
+    let path = tmp.path().join("broken.png");
+    std::fs::write(&path, b"not an image").unwrap();
+
+    let mut loader = Loader::new();
+    loader.load_image(path.clone());
+    for _ in 0..40 {
+        loader.update();
+        if loader.failed.contains_key(&path) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert!(loader.failed[&path].starts_with("Can't decode"));
+    assert!(!loader.cache.contains_key(&path));
+
+    loader.set_preload_window(&[path.clone()]);
+    assert!(loader.pending.is_empty());
+
+    let report = tmp.path().join("report.txt");
+    loader.write_failure_report(&report).unwrap();
+    assert!(std::fs::read_to_string(&report).unwrap().starts_with(&format!("{}\t", path.display())));
+}
@@ -0,0 +1,52 @@
+use imagecropper::image_utils::{encode, EncodeSettings, OutputFormat};
+use imagecropper::metadata::*;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::solid_image;
+
+#[test]
+fn set_rating_updates_or_adds_the_property() {
+    let packet = rating_packet(3);
+    assert!(packet.contains("xmp:Rating=\"3\""));
+    assert_eq!(set_rating(&packet, 5), rating_packet(5));
+
+    let element = "<rdf:Description rdf:about=\"\"><xmp:Rating>1</xmp:Rating></rdf:Description>";
+    assert_eq!(
+        set_rating(element, 4),
+        "<rdf:Description rdf:about=\"\"><xmp:Rating>4</xmp:Rating></rdf:Description>"
+    );
+
+    // darktable sidecar without a rating: other properties are kept
+    let darktable = "<rdf:Description rdf:about=\"\" xmlns:darktable=\"http://darktable.sf.net/\" darktable:xmp_version=\"5\"/>";
+    let rated = set_rating(darktable, 2);
+    assert!(rated.contains("darktable:xmp_version=\"5\""));
+    assert!(rated.contains(&format!("xmlns:xmp=\"{XMP_NAMESPACE}\" xmp:Rating=\"2\"")));
+
+    assert_eq!(set_rating("not xmp", 1), rating_packet(1));
+}
+
+#[test]
+fn write_rating_uses_sidecars_or_the_jpeg_itself() {
+    let tmp = tempdir().unwrap();
+    let png = tmp.path().join("photo.png");
+    fs::write(&png, encode(&solid_image(4, 4, [0, 0, 0, 255]), &EncodeSettings::new(OutputFormat::Png)).unwrap()).unwrap();
+
+    let sidecar = write_rating(&png, 4, RatingTarget::Sidecar).unwrap();
+    assert_eq!(sidecar, tmp.path().join("photo.png.xmp"));
+    write_rating(&png, 2, RatingTarget::Embedded).unwrap();
+    assert!(fs::read_to_string(&sidecar).unwrap().contains("xmp:Rating=\"2\""));
+
+    let jpg = tmp.path().join("photo.jpg");
+    fs::write(&jpg, encode(&solid_image(4, 4, [0, 0, 0, 255]), &EncodeSettings::new(OutputFormat::Jpg)).unwrap()).unwrap();
+    assert_eq!(write_rating(&jpg, 5, RatingTarget::Embedded).unwrap(), jpg);
+    write_rating(&jpg, 1, RatingTarget::Embedded).unwrap();
+    let data = fs::read(&jpg).unwrap();
+    let text = String::from_utf8_lossy(&data);
+    assert_eq!(text.matches("xmp:Rating=").count(), 1);
+    assert!(text.contains("xmp:Rating=\"1\""));
+    assert!(image::load_from_memory(&data).is_ok());
+
+    assert!(write_rating(&jpg, 6, RatingTarget::Embedded).is_err());
+}
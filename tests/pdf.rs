@@ -0,0 +1,30 @@
+use imagecropper::pdf::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+#[test]
+fn page_paths_lead_back_to_their_pdf() {
+    let tmp = tempdir().unwrap();
+    let paper = tmp.path().join("paper.pdf");
+    fs::write(&paper, b"%PDF-1.4").unwrap();
+
+    let page = page_path(&paper, 3);
+    assert_eq!(page, tmp.path().join("paper_page-003.png"));
+    let expected = cfg!(feature = "pdf").then(|| (paper.clone(), 3));
+    assert_eq!(page_of(&page), expected);
+
+    // Not a page: no PDF of that name, no page number or a file of that name
+    assert_eq!(page_of(&tmp.path().join("scan_page-001.png")), None);
+    assert_eq!(page_of(&tmp.path().join("paper_page-.png")), None);
+    fs::write(&page, b"").unwrap();
+    assert_eq!(page_of(&page), None);
+}
+
+#[test]
+fn images_are_kept_when_expanding_pages() {
+    let files = vec![PathBuf::from("a.png"), PathBuf::from("b.jpg")];
+    assert_eq!(expand_pages(files.clone()), files);
+    assert_eq!(is_pdf(Path::new("paper.PDF")), cfg!(feature = "pdf"));
+    assert!(!is_pdf(Path::new("paper.png")));
+}
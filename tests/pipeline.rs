@@ -0,0 +1,118 @@
+use imagecropper::fs_utils::ORIGINALS_DIR;
+use imagecropper::image_utils::{encode, CopyMetadata, EncodeSettings, OutputFormat, SaveRequest};
+use imagecropper::pipeline::{save, CropJob, OriginalsMode, SaverOptions};
+use img_parts::png::Png;
+use img_parts::{Bytes, ImageEXIF, ImageICC};
+
+mod common;
+use common::{solid_image, with_temp_workdir, write_image};
+
+#[test]
+fn crop_job_saves_crop_and_backs_up_original() {
+    with_temp_workdir(|cwd| {
+        let input = cwd.join("photo.png");
+        write_image(&input, &solid_image(100, 50, [10, 20, 30, 255]));
+
+        let result = CropJob::new(&input, vec![[10, 5, 40, 30]], OutputFormat::Png).run().unwrap();
+        assert_eq!(result.output, input);
+        assert!(result.backup.unwrap().starts_with(cwd.join(ORIGINALS_DIR)));
+        assert!(result.original_bytes.is_some() && result.new_bytes.is_some());
+        let cropped = image::open(&input).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (40, 30));
+    });
+}
+
+#[test]
+fn crop_job_clamps_rects_and_changes_extension() {
+    with_temp_workdir(|cwd| {
+        let input = cwd.join("photo.png");
+        write_image(&input, &solid_image(100, 50, [10, 20, 30, 255]));
+
+        let job = CropJob::new(&input, vec![[60, 0, 100, 100]], OutputFormat::Jpg);
+        assert_eq!(job.output_path(), cwd.join("photo.jpg"));
+        let result = job.run().unwrap();
+        assert_eq!(result.output, cwd.join("photo.jpg"));
+        assert!(!input.exists());
+        let cropped = image::open(&result.output).unwrap();
+        assert_eq!((cropped.width(), cropped.height()), (40, 50));
+    });
+}
+
+#[test]
+fn crop_job_writes_destination_and_keeps_input() {
+    with_temp_workdir(|cwd| {
+        let input = cwd.join("photo.png");
+        write_image(&input, &solid_image(100, 50, [10, 20, 30, 255]));
+
+        let format = OutputFormat::from_extension("JPEG").unwrap();
+        let mut job = CropJob::new(&input, vec![[0, 0, 20, 10], [50, 0, 20, 10]], format);
+        job.destination = Some(cwd.join("out/crop.jpg"));
+        job.options.originals = OriginalsMode::Keep;
+        let result = job.run().unwrap();
+        assert_eq!(result.output, cwd.join("out/crop.jpg"));
+        assert_eq!(result.backup, None);
+        assert!(input.exists());
+        assert!(!cwd.join(ORIGINALS_DIR).exists());
+    });
+}
+
+#[test]
+fn crop_job_writes_mask_of_input() {
+    with_temp_workdir(|cwd| {
+        let input = cwd.join("photo.png");
+        write_image(&input, &solid_image(100, 50, [10, 20, 30, 255]));
+
+        let mut job = CropJob::new(&input, vec![[10, 5, 40, 30]], OutputFormat::Jpg);
+        job.mask = true;
+        let result = job.run().unwrap();
+        let mask = image::open(cwd.join("photo_mask.png")).unwrap().to_luma8();
+        assert_eq!(mask.dimensions(), (100, 50));
+        assert_eq!((mask.get_pixel(10, 5).0, mask.get_pixel(50, 5).0), ([255], [0]));
+        assert!(result.new_bytes.unwrap() > std::fs::metadata(&result.output).unwrap().len());
+    });
+}
+
+#[test]
+fn outputs_copy_the_metadata_their_settings_allow() {
+    with_temp_workdir(|cwd| {
+        let input = cwd.join("photo.png");
+        let settings = EncodeSettings::new(OutputFormat::Png);
+        let mut png =
+            Png::from_bytes(encode(&solid_image(8, 8, [10, 20, 30, 255]), &settings).unwrap().into()).unwrap();
+        png.set_exif(Some(Bytes::from_static(b"MM\0*\0\0\0\x08\0\0")));
+        png.set_icc_profile(Some(Bytes::from_static(b"profile")));
+        let mut data = Vec::new();
+        png.encoder().write_to(&mut data).unwrap();
+        std::fs::write(&input, data).unwrap();
+
+        let options = SaverOptions {
+            originals: OriginalsMode::Keep,
+            ..SaverOptions::default()
+        };
+        for (name, settings, has_exif, has_icc) in [
+            ("full.png", settings, true, true),
+            (
+                "redacted.png",
+                EncodeSettings {
+                    metadata: CopyMetadata::ColorProfile,
+                    ..settings
+                },
+                false,
+                true,
+            ),
+            ("photo_mask.png", EncodeSettings::mask(), false, false),
+        ] {
+            let request = SaveRequest {
+                image: solid_image(8, 8, [0, 0, 0, 255]),
+                path: cwd.join(name),
+                original_path: input.clone(),
+                settings,
+                extra_outputs: Vec::new(),
+            };
+            save(&request, &options).result.unwrap();
+            let output = Png::from_bytes(std::fs::read(cwd.join(name)).unwrap().into()).unwrap();
+            assert_eq!(output.exif().is_some(), has_exif, "{name}");
+            assert_eq!(output.icc_profile().is_some(), has_icc, "{name}");
+        }
+    });
+}
@@ -0,0 +1,112 @@
+use imagecropper::probe::*;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+use common::solid_image;
+
+#[test]
+fn parses_sizes_and_pixel_counts() {
+    assert_eq!(parse_file_size("2048").unwrap(), 2048);
+    assert_eq!(parse_file_size("500K").unwrap(), 500 * 1024);
+    assert_eq!(parse_file_size("1.5mb").unwrap(), 1536 * 1024);
+    assert_eq!(parse_file_size("2G").unwrap(), 2 << 30);
+    assert!(parse_file_size("12 apples").is_err());
+
+    assert_eq!(parse_pixel_count("250000").unwrap(), 250_000);
+    assert_eq!(parse_pixel_count("12MP").unwrap(), 12_000_000);
+    assert_eq!(parse_pixel_count("0.5M").unwrap(), 500_000);
+    assert_eq!(parse_pixel_count("800x600").unwrap(), 480_000);
+    assert!(parse_pixel_count("800x").is_err());
+}
+
+#[test]
+fn size_filter_checks_header_dimensions_and_file_size() {
+    let tmp = tempdir().unwrap();
+    let small = tmp.path().join("thumb.png");
+    let large = tmp.path().join("scan.png");
+    solid_image(8, 8, [0, 0, 0, 255]).save(&small).unwrap();
+    solid_image(40, 30, [0, 0, 0, 255]).save(&large).unwrap();
+    let broken = tmp.path().join("broken.jpg");
+    fs::write(&broken, b"not an image").unwrap();
+
+    assert_eq!(dimensions(&large).unwrap(), (40, 30));
+    assert!(dimensions(&broken).is_err());
+
+    let filter = SizeFilter {
+        min_pixels: Some(1000),
+        ..Default::default()
+    };
+    assert!(!filter.matches(&small));
+    assert!(filter.matches(&large));
+    // Unreadable headers are left for the viewer to report
+    assert!(filter.matches(&broken));
+
+    let large_bytes = fs::metadata(&large).unwrap().len();
+    let filter = SizeFilter {
+        max_bytes: Some(large_bytes - 1),
+        ..Default::default()
+    };
+    assert!(!filter.matches(&large));
+    let filter = SizeFilter {
+        min_bytes: Some(large_bytes),
+        ..Default::default()
+    };
+    assert!(filter.matches(&large));
+    assert!(!filter.matches(&broken));
+    assert!(SizeFilter::default().is_empty());
+}
+
+fn encode_jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    solid_image(width, height, [200, 100, 50, 255])
+        .to_rgb8()
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+        .unwrap();
+    bytes
+}
+
+/// A JPEG with an EXIF block whose IFD1 points at `thumbnail`.
+fn jpeg_with_exif_thumbnail(thumbnail: &[u8]) -> Vec<u8> {
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    // IFD0 without entries, followed by the offset of IFD1
+    tiff.extend(0u16.to_le_bytes());
+    tiff.extend(14u32.to_le_bytes());
+    // IFD1: JPEGInterchangeFormat and JPEGInterchangeFormatLength
+    let data_offset = 14 + 2 + 2 * 12 + 4;
+    tiff.extend(2u16.to_le_bytes());
+    for (tag, value) in [(0x0201u16, data_offset), (0x0202, thumbnail.len() as u32)] {
+        tiff.extend(tag.to_le_bytes());
+        tiff.extend(4u16.to_le_bytes());
+        tiff.extend(1u32.to_le_bytes());
+        tiff.extend(value.to_le_bytes());
+    }
+    tiff.extend(0u32.to_le_bytes());
+    tiff.extend(thumbnail);
+
+    let main = encode_jpeg(64, 48);
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend(((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    jpeg.extend(b"Exif\0\0");
+    jpeg.extend(tiff);
+    jpeg.extend(&main[2..]);
+    jpeg
+}
+
+#[test]
+fn reads_embedded_exif_thumbnail() {
+    let thumbnail = encode_jpeg(16, 12);
+    let jpeg = jpeg_with_exif_thumbnail(&thumbnail);
+    assert_eq!(exif_thumbnail_bytes(&jpeg), Some(thumbnail.as_slice()));
+    assert_eq!(exif_thumbnail_bytes(&encode_jpeg(8, 8)), None);
+    assert_eq!(exif_thumbnail_bytes(b"not a jpeg"), None);
+
+    let tmp = tempdir().unwrap();
+    let path = tmp.path().join("photo.jpg");
+    fs::write(&path, &jpeg).unwrap();
+    // The full image still decodes, and the preview is the small embedded one
+    assert_eq!(image::open(&path).unwrap().width(), 64);
+    let preview = quick_preview(&path).unwrap();
+    assert_eq!((preview.width(), preview.height()), (16, 12));
+}
@@ -0,0 +1,23 @@
+use imagecropper::processed::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn processed_images_are_skipped_per_directory() {
+    let tmp = tempdir().unwrap();
+    let other = tmp.path().join("other");
+    fs::create_dir(&other).unwrap();
+    let files: Vec<_> = [tmp.path().join("a.jpg"), tmp.path().join("b.jpg"), other.join("a.jpg")].into();
+
+    mark_processed(&files[0]).unwrap();
+    mark_processed(&tmp.path().join("b.avif")).unwrap();
+    assert_eq!(
+        processed_names(tmp.path()),
+        ["a.jpg", "b.avif"].into_iter().map(String::from).collect()
+    );
+    assert!(processed_names(&other).is_empty());
+
+    let mut remaining = files.clone();
+    retain_unprocessed(&mut remaining);
+    assert_eq!(remaining, files[1..]);
+}
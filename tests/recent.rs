@@ -0,0 +1,80 @@
+use imagecropper::recent::*;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::tempdir;
+
+mod common;
+use common::with_temp_workdir;
+
+#[test]
+fn recent_dirs_keep_the_newest_first() {
+    let tmp = tempdir().unwrap();
+    let file = tmp.path().join("config").join(RECENT_FILE_NAME);
+    let photos = tmp.path().join("photos");
+    let scans = tmp.path().join("scans");
+    fs::create_dir_all(&photos).unwrap();
+    fs::create_dir_all(&scans).unwrap();
+
+    let mut recent = RecentDirs::load(&file);
+    assert!(recent.entries.is_empty());
+    recent.remember(&photos, Some(&photos.join("b.jpg")));
+    recent.remember(&scans, None);
+    recent.remember(&photos, Some(&photos.join("c.jpg")));
+    recent.save().unwrap();
+
+    let recent = RecentDirs::load(&file);
+    assert_eq!(recent.entries.len(), 2);
+    let photos_entry = recent.get(&photos.join("..").join("photos")).unwrap();
+    assert_eq!(recent.entries[0], *photos_entry);
+    assert_eq!(photos_entry.position.as_deref().and_then(|p| p.file_name()).unwrap(), "c.jpg");
+    assert_eq!(recent.get(&scans).unwrap().position, None);
+}
+
+#[test]
+fn concurrent_sessions_keep_each_others_dirs() {
+    let tmp = tempdir().unwrap();
+    let file = tmp.path().join(RECENT_FILE_NAME);
+    let (photos, scans) = (tmp.path().join("photos"), tmp.path().join("scans"));
+    let mut first = RecentDirs::load(&file);
+    let mut second = RecentDirs::load(&file);
+    first.remember(&photos, Some(&photos.join("b.jpg")));
+    second.remember(&scans, None);
+    first.save().unwrap();
+    second.save().unwrap();
+
+    let recent = RecentDirs::load(&file);
+    assert_eq!(recent.entries.len(), 2);
+    assert!(recent.get(&photos).is_some() && recent.get(&scans).is_some());
+    // Written through a temp file that is gone afterwards
+    assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn only_the_latest_dirs_are_remembered() {
+    let tmp = tempdir().unwrap();
+    let mut recent = RecentDirs::load(&tmp.path().join(RECENT_FILE_NAME));
+    for index in 0..MAX_RECENT_DIRS + 2 {
+        recent.remember(&tmp.path().join(index.to_string()), None);
+    }
+    assert_eq!(recent.entries.len(), MAX_RECENT_DIRS);
+    assert!(recent.get(&tmp.path().join("0")).is_none());
+    assert!(recent.get(&tmp.path().join((MAX_RECENT_DIRS + 1).to_string())).is_some());
+}
+
+#[test]
+fn sessions_resume_in_relative_input_dirs() {
+    with_temp_workdir(|cwd| {
+        fs::create_dir(cwd.join("photos")).unwrap();
+        let mut recent = RecentDirs::default();
+        recent.remember(&cwd.join("photos"), Some(&cwd.join("photos/b.jpg")));
+
+        // The files as collected from `imagecropper photos`
+        let dirs = vec![PathBuf::from("photos")];
+        let files: Vec<PathBuf> = ["a.jpg", "b.jpg", "c.jpg"].iter().map(|name| dirs[0].join(name)).collect();
+        for file in &files {
+            fs::write(file, []).unwrap();
+        }
+        assert_eq!(recent.resume_index(&dirs, &files), Some(1));
+        assert_eq!(recent.resume_index(&[PathBuf::from("scans")], &files), None);
+    });
+}
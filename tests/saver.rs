@@ -1,10 +1,14 @@
-use imagecropper::app::saver::Saver;
-use imagecropper::image_utils::{OutputFormat, SaveRequest};
+use imagecropper::app::saver::{Saver, SizeEstimate};
+use imagecropper::image_utils::{encode, EncodeSettings, ExtraOutput, OutputFormat, SaveRequest};
 use imagecropper::fs_utils::ORIGINALS_DIR;
-use image::{GenericImageView, ImageFormat, ImageReader};
+use imagecropper::pipeline::{OriginalsMode, SaverOptions};
+use image::codecs::tiff::TiffDecoder;
+use image::{GenericImageView, ImageDecoder, ImageFormat, ImageReader};
+use img_parts::png::Png;
+use img_parts::{Bytes, ImageICC};
 use std::{
     fs,
-    io::Read,
+    io::{Cursor, Read},
     path::Path,
     thread,
     time::{Duration, Instant},
@@ -26,8 +30,11 @@ fn run_save_test(format: OutputFormat, extension: &str, quality: u8) {
             image: image.clone(),
             path: target_path.clone(),
             original_path: original_path.clone(),
-            quality,
-            format,
+            settings: EncodeSettings {
+                quality,
+                ..EncodeSettings::new(format)
+            },
+            extra_outputs: Vec::new(),
         };
 
         saver.queue_save(request).unwrap();
@@ -36,6 +43,7 @@ fn run_save_test(format: OutputFormat, extension: &str, quality: u8) {
         assert!(target_path.exists());
         assert_decodable(format, &target_path, image.dimensions());
         assert!(cwd.join(ORIGINALS_DIR).exists());
+        assert!(saver.backups[&target_path].starts_with(cwd.join(ORIGINALS_DIR)));
         // size reporting must yield positive values
         assert!(sizes.0 > 0);
         assert!(sizes.1 > 0);
@@ -67,6 +75,180 @@ fn saver_writes_jpeg_png_webp_and_avif() {
     run_save_test(OutputFormat::Avif, "avif", 50);
 }
 
+#[test]
+fn saver_without_backup_replaces_source() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::with_options(
+            1,
+            SaverOptions {
+                originals: OriginalsMode::Replace,
+                ..Default::default()
+            },
+        );
+        let source = cwd.join("source.jpg");
+        fs::write(&source, b"original").unwrap();
+        let target = cwd.join("source.png");
+
+        let request = SaveRequest {
+            image: solid_image(3, 2, [1, 2, 3, 255]),
+            path: target.clone(),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Png),
+            extra_outputs: Vec::new(),
+        };
+        saver.queue_save(request).unwrap();
+        wait_for_save(&mut saver, &target);
+
+        assert_decodable(OutputFormat::Png, &target, (3, 2));
+        assert!(!source.exists());
+        assert!(!cwd.join(ORIGINALS_DIR).exists());
+        assert!(saver.backups.is_empty());
+    });
+}
+
+#[test]
+fn saver_with_fsync_replaces_source() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::with_options(
+            1,
+            SaverOptions {
+                originals: OriginalsMode::Replace,
+                fsync: true,
+                ..Default::default()
+            },
+        );
+        let source = cwd.join("source.jpg");
+        fs::write(&source, b"original").unwrap();
+        let target = cwd.join("source.png");
+
+        let request = SaveRequest {
+            image: solid_image(3, 2, [1, 2, 3, 255]),
+            path: target.clone(),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Png),
+            extra_outputs: Vec::new(),
+        };
+        saver.queue_save(request).unwrap();
+        wait_for_save(&mut saver, &target);
+
+        assert_decodable(OutputFormat::Png, &target, (3, 2));
+        assert!(!source.exists());
+        assert!(!cwd.join(ORIGINALS_DIR).exists());
+        assert!(saver.backups.is_empty());
+    });
+}
+
+#[test]
+fn saver_writes_tiff_with_the_color_profile_of_the_original() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::new(1);
+        let source = cwd.join("source.png");
+        let settings = EncodeSettings::new(OutputFormat::Png);
+        let mut png =
+            Png::from_bytes(encode(&solid_image(4, 4, [10, 20, 30, 255]), &settings).unwrap().into()).unwrap();
+        png.set_icc_profile(Some(Bytes::from_static(b"profile")));
+        let mut data = Vec::new();
+        png.encoder().write_to(&mut data).unwrap();
+        fs::write(&source, data).unwrap();
+        let target = cwd.join("source.tiff");
+
+        let request = SaveRequest {
+            image: solid_image(4, 4, [0, 0, 0, 255]),
+            path: target.clone(),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Tiff),
+            extra_outputs: Vec::new(),
+        };
+        saver.queue_save(request).unwrap();
+        wait_for_save(&mut saver, &target);
+
+        let mut decoder = TiffDecoder::new(Cursor::new(fs::read(&target).unwrap())).unwrap();
+        assert_eq!(decoder.icc_profile().unwrap().as_deref(), Some(&b"profile"[..]));
+    });
+}
+
+#[test]
+fn saver_keep_mode_writes_elsewhere_and_leaves_source() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::with_options(
+            1,
+            SaverOptions {
+                originals: OriginalsMode::Keep,
+                ..Default::default()
+            },
+        );
+        let source = cwd.join("source.png");
+        fs::write(&source, b"original").unwrap();
+        fs::write(cwd.join("source.png.xmp"), b"<x:xmpmeta/>").unwrap();
+        let target = cwd.join("crops").join("nested").join("source.png");
+
+        let request = SaveRequest {
+            image: solid_image(2, 2, [1, 2, 3, 255]),
+            path: target.clone(),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Png),
+            extra_outputs: Vec::new(),
+        };
+        saver.queue_save(request).unwrap();
+        wait_for_save(&mut saver, &target);
+
+        assert_decodable(OutputFormat::Png, &target, (2, 2));
+        assert_eq!(fs::read(&source).unwrap(), b"original");
+        assert!(!cwd.join(ORIGINALS_DIR).exists());
+        // The sidecar is copied next to the output and stays with the source
+        assert!(cwd.join("crops").join("nested").join("source.png.xmp").exists());
+        assert!(cwd.join("source.png.xmp").exists());
+    });
+}
+
+#[test]
+fn saver_writes_extra_outputs_after_backing_up_once() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::new(1);
+        let source = cwd.join("photo.png");
+        fs::write(&source, b"original").unwrap();
+        let first = cwd.join("photo_r0_c0.png");
+        let second = cwd.join("photo_r0_c1.png");
+
+        let request = SaveRequest {
+            image: solid_image(2, 2, [1, 2, 3, 255]),
+            path: first.clone(),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Png),
+            extra_outputs: vec![ExtraOutput {
+                path: second.clone(),
+                image: solid_image(3, 2, [1, 2, 3, 255]),
+                settings: EncodeSettings::new(OutputFormat::Png),
+            }],
+        };
+        saver.queue_save(request).unwrap();
+        let (_, new_bytes) = wait_for_save(&mut saver, &first).unwrap();
+
+        assert_decodable(OutputFormat::Png, &first, (2, 2));
+        assert_decodable(OutputFormat::Png, &second, (3, 2));
+        assert!(!source.exists());
+        assert!(saver.backups[&first].starts_with(cwd.join(ORIGINALS_DIR)));
+        assert_eq!(new_bytes, fs::metadata(&first).unwrap().len() + fs::metadata(&second).unwrap().len());
+    });
+}
+
+#[test]
+fn size_estimate_matches_encoded_output() {
+    let image = solid_image(64, 32, [200, 100, 50, 255]);
+    let settings = EncodeSettings::new(OutputFormat::Png);
+    let expected = imagecropper::image_utils::encode(&image, &settings).unwrap().len() as u64;
+
+    let mut estimate = SizeEstimate::spawn(image, settings);
+    assert_eq!(estimate.raw_bytes, 64 * 32 * 4);
+    let start = Instant::now();
+    while estimate.bytes().is_none() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for estimate");
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(*estimate.bytes().unwrap().as_ref().unwrap(), expected);
+    assert!(estimate.label().starts_with("Estimated size: "));
+}
+
 fn assert_decodable(format: OutputFormat, path: &Path, expected_dims: (u32, u32)) {
     match format {
         OutputFormat::Avif => {
@@ -89,3 +271,116 @@ fn assert_decodable(format: OutputFormat, path: &Path, expected_dims: (u32, u32)
         }
     }
 }
+
+#[test]
+fn saver_reports_full_queue_and_its_memory() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::new(1);
+        saver.max_pending = Some(2);
+        for name in ["a", "b"] {
+            assert!(!saver.is_full());
+            let source = cwd.join(format!("{name}.png"));
+            fs::write(&source, b"original").unwrap();
+            saver
+                .queue_save(SaveRequest {
+                    image: solid_image(4, 4, [0, 0, 0, 255]),
+                    path: cwd.join(format!("{name}_out.png")),
+                    original_path: source,
+                    settings: EncodeSettings::new(OutputFormat::Png),
+                    extra_outputs: Vec::new(),
+                })
+                .unwrap();
+        }
+        // Completions are only collected by check_completions
+        assert!(saver.is_full());
+        assert_eq!(saver.pending_bytes(), 2 * 4 * 4 * 4);
+        assert_eq!(saver.queue_summary(), "2 images, 128 B");
+
+        let start = Instant::now();
+        while !saver.pending_saves.is_empty() {
+            saver.check_completions();
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for saves");
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!saver.is_full());
+        assert_eq!(saver.pending_bytes(), 0);
+    });
+}
+
+#[test]
+fn kept_crops_count_as_held_and_are_saved_together() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::new(1);
+        saver.max_pending = Some(2);
+        let source = cwd.join("photo.png");
+        fs::write(&source, b"original").unwrap();
+        let crop = |name: &str| SaveRequest {
+            image: solid_image(4, 4, [0, 0, 0, 255]),
+            path: cwd.join(name),
+            original_path: source.clone(),
+            settings: EncodeSettings::new(OutputFormat::Png),
+            extra_outputs: Vec::new(),
+        };
+        assert!(saver.queue_kept().is_none());
+        saver.keep(crop("photo_out.png"));
+        saver.keep(crop("photo_out_2.png"));
+        assert_eq!((saver.kept_count(), saver.held_saves()), (2, 2));
+        assert!(saver.is_full());
+        assert_eq!(saver.pending_bytes(), 2 * 4 * 4 * 4);
+
+        let (output, count) = saver.queue_kept().unwrap().unwrap();
+        assert_eq!((output, count), (cwd.join("photo_out.png"), 2));
+        assert_eq!((saver.kept_count(), saver.held_saves()), (0, 1));
+        wait_for_save(&mut saver, &cwd.join("photo_out.png"));
+        assert!(cwd.join("photo_out_2.png").exists());
+
+        saver.keep(crop("photo_out.png"));
+        saver.discard_kept();
+        assert_eq!(saver.held_saves(), 0);
+    });
+}
+
+#[test]
+fn failed_saves_restore_the_original_and_can_be_retried() {
+    with_temp_workdir(|cwd| {
+        let mut saver = Saver::new(1);
+        saver.keep_failed = true;
+        saver.max_pending = Some(1);
+        let source = cwd.join("source.png");
+        fs::write(&source, b"original").unwrap();
+        // A file where the output directory should be
+        fs::write(cwd.join("blocked"), b"").unwrap();
+        let target = cwd.join("blocked").join("source.png");
+
+        saver
+            .queue_save(SaveRequest {
+                image: solid_image(2, 2, [1, 2, 3, 255]),
+                path: target.clone(),
+                original_path: source.clone(),
+                settings: EncodeSettings::new(OutputFormat::Png),
+                extra_outputs: Vec::new(),
+            })
+            .unwrap();
+        let start = Instant::now();
+        while saver.failed.is_empty() {
+            saver.check_completions();
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the save");
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(fs::read(&source).unwrap(), b"original");
+        // The failed save still holds its pixels
+        assert!(saver.pending_saves.is_empty());
+        assert!(saver.is_full());
+        assert_eq!(saver.pending_bytes(), 2 * 2 * 4);
+
+        let report = cwd.join("report.txt");
+        saver.write_failure_report(&report).unwrap();
+        assert!(fs::read_to_string(&report).unwrap().starts_with(&format!("{}\t", source.display())));
+
+        fs::remove_file(cwd.join("blocked")).unwrap();
+        assert_eq!(saver.retry_failed().unwrap(), 1);
+        wait_for_save(&mut saver, &target);
+        assert!(saver.failed.is_empty());
+        assert_decodable(OutputFormat::Png, &target, (2, 2));
+    });
+}
@@ -61,3 +61,203 @@ fn selection_color_varies_with_index() {
     assert_ne!(c0, c1);
     assert_ne!(c1, c2);
 }
+
+#[test]
+fn selection_style_palettes_and_custom_colors() {
+    let mut style = SelectionStyle::default();
+    assert_eq!(style.color(3), selection_color(3));
+
+    style.palette = Palette::Colorblind;
+    assert_eq!(style.color(0), COLORBLIND_PALETTE[0]);
+    assert_eq!(style.color(COLORBLIND_PALETTE.len()), COLORBLIND_PALETTE[0]);
+
+    style.colors = vec!["#ff0000".into(), "00ff00".into()];
+    assert_eq!(style.color(1), egui::Color32::from_rgb(0, 255, 0));
+    assert_eq!(style.color(2), egui::Color32::from_rgb(255, 0, 0));
+    assert!(style.validate().is_ok());
+
+    style.colors.push("#12345".into());
+    assert!(style.validate().is_err());
+    assert!(parse_hex_color("#gg0000").is_err());
+}
+
+#[test]
+fn snap_to_grid_rounds_edges_and_keeps_image_border() {
+    let bounds = Vec2::new(100.0, 50.0);
+    let mut selection = Selection::from_points(
+        egui::pos2(5.0, 3.0),
+        egui::pos2(97.0, 21.0),
+        bounds,
+    );
+    selection.snap_to_grid(8, bounds);
+    assert_eq!(selection.rect.min, egui::pos2(8.0, 0.0));
+    // 97 rounds to 96, 21 rounds to 24
+    assert_eq!(selection.rect.max, egui::pos2(96.0, 24.0));
+
+    let mut edge = Selection::from_points(egui::pos2(90.0, 40.0), egui::pos2(100.0, 50.0), bounds);
+    edge.snap_to_grid(16, bounds);
+    assert_eq!(edge.rect.max, egui::pos2(100.0, 50.0));
+    assert_eq!(edge.rect.min, egui::pos2(96.0, 48.0));
+}
+
+#[test]
+fn rotated_90_maps_selection_onto_rotated_image() {
+    let bounds = Vec2::new(100.0, 50.0);
+    let selection = Selection::from_points(egui::pos2(10.0, 5.0), egui::pos2(30.0, 15.0), bounds);
+
+    let cw = selection.rotated_90(true, bounds);
+    assert_eq!(cw.rect.min, egui::pos2(35.0, 10.0));
+    assert_eq!(cw.rect.max, egui::pos2(45.0, 30.0));
+
+    let ccw = selection.rotated_90(false, bounds);
+    assert_eq!(ccw.rect.min, egui::pos2(5.0, 70.0));
+    assert_eq!(ccw.rect.max, egui::pos2(15.0, 90.0));
+
+    // Rotating back restores the original rectangle
+    let restored = cw.rotated_90(false, Vec2::new(50.0, 100.0));
+    assert_eq!(restored.rect, selection.rect);
+}
+
+#[test]
+fn scaled_keeps_relative_geometry() {
+    let from = Vec2::new(200.0, 100.0);
+    let selection = Selection::from_points(egui::pos2(20.0, 10.0), egui::pos2(120.0, 60.0), from);
+
+    let scaled = selection.scaled(from, Vec2::new(400.0, 300.0));
+    assert_eq!(scaled.rect.min, egui::pos2(40.0, 30.0));
+    assert_eq!(scaled.rect.max, egui::pos2(240.0, 180.0));
+}
+
+#[test]
+fn rotated_selection_corners_and_hit_testing() {
+    let mut selection = Selection::new(Rect::from_min_max(egui::pos2(40.0, 45.0), egui::pos2(60.0, 55.0)));
+    assert!(selection.contains(egui::pos2(58.0, 50.0)));
+    assert!(!selection.contains(egui::pos2(50.0, 58.0)));
+
+    selection.rotate_by(90.0);
+    assert_eq!(selection.angle, 90.0);
+    // Now 10 wide and 20 high around the same center
+    assert!(!selection.contains(egui::pos2(58.0, 50.0)));
+    assert!(selection.contains(egui::pos2(50.0, 58.0)));
+    let bounds = selection.bounding_box();
+    assert!((bounds.min - egui::pos2(45.0, 40.0)).length() < 1e-3);
+    assert!((bounds.max - egui::pos2(55.0, 60.0)).length() < 1e-3);
+    // The top left corner turns into the top right one
+    assert!((selection.corners()[0] - egui::pos2(55.0, 40.0)).length() < 1e-3);
+
+    selection.rotate_by(100.0);
+    assert_eq!(selection.angle, -170.0);
+}
+
+#[test]
+fn ellipse_selection_contains_only_points_inside_the_ellipse() {
+    let selection = Selection {
+        shape: SelectionShape::Ellipse,
+        ..Selection::new(Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(20.0, 10.0)))
+    };
+    assert!(selection.contains(egui::pos2(10.0, 5.0)));
+    assert!(selection.contains(egui::pos2(19.0, 5.0)));
+    assert!(!selection.contains(egui::pos2(1.0, 1.0)));
+    assert_eq!(selection.outline().len(), 64);
+    assert!(selection
+        .outline()
+        .iter()
+        .all(|point| selection.rect.expand(1e-3).contains(*point)));
+}
+
+#[test]
+fn polygon_selection_handles_concave_outlines() {
+    // An L shape missing its bottom right quarter
+    let points = [(0.0, 0.0), (20.0, 0.0), (20.0, 10.0), (10.0, 10.0), (10.0, 20.0), (0.0, 20.0)]
+        .map(|(x, y)| egui::pos2(x, y));
+    let selection = Selection::polygon(&points, Vec2::new(100.0, 100.0)).unwrap();
+    assert_eq!(selection.rect, Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(20.0, 20.0)));
+    assert!(selection.contains(egui::pos2(5.0, 15.0)));
+    assert!(selection.contains(egui::pos2(15.0, 5.0)));
+    assert!(!selection.contains(egui::pos2(15.0, 15.0)));
+    assert_eq!(selection.outline(), points.to_vec());
+
+    assert!(Selection::polygon(&points[..2], Vec2::new(100.0, 100.0)).is_none());
+}
+
+#[test]
+fn from_center_grows_symmetrically_and_stays_centered_at_the_border() {
+    let bounds = Vec2::new(100.0, 80.0);
+    let selection = Selection::from_center(egui::pos2(50.0, 40.0), egui::pos2(60.0, 35.0), false, bounds);
+    assert_eq!(selection.rect, Rect::from_min_max(egui::pos2(40.0, 35.0), egui::pos2(60.0, 45.0)));
+
+    let square = Selection::from_center(egui::pos2(50.0, 40.0), egui::pos2(60.0, 35.0), true, bounds);
+    assert_eq!(square.rect, Rect::from_min_max(egui::pos2(40.0, 30.0), egui::pos2(60.0, 50.0)));
+
+    // 10 pixels from the left edge, so at most 20 wide
+    let clamped = Selection::from_center(egui::pos2(10.0, 40.0), egui::pos2(40.0, 50.0), false, bounds);
+    assert_eq!(clamped.rect, Rect::from_min_max(egui::pos2(0.0, 30.0), egui::pos2(20.0, 50.0)));
+}
+
+#[test]
+fn grow_expands_every_side_within_bounds() {
+    let bounds = Vec2::new(100.0, 80.0);
+    let mut selection = Selection::new(Rect::from_min_max(egui::pos2(5.0, 20.0), egui::pos2(40.0, 30.0)));
+    selection.grow(10.0, bounds);
+    assert_eq!(selection.rect, Rect::from_min_max(egui::pos2(0.0, 10.0), egui::pos2(50.0, 40.0)));
+
+    // Shrinking stops at a one pixel high band
+    selection.grow(-20.0, bounds);
+    assert_eq!(selection.rect, Rect::from_min_max(egui::pos2(14.5, 24.5), egui::pos2(35.5, 25.5)));
+}
+
+#[test]
+fn stretch_keeps_the_other_edges() {
+    let bounds = Vec2::new(100.0, 80.0);
+    let mut band = Selection::new(Rect::from_min_max(egui::pos2(20.0, 30.0), egui::pos2(40.0, 50.0)));
+    band.rotate_by(15.0);
+    band.stretch(true, false, bounds);
+    assert_eq!(band.rect, Rect::from_min_max(egui::pos2(0.0, 30.0), egui::pos2(100.0, 50.0)));
+    assert_eq!(band.angle, 0.0);
+
+    band.stretch(false, true, bounds);
+    assert_eq!(band.rect, Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(100.0, 80.0)));
+}
+
+#[test]
+fn arrange_equalizes_aligns_and_distributes() {
+    let bounds = egui::vec2(400.0, 300.0);
+    let rect = |x: f32, y: f32, w: f32, h: f32| Selection::new(Rect::from_min_size(egui::pos2(x, y), egui::vec2(w, h)));
+    let mut selections = vec![rect(0.0, 10.0, 50.0, 40.0), rect(80.0, 30.0, 20.0, 20.0), rect(370.0, 0.0, 10.0, 10.0)];
+
+    arrange(&mut selections, 0, Arrangement::SameSize, bounds);
+    let sizes: Vec<Vec2> = selections.iter().map(|s| s.rect.size()).collect();
+    assert_eq!(sizes, vec![egui::vec2(50.0, 40.0); 3]);
+    // Pushed back inside the image instead of cut off
+    assert_eq!(selections[2].rect.min, egui::pos2(350.0, 0.0));
+
+    arrange(&mut selections, 1, Arrangement::AlignTops, bounds);
+    assert!(selections.iter().all(|s| s.rect.min.y == 30.0));
+
+    arrange(&mut selections, 1, Arrangement::DistributeHorizontally, bounds);
+    let lefts: Vec<f32> = selections.iter().map(|s| s.rect.min.x).collect();
+    assert_eq!(lefts, vec![0.0, 175.0, 350.0]);
+
+    arrange(&mut selections, 2, Arrangement::AlignLefts, bounds);
+    assert!(selections.iter().all(|s| s.rect.min.x == 350.0));
+}
+
+#[test]
+fn selection_output_overrides_format_quality_and_file_name() {
+    use imagecropper::image_utils::{labeled_path, EncodeSettings, OutputFormat};
+
+    let base = EncodeSettings::new(OutputFormat::Avif);
+    let output = SelectionOutput {
+        label: " shop logo/1 ".into(),
+        format: Some(OutputFormat::Png),
+        quality: None,
+    };
+    let settings = output.settings(base);
+    assert_eq!((settings.format, settings.quality), (OutputFormat::Png, base.quality));
+    assert_eq!(output.file_label(3), "shop_logo_1");
+    assert_eq!(SelectionOutput::default().file_label(3), "3");
+    assert_eq!(SelectionOutput::default().settings(base), base);
+
+    let path = labeled_path(std::path::Path::new("dir/photo.avif"), &output.file_label(3), "png");
+    assert_eq!(path, std::path::Path::new("dir/photo_shop_logo_1.png"));
+}
@@ -0,0 +1,70 @@
+use imagecropper::app::loader::{Loader, LoaderOptions};
+use imagecropper::thumbnail_cache::{ThumbnailCache, PREVIEW_SIZE};
+use std::{
+    fs,
+    thread,
+    time::{Duration, SystemTime},
+};
+use tempfile::tempdir;
+
+mod common;
+use common::{solid_image, write_image};
+
+#[test]
+fn cache_entries_follow_file_modifications() {
+    let tmp = tempdir().unwrap();
+    let cache = ThumbnailCache::new(tmp.path().join("cache"));
+    let path = tmp.path().join("large.png");
+    let image = solid_image(1024, 256, [10, 20, 30, 255]);
+    write_image(&path, &image);
+
+    assert!(cache.get(&path).is_none());
+    cache.put(&path, &image).unwrap();
+    let preview = cache.get(&path).unwrap();
+    assert_eq!((preview.width(), preview.height()), (PREVIEW_SIZE, PREVIEW_SIZE / 4));
+
+    // Editing the image invalidates its entry
+    let entry = cache.entry_path(&path).unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+    assert_ne!(cache.entry_path(&path).unwrap(), entry);
+    assert!(cache.get(&path).is_none());
+}
+
+#[test]
+fn loader_fills_and_reads_thumbnail_cache() {
+    let tmp = tempdir().unwrap();
+    let cache = ThumbnailCache::new(tmp.path().join("cache"));
+    let path = tmp.path().join("photo.png");
+    write_image(&path, &solid_image(64, 32, [10, 20, 30, 255]));
+
+    let mut loader = Loader::with_options(LoaderOptions {
+        thumbnail_cache: Some(cache.clone()),
+        ..Default::default()
+    });
+    loader.load_image(path.clone());
+    for _ in 0..40 {
+        loader.update();
+        if loader.cache.contains_key(&path) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert!(cache.get(&path).is_some());
+
+    // A later session shows the cached preview while loading
+    let mut loader = Loader::with_options(LoaderOptions {
+        thumbnail_cache: Some(cache),
+        ..Default::default()
+    });
+    loader.load_preview(path.clone());
+    for _ in 0..40 {
+        loader.update();
+        if !loader.previews_ready.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+    assert_eq!(loader.previews_ready.len(), 1);
+    assert_eq!(loader.previews_ready[0].1.size, [64, 32]);
+}
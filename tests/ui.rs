@@ -24,9 +24,7 @@ fn screen_to_image_inverts_selection_rect() {
 fn selection_rect_scales_with_metrics() {
     let canvas = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(200.0, 200.0));
     let metrics = ImageMetrics::new(canvas, Vec2::new(100.0, 100.0));
-    let selection = Selection {
-        rect: Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(30.0, 40.0)),
-    };
+    let selection = Selection::new(Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(30.0, 40.0)));
     let rect = metrics.selection_rect(&selection);
     assert!(rect.width() > 0.0);
     assert!(rect.height() > 0.0);
@@ -39,3 +37,36 @@ fn fit_within_respects_available_bounds() {
     assert!(display.y <= 200.0);
     assert_eq!(scale, 0.5);
 }
+
+#[test]
+fn guide_mode_cycles_through_all_modes() {
+    let mut mode = GuideMode::default();
+    assert!(mode.fractions().is_empty());
+    for _ in 0..4 {
+        mode = mode.next();
+        assert!(mode.fractions().iter().all(|f| *f > 0.0 && *f < 1.0));
+    }
+    assert_eq!(mode, GuideMode::None);
+    assert_eq!(GuideMode::RuleOfThirds.fractions().len(), 2);
+}
+
+#[test]
+fn horizon_angle_normalizes_to_nearest_axis() {
+    let angle = horizon_angle(egui::pos2(0.0, 0.0), egui::pos2(100.0, 10.0)).unwrap();
+    assert!((angle - 5.71).abs() < 0.01);
+    // A nearly vertical line is measured against the vertical axis
+    let angle = horizon_angle(egui::pos2(0.0, 0.0), egui::pos2(-5.0, 100.0)).unwrap();
+    assert!((angle - 2.86).abs() < 0.01);
+    assert!(horizon_angle(egui::pos2(1.0, 1.0), egui::pos2(1.5, 1.0)).is_none());
+}
+
+#[test]
+fn selection_readout_shows_size_ratio_and_megapixels() {
+    assert_eq!(selection_readout(Vec2::new(1200.0, 800.0)), "1200×800 px · 3:2 · 0.96 MP");
+    assert_eq!(selection_readout(Vec2::new(1001.0, 500.0)), "1001×500 px · 2.00:1 · 0.50 MP");
+}
+
+#[test]
+fn pixel_readout_shows_position_and_color() {
+    assert_eq!(pixel_readout(12, 34, [255, 128, 0]), "x 12  y 34  RGB 255 128 0  #ff8000");
+}
@@ -0,0 +1,28 @@
+use imagecropper::video::*;
+use std::path::Path;
+
+#[test]
+fn formats_timestamps_of_frames() {
+    assert_eq!(timestamp(83.456, ':'), "00:01:23.456");
+    assert_eq!(timestamp(3725.0, ':'), "01:02:05.000");
+    assert_eq!(timestamp(-1.0, ':'), "00:00:00.000");
+    assert_eq!(
+        frame_path(Path::new("clips/clip.mp4"), 83.456),
+        Path::new("clips/clip_00-01-23.456.png")
+    );
+}
+
+#[test]
+fn grabbed_frames_are_recognized_by_name() {
+    let video = Path::new("clips/clip.mp4");
+    assert!(is_frame_of(video, &frame_path(video, 1.5)));
+    assert!(is_frame_of(video, Path::new("clips/clip_00-00-01.500-2.png")));
+    assert!(!is_frame_of(video, Path::new("clips/other_00-00-01.500.png")));
+    assert!(!is_frame_of(video, Path::new("elsewhere/clip_00-00-01.500.png")));
+}
+
+#[test]
+fn videos_are_only_taken_with_the_feature() {
+    assert_eq!(is_video(Path::new("clip.MKV")), cfg!(feature = "video"));
+    assert!(!is_video(Path::new("photo.png")));
+}